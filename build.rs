@@ -0,0 +1,64 @@
+//! Records how the binary was built, for `--version --verbose` and the
+//! provenance records (see `src/build_info.rs`). Anything that cannot be
+//! determined is recorded as `unknown` rather than failing the build.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let lockfile = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(Command::new(rustc).arg("--version"));
+    // Builds from a source tree without `.git` (e.g. in Docker) can pass it in
+    println!("cargo:rerun-if-env-changed=HASHSIG_GIT_COMMIT");
+    let git_commit = env::var("HASHSIG_GIT_COMMIT")
+        .ok()
+        .or_else(|| output(Command::new("git").args(["rev-parse", "HEAD"])));
+    let git_dirty = output(Command::new("git").args(["status", "--porcelain"]))
+        .map(|status| !status.is_empty());
+    let lockfile_sha256 = fs::read(&lockfile)
+        .ok()
+        .map(|contents| hex(&Sha256::digest(contents)));
+
+    set("HASHSIG_BUILD_RUSTC", rustc_version);
+    set(
+        "HASHSIG_BUILD_GIT_COMMIT",
+        git_commit.map(|commit| match git_dirty {
+            Some(true) => format!("{}-dirty", commit),
+            _ => commit,
+        }),
+    );
+    set("HASHSIG_BUILD_LOCKFILE_SHA256", lockfile_sha256);
+    set("HASHSIG_BUILD_TARGET", env::var("TARGET").ok());
+    set("HASHSIG_BUILD_PROFILE", env::var("PROFILE").ok());
+}
+
+/// Trimmed stdout of `command`, if it ran and succeeded.
+fn output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+fn set(name: &str, value: Option<String>) {
+    println!(
+        "cargo:rustc-env={}={}",
+        name,
+        value.unwrap_or_else(|| "unknown".to_string())
+    );
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
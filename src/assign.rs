@@ -0,0 +1,220 @@
+//! `assign`: partition a key directory across the validator client
+//! instances of several nodes.
+//!
+//! Every node gets a contiguous range of the manifest's validators, sized by
+//! its weight, in `node-<i>` under the output directory with a manifest of
+//! its own. `assignment.json` maps every node to its validators.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::manifest::{self, Manifest};
+use crate::metadata::unix_now;
+
+/// Written to the output directory.
+pub const ASSIGNMENT_FILE: &str = "assignment.json";
+
+/// How key files get into the node directories.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum AssignMode {
+    /// Copy every file
+    Copy,
+    /// Symlink every file to the original, so there is one physical copy
+    Symlink,
+}
+
+#[derive(Serialize)]
+struct Assignment {
+    source_dir: String,
+    /// Manifest ceremony ID of the key set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ceremony_id: Option<String>,
+    mode: &'static str,
+    created_at: u64,
+    nodes: Vec<NodeAssignment>,
+}
+
+#[derive(Serialize)]
+struct NodeAssignment {
+    node: usize,
+    dir: String,
+    weight: u32,
+    /// Positions of the node's validators in the source manifest (end
+    /// exclusive).
+    first: usize,
+    end: usize,
+    pubkeys: Vec<String>,
+}
+
+/// Partition the validators of `dir` into `nodes` node directories of
+/// `output_dir`. `weights` sizes each node's share (equal shares if empty).
+pub fn assign(
+    dir: &Path,
+    nodes: usize,
+    weights: &[u32],
+    mode: AssignMode,
+    output_dir: &Path,
+) -> io::Result<()> {
+    let source = Manifest::read(dir)?;
+    let ranges = partition(source.validators.len(), nodes, weights)?;
+
+    println!(
+        "🖥️  Assigning {} validators of {} to {} nodes in {}\n",
+        source.validators.len(),
+        dir.display(),
+        nodes,
+        output_dir.display()
+    );
+    let mut assignment = Assignment {
+        source_dir: dir.display().to_string(),
+        ceremony_id: source.ceremony_id.clone(),
+        mode: match mode {
+            AssignMode::Copy => "copy",
+            AssignMode::Symlink => "symlink",
+        },
+        created_at: unix_now(),
+        nodes: Vec::with_capacity(nodes),
+    };
+    for (node, (range, weight)) in ranges.into_iter().enumerate() {
+        let node_dir = output_dir.join(format!("node-{}", node));
+        if manifest::manifest_path(&node_dir).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} already holds a manifest; refusing to overwrite it",
+                    node_dir.display()
+                ),
+            ));
+        }
+        fs::create_dir_all(&node_dir)?;
+
+        // Each node's manifest is the source's, cut down to its range and
+        // with secret keys referred to by file name
+        let mut manifest = Manifest::read(dir)?;
+        manifest.revoked.clear();
+        manifest.validators = manifest.validators.drain(range.clone()).collect();
+        manifest.num_validators = manifest.validators.len();
+        for validator in &mut manifest.validators {
+            for (name, path) in validator_files(dir, &validator.privkey_file)? {
+                place(&path, &node_dir.join(name), mode)?;
+            }
+            validator.privkey_file = manifest::file_name(&validator.privkey_file).to_string();
+        }
+        manifest.write(&node_dir)?;
+        println!(
+            "  ✅ {}: validators {}..{} ({})",
+            node_dir.display(),
+            range.start,
+            range.end,
+            manifest.validators.len()
+        );
+
+        assignment.nodes.push(NodeAssignment {
+            node,
+            dir: node_dir.display().to_string(),
+            weight,
+            first: range.start,
+            end: range.end,
+            pubkeys: manifest
+                .validators
+                .iter()
+                .map(|v| v.pubkey_hex.clone())
+                .collect(),
+        });
+    }
+
+    let path = output_dir.join(ASSIGNMENT_FILE);
+    let json = serde_json::to_vec_pretty(&assignment).map_err(io::Error::other)?;
+    fs::write(&path, json)?;
+    println!("\n📄 Assignment map: {}", path.display());
+    Ok(())
+}
+
+/// Split `count` positions into contiguous ranges, one per node, sized in
+/// proportion to `weights` (equal if empty).
+fn partition(
+    count: usize,
+    nodes: usize,
+    weights: &[u32],
+) -> io::Result<Vec<(std::ops::Range<usize>, u32)>> {
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, reason);
+    if nodes == 0 {
+        return Err(invalid("--nodes must be at least 1".to_string()));
+    }
+    let weights = if weights.is_empty() {
+        vec![1; nodes]
+    } else if weights.len() == nodes {
+        weights.to_vec()
+    } else {
+        return Err(invalid(format!(
+            "{} weights given for {} nodes",
+            weights.len(),
+            nodes
+        )));
+    };
+    let total: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+    if total == 0 {
+        return Err(invalid("At least one weight must be positive".to_string()));
+    }
+
+    let mut ranges = Vec::with_capacity(nodes);
+    let mut cumulative = 0;
+    let mut start = 0;
+    for weight in weights {
+        cumulative += u64::from(weight);
+        let end = (count as u64 * cumulative / total) as usize;
+        ranges.push((start..end, weight));
+        start = end;
+    }
+    Ok(ranges)
+}
+
+/// Every existing file of the validator whose secret key is `privkey_file`,
+/// as (file name, path).
+pub fn validator_files(dir: &Path, privkey_file: &str) -> io::Result<Vec<(String, PathBuf)>> {
+    let name = manifest::file_name(privkey_file);
+    let prefix = name.strip_suffix("_sk.ssz").unwrap_or(name);
+    let secret = dir.join(privkey_file);
+    if !secret.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is missing", secret.display()),
+        ));
+    }
+    let secret_dir = secret.parent().unwrap_or(dir);
+
+    let mut files = vec![(name.to_string(), secret.clone())];
+    for (parent, file) in [
+        (secret_dir, format!("{}_sk.json", prefix)),
+        (dir, format!("{}_pk.ssz", prefix)),
+        (dir, format!("{}_pk.json", prefix)),
+        (dir, format!("{}.meta.json", prefix)),
+    ] {
+        let path = parent.join(&file);
+        if path.is_file() {
+            files.push((file, path));
+        }
+    }
+    Ok(files)
+}
+
+fn place(source: &Path, target: &Path, mode: AssignMode) -> io::Result<()> {
+    match mode {
+        AssignMode::Copy => fs::copy(source, target).map(|_| ()),
+        AssignMode::Symlink => symlink(&fs::canonicalize(source)?, target),
+    }
+}
+
+#[cfg(unix)]
+pub fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+pub fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
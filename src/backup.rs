@@ -0,0 +1,1064 @@
+//! Encrypted, checksummed backup bundles of a key directory.
+//!
+//! A bundle is laid out as:
+//!
+//! ```text
+//! magic (8) | version (1) | salt (16) | nonce prefix (7) | frame*
+//! frame = last flag (1) | ciphertext length (u32 LE) | ciphertext
+//! ```
+//!
+//! The plaintext is a length-prefixed JSON header (provenance plus name, size
+//! and SHA-256 of every file) followed by the file contents in header order.
+//! It is sealed in chunks of at most `CHUNK_SIZE` bytes with ChaCha20-Poly1305
+//! under an Argon2id key derived from the passphrase. Each nonce is
+//! `prefix || frame counter (u32 BE) || last flag`, so frames cannot be
+//! reordered, dropped or truncated without failing authentication.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::build_info::BuildInfo;
+use crate::checksum::{self, HashingReader};
+use crate::exit::{self, Failure};
+use crate::keystore::{FsKeyStore, KeyStore};
+use crate::layout::{self, Layout};
+use crate::manifest::{self, Manifest, MANIFEST_FILE};
+use crate::metadata::unix_now;
+use crate::scheme;
+use crate::shutdown;
+use crate::units::format_size;
+use crate::verify_dir::QUARANTINE_DIR;
+
+const MAGIC: &[u8; 8] = b"HSIGBNDL";
+const VERSION: u8 = 1;
+const CHUNK_SIZE: usize = 1 << 20;
+const TAG_SIZE: usize = 16;
+
+/// Environment variable consulted for the passphrase when no file is given.
+pub const PASSPHRASE_ENV: &str = "HASHSIG_PASSPHRASE";
+
+/// Suffix of the bundle `reencrypt` is writing, until it replaces the
+/// original.
+const REENCRYPT_SUFFIX: &str = ".reencrypt.tmp";
+
+#[derive(Serialize, Deserialize)]
+struct BundleHeader {
+    provenance: Provenance,
+    files: Vec<BundleFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Provenance {
+    tool_version: String,
+    created_at: u64,
+    source_dir: String,
+    /// Absent from bundles written before build metadata was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    build: Option<BuildInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleFile {
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Read the bundle passphrase from `passphrase_file`, falling back to the
+/// `HASHSIG_PASSPHRASE` environment variable.
+pub fn read_passphrase(passphrase_file: Option<&Path>) -> io::Result<String> {
+    let passphrase = match passphrase_file {
+        Some(path) => fs::read_to_string(path)?
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        None => std::env::var(PASSPHRASE_ENV).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "No passphrase given: use --passphrase-file or set {}",
+                    PASSPHRASE_ENV
+                ),
+            )
+        })?,
+    };
+
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Passphrase must not be empty",
+        ));
+    }
+
+    Ok(passphrase)
+}
+
+pub fn backup(dir: &Path, output: &Path, passphrase: &str) -> io::Result<()> {
+    // Bundles hold the files of a flat directory only
+    if layout::check(dir)? == Layout::ContentAddressed {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            format!(
+                "{} is content-addressed, and a bundle cannot hold its objects; `copy` it to a flat directory first",
+                dir.display()
+            ),
+        ));
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Files `verify-dir --quarantine` moved out of the key set
+        if name == QUARANTINE_DIR && entry.file_type()?.is_dir() {
+            println!("⏭️  Skipping {}/", name);
+            continue;
+        }
+        if !entry.file_type()?.is_file() {
+            return Err(exit::error(
+                Failure::InvalidArgs,
+                format!(
+                    "{} holds {}, which is not a regular file and cannot be backed up; move it out first",
+                    dir.display(),
+                    name
+                ),
+            ));
+        }
+        names.push(name);
+    }
+    names.sort_by(|a, b| manifest::compare_names(a, b));
+
+    println!(
+        "📦 Backing up {} files from {} to {}\n",
+        names.len(),
+        dir.display(),
+        output.display()
+    );
+
+    let mut files = Vec::with_capacity(names.len());
+    for name in names {
+        let path = dir.join(&name);
+        files.push(BundleFile {
+            size: fs::metadata(&path)?.len(),
+            sha256: checksum::sha256_file(&path)?,
+            name,
+        });
+    }
+
+    write_bundle(
+        output,
+        passphrase,
+        &dir.display().to_string(),
+        files,
+        |name| File::open(dir.join(name)),
+        |name| println!("  ✅ {}", name),
+    )?;
+
+    println!("\n✅ Backup bundle written to: {}", output.display());
+
+    Ok(())
+}
+
+pub fn restore(
+    bundle: &Path,
+    output_dir: &Path,
+    passphrase: &str,
+    verify_keys: bool,
+) -> io::Result<()> {
+    let bundle = BufReader::new(File::open(bundle)?);
+    restore_from(bundle, output_dir, passphrase, verify_keys)
+}
+
+/// Restore the bundle read from `bundle`, e.g. one embedded in another file.
+pub fn restore_from<R: Read>(
+    bundle: R,
+    output_dir: &Path,
+    passphrase: &str,
+    verify_keys: bool,
+) -> io::Result<()> {
+    let (mut reader, header) = open_bundle(bundle, passphrase)?;
+
+    println!(
+        "📦 Restoring {} files into {}",
+        header.files.len(),
+        output_dir.display()
+    );
+    println!(
+        "   Bundle created by hash-sig-cli {} from {} (unix time {})",
+        header.provenance.tool_version, header.provenance.source_dir, header.provenance.created_at
+    );
+    if let Some(build) = &header.provenance.build {
+        println!(
+            "   Built from commit {} with {} for {}",
+            build.git_commit, build.rustc, build.target
+        );
+    }
+    println!();
+
+    for file in &header.files {
+        let path = output_dir.join(&file.name);
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Refusing to overwrite existing file {}", path.display()),
+            ));
+        }
+    }
+
+    // Files are written to a staging directory and only moved into place
+    // once the last frame authenticates, so a bundle that fails partway
+    // leaves nothing behind
+    let existed = output_dir.exists();
+    let staging = staging_dir(output_dir, existed);
+    fs::create_dir_all(&staging)?;
+    let restored = (|| {
+        for file in &header.files {
+            let mut source = HashingReader::new((&mut reader).take(file.size));
+            let copied = io::copy(&mut source, &mut File::create(staging.join(&file.name))?)?;
+            if copied != file.size || source.hex_digest() != file.sha256 {
+                return Err(invalid_data(format!("Checksum mismatch for {}", file.name)));
+            }
+            println!("  ✅ {}", file.name);
+        }
+        finish_bundle(&mut reader)?;
+        if verify_keys {
+            verify_restored(&staging, &header)?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = restored {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    if existed {
+        for file in &header.files {
+            let path = output_dir.join(&file.name);
+            if path.exists() {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Refusing to overwrite existing file {}", path.display()),
+                ));
+            }
+            fs::rename(staging.join(&file.name), path)?;
+        }
+        fs::remove_dir(&staging)?;
+    } else {
+        fs::rename(&staging, output_dir)?;
+    }
+
+    println!(
+        "\n✅ Successfully restored {} files into: {}",
+        header.files.len(),
+        output_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Directory a restore into `output_dir` is staged in: next to it, so it
+/// can be renamed into place, or inside it if it already exists.
+fn staging_dir(output_dir: &Path, existed: bool) -> PathBuf {
+    if existed {
+        return output_dir.join(format!(".hashsig-restore-{}", std::process::id()));
+    }
+    let name = output_dir.file_name().unwrap_or_default().to_string_lossy();
+    output_dir.with_file_name(format!(".{}.hashsig-restore-{}", name, std::process::id()))
+}
+
+/// Check that every restored secret key in `dir` belongs to its public key.
+fn verify_restored(dir: &Path, header: &BundleHeader) -> io::Result<()> {
+    println!("\n🔍 Verifying restored key pairs...");
+    let scheme = scheme::for_dir(dir)?.unwrap_or_else(scheme::default_scheme);
+    let store = FsKeyStore::new(dir);
+    for file in &header.files {
+        let Some(prefix) = file.name.strip_suffix("_pk.ssz") else {
+            continue;
+        };
+        let sk_name = format!("{}_sk.ssz", prefix);
+        if !header.files.iter().any(|f| f.name == sk_name) {
+            continue;
+        }
+
+        let pk = store.load(&file.name)?;
+        let sk = store.load(&sk_name)?;
+        if !scheme.keypair_matches(&pk, &sk)? {
+            return Err(exit::error(
+                Failure::VerificationFailed,
+                format!("{} does not belong to {}", sk_name, file.name),
+            ));
+        }
+        println!("  ✅ {}", prefix);
+    }
+    Ok(())
+}
+
+/// Read every file of the bundle read from `bundle` into memory in turn,
+/// check its checksum and pass it to `inspect`, without writing anything.
+/// Each file's contents are zeroized once `inspect` returns. Returns the
+/// number of files.
+pub fn inspect_from<R: Read>(
+    bundle: R,
+    passphrase: &str,
+    mut inspect: impl FnMut(&str, &[u8]) -> io::Result<()>,
+) -> io::Result<usize> {
+    let (mut reader, header) = open_bundle(bundle, passphrase)?;
+    for file in &header.files {
+        // Sized up front, so no reallocation leaves a copy behind
+        let mut contents = Zeroizing::new(vec![0u8; file.size as usize]);
+        reader
+            .read_exact(&mut contents)
+            .map_err(|_| invalid_data(format!("Bundle is truncated in {}", file.name)))?;
+        if checksum::sha256_hex(&contents) != file.sha256 {
+            return Err(invalid_data(format!("Checksum mismatch for {}", file.name)));
+        }
+        inspect(&file.name, &contents)?;
+    }
+    finish_bundle(&mut reader)?;
+    Ok(header.files.len())
+}
+
+/// Check the bundle at `bundle` without writing anything to disk: its
+/// framing, and with `passphrase` also its authentication, the checksum of
+/// every file and that every secret key belongs to its public key. Keys are
+/// checked with `scheme_name`, else the scheme of the bundled manifest.
+pub fn verify_keystore(
+    bundle: &Path,
+    passphrase: Option<&str>,
+    scheme_name: Option<&str>,
+) -> io::Result<()> {
+    println!("🔍 Checking bundle {}\n", bundle.display());
+    let frames = check_framing(BufReader::new(File::open(bundle)?))?;
+    println!(
+        "  ✅ Structure: version {}, {} frames, {}",
+        VERSION,
+        frames,
+        format_size(fs::metadata(bundle)?.len())
+    );
+    let Some(passphrase) = passphrase else {
+        println!(
+            "\n✅ {} is a well-formed bundle; its contents were not checked without the passphrase",
+            bundle.display()
+        );
+        return Ok(());
+    };
+
+    // First pass: the manifest, and the public keys to check secret keys against
+    let mut recorded = None;
+    let mut pks = BTreeMap::new();
+    let files = inspect_from(
+        BufReader::new(File::open(bundle)?),
+        passphrase,
+        |name, contents| {
+            if name == MANIFEST_FILE {
+                recorded = Some(Manifest::parse(&String::from_utf8_lossy(contents), name)?);
+            } else if let Some(prefix) = name.strip_suffix("_pk.ssz") {
+                pks.insert(prefix.to_string(), contents.to_vec());
+            }
+            Ok(())
+        },
+    )?;
+    println!(
+        "  ✅ Decrypted and authenticated; checksums of all {} files match",
+        files
+    );
+    let scheme = match (scheme_name, recorded) {
+        (Some(name), _) => scheme::lookup(name)?,
+        (None, Some(manifest)) => scheme::for_manifest(
+            &manifest,
+            &format!("{} in {}", MANIFEST_FILE, bundle.display()),
+        )?,
+        (None, None) => scheme::default_scheme(),
+    };
+
+    // Second pass: each secret key, only ever in memory
+    println!("\n🔑 Checking key pairs with {}", scheme.name());
+    let mut checked = 0;
+    let mut mismatched = 0;
+    inspect_from(
+        BufReader::new(File::open(bundle)?),
+        passphrase,
+        |name, sk| {
+            let Some(prefix) = name.strip_suffix("_sk.ssz") else {
+                return Ok(());
+            };
+            let Some(pk) = pks.get(prefix) else {
+                println!("  ⚠️  {}: no public key to check against", prefix);
+                return Ok(());
+            };
+            checked += 1;
+            if scheme.keypair_matches(pk, sk)? {
+                println!("  ✅ {}", prefix);
+            } else {
+                println!(
+                    "  ❌ {}: secret key does not belong to its public key",
+                    prefix
+                );
+                mismatched += 1;
+            }
+            Ok(())
+        },
+    )?;
+    if mismatched > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} key pairs in {} do not match",
+                mismatched,
+                checked,
+                bundle.display()
+            ),
+        ));
+    }
+
+    println!(
+        "\n✅ {} is intact: {} files, {} matching key pairs; nothing was written to disk",
+        bundle.display(),
+        files,
+        checked
+    );
+    Ok(())
+}
+
+/// Re-encrypt every bundle in `dir` (backups and `generate --archive`
+/// keystores) from `passphrase` to `new_passphrase`. Each is streamed into a
+/// new bundle next to it, which then replaces it; nothing is decrypted to
+/// disk. Bundles that already open with `new_passphrase` are skipped, so an
+/// interrupted run just needs to be run again.
+pub fn reencrypt(dir: &Path, passphrase: &str, new_passphrase: &str) -> io::Result<()> {
+    if passphrase == new_passphrase {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            "The new passphrase is the same as the current one",
+        ));
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        // Left behind by an interrupted run; the original is still in place
+        if name.ends_with(REENCRYPT_SUFFIX) {
+            fs::remove_file(entry.path())?;
+            println!("  🧹 Removed unfinished {}", name);
+        } else if is_bundle(&entry.path())? {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No bundles found in {}", dir.display()),
+        ));
+    }
+    names.sort_by(|a, b| manifest::compare_names(a, b));
+
+    shutdown::install()?;
+    println!(
+        "🔐 Re-encrypting {} bundles in {}\n",
+        names.len(),
+        dir.display()
+    );
+    let mut skipped = 0;
+    for (done, name) in names.iter().enumerate() {
+        if shutdown::requested() {
+            return Err(exit::error(
+                Failure::Resumable,
+                format!(
+                    "Stopped after {} of {} bundles; run reencrypt again to finish",
+                    done,
+                    names.len()
+                ),
+            ));
+        }
+        if reencrypt_bundle(&dir.join(name), passphrase, new_passphrase)? {
+            println!("  ✅ {}", name);
+        } else {
+            println!("  ⏭️  {} already uses the new passphrase", name);
+            skipped += 1;
+        }
+    }
+
+    println!(
+        "\n✅ Re-encrypted {} bundles in {} ({} already were)",
+        names.len() - skipped,
+        dir.display(),
+        skipped
+    );
+    Ok(())
+}
+
+/// Whether `path` starts like a bundle.
+fn is_bundle(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; 8];
+    Ok(File::open(path)?.read_exact(&mut magic).is_ok() && &magic == MAGIC)
+}
+
+/// Stream the bundle at `path` into one sealed with `new_passphrase` that
+/// replaces it, checking every file's checksum on the way. Returns `false`,
+/// leaving it as it is, if it already opens with `new_passphrase`.
+fn reencrypt_bundle(path: &Path, passphrase: &str, new_passphrase: &str) -> io::Result<bool> {
+    let (mut reader, header) = match open_bundle(BufReader::new(File::open(path)?), passphrase) {
+        Ok(opened) => opened,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            if open_bundle(BufReader::new(File::open(path)?), new_passphrase).is_ok() {
+                return Ok(false);
+            }
+            return Err(invalid_data(format!("{}: {}", path.display(), e)));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(REENCRYPT_SUFFIX);
+    let temp = Path::new(&temp);
+    let written = (|| {
+        let header_json = serde_json::to_vec(&header).map_err(io::Error::other)?;
+        let mut writer = EncryptWriter::new(BufWriter::new(File::create(temp)?), new_passphrase)?;
+        writer.write_all(&(header_json.len() as u32).to_le_bytes())?;
+        writer.write_all(&header_json)?;
+        for file in &header.files {
+            let mut source = HashingReader::new((&mut reader).take(file.size));
+            let copied = io::copy(&mut source, &mut writer)?;
+            if copied != file.size || source.hex_digest() != file.sha256 {
+                return Err(invalid_data(format!(
+                    "Checksum mismatch for {} in {}",
+                    file.name,
+                    path.display()
+                )));
+            }
+        }
+        finish_bundle(&mut reader)?;
+        writer
+            .finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(temp);
+        return Err(e);
+    }
+    fs::rename(temp, path)?;
+    Ok(true)
+}
+
+/// Walk the frames of the bundle read from `bundle` without decrypting
+/// them: every frame but the last is full, and nothing follows the last.
+/// Returns the number of frames.
+fn check_framing<R: Read>(mut bundle: R) -> io::Result<u32> {
+    let mut preamble = [0u8; 8 + 1 + 16 + 7];
+    bundle
+        .read_exact(&mut preamble)
+        .map_err(|_| invalid_data("Not a hash-sig-cli backup bundle"))?;
+    if &preamble[..8] != MAGIC {
+        return Err(invalid_data("Not a hash-sig-cli backup bundle"));
+    }
+    if preamble[8] != VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported bundle version {}",
+            preamble[8]
+        )));
+    }
+
+    let mut frames = 0;
+    loop {
+        let mut frame_header = [0u8; 5];
+        bundle
+            .read_exact(&mut frame_header)
+            .map_err(|_| invalid_data(format!("Bundle is truncated after {} frames", frames)))?;
+        let last = match frame_header[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid_data(format!("Frame {} is corrupted", frames))),
+        };
+        let len = u32::from_le_bytes([
+            frame_header[1],
+            frame_header[2],
+            frame_header[3],
+            frame_header[4],
+        ]) as usize;
+        let well_formed = if last {
+            (TAG_SIZE..=CHUNK_SIZE + TAG_SIZE).contains(&len)
+        } else {
+            len == CHUNK_SIZE + TAG_SIZE
+        };
+        if !well_formed {
+            return Err(invalid_data(format!(
+                "Frame {} has an impossible length of {} bytes",
+                frames, len
+            )));
+        }
+        let skipped = io::copy(&mut (&mut bundle).take(len as u64), &mut io::sink())?;
+        if skipped != len as u64 {
+            return Err(invalid_data(format!(
+                "Bundle is truncated in frame {}",
+                frames
+            )));
+        }
+        frames += 1;
+        if last {
+            break;
+        }
+    }
+    if bundle.read(&mut [0u8; 1])? != 0 {
+        return Err(invalid_data("Unexpected trailing data in bundle"));
+    }
+    Ok(frames)
+}
+
+/// Write an in-memory set of files as a bundle.
+pub fn write_bundle_from_memory(
+    output: &Path,
+    passphrase: &str,
+    source: &str,
+    files: &BTreeMap<String, Vec<u8>>,
+) -> io::Result<()> {
+    let entries = files
+        .iter()
+        .map(|(name, contents)| BundleFile {
+            name: name.clone(),
+            size: contents.len() as u64,
+            sha256: checksum::sha256_hex(contents),
+        })
+        .collect();
+
+    write_bundle(
+        output,
+        passphrase,
+        source,
+        entries,
+        |name| Ok(files[name].as_slice()),
+        |_| {},
+    )
+}
+
+/// Stream `files` into a new bundle at `output`. `open` provides the contents
+/// of each file, which are checked against its recorded checksum.
+fn write_bundle<R: Read>(
+    output: &Path,
+    passphrase: &str,
+    source: &str,
+    files: Vec<BundleFile>,
+    mut open: impl FnMut(&str) -> io::Result<R>,
+    mut on_written: impl FnMut(&str),
+) -> io::Result<()> {
+    let header = BundleHeader {
+        provenance: Provenance {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: unix_now(),
+            source_dir: source.to_string(),
+            build: Some(BuildInfo::current()),
+        },
+        files,
+    };
+    let header_json = serde_json::to_vec(&header).expect("Failed to serialize bundle header");
+
+    let mut writer = EncryptWriter::new(BufWriter::new(File::create(output)?), passphrase)?;
+    writer.write_all(&(header_json.len() as u32).to_le_bytes())?;
+    writer.write_all(&header_json)?;
+
+    for file in &header.files {
+        let mut contents = HashingReader::new(open(&file.name)?);
+        io::copy(&mut contents, &mut writer)?;
+        if contents.hex_digest() != file.sha256 {
+            return Err(io::Error::other(format!(
+                "{} changed while the bundle was being written",
+                file.name
+            )));
+        }
+        on_written(&file.name);
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// Open a bundle and read its header, leaving the reader at the first file.
+fn open_bundle<R: Read>(
+    bundle: R,
+    passphrase: &str,
+) -> io::Result<(DecryptReader<R>, BundleHeader)> {
+    let mut reader = DecryptReader::new(bundle, passphrase)?;
+
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let mut header_json = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+    reader.read_exact(&mut header_json)?;
+    let header: BundleHeader = serde_json::from_slice(&header_json)
+        .map_err(|e| invalid_data(format!("Corrupted bundle header: {}", e)))?;
+
+    for file in &header.files {
+        if Path::new(&file.name).file_name().and_then(|n| n.to_str()) != Some(file.name.as_str()) {
+            return Err(invalid_data(format!(
+                "Refusing to restore unsafe file name {:?}",
+                file.name
+            )));
+        }
+    }
+
+    Ok((reader, header))
+}
+
+/// Check that the whole bundle, including its last frame, has been consumed.
+fn finish_bundle<R: Read>(reader: &mut DecryptReader<R>) -> io::Result<()> {
+    if reader.read(&mut [0u8; 1])? != 0 {
+        return Err(invalid_data("Unexpected trailing data in bundle"));
+    }
+    Ok(())
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> io::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| io::Error::other(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+fn frame_nonce(prefix: &[u8; 7], counter: u32, last: bool) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[..7].copy_from_slice(prefix);
+    nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+struct EncryptWriter<W: Write> {
+    inner: W,
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; 7],
+    counter: u32,
+    /// Plaintext not sealed yet, at most one byte over `CHUNK_SIZE` so it
+    /// never reallocates and leaves a copy behind.
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    fn new(mut inner: W, passphrase: &str) -> io::Result<Self> {
+        let salt: [u8; 16] = rand::random();
+        let prefix: [u8; 7] = rand::random();
+        let key = derive_key(passphrase, &salt)?;
+
+        inner.write_all(MAGIC)?;
+        inner.write_all(&[VERSION])?;
+        inner.write_all(&salt)?;
+        inner.write_all(&prefix)?;
+
+        Ok(Self {
+            inner,
+            cipher: ChaCha20Poly1305::new(&key),
+            prefix,
+            counter: 0,
+            buffer: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE + 1)),
+        })
+    }
+
+    fn seal_frame(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let nonce = frame_nonce(&self.prefix, self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, &self.buffer[..len])
+            .map_err(|_| io::Error::other("Bundle encryption failed"))?;
+
+        self.inner.write_all(&[last as u8])?;
+        self.inner
+            .write_all(&(ciphertext.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&ciphertext)?;
+
+        self.buffer.drain(..len);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("Bundle exceeds the maximum number of frames"))?;
+        Ok(())
+    }
+
+    /// Seal the remaining buffered data as the last frame.
+    fn finish(mut self) -> io::Result<W> {
+        let len = self.buffer.len();
+        self.seal_frame(len, true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE + 1 - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        // Keep at least one byte buffered so the final frame is never empty
+        // unless the whole stream is.
+        if self.buffer.len() > CHUNK_SIZE {
+            self.seal_frame(CHUNK_SIZE, false)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct DecryptReader<R: Read> {
+    inner: R,
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; 7],
+    counter: u32,
+    plaintext: Zeroizing<Vec<u8>>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    fn new(mut inner: R, passphrase: &str) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        inner
+            .read_exact(&mut magic)
+            .map_err(|_| invalid_data("Not a hash-sig-cli backup bundle"))?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a hash-sig-cli backup bundle"));
+        }
+
+        let mut version = [0u8; 1];
+        inner.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported bundle version {}",
+                version[0]
+            )));
+        }
+
+        let mut salt = [0u8; 16];
+        inner.read_exact(&mut salt)?;
+        let mut prefix = [0u8; 7];
+        inner.read_exact(&mut prefix)?;
+        let key = derive_key(passphrase, &salt)?;
+
+        Ok(Self {
+            inner,
+            cipher: ChaCha20Poly1305::new(&key),
+            prefix,
+            counter: 0,
+            plaintext: Zeroizing::new(Vec::new()),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    fn open_frame(&mut self) -> io::Result<()> {
+        let mut frame_header = [0u8; 5];
+        self.inner
+            .read_exact(&mut frame_header)
+            .map_err(|_| invalid_data("Bundle is truncated"))?;
+
+        let last = match frame_header[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid_data("Corrupted bundle frame")),
+        };
+        let len = u32::from_le_bytes([
+            frame_header[1],
+            frame_header[2],
+            frame_header[3],
+            frame_header[4],
+        ]) as usize;
+        if len > CHUNK_SIZE + TAG_SIZE {
+            return Err(invalid_data("Corrupted bundle frame"));
+        }
+
+        let mut ciphertext = vec![0u8; len];
+        self.inner
+            .read_exact(&mut ciphertext)
+            .map_err(|_| invalid_data("Bundle is truncated"))?;
+
+        let nonce = frame_nonce(&self.prefix, self.counter, last);
+        self.plaintext = Zeroizing::new(
+            self.cipher
+                .decrypt(&nonce, ciphertext.as_slice())
+                .map_err(|_| {
+                    invalid_data(
+                        "Bundle authentication failed (wrong passphrase or corrupted data)",
+                    )
+                })?,
+        );
+        self.position = 0;
+        self.counter = self.counter.wrapping_add(1);
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.open_frame()?;
+        }
+
+        let n = buf.len().min(self.plaintext.len() - self.position);
+        buf[..n].copy_from_slice(&self.plaintext[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    /// Length of everything before the first frame.
+    const PREAMBLE: usize = MAGIC.len() + 1 + 16 + 7;
+
+    fn seal(plaintext: &[u8]) -> Vec<u8> {
+        let mut writer = EncryptWriter::new(Vec::new(), PASSPHRASE).unwrap();
+        writer.write_all(plaintext).unwrap();
+        assert_eq!(writer.buffer.capacity(), CHUNK_SIZE + 1);
+        writer.finish().unwrap()
+    }
+
+    fn open(bundle: &[u8], passphrase: &str) -> io::Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        DecryptReader::new(bundle, passphrase)?.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// The preamble and the frames of `bundle`.
+    fn frames(bundle: &[u8]) -> (&[u8], Vec<&[u8]>) {
+        let (preamble, mut rest) = bundle.split_at(PREAMBLE);
+        let mut frames = Vec::new();
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+            let (frame, tail) = rest.split_at(5 + len);
+            frames.push(frame);
+            rest = tail;
+        }
+        (preamble, frames)
+    }
+
+    fn plaintext(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn round_trips() {
+        for len in [0, 1, CHUNK_SIZE, 2 * CHUNK_SIZE + 5] {
+            let plaintext = plaintext(len);
+            let bundle = seal(&plaintext);
+            let (_, frames) = frames(&bundle);
+            assert_eq!(frames.len(), len.div_ceil(CHUNK_SIZE).max(1));
+            let last_flags: Vec<u8> = frames.iter().map(|frame| frame[0]).collect();
+            assert_eq!(last_flags.last(), Some(&1));
+            assert!(last_flags[..last_flags.len() - 1]
+                .iter()
+                .all(|&flag| flag == 0));
+            assert_eq!(open(&bundle, PASSPHRASE).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let bundle = seal(b"secret");
+        assert!(open(&bundle, "wrong").is_err());
+    }
+
+    #[test]
+    fn rejects_reordered_frames() {
+        let bundle = seal(&plaintext(2 * CHUNK_SIZE + 5));
+        let (preamble, frames) = frames(&bundle);
+        let reordered = [preamble, frames[1], frames[0], frames[2]].concat();
+        assert!(open(&reordered, PASSPHRASE).is_err());
+    }
+
+    #[test]
+    fn rejects_truncation() {
+        let bundle = seal(&plaintext(2 * CHUNK_SIZE + 5));
+        let (preamble, frames) = frames(&bundle);
+        // Whole frames dropped from the end
+        assert!(open(&[preamble, frames[0], frames[1]].concat(), PASSPHRASE).is_err());
+        assert!(open(&[preamble, frames[0]].concat(), PASSPHRASE).is_err());
+        // Cut inside a frame
+        assert!(open(&bundle[..bundle.len() - 1], PASSPHRASE).is_err());
+        // A frame claiming to be the last, to hide the ones after it
+        let mut first = frames[0].to_vec();
+        first[0] = 1;
+        assert!(open(&[preamble, &first].concat(), PASSPHRASE).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_frames() {
+        let mut bundle = seal(b"secret");
+        let last = bundle.len() - 1;
+        bundle[last] ^= 1;
+        assert!(open(&bundle, PASSPHRASE).is_err());
+    }
+
+    #[test]
+    fn restores_only_complete_bundles() {
+        let root =
+            std::env::temp_dir().join(format!("hashsig-backup-restore-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = root.join("keys");
+        fs::create_dir_all(dir.join(QUARANTINE_DIR)).unwrap();
+        fs::write(dir.join(QUARANTINE_DIR).join("validator_1_sk.ssz"), b"bad").unwrap();
+        fs::write(dir.join("notes.txt"), b"notes").unwrap();
+        // Spans several frames, so truncation is only caught after it started
+        fs::write(dir.join("large.bin"), plaintext(2 * CHUNK_SIZE + 5)).unwrap();
+        let bundle = root.join("keys.bundle");
+        backup(&dir, &bundle, PASSPHRASE).unwrap();
+        let sealed = fs::read(&bundle).unwrap();
+        let truncated = &sealed[..sealed.len() - 1];
+
+        // Into a new directory: nothing is left, not even the staging directory
+        let fresh = root.join("fresh");
+        assert!(restore_from(truncated, &fresh, PASSPHRASE, false).is_err());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+
+        // Into an existing one: only what was there before
+        let existing = root.join("existing");
+        fs::create_dir(&existing).unwrap();
+        fs::write(existing.join("other.txt"), b"other").unwrap();
+        assert!(restore_from(truncated, &existing, PASSPHRASE, false).is_err());
+        let left: Vec<_> = fs::read_dir(&existing)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+
+        restore_from(&sealed[..], &fresh, PASSPHRASE, false).unwrap();
+        let mut restored: Vec<_> = fs::read_dir(&fresh)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        restored.sort();
+        restore_from(&sealed[..], &existing, PASSPHRASE, false).unwrap();
+        let mut merged: Vec<_> = fs::read_dir(&existing)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        merged.sort();
+        let large = fs::read(fresh.join("large.bin")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(left, ["other.txt"]);
+        assert_eq!(restored, ["large.bin", "notes.txt"]);
+        assert_eq!(merged, ["large.bin", "notes.txt", "other.txt"]);
+        assert_eq!(large, plaintext(2 * CHUNK_SIZE + 5));
+    }
+}
@@ -0,0 +1,455 @@
+//! Key generation and signing benchmarks, and the hardware tuning profile.
+//!
+//! `keygen-bench` measures key generation throughput (dominated by Poseidon2
+//! hashing) at increasing thread counts. With `--profile` the best setting
+//! is saved to a tuning profile, which `generate` uses when `--threads` is
+//! not given. `sign-bench` measures signing latency across epochs, and
+//! `compare-schemes` puts every registered scheme side by side.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use leansig::MESSAGE_LENGTH;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::metadata::unix_now;
+use crate::scheme::{self, SchemeHandle};
+use crate::units::format_size;
+
+/// Environment variable overriding the tuning profile location.
+pub const PROFILE_ENV: &str = "HASHSIG_PROFILE";
+
+/// Thread counts within this fraction of the best throughput count as
+/// equally good; the smallest of them is recommended.
+const RECOMMENDATION_TOLERANCE: f64 = 0.95;
+
+#[derive(Serialize, Deserialize)]
+pub struct Measurement {
+    pub threads: usize,
+    pub keys_per_sec: f64,
+}
+
+/// Result of a profiling run, as stored in the tuning profile.
+#[derive(Serialize, Deserialize)]
+pub struct TuningProfile {
+    pub tool_version: String,
+    pub created_at: u64,
+    #[serde(default)]
+    pub key_scheme: String,
+    /// Logical CPUs of the machine that was profiled.
+    pub cpus: usize,
+    pub log_num_active_epochs: usize,
+    pub measurements: Vec<Measurement>,
+    pub recommended_threads: usize,
+}
+
+/// Default tuning profile location: `$HASHSIG_PROFILE`, else `profile.json`
+/// in [`config::config_dir`].
+pub fn default_profile_path() -> Option<PathBuf> {
+    match env::var_os(PROFILE_ENV) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(config::config_dir()?.join("profile.json")),
+    }
+}
+
+fn available_cpus() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Powers of two up to the number of CPUs, plus the CPU count itself.
+fn thread_counts(cpus: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = (0..)
+        .map(|shift| 1 << shift)
+        .take_while(|&n| n < cpus)
+        .collect();
+    counts.push(cpus);
+    counts
+}
+
+/// Generate `keys_per_thread` keys on each of `threads` threads, returning
+/// the overall throughput.
+fn measure(
+    scheme: &dyn SchemeHandle,
+    threads: usize,
+    keys_per_thread: usize,
+    num_active_epochs: usize,
+) -> f64 {
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut rng = rand::rng();
+                for _ in 0..keys_per_thread {
+                    scheme.key_gen(&mut rng, 0, num_active_epochs);
+                }
+            });
+        }
+    });
+    (threads * keys_per_thread) as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Benchmark key generation with 2^`log_num_active_epochs` active epochs and
+/// optionally save the result as a tuning profile.
+pub fn keygen_bench(
+    scheme: &dyn SchemeHandle,
+    log_num_active_epochs: usize,
+    keys_per_thread: usize,
+    profile: Option<&Path>,
+) -> io::Result<()> {
+    if keys_per_thread == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--keys-per-thread must be at least 1",
+        ));
+    }
+
+    let cpus = available_cpus();
+    println!(
+        "⏱️  Benchmarking {} key generation with 2^{} active epochs, {} keys per thread ({} CPUs)\n",
+        scheme.name(),
+        log_num_active_epochs,
+        keys_per_thread,
+        cpus
+    );
+
+    let mut measurements = Vec::new();
+    for threads in thread_counts(cpus) {
+        let keys_per_sec = measure(scheme, threads, keys_per_thread, 1 << log_num_active_epochs);
+        println!("  {:>3} threads: {:>10.3} keys/s", threads, keys_per_sec);
+        measurements.push(Measurement {
+            threads,
+            keys_per_sec,
+        });
+    }
+
+    let best = measurements
+        .iter()
+        .map(|m| m.keys_per_sec)
+        .fold(0.0, f64::max);
+    let recommended_threads = measurements
+        .iter()
+        .find(|m| m.keys_per_sec >= best * RECOMMENDATION_TOLERANCE)
+        .map_or(cpus, |m| m.threads);
+
+    println!("\n✅ Recommended: --threads {}", recommended_threads);
+
+    if let Some(path) = profile {
+        let profile = TuningProfile {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: unix_now(),
+            key_scheme: scheme.name().to_string(),
+            cpus,
+            log_num_active_epochs,
+            measurements,
+            recommended_threads,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&profile).map_err(io::Error::other)?;
+        fs::write(path, json)?;
+        println!("📄 Tuning profile written to: {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Thread count for `generate` when `--threads` is not given: the tuning
+/// profile's recommendation if one exists for this machine, else the number
+/// of CPUs.
+pub fn default_threads() -> usize {
+    let cpus = available_cpus();
+    let Some(path) = default_profile_path() else {
+        return cpus;
+    };
+    let Ok(contents) = fs::read(&path) else {
+        return cpus;
+    };
+
+    match serde_json::from_slice::<TuningProfile>(&contents) {
+        Ok(profile) if profile.cpus == cpus => {
+            println!(
+                "⚙️  Using {} threads from tuning profile {}",
+                profile.recommended_threads,
+                path.display()
+            );
+            profile.recommended_threads
+        }
+        Ok(profile) => {
+            println!(
+                "⚠️  Ignoring tuning profile {}: made on a machine with {} CPUs, this one has {}",
+                path.display(),
+                profile.cpus,
+                cpus
+            );
+            cpus
+        }
+        Err(e) => {
+            println!(
+                "⚠️  Ignoring unreadable tuning profile {}: {}",
+                path.display(),
+                e
+            );
+            cpus
+        }
+    }
+}
+
+/// Parse an epoch range written as `start..end` (end exclusive).
+pub fn parse_epoch_range(range: &str) -> Result<Range<u32>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| format!("Expected start..end, got {}", range))?;
+    let start: u32 = start
+        .parse()
+        .map_err(|e| format!("Invalid range start {}: {}", start, e))?;
+    let end: u32 = end
+        .parse()
+        .map_err(|e| format!("Invalid range end {}: {}", end, e))?;
+    if start >= end {
+        return Err(format!("Empty epoch range {}", range));
+    }
+    Ok(start..end)
+}
+
+/// Measure signing latency at every `step`-th epoch of `epochs`, with the
+/// secret key `sk` or, if none is given, a throwaway key generated for the
+/// range. Signatures are discarded, so no epoch of `sk` is ever published.
+pub fn sign_bench(
+    scheme: &dyn SchemeHandle,
+    sk: Option<&Path>,
+    epochs: Range<u32>,
+    step: usize,
+) -> io::Result<()> {
+    if step == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--step must be at least 1",
+        ));
+    }
+
+    let sk = match sk {
+        Some(path) => fs::read(path)?,
+        None => {
+            println!(
+                "🔑 Generating a throwaway {} key for epochs {}..{}",
+                scheme.name(),
+                epochs.start,
+                epochs.end
+            );
+            scheme
+                .key_gen(
+                    &mut rand::rng(),
+                    epochs.start as usize,
+                    (epochs.end - epochs.start) as usize,
+                )
+                .sk_bytes()
+        }
+    };
+
+    let sampled: Vec<u32> = epochs.clone().step_by(step).collect();
+    println!(
+        "⏱️  Benchmarking {} signing at {} epochs in {}..{}\n",
+        scheme.name(),
+        sampled.len(),
+        epochs.start,
+        epochs.end
+    );
+    let latencies = scheme.sign_latencies(&sk, &sampled)?;
+
+    let mut sorted = latencies.clone();
+    sorted.sort();
+    let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+    let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+    let (slowest_epoch, slowest) = sampled
+        .iter()
+        .zip(&latencies)
+        .max_by_key(|(_, latency)| **latency)
+        .expect("at least one epoch is sampled");
+
+    println!("  min:  {:>10.3} ms", millis(sorted[0]));
+    println!("  p50:  {:>10.3} ms", millis(percentile(50)));
+    println!("  p90:  {:>10.3} ms", millis(percentile(90)));
+    println!("  p99:  {:>10.3} ms", millis(percentile(99)));
+    println!(
+        "  max:  {:>10.3} ms (epoch {})",
+        millis(*slowest),
+        slowest_epoch
+    );
+    println!("  mean: {:>10.3} ms", millis(mean));
+
+    println!(
+        "\n✅ Budget at least {:.3} ms per signature for worst-case signing",
+        millis(*slowest)
+    );
+    Ok(())
+}
+
+/// One scheme's measurements in `compare-schemes`.
+#[derive(Serialize)]
+struct SchemeComparison {
+    key_scheme: String,
+    hash_function: String,
+    encoding: String,
+    lifetime: u64,
+    pk_bytes: usize,
+    sk_bytes: usize,
+    signature_bytes: usize,
+    keygen_ms: f64,
+    /// Mean over the sampled epochs.
+    sign_ms: f64,
+    /// Mean over the sampled epochs.
+    verify_ms: f64,
+}
+
+/// Contents of the `compare-schemes --output` file.
+#[derive(Serialize)]
+struct Comparison {
+    tool_version: String,
+    created_at: u64,
+    cpus: usize,
+    log_num_active_epochs: usize,
+    signatures: usize,
+    schemes: Vec<SchemeComparison>,
+}
+
+/// Generate a throwaway key of 2^`log_num_active_epochs` active epochs for
+/// every registered scheme, sign and verify at its first `signatures`
+/// prepared epochs, and report the results side by side, optionally also
+/// as JSON in `output`.
+pub fn compare_schemes(
+    log_num_active_epochs: usize,
+    signatures: usize,
+    output: Option<&Path>,
+) -> io::Result<()> {
+    if signatures == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--signatures must be at least 1",
+        ));
+    }
+
+    let cpus = available_cpus();
+    println!(
+        "⏱️  Comparing {} schemes with 2^{} active epochs, {} signatures each ({} CPUs)\n",
+        scheme::all().len(),
+        log_num_active_epochs,
+        signatures,
+        cpus
+    );
+
+    let mut schemes = Vec::new();
+    for &scheme in scheme::all() {
+        println!("🔑 {}", scheme.name());
+        schemes.push(compare_scheme(scheme, log_num_active_epochs, signatures)?);
+    }
+
+    println!(
+        "\n  {:<42} {:>8} {:>10} {:>12} {:>10} {:>12} {:>10} {:>10}",
+        "Scheme", "Lifetime", "PK size", "SK size", "Sig size", "Keygen ms", "Sign ms", "Verify ms"
+    );
+    for comparison in &schemes {
+        let lifetime = if comparison.lifetime.is_power_of_two() {
+            format!("2^{}", comparison.lifetime.trailing_zeros())
+        } else {
+            comparison.lifetime.to_string()
+        };
+        println!(
+            "  {:<42} {:>8} {:>10} {:>12} {:>10} {:>12.3} {:>10.3} {:>10.3}",
+            comparison.key_scheme,
+            lifetime,
+            format_size(comparison.pk_bytes as u64),
+            format_size(comparison.sk_bytes as u64),
+            format_size(comparison.signature_bytes as u64),
+            comparison.keygen_ms,
+            comparison.sign_ms,
+            comparison.verify_ms
+        );
+    }
+
+    if let Some(output) = output {
+        let comparison = Comparison {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: unix_now(),
+            cpus,
+            log_num_active_epochs,
+            signatures,
+            schemes,
+        };
+        let json = serde_json::to_vec_pretty(&comparison).map_err(io::Error::other)?;
+        fs::write(output, json)?;
+        println!("\n📄 Comparison written to: {}", output.display());
+    }
+
+    println!("\n✅ Compared every scheme in this build");
+    Ok(())
+}
+
+fn compare_scheme(
+    scheme: &dyn SchemeHandle,
+    log_num_active_epochs: usize,
+    signatures: usize,
+) -> io::Result<SchemeComparison> {
+    let mut rng = rand::rng();
+
+    let start = Instant::now();
+    let pair = scheme.key_gen(&mut rng, 0, 1 << log_num_active_epochs);
+    let keygen = start.elapsed();
+    let pk = pair.pk_bytes();
+    let sk = pair.sk_bytes();
+
+    let epochs: Vec<u32> = pair
+        .prepared_interval()
+        .take(signatures)
+        .map(|epoch| epoch as u32)
+        .collect();
+    let sign_latencies = scheme.sign_latencies(&sk, &epochs)?;
+
+    let mut signature_bytes = 0;
+    let mut verify_latencies = Vec::with_capacity(epochs.len());
+    for &epoch in &epochs {
+        let mut message = [0u8; MESSAGE_LENGTH];
+        rng.fill_bytes(&mut message);
+        let signature = scheme.sign(&sk, epoch, &message)?;
+        signature_bytes = signature.len();
+
+        let start = Instant::now();
+        let valid = scheme.verify(&pk, epoch, &message, &signature)?;
+        verify_latencies.push(start.elapsed());
+        if !valid {
+            return Err(io::Error::other(format!(
+                "{} rejected its own signature at epoch {}",
+                scheme.name(),
+                epoch
+            )));
+        }
+    }
+
+    Ok(SchemeComparison {
+        key_scheme: scheme.name().to_string(),
+        hash_function: scheme.hash_function().name().to_string(),
+        encoding: scheme.encoding().to_string(),
+        lifetime: scheme.lifetime(),
+        pk_bytes: pk.len(),
+        sk_bytes: sk.len(),
+        signature_bytes,
+        keygen_ms: millis(keygen),
+        sign_ms: mean_millis(&sign_latencies),
+        verify_ms: mean_millis(&verify_latencies),
+    })
+}
+
+fn mean_millis(latencies: &[Duration]) -> f64 {
+    millis(latencies.iter().sum::<Duration>()) / latencies.len() as f64
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
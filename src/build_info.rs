@@ -0,0 +1,55 @@
+//! How this binary was built, as recorded by `build.rs`: shown by
+//! `--version --verbose` and recorded in provenance records, so an artifact
+//! can be traced to the build that produced it and that build reproduced.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BuildInfo {
+    pub tool_version: String,
+    /// Output of `rustc --version`.
+    pub rustc: String,
+    /// Commit the binary was built from, suffixed `-dirty` if the tree had
+    /// uncommitted changes.
+    pub git_commit: String,
+    /// SHA-256 of `Cargo.lock`, pinning every dependency version.
+    pub lockfile_sha256: String,
+    pub target: String,
+    /// Cargo profile, `release` or `debug`.
+    pub profile: String,
+}
+
+impl BuildInfo {
+    /// Metadata of the running binary. Anything the build could not
+    /// determine is `unknown`.
+    pub fn current() -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            rustc: env!("HASHSIG_BUILD_RUSTC").to_string(),
+            git_commit: env!("HASHSIG_BUILD_GIT_COMMIT").to_string(),
+            lockfile_sha256: env!("HASHSIG_BUILD_LOCKFILE_SHA256").to_string(),
+            target: env!("HASHSIG_BUILD_TARGET").to_string(),
+            profile: env!("HASHSIG_BUILD_PROFILE").to_string(),
+        }
+    }
+}
+
+/// Whether the command line is exactly `--version --verbose`, in either
+/// order. clap stops at `--version`, so this is checked before parsing.
+pub fn wants_verbose_version() -> bool {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    args.len() == 2
+        && args.iter().any(|arg| arg == "--version" || arg == "-V")
+        && args.iter().any(|arg| arg == "--verbose" || arg == "-v")
+}
+
+/// Print the version with the build metadata.
+pub fn print_verbose(bin_name: &str) {
+    let build = BuildInfo::current();
+    println!("{} {}", bin_name, build.tool_version);
+    println!("rustc:           {}", build.rustc);
+    println!("git commit:      {}", build.git_commit);
+    println!("Cargo.lock hash: {}", build.lockfile_sha256);
+    println!("target:          {}", build.target);
+    println!("profile:         {}", build.profile);
+}
@@ -0,0 +1,222 @@
+//! `ceremony report`: a single transcript of a key generation ceremony for
+//! auditors.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::build_info::BuildInfo;
+use crate::checksum;
+use crate::entropy::PROVENANCE_FILE;
+use crate::manifest::{self, Manifest};
+use crate::metadata::unix_now;
+use crate::report::{self, ReportSigner};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum TranscriptFormat {
+    Markdown,
+    Json,
+}
+
+/// What the transcript records besides the key directory itself.
+pub struct TranscriptInputs<'a> {
+    pub operators: &'a [String],
+    pub notes: &'a [String],
+    /// Report written by `verify-dir --report`.
+    pub validation_report: Option<&'a Path>,
+}
+
+#[derive(Serialize)]
+struct Transcript {
+    tool_version: String,
+    build: BuildInfo,
+    created_at: u64,
+    dir: String,
+    /// ID of the `generate` run, as recorded in the manifest.
+    ceremony_id: Option<String>,
+    operators: Vec<String>,
+    notes: Vec<String>,
+    /// `provenance.json` written by `generate --audit-entropy`.
+    provenance: Option<Value>,
+    validation_report: Option<Value>,
+    /// The manifest exactly as stored.
+    manifest: Option<String>,
+    /// SHA-256 of every file in the directory, keyed by file name.
+    checksums: BTreeMap<String, String>,
+}
+
+/// Compile the transcript of the ceremony that produced `dir` into `output`,
+/// optionally signing it.
+pub fn report(
+    dir: &Path,
+    inputs: &TranscriptInputs,
+    format: TranscriptFormat,
+    output: &Path,
+    signer: Option<ReportSigner>,
+) -> io::Result<()> {
+    println!("📜 Compiling ceremony transcript for {}\n", dir.display());
+
+    // Parsed to fail early on a corrupt manifest; the raw text is kept
+    let parsed = Manifest::read_if_present(dir)?;
+    if parsed.is_none() {
+        println!("  ⚠️  No manifest in {}", dir.display());
+    }
+    let ceremony_id = parsed.and_then(|manifest| manifest.ceremony_id);
+    let manifest_path = manifest::manifest_path(dir);
+    let manifest = manifest_path
+        .exists()
+        .then(|| fs::read_to_string(&manifest_path))
+        .transpose()?;
+
+    let provenance_path = dir.join(PROVENANCE_FILE);
+    let provenance = if provenance_path.exists() {
+        Some(read_json(&provenance_path)?)
+    } else {
+        println!(
+            "  ⚠️  No {} (generate without --audit-entropy)",
+            PROVENANCE_FILE
+        );
+        None
+    };
+    let validation_report = inputs.validation_report.map(read_json).transpose()?;
+    if validation_report.is_none() {
+        println!("  ⚠️  No validation report (pass --validation-report from verify-dir)");
+    }
+
+    let transcript = Transcript {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        build: BuildInfo::current(),
+        created_at: unix_now(),
+        dir: dir.display().to_string(),
+        ceremony_id,
+        operators: inputs.operators.to_vec(),
+        notes: inputs.notes.to_vec(),
+        provenance,
+        validation_report,
+        manifest,
+        checksums: checksum::sha256_dir(dir)?,
+    };
+
+    let contents = match format {
+        TranscriptFormat::Json => {
+            serde_json::to_vec_pretty(&transcript).map_err(io::Error::other)?
+        }
+        TranscriptFormat::Markdown => to_markdown(&transcript).into_bytes(),
+    };
+    fs::write(output, &contents)?;
+    println!("\n✅ Transcript written to: {}", output.display());
+
+    if let Some(signer) = signer {
+        report::sign(&contents, output, &signer)?;
+    }
+
+    Ok(())
+}
+
+fn read_json(path: &Path) -> io::Result<Value> {
+    serde_json::from_slice(&fs::read(path)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        )
+    })
+}
+
+fn to_markdown(transcript: &Transcript) -> String {
+    // Writing to a `String` cannot fail
+    let mut md = String::new();
+
+    writeln!(md, "# Key Ceremony Transcript\n").unwrap();
+    writeln!(md, "- Key directory: `{}`", transcript.dir).unwrap();
+    if let Some(ceremony_id) = &transcript.ceremony_id {
+        writeln!(md, "- Ceremony ID: `{}`", ceremony_id).unwrap();
+    }
+    writeln!(
+        md,
+        "- Compiled by hash-sig-cli {} at unix time {}",
+        transcript.tool_version, transcript.created_at
+    )
+    .unwrap();
+    writeln!(
+        md,
+        "- Built from commit `{}` with {} for `{}` (Cargo.lock SHA-256 `{}`)",
+        transcript.build.git_commit,
+        transcript.build.rustc,
+        transcript.build.target,
+        transcript.build.lockfile_sha256
+    )
+    .unwrap();
+    if let Some(passed) = transcript
+        .validation_report
+        .as_ref()
+        .and_then(|report| report["passed"].as_bool())
+    {
+        writeln!(
+            md,
+            "- Validation: {}",
+            if passed { "passed" } else { "**FAILED**" }
+        )
+        .unwrap();
+    }
+
+    writeln!(md, "\n## Operators\n").unwrap();
+    if transcript.operators.is_empty() {
+        writeln!(md, "_None recorded._").unwrap();
+    }
+    for operator in &transcript.operators {
+        writeln!(md, "- {}", operator).unwrap();
+    }
+
+    writeln!(md, "\n## Annotations\n").unwrap();
+    if transcript.notes.is_empty() {
+        writeln!(md, "_None recorded._").unwrap();
+    }
+    for note in &transcript.notes {
+        writeln!(md, "- {}", note).unwrap();
+    }
+
+    write_json_section(
+        &mut md,
+        "Entropy Provenance",
+        transcript.provenance.as_ref(),
+    );
+    write_json_section(
+        &mut md,
+        "Validation Report",
+        transcript.validation_report.as_ref(),
+    );
+
+    writeln!(md, "\n## Manifest\n").unwrap();
+    match &transcript.manifest {
+        Some(manifest) => writeln!(md, "```yaml\n{}```", manifest).unwrap(),
+        None => writeln!(md, "_No manifest._").unwrap(),
+    }
+
+    writeln!(md, "\n## File Checksums\n").unwrap();
+    writeln!(md, "| File | SHA-256 |").unwrap();
+    writeln!(md, "| --- | --- |").unwrap();
+    for (name, sha256) in &transcript.checksums {
+        writeln!(md, "| `{}` | `{}` |", name, sha256).unwrap();
+    }
+
+    md
+}
+
+fn write_json_section(md: &mut String, title: &str, value: Option<&Value>) {
+    writeln!(md, "\n## {}\n", title).unwrap();
+    match value {
+        Some(value) => writeln!(
+            md,
+            "```json\n{}\n```",
+            serde_json::to_string_pretty(value).expect("JSON value serialization")
+        )
+        .unwrap(),
+        None => writeln!(md, "_Not available._").unwrap(),
+    }
+}
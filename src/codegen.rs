@@ -0,0 +1,184 @@
+//! `export --format code`: the validators' public keys as a source file of
+//! constant byte arrays, for genesis configs and test fixtures of client
+//! codebases that need them baked in.
+//!
+//! Keys are the SSZ-encoded public keys, in the order `--sort` gives, each
+//! commented with its key file prefix.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::manifest::{self, normalize_pubkey, Manifest, SortOrder};
+
+/// Bytes per line of an emitted array.
+const BYTES_PER_LINE: usize = 16;
+
+/// Go package name when the output directory's name is not one.
+const DEFAULT_GO_PACKAGE: &str = "pubkeys";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Lang {
+    /// A `pub const` array of byte slices
+    Rust,
+    /// A package-level slice of byte slices
+    Go,
+    /// An exported `readonly Uint8Array[]`
+    Ts,
+}
+
+impl Lang {
+    fn name(self) -> &'static str {
+        match self {
+            Lang::Rust => "Rust",
+            Lang::Go => "Go",
+            Lang::Ts => "TypeScript",
+        }
+    }
+}
+
+/// A validator's public key and its comment.
+struct Key {
+    label: String,
+    pubkey: Vec<u8>,
+}
+
+/// Write the public keys of the validators in the manifest of `dir`,
+/// canaries aside, in `order`, to `output` as `lang` source.
+pub fn export_code(dir: &Path, output: &Path, order: SortOrder, lang: Lang) -> io::Result<()> {
+    let mut manifest = Manifest::read(dir)?;
+    // Canaries are never registered
+    manifest.validators.retain(|entry| !entry.canary);
+    manifest.sort_validators(order);
+    let mut keys = Vec::with_capacity(manifest.validators.len());
+    for validator in &manifest.validators {
+        let pubkey = hex::decode(normalize_pubkey(&validator.pubkey_hex)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid pubkey_hex for {}: {}", validator.privkey_file, e),
+            )
+        })?;
+        let name = manifest::file_name(&validator.privkey_file);
+        let mut label = manifest::key_prefix(name).unwrap_or(name).to_string();
+        // In a mixed key set, the keys not of the manifest's scheme
+        if validator.key_scheme.is_some() {
+            label = format!("{} ({})", label, manifest.scheme_of(validator));
+        }
+        keys.push(Key { label, pubkey });
+    }
+
+    let header = format!(
+        "Public keys of the {} validators in {}, SSZ-encoded, in {} order.\nScheme: {}.",
+        keys.len(),
+        dir.display(),
+        order_name(order),
+        manifest.key_scheme
+    );
+    let source = match lang {
+        Lang::Rust => rust_source(&header, &keys),
+        Lang::Go => go_source(&header, &keys, &go_package(output)),
+        Lang::Ts => ts_source(&header, &keys),
+    };
+    fs::write(output, source)?;
+    println!(
+        "📦 Exported {} public keys as {} constants: {}",
+        keys.len(),
+        lang.name(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn order_name(order: SortOrder) -> &'static str {
+    match order {
+        SortOrder::Index => "validator index",
+        SortOrder::Pubkey => "public key",
+        SortOrder::Name => "key file name",
+    }
+}
+
+/// Package of a Go file at `output`: its directory's name, as Go expects,
+/// when that is a valid package name.
+fn go_package(output: &Path) -> String {
+    let dir_name = fs::canonicalize(output.parent().unwrap_or(Path::new(".")))
+        .ok()
+        .and_then(|dir| dir.file_name()?.to_str().map(str::to_string));
+    match dir_name {
+        Some(name)
+            if name.starts_with(|c: char| c.is_ascii_lowercase())
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') =>
+        {
+            name
+        }
+        _ => DEFAULT_GO_PACKAGE.to_string(),
+    }
+}
+
+/// `bytes` as `0x..` literals, [`BYTES_PER_LINE`] to a line, each line
+/// starting with `indent`.
+fn byte_lines(bytes: &[u8], indent: &str) -> String {
+    bytes
+        .chunks(BYTES_PER_LINE)
+        .map(|line| {
+            let literals: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+            format!("{}{},\n", indent, literals.join(", "))
+        })
+        .collect()
+}
+
+/// `text` as line comments starting with `prefix`.
+fn comment(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| format!("{} {}\n", prefix, line))
+        .collect()
+}
+
+fn rust_source(header: &str, keys: &[Key]) -> String {
+    let mut source = comment(header, "//");
+    source.push_str("// Generated by `hashsig export --format code --lang rust`; do not edit.\n\n");
+    source.push_str(&format!(
+        "pub const VALIDATOR_PUBKEYS: [&[u8]; {}] = [\n",
+        keys.len()
+    ));
+    for key in keys {
+        source.push_str(&format!("    // {}\n    &[\n", key.label));
+        source.push_str(&byte_lines(&key.pubkey, "        "));
+        source.push_str("    ],\n");
+    }
+    source.push_str("];\n");
+    source
+}
+
+fn go_source(header: &str, keys: &[Key], package: &str) -> String {
+    let mut source =
+        "// Code generated by hashsig export --format code --lang go. DO NOT EDIT.\n//\n"
+            .to_string();
+    source.push_str(&comment(header, "//"));
+    source.push_str(&format!("\npackage {}\n\n", package));
+    source.push_str("// ValidatorPubkeys holds the validators' SSZ-encoded public keys.\n");
+    source.push_str("var ValidatorPubkeys = [][]byte{\n");
+    for key in keys {
+        source.push_str(&format!("\t// {}\n\t{{\n", key.label));
+        source.push_str(&byte_lines(&key.pubkey, "\t\t"));
+        source.push_str("\t},\n");
+    }
+    source.push_str("}\n");
+    source
+}
+
+fn ts_source(header: &str, keys: &[Key]) -> String {
+    let mut source = comment(header, "//");
+    source.push_str("// Generated by `hashsig export --format code --lang ts`; do not edit.\n\n");
+    source.push_str("export const VALIDATOR_PUBKEYS: readonly Uint8Array[] = [\n");
+    for key in keys {
+        source.push_str(&format!("  // {}\n  Uint8Array.from([\n", key.label));
+        source.push_str(&byte_lines(&key.pubkey, "    "));
+        source.push_str("  ]),\n");
+    }
+    source.push_str("];\n");
+    source
+}
@@ -0,0 +1,158 @@
+//! Named configuration profiles.
+//!
+//! `config.toml` holds profiles of option defaults:
+//!
+//! ```toml
+//! [profiles.devnet-7]
+//! scheme = "SIGTopLevelTargetSumLifetime32Dim64Base8"
+//! export-format = "ssz"
+//! output-dir = "./devnet-7-keys"
+//! write-metadata = true
+//! ```
+//!
+//! Keys are long option names. `--profile devnet-7` turns every entry into
+//! the default of that option on each subcommand that has it, so flags
+//! given on the command line still win and values are validated exactly
+//! like command line arguments.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::Command;
+use serde::Deserialize;
+
+/// Environment variable overriding the config file location.
+pub const CONFIG_ENV: &str = "HASHSIG_CONFIG";
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    profiles: BTreeMap<String, BTreeMap<String, toml::Value>>,
+}
+
+/// Directory for hashsig's own files: `$XDG_CONFIG_HOME/hashsig`, else
+/// `$HOME/.config/hashsig`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("hashsig"))
+}
+
+/// Default config file location: `$HASHSIG_CONFIG`, else `config.toml` in
+/// [`config_dir`].
+pub fn default_config_path() -> Option<PathBuf> {
+    match env::var_os(CONFIG_ENV) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(config_dir()?.join("config.toml")),
+    }
+}
+
+/// Make the entries of profile `name` the defaults of `command`'s
+/// subcommands.
+pub fn apply_profile(mut command: Command, name: &str) -> io::Result<Command> {
+    let path = default_config_path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "No config file location (set {} or HOME) for --profile {}",
+                CONFIG_ENV, name
+            ),
+        )
+    })?;
+    let mut profiles = read_config(&path)?.profiles;
+    let profile = profiles.remove(name).ok_or_else(|| {
+        let available: Vec<_> = profiles.keys().map(String::as_str).collect();
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "No profile {} in {} (available: {})",
+                name,
+                path.display(),
+                available.join(", ")
+            ),
+        )
+    })?;
+
+    for (key, value) in profile {
+        let id = key.replace('-', "_");
+        let values = option_values(&value).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} in profile {} must be a string, number, boolean or an array of them",
+                    key, name
+                ),
+            )
+        })?;
+
+        let subcommands: Vec<String> = command
+            .get_subcommands()
+            .filter(|subcommand| {
+                subcommand
+                    .get_arguments()
+                    .any(|arg| arg.get_id().as_str() == id)
+            })
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect();
+        // An entry no subcommand can use is almost certainly a typo
+        if subcommands.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unknown option {} in profile {} ({})",
+                    key,
+                    name,
+                    path.display()
+                ),
+            ));
+        }
+        for subcommand in subcommands {
+            let id = id.clone();
+            let values = values.clone();
+            command = command.mut_subcommand(subcommand, move |subcommand| {
+                subcommand.mut_arg(id, move |arg| arg.default_values(values))
+            });
+        }
+    }
+
+    Ok(command)
+}
+
+fn read_config(path: &Path) -> io::Result<Config> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read config {}: {}", path.display(), e),
+        )
+    })?;
+    toml::from_str(&contents).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse config {}: {}", path.display(), e),
+        )
+    })
+}
+
+/// A profile entry as it would be written on the command line: one value,
+/// or one per element of an array, as for an option given repeatedly (e.g.
+/// `batch`).
+fn option_values(value: &toml::Value) -> Option<Vec<String>> {
+    match value {
+        toml::Value::Array(values) => values.iter().map(option_value).collect(),
+        value => option_value(value).map(|value| vec![value]),
+    }
+}
+
+fn option_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value.clone()),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        toml::Value::Datetime(_) | toml::Value::Array(_) | toml::Value::Table(_) => None,
+    }
+}
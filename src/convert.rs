@@ -0,0 +1,205 @@
+//! `convert`: move keys and signatures between this CLI's SSZ files and the
+//! serialization of the leansig reference implementation, for interop
+//! testing.
+//!
+//! The reference format is the serde JSON of leansig's own types, as the
+//! reference tools read and write it; it is also what `generate
+//! --export-format both` writes as the legacy `.json` export. Everything is
+//! decoded and re-encoded through leansig, so a file that converts is a
+//! valid object of its scheme, and the result must convert back to exactly
+//! the input before it is written.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::exit::{self, Failure};
+use crate::scheme::{self, KeyKind, SchemeHandle};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// SSZ, as this CLI writes and reads it (`.ssz`, `.sig`)
+    Ssz,
+    /// Serde JSON of the leansig reference implementation
+    Reference,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ObjectKind {
+    Public,
+    Secret,
+    Signature,
+}
+
+impl ObjectKind {
+    /// Kind of the object in `path`, from its name as this CLI writes it.
+    fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.contains("_pk.") {
+            Some(ObjectKind::Public)
+        } else if name.contains("_sk.") {
+            Some(ObjectKind::Secret)
+        } else if name.ends_with(".sig") {
+            Some(ObjectKind::Signature)
+        } else {
+            None
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ObjectKind::Public => "public key",
+            ObjectKind::Secret => "secret key",
+            ObjectKind::Signature => "signature",
+        }
+    }
+}
+
+/// Convert the key or signature in `input` from `from` to `to` and write it
+/// to `output`, which must not exist yet, or to stdout if `output` is
+/// `None`. `kind` defaults to the one [`ObjectKind::detect`] finds, the
+/// scheme to the one recorded next to `input`.
+pub fn convert(
+    input: &Path,
+    output: Option<&Path>,
+    from: Format,
+    to: Format,
+    kind: Option<ObjectKind>,
+    scheme_name: Option<&str>,
+) -> io::Result<()> {
+    if from == to {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            "--from and --to are the same format; nothing to convert",
+        ));
+    }
+    if let Some(output) = output.filter(|output| output.exists()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite existing file {}", output.display()),
+        ));
+    }
+    let kind = kind.or_else(|| ObjectKind::detect(input)).ok_or_else(|| {
+        exit::error(
+            Failure::InvalidArgs,
+            format!(
+                "Cannot tell whether {} is a public key, secret key or signature; pass --kind",
+                input.display()
+            ),
+        )
+    })?;
+    // SSZ is binary, and a secret key must not end up in a terminal's scrollback
+    if output.is_none()
+        && (to == Format::Ssz || kind == ObjectKind::Secret)
+        && io::stdout().is_terminal()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Refusing to write a {} to a terminal; redirect or pipe stdout",
+                match to {
+                    Format::Reference => kind.describe().to_string(),
+                    Format::Ssz => format!("binary {}", kind.describe()),
+                }
+            ),
+        ));
+    }
+    let scheme = scheme::for_key(scheme_name, input)?;
+    let contents = Zeroizing::new(fs::read(input)?);
+
+    let converted = Zeroizing::new(match (kind, to) {
+        (ObjectKind::Signature, Format::Reference) => {
+            let json = scheme
+                .signature_json(&contents)
+                .map_err(|reason| corrupt(input, &reason))?;
+            serde_json::to_vec(&json).map_err(io::Error::other)?
+        }
+        (ObjectKind::Signature, Format::Ssz) => {
+            return Err(exit::error(
+                Failure::InvalidArgs,
+                "Signatures can only be converted to the reference format; leansig does not decode them from JSON",
+            ));
+        }
+        (ObjectKind::Public, to) => convert_key(scheme, KeyKind::Public, &contents, to, input)?,
+        (ObjectKind::Secret, to) => convert_key(scheme, KeyKind::Secret, &contents, to, input)?,
+    });
+
+    let status = format!(
+        "✅ Converted {} ({} of {}) to {}",
+        input.display(),
+        kind.describe(),
+        scheme.name(),
+        match to {
+            Format::Ssz => "SSZ",
+            Format::Reference => "the reference format",
+        }
+    );
+    match output {
+        Some(output) => {
+            fs::write(output, &converted)?;
+            println!("{}: {}", status, output.display());
+        }
+        // Status goes to stderr so it cannot corrupt the piped bytes
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&converted)?;
+            stdout.flush()?;
+            eprintln!("{}: stdout", status);
+        }
+    }
+    Ok(())
+}
+
+/// Convert the key `contents` to `to`, checking that the result converts
+/// back to the same key.
+fn convert_key(
+    scheme: &dyn SchemeHandle,
+    kind: KeyKind,
+    contents: &[u8],
+    to: Format,
+    input: &Path,
+) -> io::Result<Vec<u8>> {
+    match to {
+        Format::Reference => {
+            let json = scheme
+                .ssz_to_json(kind, contents)
+                .map_err(|reason| corrupt(input, &reason))?;
+            let json = Zeroizing::new(serde_json::to_vec(&json).map_err(io::Error::other)?);
+            if scheme.json_to_ssz(kind, &json).ok().as_deref() != Some(contents) {
+                return Err(round_trip_failed(input));
+            }
+            Ok(json.to_vec())
+        }
+        Format::Ssz => {
+            let ssz = scheme
+                .json_to_ssz(kind, contents)
+                .map_err(|reason| corrupt(input, &reason))?;
+            let given: Value = serde_json::from_slice(contents).map_err(io::Error::other)?;
+            if scheme.ssz_to_json(kind, &ssz).ok() != Some(given) {
+                return Err(round_trip_failed(input));
+            }
+            Ok(ssz)
+        }
+    }
+}
+
+fn corrupt(input: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Failed to decode {}: {}", input.display(), reason),
+    )
+}
+
+fn round_trip_failed(input: &Path) -> io::Error {
+    exit::error(
+        Failure::VerificationFailed,
+        format!(
+            "{} does not convert back to the same object; nothing was written",
+            input.display()
+        ),
+    )
+}
@@ -0,0 +1,265 @@
+//! `copy`: copy a key set with every file verified, resumably.
+//!
+//! Each file is copied to `<name>.partial` while its source is hashed, synced,
+//! read back and checked against that hash, and only then renamed into
+//! place. Progress is recorded in `.hashsig-copy.json` in the destination
+//! after every file, so an interrupted copy picks up where it stopped when
+//! run again. Once every file is in place, a final audit re-reads the whole
+//! destination against the source checksums.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::{self, HashingReader};
+use crate::exit::{self, Failure};
+use crate::keystore::{KeyStore, ManifestKeyStore};
+use crate::layout::{self, Layout, LAYOUT_FILE};
+use crate::manifest::{self, Manifest, MANIFEST_FILE};
+
+/// Progress of a copy, kept in the destination until the audit passes.
+pub const COPY_STATE_FILE: &str = ".hashsig-copy.json";
+
+#[derive(Serialize, Deserialize)]
+struct CopyState {
+    source: String,
+    files: BTreeMap<String, CopiedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CopiedFile {
+    size: u64,
+    /// Modification time of the source, in seconds since the Unix epoch,
+    /// so an unchanged source is not hashed again on resume.
+    modified: u64,
+    sha256: String,
+    done: bool,
+}
+
+/// Copy the key set in `src` to `dst`. Secret keys the manifest places
+/// outside `src` are copied too, and the copy's manifest then refers to
+/// every key by file name.
+pub fn copy(src: &Path, dst: &Path) -> io::Result<()> {
+    let state_path = dst.join(COPY_STATE_FILE);
+    let resuming = state_path.exists();
+    if !resuming && manifest::manifest_path(dst).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                dst.display()
+            ),
+        ));
+    }
+    fs::create_dir_all(dst)?;
+    // The copy is flat whatever the layout of the source
+    layout::mark(dst, Layout::Flat)?;
+    let source_name = fs::canonicalize(src)?.display().to_string();
+
+    let mut state = if resuming {
+        let state: CopyState = serde_json::from_slice(&fs::read(&state_path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", state_path.display(), e),
+            )
+        })?;
+        if state.source != source_name {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is an unfinished copy of {}, not {}",
+                    dst.display(),
+                    state.source,
+                    source_name
+                ),
+            ));
+        }
+        state
+    } else {
+        CopyState {
+            source: source_name,
+            files: BTreeMap::new(),
+        }
+    };
+
+    let store = ManifestKeyStore::open(src)?;
+    let sources = source_paths(&store)?;
+    let manifest = flat_manifest(src)?;
+    // Files gone from the source since the interrupted run
+    state.files.retain(|name, _| sources.contains_key(name));
+    let done = state.files.values().filter(|f| f.done).count();
+    if resuming {
+        println!(
+            "🔄 Resuming the copy of {} into {}: {} of {} files already copied\n",
+            src.display(),
+            dst.display(),
+            done,
+            sources.len()
+        );
+    } else {
+        println!(
+            "📦 Copying {} files from {} into {}\n",
+            sources.len(),
+            src.display(),
+            dst.display()
+        );
+    }
+
+    // The manifest goes last, so a destination with a manifest and no
+    // state file is always complete
+    let names: Vec<&String> = sources
+        .keys()
+        .filter(|name| name.as_str() != MANIFEST_FILE)
+        .chain(sources.keys().filter(|name| name.as_str() == MANIFEST_FILE))
+        .collect();
+    for name in names {
+        let path = &sources[name];
+        let (size, modified) = stat(path)?;
+        let unchanged = |f: &&mut CopiedFile| f.size == size && f.modified == modified;
+        if let Some(file) = state.files.get_mut(name).filter(unchanged) {
+            if file.done && dst.join(name).is_file() {
+                continue;
+            }
+        }
+
+        let sha256 = match (name.as_str(), &manifest) {
+            (MANIFEST_FILE, Some(manifest)) => write_verified(&mut manifest.as_bytes(), dst, name)?,
+            _ => write_verified(&mut File::open(path)?, dst, name)?,
+        };
+        state.files.insert(
+            name.clone(),
+            CopiedFile {
+                size,
+                modified,
+                sha256,
+                done: true,
+            },
+        );
+        save_state(&state, &state_path)?;
+        println!("  ✅ {}", name);
+    }
+
+    println!(
+        "\n🔍 Auditing {} files in {}",
+        state.files.len(),
+        dst.display()
+    );
+    let mut failures = Vec::new();
+    for (name, file) in &mut state.files {
+        let path = dst.join(name);
+        if !path.is_file() || checksum::sha256_file(&path)? != file.sha256 {
+            // Copied again on the next run
+            file.done = false;
+            failures.push(name.clone());
+        }
+    }
+    if !failures.is_empty() {
+        save_state(&state, &state_path)?;
+        for name in &failures {
+            println!("  ❌ {}: does not match its source", name);
+        }
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} copied files failed the audit; run the copy again to recopy them",
+                failures.len(),
+                state.files.len()
+            ),
+        ));
+    }
+    fs::remove_file(&state_path)?;
+
+    println!(
+        "\n✅ {} files copied and verified into {}",
+        state.files.len(),
+        dst.display()
+    );
+    Ok(())
+}
+
+/// Every file of the key set, by file name, without leftovers of copying
+/// into it or its layout marker.
+fn source_paths(store: &ManifestKeyStore) -> io::Result<BTreeMap<String, PathBuf>> {
+    let mut paths = BTreeMap::new();
+    for name in store.list()? {
+        if name == COPY_STATE_FILE || name == LAYOUT_FILE || name.ends_with(".partial") {
+            continue;
+        }
+        let path = store.file_path(&name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such key file {}", name),
+            )
+        })?;
+        paths.insert(name, path);
+    }
+    Ok(paths)
+}
+
+/// The manifest of `src` rewritten to refer to keys by file name, if it
+/// refers to any by path.
+fn flat_manifest(src: &Path) -> io::Result<Option<String>> {
+    let Some(mut manifest) = Manifest::read_if_present(src)? else {
+        return Ok(None);
+    };
+    let privkey_files = manifest
+        .validators
+        .iter_mut()
+        .map(|v| &mut v.privkey_file)
+        .chain(manifest.revoked.iter_mut().map(|t| &mut t.privkey_file));
+    let mut rewritten = false;
+    for privkey_file in privkey_files {
+        let name = manifest::file_name(privkey_file).to_string();
+        if name != *privkey_file {
+            *privkey_file = name;
+            rewritten = true;
+        }
+    }
+    Ok(rewritten.then(|| manifest.to_yaml()))
+}
+
+/// Copy `source` to `<dir>/<name>` through `<name>.partial`, checking the
+/// synced copy against the SHA-256 of what was read. Returns that hash.
+fn write_verified(source: &mut dyn Read, dir: &Path, name: &str) -> io::Result<String> {
+    let partial = dir.join(format!("{}.partial", name));
+    let mut reader = HashingReader::new(source);
+    let mut file = File::create(&partial)?;
+    io::copy(&mut reader, &mut file)?;
+    file.flush()?;
+    file.sync_all()?;
+    drop(file);
+
+    let sha256 = reader.hex_digest();
+    if checksum::sha256_file(&partial)? != sha256 {
+        fs::remove_file(&partial)?;
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("{} was corrupted while it was written", name),
+        ));
+    }
+    fs::rename(&partial, dir.join(name))?;
+    Ok(sha256)
+}
+
+fn stat(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((metadata.len(), modified))
+}
+
+/// Write the state next to its final path first, so an interruption never
+/// leaves it half-written.
+fn save_state(state: &CopyState, path: &Path) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(state).map_err(io::Error::other)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(tmp, path)
+}
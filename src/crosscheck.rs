@@ -0,0 +1,114 @@
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::exit::{self, Failure};
+use crate::keystore::{FsKeyStore, KeyStore};
+use crate::scheme::{self, KeyKind, SchemeHandle};
+
+/// For every key exported as both SSZ and legacy JSON in `dir`, load it
+/// through both deserializers and check that the results agree.
+pub fn crosscheck(dir: &Path) -> io::Result<()> {
+    let store = FsKeyStore::new(dir);
+    let files = store.list()?;
+    let scheme = scheme::for_dir(dir)?.unwrap_or_else(scheme::default_scheme);
+
+    println!(
+        "🔍 Cross-checking SSZ and JSON encodings in {}\n",
+        dir.display()
+    );
+
+    let mut checked = 0;
+    let mut failures = 0;
+    let ssz_names = files
+        .iter()
+        .filter(|name| name.ends_with("_pk.ssz") || name.ends_with("_sk.ssz"));
+    for ssz_name in ssz_names {
+        let json_name = format!("{}.json", ssz_name.trim_end_matches(".ssz"));
+        if !files.contains(&json_name) {
+            continue;
+        }
+
+        let kind = if ssz_name.ends_with("_pk.ssz") {
+            KeyKind::Public
+        } else {
+            KeyKind::Secret
+        };
+        let result = crosscheck_key(scheme, kind, &store, ssz_name, &json_name)?;
+        checked += 1;
+
+        match result {
+            Ok(()) => println!("  ✅ {} ↔ {}", ssz_name, json_name),
+            Err(reason) => {
+                println!("  ❌ {} ↔ {}: {}", ssz_name, json_name, reason);
+                failures += 1;
+            }
+        }
+    }
+
+    if checked == 0 {
+        println!("  ⚠️  No keys exported in both SSZ and JSON");
+    }
+
+    if failures > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} keys differ between SSZ and JSON",
+                failures, checked
+            ),
+        ));
+    }
+
+    println!(
+        "\n✅ {} keys have identical SSZ and JSON encodings",
+        checked
+    );
+    Ok(())
+}
+
+/// Returns `Ok(Err(reason))` when the two encodings disagree and `Err` only
+/// for I/O failures.
+fn crosscheck_key(
+    scheme: &dyn SchemeHandle,
+    kind: KeyKind,
+    store: &dyn KeyStore,
+    ssz_name: &str,
+    json_name: &str,
+) -> io::Result<Result<(), String>> {
+    let ssz_bytes = store.load(ssz_name)?;
+    let json_bytes = store.load(json_name)?;
+
+    // Keys don't implement `PartialEq`, so compare them through both
+    // serializations instead
+    let reencoded = match scheme.reencode_ssz(kind, &ssz_bytes) {
+        Ok(bytes) => bytes,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    if reencoded != ssz_bytes {
+        return Ok(Err("SSZ key does not re-serialize to the same bytes".into()));
+    }
+    let ssz_of_json = match scheme.json_to_ssz(kind, &json_bytes) {
+        Ok(bytes) => bytes,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    if ssz_of_json != ssz_bytes {
+        return Ok(Err(
+            "JSON key re-serializes to different SSZ bytes than the .ssz file".into(),
+        ));
+    }
+
+    let json_of_ssz = match scheme.ssz_to_json(kind, &ssz_bytes) {
+        Ok(value) => value,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let json_file: Value = serde_json::from_slice(&json_bytes).map_err(io::Error::other)?;
+    if json_of_ssz != json_file {
+        return Ok(Err(
+            "JSON key does not re-serialize to the same JSON document".into(),
+        ));
+    }
+
+    Ok(Ok(()))
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::checksum;
+use crate::exit::{self, Failure};
+use crate::manifest::{self, short_pubkey, Manifest, ManifestEntry, SortOrder};
+
+/// Compare two key directories, failing if they are not identical.
+/// Validators are listed in `order`, files by name.
+pub fn diff(dir_a: &Path, dir_b: &Path, order: SortOrder) -> io::Result<()> {
+    println!(
+        "🔍 Comparing {} (A) with {} (B)\n",
+        dir_a.display(),
+        dir_b.display()
+    );
+
+    let files_a = checksum::sha256_dir(dir_a)?;
+    let files_b = checksum::sha256_dir(dir_b)?;
+    let mut differences = 0;
+
+    match (
+        Manifest::read_if_present(dir_a)?,
+        Manifest::read_if_present(dir_b)?,
+    ) {
+        (Some(mut manifest_a), Some(mut manifest_b)) => {
+            manifest_a.sort_validators(order);
+            manifest_b.sort_validators(order);
+            differences += diff_manifests(&manifest_a, &manifest_b, &files_a, &files_b);
+        }
+        (None, None) => println!("⚠️  Neither directory has a manifest, comparing files only\n"),
+        (Some(_), None) => {
+            println!("➖ Manifest missing from B\n");
+            differences += 1;
+        }
+        (None, Some(_)) => {
+            println!("➕ Manifest only present in B\n");
+            differences += 1;
+        }
+    }
+
+    println!("📁 Files:");
+    let mut file_differences = 0;
+    let mut names_a: Vec<&String> = files_a.keys().collect();
+    names_a.sort_by(|a, b| manifest::compare_names(a, b));
+    for name in names_a {
+        let sha_a = &files_a[name];
+        match files_b.get(name) {
+            None => {
+                println!("  ➖ {} (only in A)", name);
+                file_differences += 1;
+            }
+            Some(sha_b) if sha_a != sha_b => {
+                println!("  ✏️  {} (checksum differs)", name);
+                file_differences += 1;
+            }
+            Some(_) => {}
+        }
+    }
+    let mut only_b: Vec<&String> = files_b
+        .keys()
+        .filter(|name| !files_a.contains_key(*name))
+        .collect();
+    only_b.sort_by(|a, b| manifest::compare_names(a, b));
+    for name in only_b {
+        println!("  ➕ {} (only in B)", name);
+        file_differences += 1;
+    }
+    if file_differences == 0 {
+        println!("  ✅ {} files, all bit-identical", files_a.len());
+    }
+    differences += file_differences;
+
+    if differences > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("Key directories differ ({} differences)", differences),
+        ));
+    }
+
+    println!("\n✅ Key directories are identical");
+    Ok(())
+}
+
+fn diff_manifests(
+    a: &Manifest,
+    b: &Manifest,
+    files_a: &BTreeMap<String, String>,
+    files_b: &BTreeMap<String, String>,
+) -> usize {
+    let mut differences = 0;
+
+    println!("📄 Manifest:");
+    let fields = [
+        ("key_scheme", a.key_scheme.clone(), b.key_scheme.clone()),
+        (
+            "hash_function",
+            a.hash_function.clone(),
+            b.hash_function.clone(),
+        ),
+        ("encoding", a.encoding.clone(), b.encoding.clone()),
+        ("lifetime", a.lifetime.to_string(), b.lifetime.to_string()),
+        (
+            "log_num_active_epochs",
+            a.log_num_active_epochs.to_string(),
+            b.log_num_active_epochs.to_string(),
+        ),
+        (
+            "num_active_epochs",
+            a.num_active_epochs.to_string(),
+            b.num_active_epochs.to_string(),
+        ),
+        (
+            "num_validators",
+            a.num_validators.to_string(),
+            b.num_validators.to_string(),
+        ),
+    ];
+    for (field, value_a, value_b) in fields {
+        if value_a != value_b {
+            println!("  ✏️  {}: {} → {}", field, value_a, value_b);
+            differences += 1;
+        }
+    }
+
+    let validators_a: BTreeMap<&str, &ManifestEntry> = a
+        .validators
+        .iter()
+        .map(|v| (v.pubkey_hex.as_str(), v))
+        .collect();
+    let validators_b: BTreeMap<&str, &ManifestEntry> = b
+        .validators
+        .iter()
+        .map(|v| (v.pubkey_hex.as_str(), v))
+        .collect();
+
+    let mut common = Vec::new();
+    for entry_a in &a.validators {
+        let pubkey = entry_a.pubkey_hex.as_str();
+        let Some(entry_b) = validators_b.get(pubkey) else {
+            println!(
+                "  ➖ removed {} ({})",
+                short_pubkey(pubkey),
+                entry_a.privkey_file
+            );
+            differences += 1;
+            continue;
+        };
+
+        let sk_a = files_a.get(&entry_a.privkey_file);
+        let sk_b = files_b.get(&entry_b.privkey_file);
+        if entry_a.index != entry_b.index
+            || entry_a.privkey_file != entry_b.privkey_file
+            || sk_a.is_none()
+            || sk_a != sk_b
+        {
+            println!(
+                "  ✏️  changed {} ({} → {})",
+                short_pubkey(pubkey),
+                entry_a.privkey_file,
+                entry_b.privkey_file
+            );
+            differences += 1;
+        }
+        common.push(pubkey);
+    }
+    for entry_b in &b.validators {
+        let pubkey = entry_b.pubkey_hex.as_str();
+        if !validators_a.contains_key(pubkey) {
+            println!(
+                "  ➕ added {} ({})",
+                short_pubkey(pubkey),
+                entry_b.privkey_file
+            );
+            differences += 1;
+        }
+    }
+
+    println!("  🔁 {} pubkeys present in both:", common.len());
+    for pubkey in common {
+        println!("     {}", short_pubkey(pubkey));
+    }
+    println!();
+
+    differences
+}
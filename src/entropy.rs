@@ -0,0 +1,276 @@
+//! Sources of randomness for key generation.
+//!
+//! Every key pair gets its own RNG from an [`EntropySource`], so OS entropy,
+//! a deterministic master seed and hardware RNG devices are interchangeable.
+//! With auditing enabled, the bytes each key consumes are hashed and the
+//! digests are committed to in a provenance file.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::build_info::BuildInfo;
+use crate::keystore::KeyStore;
+use crate::metadata::unix_now;
+
+/// File written next to the keys when entropy auditing is enabled.
+pub const PROVENANCE_FILE: &str = "provenance.json";
+
+/// Domain separator for per-key seeds derived from a master seed.
+const SEED_DOMAIN: &[u8] = b"hashsig/validator/";
+
+/// Minimum length of a master seed, in bytes.
+const MIN_SEED_LEN: usize = 32;
+
+/// Prefix of the derivation path of a key derived from a master seed.
+const DERIVATION_PATH_PREFIX: &str = "m/validator/";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum EntropyKind {
+    /// Operating system entropy
+    Os,
+    /// Keys derived from a master seed (`--seed-file`), reproducible
+    Deterministic,
+    /// Per-key seeds read from a hardware RNG or token device (`--entropy-device`)
+    Device,
+}
+
+pub trait EntropySource: Send + Sync {
+    /// Description recorded in the provenance file. Must not reveal secrets.
+    fn describe(&self) -> String;
+
+    /// RNG used to generate the key pair with the given index.
+    fn rng_for(&self, index: usize) -> io::Result<Box<dyn RngCore>>;
+
+    /// Path from which the key pair with the given index is derived, for
+    /// sources that can regenerate a single key.
+    fn derivation_path(&self, _index: usize) -> Option<String> {
+        None
+    }
+}
+
+/// Derivation path of the key with the given index: `m/validator/{index}`.
+pub fn derivation_path(index: usize) -> String {
+    format!("{}{}", DERIVATION_PATH_PREFIX, index)
+}
+
+/// Key index of a derivation path written by [`derivation_path`].
+pub fn parse_derivation_path(path: &str) -> Result<usize, String> {
+    path.strip_prefix(DERIVATION_PATH_PREFIX)
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| {
+            format!(
+                "Invalid derivation path {} (expected {}<index>)",
+                path, DERIVATION_PATH_PREFIX
+            )
+        })
+}
+
+/// Open the entropy source selected on the command line.
+pub fn open_source(
+    kind: EntropyKind,
+    seed_file: Option<&Path>,
+    device: &Path,
+) -> io::Result<Box<dyn EntropySource>> {
+    Ok(match kind {
+        EntropyKind::Os => Box::new(OsEntropy),
+        EntropyKind::Deterministic => {
+            let seed_file = seed_file.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--entropy-source deterministic requires --seed-file",
+                )
+            })?;
+            Box::new(DeterministicEntropy::from_seed_file(seed_file)?)
+        }
+        EntropyKind::Device => Box::new(DeviceEntropy {
+            path: device.to_path_buf(),
+        }),
+    })
+}
+
+/// Fresh operating system entropy for every key.
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn describe(&self) -> String {
+        "os".to_string()
+    }
+
+    fn rng_for(&self, _index: usize) -> io::Result<Box<dyn RngCore>> {
+        Ok(Box::new(rand::rng()))
+    }
+}
+
+/// Every key derived from one master seed, so a ceremony can be replayed
+/// exactly regardless of thread scheduling. Anyone holding the seed can
+/// recreate every secret key.
+pub struct DeterministicEntropy {
+    seed: Vec<u8>,
+}
+
+impl DeterministicEntropy {
+    /// Read a hex-encoded seed of at least 32 bytes from `path`.
+    pub fn from_seed_file(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let seed = hex::decode(contents.trim().trim_start_matches("0x")).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid seed hex in {}: {}", path.display(), e),
+            )
+        })?;
+        if seed.len() < MIN_SEED_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Seed in {} must be at least {} bytes, got {}",
+                    path.display(),
+                    MIN_SEED_LEN,
+                    seed.len()
+                ),
+            ));
+        }
+        Ok(Self { seed })
+    }
+}
+
+impl EntropySource for DeterministicEntropy {
+    fn describe(&self) -> String {
+        // Identifies the seed without revealing it
+        format!(
+            "deterministic (seed sha256 {})",
+            hex::encode(Sha256::digest(&self.seed))
+        )
+    }
+
+    fn rng_for(&self, index: usize) -> io::Result<Box<dyn RngCore>> {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.seed);
+        hasher.update(SEED_DOMAIN);
+        hasher.update((index as u64).to_le_bytes());
+        Ok(Box::new(ChaCha20Rng::from_seed(hasher.finalize().into())))
+    }
+
+    fn derivation_path(&self, index: usize) -> Option<String> {
+        Some(derivation_path(index))
+    }
+}
+
+/// Each key's RNG seeded with 32 bytes read from a device such as
+/// `/dev/hwrng` or a hardware token exposing a character device.
+pub struct DeviceEntropy {
+    path: PathBuf,
+}
+
+impl EntropySource for DeviceEntropy {
+    fn describe(&self) -> String {
+        format!("device ({})", self.path.display())
+    }
+
+    fn rng_for(&self, _index: usize) -> io::Result<Box<dyn RngCore>> {
+        let mut seed = [0u8; 32];
+        File::open(&self.path)
+            .and_then(|mut device| device.read_exact(&mut seed))
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to read entropy from {}: {}", self.path.display(), e),
+                )
+            })?;
+        Ok(Box::new(ChaCha20Rng::from_seed(seed)))
+    }
+}
+
+/// RNG adapter that hashes every byte it hands out, so the entropy consumed
+/// by a key can be committed to without revealing it.
+pub struct AuditedRng<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: RngCore> AuditedRng<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Hex-encoded SHA-256 of all bytes produced so far.
+    pub fn hex_digest(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
+
+impl<R: RngCore> RngCore for AuditedRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.inner.next_u32();
+        self.hasher.update(value.to_le_bytes());
+        value
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.inner.next_u64();
+        self.hasher.update(value.to_le_bytes());
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        self.hasher.update(&*dest);
+    }
+}
+
+/// Entropy consumed by one key pair.
+#[derive(Serialize)]
+pub struct KeyEntropy {
+    pub privkey_file: String,
+    pub entropy_sha256: String,
+}
+
+/// Contents of [`PROVENANCE_FILE`].
+#[derive(Serialize)]
+pub struct EntropyProvenance {
+    pub tool_version: String,
+    pub build: BuildInfo,
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ceremony_id: Option<String>,
+    pub entropy_source: String,
+    /// SHA-256 over the hex `entropy_sha256` of every key, in key order.
+    pub entropy_commitment: String,
+    pub keys: Vec<KeyEntropy>,
+}
+
+impl EntropyProvenance {
+    pub fn new(
+        source: &dyn EntropySource,
+        ceremony_id: Option<&str>,
+        keys: Vec<KeyEntropy>,
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        for key in &keys {
+            hasher.update(key.entropy_sha256.as_bytes());
+        }
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            build: BuildInfo::current(),
+            created_at: unix_now(),
+            ceremony_id: ceremony_id.map(str::to_string),
+            entropy_source: source.describe(),
+            entropy_commitment: hex::encode(hasher.finalize()),
+            keys,
+        }
+    }
+
+    pub fn write(&self, store: &dyn KeyStore) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        store.store(PROVENANCE_FILE, &json)
+    }
+}
@@ -0,0 +1,851 @@
+//! `escrow`: split a key directory among custodians, any `threshold` of whom
+//! can recover it.
+//!
+//! The directory is packed into a backup bundle (see [`backup`]) under a
+//! random 32-byte secret, and the secret is Shamir-split into one share per
+//! custodian. Each share is encrypted to its custodian's X25519 key, so every
+//! custodian bundle holds the same encrypted key set but only its own share:
+//!
+//! ```text
+//! magic (8) | version (1) | header length (u32 LE) | JSON header | backup bundle
+//! ```
+//!
+//! A share is sealed with ChaCha20-Poly1305 under
+//! `SHA-256(context || X25519(ephemeral, recipient) || ephemeral || recipient)`,
+//! authenticating its index and the SHA-256 of the embedded bundle.
+//!
+//! Recovered secrets, shares and, in `escrow verify`, decrypted key files are
+//! zeroized as soon as they are no longer needed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::backup;
+use crate::checksum::{self, HashingReader};
+use crate::exit::{self, Failure};
+use crate::manifest::{self, normalize_pubkey, short_pubkey, Manifest, MANIFEST_FILE};
+use crate::metadata::unix_now;
+use crate::scheme;
+use crate::shamir;
+
+const MAGIC: &[u8; 8] = b"HSIGESCR";
+const VERSION: u8 = 1;
+const SHARE_KEY_CONTEXT: &[u8] = b"hashsig/escrow/share-key/v1";
+
+/// Extension of custodian bundles.
+const BUNDLE_EXTENSION: &str = "escrow";
+
+/// Backup bundle staged in the output directory while the custodian bundles
+/// are written. It is encrypted, and deleted afterwards.
+const PAYLOAD_FILE: &str = ".escrow-payload.bundle";
+
+#[derive(Serialize, Deserialize)]
+struct EscrowHeader {
+    tool_version: String,
+    created_at: u64,
+    source_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ceremony_id: Option<String>,
+    threshold: u8,
+    shares: u8,
+    /// x coordinate of this bundle's share, from 1.
+    share_index: u8,
+    /// Hex X25519 public key of the custodian.
+    recipient: String,
+    ephemeral_pubkey: String,
+    sealed_share: String,
+    /// SHA-256 of every share's index byte and share, by index.
+    share_commitments: Vec<String>,
+    /// SHA-256 of the secret the shares recover.
+    secret_commitment: String,
+    /// Public keys in the escrowed manifest.
+    pubkeys: Vec<String>,
+    /// SHA-256 of the embedded backup bundle.
+    bundle_sha256: String,
+}
+
+/// `path` with `suffix` appended to its file name.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Write a custodian key pair to `<output>.pub` and `<output>.key`.
+pub fn keygen(output: &Path) -> io::Result<()> {
+    let secret = StaticSecret::from(rand::random::<[u8; 32]>());
+    let public = PublicKey::from(&secret);
+
+    let pub_path = with_suffix(output, ".pub");
+    let key_path = with_suffix(output, ".key");
+    for path in [&pub_path, &key_path] {
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Refusing to overwrite existing file {}", path.display()),
+            ));
+        }
+    }
+    fs::write(&key_path, format!("{}\n", hex::encode(secret.to_bytes())))?;
+    fs::write(&pub_path, format!("{}\n", hex::encode(public.as_bytes())))?;
+
+    println!("🔑 Custodian key pair written:");
+    println!(
+        "  📄 {} (give to whoever runs `escrow create`)",
+        pub_path.display()
+    );
+    println!(
+        "  📄 {} (keep secret; needed to recover)",
+        key_path.display()
+    );
+    Ok(())
+}
+
+/// Read a hex-encoded 32-byte X25519 key from `path`.
+pub fn read_key(path: &Path) -> io::Result<[u8; 32]> {
+    let contents = fs::read_to_string(path)?;
+    let hex_key = contents.trim();
+    let hex_key = hex_key.strip_prefix("0x").unwrap_or(hex_key);
+    hex::decode(hex_key)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a hex-encoded 32-byte X25519 key", path.display()),
+            )
+        })
+}
+
+fn share_key(shared_secret: &[u8], ephemeral: &[u8; 32], recipient: &[u8; 32]) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(SHARE_KEY_CONTEXT);
+    hasher.update(shared_secret);
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+    let mut key = Key::default();
+    key.copy_from_slice(&hasher.finalize());
+    key
+}
+
+fn share_aad(share_index: u8, bundle_sha256: &str) -> Vec<u8> {
+    let mut aad = vec![share_index];
+    aad.extend_from_slice(bundle_sha256.as_bytes());
+    aad
+}
+
+fn share_commitment(share_index: u8, share: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update([share_index]);
+    hasher.update(share);
+    hex::encode(hasher.finalize())
+}
+
+/// Seal `share` to `recipient`, returning the ephemeral public key and the
+/// ciphertext.
+fn seal_share(
+    recipient: &[u8; 32],
+    share_index: u8,
+    share: &[u8],
+    bundle_sha256: &str,
+) -> io::Result<([u8; 32], Vec<u8>)> {
+    let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+    let ephemeral_pubkey = PublicKey::from(&ephemeral).to_bytes();
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    let cipher = ChaCha20Poly1305::new(&share_key(shared.as_bytes(), &ephemeral_pubkey, recipient));
+    let aad = share_aad(share_index, bundle_sha256);
+    let sealed = cipher
+        .encrypt(
+            &Nonce::default(),
+            Payload {
+                msg: share,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| io::Error::other("Share encryption failed"))?;
+    Ok((ephemeral_pubkey, sealed))
+}
+
+/// Decrypt the share of `header` with the custodian secret key `secret`.
+fn open_share(
+    header: &EscrowHeader,
+    secret: &[u8; 32],
+    source: &str,
+) -> io::Result<Zeroizing<Vec<u8>>> {
+    let secret = StaticSecret::from(*secret);
+    let recipient = PublicKey::from(&secret).to_bytes();
+    if hex::encode(recipient) != header.recipient {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not sealed to this custodian key", source),
+        ));
+    }
+    let corrupt = || {
+        exit::error(
+            Failure::CorruptData,
+            format!("The share in {} is corrupted", source),
+        )
+    };
+    let ephemeral: [u8; 32] = hex::decode(&header.ephemeral_pubkey)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(corrupt)?;
+    let sealed = hex::decode(&header.sealed_share).map_err(|_| corrupt())?;
+    let shared = secret.diffie_hellman(&PublicKey::from(ephemeral));
+    let cipher = ChaCha20Poly1305::new(&share_key(shared.as_bytes(), &ephemeral, &recipient));
+    let aad = share_aad(header.share_index, &header.bundle_sha256);
+    let share = cipher
+        .decrypt(
+            &Nonce::default(),
+            Payload {
+                msg: &sealed,
+                aad: &aad,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| corrupt())?;
+
+    let commitment = header
+        .share_commitments
+        .get(usize::from(header.share_index).wrapping_sub(1));
+    if commitment != Some(&share_commitment(header.share_index, &share)) {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("The share in {} does not match its commitment", source),
+        ));
+    }
+    Ok(share)
+}
+
+fn write_header(writer: &mut impl Write, header: &EscrowHeader) -> io::Result<()> {
+    let json = serde_json::to_vec(header).map_err(io::Error::other)?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&json)
+}
+
+/// Read the header of a custodian bundle, leaving `reader` at the embedded
+/// backup bundle.
+fn read_header(reader: &mut impl Read, source: &str) -> io::Result<EscrowHeader> {
+    let not_escrow = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not an escrow bundle", source),
+        )
+    };
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic).map_err(|_| not_escrow())?;
+    if &magic != MAGIC {
+        return Err(not_escrow());
+    }
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version)?;
+    if version[0] != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported escrow bundle version {}", version[0]),
+        ));
+    }
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let mut json = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+    reader.read_exact(&mut json)?;
+    serde_json::from_slice(&json).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Corrupted escrow header in {}: {}", source, e),
+        )
+    })
+}
+
+/// Escrow the key directory `dir` to the custodians whose public keys are in
+/// `recipients`, writing one `<name>.escrow` bundle each into `output_dir`.
+pub fn create(
+    dir: &Path,
+    recipients: &[PathBuf],
+    threshold: u8,
+    output_dir: &Path,
+) -> io::Result<()> {
+    let shares = u8::try_from(recipients.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "At most 255 recipients are supported",
+        )
+    })?;
+    let mut names = BTreeSet::new();
+    let mut custodians = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let name = recipient
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !names.insert(name.clone()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Two recipients are named {}; rename one key file", name),
+            ));
+        }
+        custodians.push((name, read_key(recipient)?));
+    }
+    let secret: [u8; 32] = rand::random();
+    let split = shamir::split(&secret, threshold, shares)?;
+    let manifest = Manifest::read_if_present(dir)?;
+
+    println!(
+        "🔐 Escrowing {} to {} custodians, any {} of whom can recover it\n",
+        dir.display(),
+        shares,
+        threshold
+    );
+    fs::create_dir_all(output_dir)?;
+    let payload = output_dir.join(PAYLOAD_FILE);
+    let result = backup::backup(dir, &payload, &hex::encode(secret)).and_then(|()| {
+        let bundle_sha256 = checksum::sha256_file(&payload)?;
+        let share_commitments: Vec<String> = split
+            .iter()
+            .zip(1..=shares)
+            .map(|(share, index)| share_commitment(index, share))
+            .collect();
+
+        println!();
+        for (((name, recipient), share), share_index) in
+            custodians.iter().zip(&split).zip(1..=shares)
+        {
+            let (ephemeral_pubkey, sealed) =
+                seal_share(recipient, share_index, share, &bundle_sha256)?;
+            let header = EscrowHeader {
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                created_at: unix_now(),
+                source_dir: dir.display().to_string(),
+                ceremony_id: manifest.as_ref().and_then(|m| m.ceremony_id.clone()),
+                threshold,
+                shares,
+                share_index,
+                recipient: hex::encode(recipient),
+                ephemeral_pubkey: hex::encode(ephemeral_pubkey),
+                sealed_share: hex::encode(sealed),
+                share_commitments: share_commitments.clone(),
+                secret_commitment: checksum::sha256_hex(&secret),
+                pubkeys: manifest
+                    .iter()
+                    .flat_map(|m| &m.validators)
+                    .map(|v| v.pubkey_hex.clone())
+                    .collect(),
+                bundle_sha256: bundle_sha256.clone(),
+            };
+
+            let path = output_dir.join(format!("{}.{}", name, BUNDLE_EXTENSION));
+            let mut writer = BufWriter::new(File::create(&path)?);
+            write_header(&mut writer, &header)?;
+            io::copy(&mut File::open(&payload)?, &mut writer)?;
+            writer.flush()?;
+            println!(
+                "  📄 {} (share {} of {})",
+                path.display(),
+                share_index,
+                shares
+            );
+        }
+        Ok(())
+    });
+    fs::remove_file(&payload)?;
+    result?;
+
+    println!("\n✅ Escrow bundles written to: {}", output_dir.display());
+    println!(
+        "   Hand each custodian their bundle; any {} of them, with their keys, recover the key set",
+        threshold
+    );
+    Ok(())
+}
+
+/// Recover an escrowed key set into `output_dir` from custodian `bundles`,
+/// each opened with the custodian key at the same position in `keys`.
+pub fn recover(
+    bundles: &[PathBuf],
+    keys: &[PathBuf],
+    output_dir: &Path,
+    verify_keys: bool,
+) -> io::Result<()> {
+    if bundles.len() != keys.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Give one --key per --bundle, in the same order",
+        ));
+    }
+
+    let mut opened: Vec<(EscrowHeader, Zeroizing<Vec<u8>>)> = Vec::with_capacity(bundles.len());
+    for (bundle, key) in bundles.iter().zip(keys) {
+        let source = bundle.display().to_string();
+        let header = read_header(&mut BufReader::new(File::open(bundle)?), &source)?;
+        let share = open_share(&header, &Zeroizing::new(read_key(key)?), &source)?;
+        println!("  🔓 Share {} from {}", header.share_index, source);
+        opened.push((header, share));
+    }
+    let Some((first, _)) = opened.first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No bundles given",
+        ));
+    };
+    if let Some((other, _)) = opened.iter().find(|(header, _)| {
+        header.secret_commitment != first.secret_commitment
+            || header.bundle_sha256 != first.bundle_sha256
+    }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Share {} belongs to a different escrow than share {}",
+                other.share_index, first.share_index
+            ),
+        ));
+    }
+    if opened.len() < usize::from(first.threshold) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} shares given, but {} are needed",
+                opened.len(),
+                first.threshold
+            ),
+        ));
+    }
+
+    let shares: Vec<(u8, &[u8])> = opened
+        .iter()
+        .map(|(header, share)| (header.share_index, share.as_slice()))
+        .collect();
+    let secret = Zeroizing::new(shamir::combine(&shares)?);
+    if checksum::sha256_hex(&secret) != first.secret_commitment {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            "The shares do not recover the escrowed secret",
+        ));
+    }
+    println!(
+        "\n🔄 Recovered the escrow secret from {} of {} shares\n",
+        opened.len(),
+        first.shares
+    );
+
+    let passphrase = Zeroizing::new(hex::encode(&*secret));
+    backup::restore_from(
+        open_payload(&bundles[0])?,
+        output_dir,
+        &passphrase,
+        verify_keys,
+    )
+}
+
+/// Open the custodian bundle `bundle` at its embedded backup bundle.
+fn open_payload(bundle: &Path) -> io::Result<BufReader<File>> {
+    let mut reader = BufReader::new(File::open(bundle)?);
+    read_header(&mut reader, &bundle.display().to_string())?;
+    Ok(reader)
+}
+
+/// Check custodian `bundles` without writing any key material: that each is
+/// intact, that they belong to one escrow covering the pubkeys published in
+/// the manifest of `published_dir`, and, with custodian `keys` for at least
+/// the threshold, that the shares reconstruct the secret and the key set it
+/// protects. Fails if anything is wrong.
+pub fn verify(
+    bundles: &[PathBuf],
+    keys: &[PathBuf],
+    published_dir: Option<&Path>,
+) -> io::Result<()> {
+    if !keys.is_empty() && keys.len() != bundles.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Give one --key per --bundle, in the same order, or none",
+        ));
+    }
+
+    println!("🔍 Verifying {} escrow bundles\n", bundles.len());
+    let mut failures = Vec::new();
+    let mut headers = Vec::with_capacity(bundles.len());
+    for bundle in bundles {
+        let source = bundle.display().to_string();
+        let mut reader = BufReader::new(File::open(bundle)?);
+        let header = read_header(&mut reader, &source)?;
+        let mut payload = HashingReader::new(reader);
+        io::copy(&mut payload, &mut io::sink())?;
+        if payload.hex_digest() == header.bundle_sha256 {
+            println!(
+                "  ✅ {} (share {} of {}, threshold {})",
+                source, header.share_index, header.shares, header.threshold
+            );
+        } else {
+            failures.push(format!(
+                "{}: the embedded key set does not match its checksum",
+                source
+            ));
+        }
+        headers.push(header);
+    }
+    let Some(first) = headers.first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No bundles given",
+        ));
+    };
+
+    let mut indices = BTreeSet::new();
+    for (header, bundle) in headers.iter().zip(bundles) {
+        let source = bundle.display();
+        if header.secret_commitment != first.secret_commitment
+            || header.share_commitments != first.share_commitments
+            || header.bundle_sha256 != first.bundle_sha256
+            || header.threshold != first.threshold
+            || header.shares != first.shares
+            || header.pubkeys != first.pubkeys
+        {
+            failures.push(format!(
+                "{}: belongs to a different escrow than {}",
+                source,
+                bundles[0].display()
+            ));
+        }
+        if header.share_index == 0
+            || header.share_index > header.shares
+            || header.share_commitments.len() != usize::from(header.shares)
+        {
+            failures.push(format!(
+                "{}: share {} is not one of {} shares",
+                source, header.share_index, header.shares
+            ));
+        }
+        if !indices.insert(header.share_index) {
+            failures.push(format!(
+                "{}: share {} is given twice",
+                source, header.share_index
+            ));
+        }
+    }
+
+    let escrowed: BTreeSet<String> = first
+        .pubkeys
+        .iter()
+        .map(|pk| normalize_pubkey(pk))
+        .collect();
+    if let Some(dir) = published_dir {
+        let published: BTreeSet<String> = Manifest::read(dir)?
+            .validators
+            .iter()
+            .map(|v| normalize_pubkey(&v.pubkey_hex))
+            .collect();
+        for missing in published.difference(&escrowed) {
+            failures.push(format!(
+                "{} is published but not escrowed",
+                short_pubkey(missing)
+            ));
+        }
+        for extra in escrowed.difference(&published) {
+            failures.push(format!(
+                "{} is escrowed but not published",
+                short_pubkey(extra)
+            ));
+        }
+        println!(
+            "  ✅ Compared {} escrowed pubkeys with {}",
+            escrowed.len(),
+            manifest::manifest_path(dir).display()
+        );
+    }
+
+    if keys.is_empty() {
+        println!("\n⚠️  No --key given: reconstruction was not tested");
+    } else if failures.is_empty() {
+        test_reconstruction(bundles, keys, &headers, &escrowed, &mut failures)?;
+    }
+
+    if !failures.is_empty() {
+        println!();
+        for failure in &failures {
+            println!("  ❌ {}", failure);
+        }
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "Escrow verification failed with {} problems",
+                failures.len()
+            ),
+        ));
+    }
+    println!("\n✅ Escrow bundles verified");
+    Ok(())
+}
+
+/// Open the shares of `bundles` with `keys`, check that every share agrees
+/// on the secret, and decrypt the key set in memory to check its files, key
+/// pairs and pubkeys.
+fn test_reconstruction(
+    bundles: &[PathBuf],
+    keys: &[PathBuf],
+    headers: &[EscrowHeader],
+    escrowed: &BTreeSet<String>,
+    failures: &mut Vec<String>,
+) -> io::Result<()> {
+    let first = &headers[0];
+    let threshold = usize::from(first.threshold);
+    if bundles.len() < threshold {
+        failures.push(format!(
+            "{} shares can be opened, but {} are needed to reconstruct",
+            bundles.len(),
+            threshold
+        ));
+        return Ok(());
+    }
+
+    let mut shares = Vec::with_capacity(bundles.len());
+    for ((header, bundle), key) in headers.iter().zip(bundles).zip(keys) {
+        let key = Zeroizing::new(read_key(key)?);
+        shares.push((
+            header.share_index,
+            open_share(header, &key, &bundle.display().to_string())?,
+        ));
+    }
+
+    // The first threshold shares fix the secret; every further share must
+    // reconstruct the same one in place of the last of them
+    let base: Vec<(u8, &[u8])> = shares[..threshold]
+        .iter()
+        .map(|(index, share)| (*index, share.as_slice()))
+        .collect();
+    let secret = Zeroizing::new(shamir::combine(&base)?);
+    if checksum::sha256_hex(&secret) != first.secret_commitment {
+        failures.push("The shares do not reconstruct the escrowed secret".to_string());
+        return Ok(());
+    }
+    for (index, share) in &shares[threshold..] {
+        let mut subset = base[..threshold - 1].to_vec();
+        subset.push((*index, share.as_slice()));
+        if *Zeroizing::new(shamir::combine(&subset)?) != *secret {
+            failures.push(format!("Share {} is inconsistent with the others", index));
+        }
+    }
+    println!(
+        "\n🔄 {} shares reconstruct the escrow secret (in memory)",
+        shares.len()
+    );
+
+    // Two passes over the key set: the manifest and public keys may come
+    // after secret keys in file order
+    let passphrase = Zeroizing::new(hex::encode(&*secret));
+    let mut manifest = None;
+    let mut public_keys = BTreeMap::new();
+    let files = backup::inspect_from(open_payload(&bundles[0])?, &passphrase, |name, contents| {
+        if name == MANIFEST_FILE {
+            manifest = Some(Manifest::parse(&String::from_utf8_lossy(contents), name)?);
+        } else if name.ends_with("_pk.ssz") {
+            public_keys.insert(name.to_string(), contents.to_vec());
+        }
+        Ok(())
+    })?;
+    for (name, pk) in &public_keys {
+        if !escrowed.contains(&normalize_pubkey(&hex::encode(pk))) {
+            failures.push(format!("{}: not among the escrowed pubkeys", name));
+        }
+    }
+
+    let scheme = match &manifest {
+        Some(manifest) => scheme::for_manifest(manifest, MANIFEST_FILE)?,
+        None => scheme::default_scheme(),
+    };
+    let mut pairs = 0;
+    backup::inspect_from(open_payload(&bundles[0])?, &passphrase, |name, sk| {
+        let Some(prefix) = name.strip_suffix("_sk.ssz") else {
+            return Ok(());
+        };
+        match public_keys.get(&format!("{}_pk.ssz", prefix)) {
+            Some(pk) if scheme.keypair_matches(pk, sk)? => pairs += 1,
+            Some(_) => failures.push(format!("{} does not belong to {}_pk.ssz", name, prefix)),
+            None => failures.push(format!("{}: no public key in the escrow", name)),
+        }
+        Ok(())
+    })?;
+    println!(
+        "  ✅ {} files intact, {} key pairs match (decrypted in memory only)",
+        files, pairs
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory with three custodian key pairs, a small key
+    /// directory and its 2-of-3 escrow bundles.
+    struct Escrow {
+        root: PathBuf,
+        bundles: Vec<PathBuf>,
+        keys: Vec<PathBuf>,
+    }
+
+    impl Escrow {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "hashsig-escrow-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&root);
+            let keys_dir = root.join("keys");
+            fs::create_dir_all(&keys_dir).unwrap();
+            fs::write(keys_dir.join("validator_0_sk.json"), b"secret key").unwrap();
+            fs::write(keys_dir.join("validator_0_pk.json"), b"public key").unwrap();
+
+            let names = ["alice", "bob", "carol"];
+            let recipients: Vec<PathBuf> = names
+                .iter()
+                .map(|name| {
+                    let output = root.join(name);
+                    keygen(&output).unwrap();
+                    with_suffix(&output, ".pub")
+                })
+                .collect();
+            let escrow_dir = root.join("escrow");
+            create(&keys_dir, &recipients, 2, &escrow_dir).unwrap();
+            Escrow {
+                bundles: names
+                    .iter()
+                    .map(|name| escrow_dir.join(format!("{}.{}", name, BUNDLE_EXTENSION)))
+                    .collect(),
+                keys: names
+                    .iter()
+                    .map(|name| with_suffix(&root.join(name), ".key"))
+                    .collect(),
+                root,
+            }
+        }
+
+        fn recover(&self, indices: &[usize], output: &str) -> io::Result<PathBuf> {
+            let output_dir = self.root.join(output);
+            let bundles: Vec<PathBuf> = indices.iter().map(|&i| self.bundles[i].clone()).collect();
+            let keys: Vec<PathBuf> = indices.iter().map(|&i| self.keys[i].clone()).collect();
+            recover(&bundles, &keys, &output_dir, false).map(|()| output_dir)
+        }
+
+        fn header(&self, index: usize) -> EscrowHeader {
+            let source = self.bundles[index].display().to_string();
+            read_header(&mut File::open(&self.bundles[index]).unwrap(), &source).unwrap()
+        }
+
+        /// Rewrite bundle `index` with `header` in front of its payload.
+        fn rewrite(&self, index: usize, header: &EscrowHeader) {
+            let mut payload = Vec::new();
+            open_payload(&self.bundles[index])
+                .unwrap()
+                .read_to_end(&mut payload)
+                .unwrap();
+            let mut bytes = Vec::new();
+            write_header(&mut bytes, header).unwrap();
+            bytes.extend_from_slice(&payload);
+            fs::write(&self.bundles[index], bytes).unwrap();
+        }
+    }
+
+    impl Drop for Escrow {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn recovers_from_every_threshold_subset() {
+        let escrow = Escrow::new("subsets");
+        for (n, subset) in [[0, 1], [0, 2], [1, 2], [2, 0]].iter().enumerate() {
+            let output = escrow.recover(subset, &format!("out{}", n)).unwrap();
+            assert_eq!(
+                fs::read(output.join("validator_0_sk.json")).unwrap(),
+                b"secret key"
+            );
+            assert_eq!(
+                fs::read(output.join("validator_0_pk.json")).unwrap(),
+                b"public key"
+            );
+        }
+        escrow.recover(&[0, 1, 2], "all").unwrap();
+    }
+
+    #[test]
+    fn fails_below_threshold() {
+        let escrow = Escrow::new("below");
+        for index in 0..3 {
+            let err = escrow.recover(&[index], "out").unwrap_err();
+            assert_eq!(Failure::of(&err), Failure::InvalidArgs);
+            assert!(!escrow.root.join("out").exists());
+        }
+    }
+
+    #[test]
+    fn rejects_a_duplicated_share() {
+        let escrow = Escrow::new("duplicate");
+        assert!(escrow.recover(&[1, 1], "out").is_err());
+        assert!(!escrow.root.join("out").exists());
+    }
+
+    #[test]
+    fn rejects_the_wrong_custodian_key() {
+        let escrow = Escrow::new("wrong-key");
+        let bundles = [escrow.bundles[0].clone(), escrow.bundles[1].clone()];
+        let keys = [escrow.keys[1].clone(), escrow.keys[0].clone()];
+        let err = recover(&bundles, &keys, &escrow.root.join("out"), false).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::InvalidArgs);
+    }
+
+    #[test]
+    fn rejects_tampered_shares() {
+        let escrow = Escrow::new("tampered");
+
+        // A share resealed under a forged commitment decrypts, but does not
+        // recover the committed secret.
+        let mut header = escrow.header(2);
+        let key = read_key(&escrow.keys[2]).unwrap();
+        let mut share = open_share(&header, &key, "test").unwrap();
+        share[0] ^= 1;
+        let recipient = PublicKey::from(&StaticSecret::from(key)).to_bytes();
+        let (ephemeral, sealed) = seal_share(&recipient, 3, &share, &header.bundle_sha256).unwrap();
+        header.ephemeral_pubkey = hex::encode(ephemeral);
+        header.sealed_share = hex::encode(sealed);
+        header.share_commitments[2] = share_commitment(3, &share);
+        escrow.rewrite(2, &header);
+        let err = escrow.recover(&[2, 0], "out").unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+
+        // Without the forged commitment, the share fails its own.
+        header.share_commitments[2] = escrow.header(0).share_commitments[2].clone();
+        escrow.rewrite(2, &header);
+        let err = escrow.recover(&[2, 0], "out").unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+
+        // A flipped bit in the sealed share fails its authentication.
+        let mut header = escrow.header(0);
+        let mut sealed = hex::decode(&header.sealed_share).unwrap();
+        sealed[0] ^= 1;
+        header.sealed_share = hex::encode(sealed);
+        escrow.rewrite(0, &header);
+        let err = escrow.recover(&[0, 1], "out").unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::CorruptData);
+
+        // So does a share moved to another index.
+        let mut header = escrow.header(1);
+        header.share_index = 3;
+        escrow.rewrite(1, &header);
+        let err = escrow.recover(&[1, 2], "out").unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::CorruptData);
+        assert!(!escrow.root.join("out").exists());
+    }
+}
@@ -0,0 +1,117 @@
+//! Exit codes, so scripts can branch on the class of a failure instead of
+//! parsing stderr.
+//!
+//! Errors stay `io::Error`s. Ones whose class is not evident from their
+//! [`io::ErrorKind`] are created with [`error`], which tags them with a
+//! [`Failure`]; [`Failure::of`] reads the tag back, falling back to the kind.
+//! Ones with a common, explained cause are created with [`diagnosed`].
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process::ExitCode;
+
+use crate::explain::Cause;
+
+/// Class of a failed run, as reported by the exit code. Success is 0.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Failure {
+    /// Invalid or missing arguments (also used by argument parsing itself).
+    InvalidArgs = 2,
+    /// A signature, key pair or key set failed verification.
+    VerificationFailed = 3,
+    /// Refused to sign at an epoch the key cannot or must not sign at.
+    EpochRefused = 4,
+    /// A key, manifest or other input file is corrupted or malformed.
+    CorruptData = 5,
+    /// Reading or writing failed.
+    Io = 6,
+    /// Failed after part of the work was done and written.
+    Partial = 7,
+    /// Stopped early (by SIGTERM) after saving a checkpoint; rerun with
+    /// `--resume` to finish.
+    Resumable = 8,
+    /// Refused to sign with a canary key; whatever asked for the signature
+    /// needs investigating.
+    CanaryTriggered = 9,
+    /// An operation did not finish within its time limit.
+    TimedOut = 10,
+}
+
+impl Failure {
+    /// Class of `error`: its tag if it has one, else derived from its kind.
+    pub fn of(error: &io::Error) -> Self {
+        if let Some(tagged) = error.get_ref().and_then(|e| e.downcast_ref::<Tagged>()) {
+            return tagged.failure;
+        }
+        match error.kind() {
+            io::ErrorKind::InvalidInput => Failure::InvalidArgs,
+            io::ErrorKind::InvalidData => Failure::CorruptData,
+            _ => Failure::Io,
+        }
+    }
+
+    fn kind(self) -> io::ErrorKind {
+        match self {
+            Failure::InvalidArgs | Failure::EpochRefused => io::ErrorKind::InvalidInput,
+            Failure::VerificationFailed | Failure::CorruptData => io::ErrorKind::InvalidData,
+            Failure::Io | Failure::Partial | Failure::Resumable => io::ErrorKind::Other,
+            Failure::CanaryTriggered => io::ErrorKind::PermissionDenied,
+            Failure::TimedOut => io::ErrorKind::TimedOut,
+        }
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+#[derive(Debug)]
+struct Tagged {
+    failure: Failure,
+    cause: Option<Cause>,
+    message: String,
+}
+
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Tagged {}
+
+/// An error of class `failure`.
+pub fn error(failure: Failure, message: impl Into<String>) -> io::Error {
+    io::Error::new(
+        failure.kind(),
+        Tagged {
+            failure,
+            cause: None,
+            message: message.into(),
+        },
+    )
+}
+
+/// An error with the known `cause`, of that cause's class.
+pub fn diagnosed(cause: Cause, message: impl Into<String>) -> io::Error {
+    let failure = cause.failure();
+    io::Error::new(
+        failure.kind(),
+        Tagged {
+            failure,
+            cause: Some(cause),
+            message: message.into(),
+        },
+    )
+}
+
+/// Known cause of `error`, if it was created with [`diagnosed`].
+pub fn cause_of(error: &io::Error) -> Option<Cause> {
+    error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<Tagged>())
+        .and_then(|tagged| tagged.cause)
+}
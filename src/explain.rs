@@ -0,0 +1,191 @@
+//! `explain`: likely causes of a failure and how to fix it.
+//!
+//! Errors of a common, well-understood kind carry a [`Cause`] (see
+//! [`exit::diagnosed`]), whose explanation is printed under the error
+//! message. `explain` prints the same for a cause name, or the meaning of an
+//! exit code, so the advice is also at hand after the fact.
+
+use std::io;
+
+use crate::exit::{self, Failure};
+
+/// A common failure with a known explanation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Cause {
+    /// A key was used with a scheme other than the one it was generated for.
+    SchemeMismatch,
+    /// Signing at an epoch outside the key's prepared epochs.
+    EpochOutOfWindow,
+    /// A key or signature does not decode as SSZ of its scheme.
+    CorruptSsz,
+    /// A key directory was written with a newer layout version.
+    UnsupportedLayout,
+}
+
+const CAUSES: [Cause; 4] = [
+    Cause::SchemeMismatch,
+    Cause::EpochOutOfWindow,
+    Cause::CorruptSsz,
+    Cause::UnsupportedLayout,
+];
+
+const FAILURES: [Failure; 9] = [
+    Failure::InvalidArgs,
+    Failure::VerificationFailed,
+    Failure::EpochRefused,
+    Failure::CorruptData,
+    Failure::Io,
+    Failure::Partial,
+    Failure::Resumable,
+    Failure::CanaryTriggered,
+    Failure::TimedOut,
+];
+
+impl Cause {
+    /// Name to pass to `explain`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Cause::SchemeMismatch => "scheme-mismatch",
+            Cause::EpochOutOfWindow => "epoch-out-of-window",
+            Cause::CorruptSsz => "corrupt-ssz",
+            Cause::UnsupportedLayout => "unsupported-layout",
+        }
+    }
+
+    /// Class of the failures with this cause.
+    pub fn failure(self) -> Failure {
+        match self {
+            Cause::SchemeMismatch => Failure::InvalidArgs,
+            Cause::EpochOutOfWindow => Failure::EpochRefused,
+            Cause::CorruptSsz => Failure::CorruptData,
+            Cause::UnsupportedLayout => Failure::InvalidArgs,
+        }
+    }
+
+    fn likely_cause(self) -> &'static str {
+        match self {
+            Cause::SchemeMismatch => {
+                "The key was generated for a different signature scheme than the one requested, by `--scheme` or a profile, or the manifest next to the key names another scheme. Signatures made across schemes never verify."
+            }
+            Cause::EpochOutOfWindow => {
+                "The key only holds one-time keys for its prepared epochs. The epoch is before them (passed, or dropped by `prune`), or after them (the key was generated with too few active epochs)."
+            }
+            Cause::CorruptSsz => {
+                "The file is not an SSZ encoding of this scheme's key or signature: it was truncated or altered, is a JSON export, or belongs to a different scheme or parameter set."
+            }
+            Cause::UnsupportedLayout => {
+                "The key directory was written by a newer hashsig, in a layout this version does not know. Reading it anyway could pick up the wrong files or miss some."
+            }
+        }
+    }
+
+    fn remediation(self) -> &'static [&'static str] {
+        match self {
+            Cause::SchemeMismatch => &[
+                "Run `hashsig manifest info <dir>` to see the scheme the key set was generated for",
+                "Drop `--scheme`, or pass that scheme, and check the active profile (`--profile`)",
+            ],
+            Cause::EpochOutOfWindow => &[
+                "Run `hashsig stats key <sk>` to see the key's prepared epochs",
+                "Sign at an epoch inside them, never at one the key has already signed at",
+                "Past the end of its lifetime, rotate the validator (`hashsig rotate start`)",
+            ],
+            Cause::CorruptSsz => &[
+                "Check the file with `hashsig verify-dir <dir>`, which compares it against its recorded checksum",
+                "Pass the `.ssz` file, not the `.json` export, and the scheme it was made with",
+                "Restore a corrupted key from a backup (`hashsig restore`)",
+            ],
+            Cause::UnsupportedLayout => &[
+                "Run `cat <dir>/layout-version` to see the layout version, and `hashsig --version` this binary's",
+                "Upgrade hashsig to the release that wrote the directory, or a later one",
+            ],
+        }
+    }
+}
+
+/// Print the likely cause and remediation of `error` to stderr, if it has a
+/// known cause.
+pub fn hint(error: &io::Error) {
+    if let Some(cause) = exit::cause_of(error) {
+        eprintln!("\n💡 Likely cause: {}", cause.likely_cause());
+        for step in cause.remediation() {
+            eprintln!("   - {}", step);
+        }
+        eprintln!("   (`hashsig explain {}` shows this again)", cause.name());
+    }
+}
+
+/// Explain `code`: a cause name as printed with an error, or an exit code.
+pub fn explain(code: &str) -> io::Result<()> {
+    if let Some(cause) = CAUSES.into_iter().find(|cause| cause.name() == code) {
+        println!("🩺 {}\n", cause.name());
+        println!("Likely cause: {}\n", cause.likely_cause());
+        println!("What to do:");
+        for step in cause.remediation() {
+            println!("  - {}", step);
+        }
+        return Ok(());
+    }
+
+    let failure = code
+        .parse::<u8>()
+        .ok()
+        .and_then(|code| FAILURES.into_iter().find(|f| *f as u8 == code))
+        .ok_or_else(|| {
+            let causes: Vec<&str> = CAUSES.iter().map(|cause| cause.name()).collect();
+            exit::error(
+                Failure::InvalidArgs,
+                format!(
+                    "Unknown error code {:?}; give an exit code (2-{}) or one of: {}",
+                    code,
+                    Failure::TimedOut as u8,
+                    causes.join(", ")
+                ),
+            )
+        })?;
+    println!("🩺 Exit code {}\n", failure as u8);
+    println!("{}", failure.explanation());
+    let related: Vec<&str> = CAUSES
+        .iter()
+        .filter(|cause| cause.failure() == failure)
+        .map(|cause| cause.name())
+        .collect();
+    if !related.is_empty() {
+        println!("\nCommon causes: {}", related.join(", "));
+    }
+    Ok(())
+}
+
+impl Failure {
+    fn explanation(self) -> &'static str {
+        match self {
+            Failure::InvalidArgs => {
+                "Invalid or missing arguments. Run the subcommand with `--help` to see what it takes."
+            }
+            Failure::VerificationFailed => {
+                "A signature, key pair or key set failed verification. Nothing was accepted; check that the inputs are the ones meant, and treat a key set failing `verify-dir` as damaged until it is restored from a backup."
+            }
+            Failure::EpochRefused => {
+                "Signing was refused because the epoch is outside the key's prepared epochs. No signature was made."
+            }
+            Failure::CorruptData => {
+                "A key, signature, manifest, bundle or other input file is corrupted or malformed. Compare it against its checksum with `verify-dir`, or restore it from a backup."
+            }
+            Failure::Io => {
+                "Reading or writing failed. Check the paths, permissions and free disk space named in the error."
+            }
+            Failure::Partial => {
+                "Part of the work was done and written before the failure. Inspect the output directory before retrying, so nothing is generated or signed twice. A stopped `generate` run records its progress in `generate-checkpoint.json`; rerun it with `--resume` to generate only the missing key pairs."
+            }
+            Failure::Resumable => {
+                "`generate` was stopped by SIGTERM with a checkpoint saved. Rerun it with the same arguments plus `--resume` to finish. A stopped `reencrypt` finishes when run again as it was."
+            }
+            Failure::CanaryTriggered => {
+                "A signature was requested from a canary key, which no legitimate client ever asks for. Find out which client sent the request, and treat its host as compromised until shown otherwise."
+            }
+            Failure::TimedOut => {
+                "Key generation or signing did not finish within its time limit (`--key-timeout`, `--timeout`). Timed-out key pairs are recorded in `generate-checkpoint.json`; rerun `generate` with `--resume` to retry them. Repeated timeouts point at a broken entropy source or an overloaded machine."
+            }
+        }
+    }
+}
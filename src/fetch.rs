@@ -0,0 +1,211 @@
+//! `fetch`: download a published public key set and keep it only if it
+//! verifies.
+//!
+//! The counterpart to publishing a ceremony's public outputs: the manifest,
+//! its signature (`<manifest>.sig` and the `.sig.json` sidecar, as written
+//! by `sign`), and every validator's public key next to it. The manifest
+//! signature must verify against a signer key the consumer already trusts,
+//! and every public key must match the signed manifest. Only files the
+//! signature covers are kept: metadata, which it does not cover, is not
+//! fetched, and secret keys are never requested.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::exit::{self, Failure};
+use crate::manifest::{self, normalize_pubkey, short_pubkey, Manifest, MANIFEST_FILE};
+use crate::scheme::{self, KeyKind, SchemeHandle};
+use crate::sign;
+use crate::units::format_size;
+
+/// Largest file accepted from a download, so a hostile server cannot fill
+/// memory.
+const MAX_DOWNLOAD_BYTES: u64 = 64 << 20;
+
+/// Fetch the key set published at `manifest_url` into `output_dir`, which
+/// must not hold a manifest yet. The manifest signature is checked against
+/// `signer_pk` (of `signer_scheme`, else the scheme recorded with it).
+pub fn fetch(
+    manifest_url: &str,
+    signer_pk: &Path,
+    signer_scheme: Option<&str>,
+    output_dir: &Path,
+) -> io::Result<()> {
+    if manifest::manifest_path(output_dir).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                output_dir.display()
+            ),
+        ));
+    }
+    let base = manifest_url
+        .rsplit_once('/')
+        .map(|(base, _)| base)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a URL", manifest_url),
+            )
+        })?;
+    println!("📥 Fetching key set from {}\n", manifest_url);
+
+    // Nothing published is trusted before the manifest signature checks out
+    let contents = download(manifest_url)?;
+    let sig_url = format!("{}.sig", manifest_url);
+    let signature = download(&sig_url)?;
+    let sidecar_url = format!("{}.json", sig_url);
+    let sidecar = download(&sidecar_url)?;
+    let signer = scheme::for_key(signer_scheme, signer_pk)?;
+    let epoch = sign::verify_file_signature(
+        signer,
+        &fs::read(signer_pk)?,
+        &contents,
+        &signature,
+        &sidecar,
+        &sidecar_url,
+    )?;
+    println!(
+        "  🔏 Manifest signed by {} at epoch {}",
+        signer_pk.display(),
+        epoch
+    );
+
+    let manifest = Manifest::parse(&String::from_utf8_lossy(&contents), manifest_url)?;
+    scheme::for_manifest(&manifest, manifest_url)?;
+
+    // Verified files, written only once the whole set checks out
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut failures = Vec::new();
+    for validator in &manifest.validators {
+        let name = manifest::file_name(&validator.privkey_file);
+        let prefix = name.strip_suffix("_sk.ssz").unwrap_or(name);
+        let scheme = scheme::for_entry(&manifest, validator, manifest_url)?;
+        match fetch_validator(base, scheme, prefix, &validator.pubkey_hex) {
+            Ok(fetched) => {
+                println!("  ✅ {} ({})", prefix, short_pubkey(&validator.pubkey_hex));
+                files.extend(fetched);
+            }
+            Err(e) => {
+                println!("  ❌ {}: {}", prefix, e);
+                failures.push(prefix.to_string());
+            }
+        }
+    }
+    if !failures.is_empty() {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} published validators failed verification; nothing was written",
+                failures.len(),
+                manifest.validators.len()
+            ),
+        ));
+    }
+
+    fs::create_dir_all(output_dir)?;
+    for (name, contents) in &files {
+        fs::write(output_dir.join(name), contents)?;
+    }
+    fs::write(
+        output_dir.join(format!("{}.sig", MANIFEST_FILE)),
+        &signature,
+    )?;
+    fs::write(
+        output_dir.join(format!("{}.sig.json", MANIFEST_FILE)),
+        &sidecar,
+    )?;
+    // Last, so a directory with a manifest is always complete
+    fs::write(manifest::manifest_path(output_dir), &contents)?;
+
+    println!(
+        "\n✅ {} verified public keys written to {}",
+        manifest.validators.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+/// Download and check the public files of the validator with file prefix
+/// `prefix` and public key `pubkey_hex` of `scheme`, as (file name,
+/// contents): its SSZ public key and, if published, its legacy JSON copy.
+/// Both must be the signed public key. Nothing else is fetched, as nothing
+/// else is covered by the manifest signature.
+fn fetch_validator(
+    base: &str,
+    scheme: &dyn SchemeHandle,
+    prefix: &str,
+    pubkey_hex: &str,
+) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mismatch = |reason: String| exit::error(Failure::VerificationFailed, reason);
+
+    let pk_name = format!("{}_pk.ssz", prefix);
+    let pk = download(&format!("{}/{}", base, pk_name))?;
+    if hex::encode(&pk) != normalize_pubkey(pubkey_hex) {
+        return Err(mismatch(format!(
+            "{} does not match the signed manifest",
+            pk_name
+        )));
+    }
+
+    let json_name = format!("{}_pk.json", prefix);
+    let json = download_if_present(&format!("{}/{}", base, json_name))?;
+    if let Some(json) = &json {
+        if scheme.json_to_ssz(KeyKind::Public, json).as_ref() != Ok(&pk) {
+            return Err(mismatch(format!(
+                "{} does not match the signed manifest",
+                json_name
+            )));
+        }
+    }
+    let mut files = vec![(pk_name, pk)];
+    files.extend(json.map(|json| (json_name, json)));
+    Ok(files)
+}
+
+fn download(url: &str) -> io::Result<Vec<u8>> {
+    download_if_present(url)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", url)))
+}
+
+/// Contents of `url` (`http://`, `https://` or `file://`), or `None` if it
+/// does not exist.
+fn download_if_present(url: &str) -> io::Result<Option<Vec<u8>>> {
+    let reader: Box<dyn Read> = if let Some(path) = url.strip_prefix("file://") {
+        match fs::File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        match ureq::get(url).call() {
+            Ok(response) => response.into_reader(),
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => {
+                return Err(io::Error::other(format!(
+                    "Failed to download {}: {}",
+                    url, e
+                )))
+            }
+        }
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported URL {} (use http://, https:// or file://)", url),
+        ));
+    };
+
+    let mut contents = Vec::new();
+    reader
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut contents)?;
+    if contents.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is larger than {}", url, format_size(MAX_DOWNLOAD_BYTES)),
+        ));
+    }
+    Ok(Some(contents))
+}
@@ -0,0 +1,143 @@
+//! `fuzz-verify`: a quick in-the-field check of the encode/decode/verify
+//! pipeline on this build and CPU.
+//!
+//! Random messages are signed with a throwaway key. Every valid triple must
+//! verify, and flipping a single bit of the signature, public key or
+//! message must make verification fail (or decoding reject the input).
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use leansig::MESSAGE_LENGTH;
+use rand::{Rng, RngCore};
+
+use crate::exit::{self, Failure};
+use crate::scheme::SchemeHandle;
+use crate::units::format_duration;
+
+/// Counts of one fuzzing run.
+#[derive(Default)]
+struct FuzzStats {
+    valid_checked: usize,
+    signatures_rejected: usize,
+    pubkeys_rejected: usize,
+    messages_rejected: usize,
+    failures: Vec<String>,
+}
+
+/// Fuzz verification for `duration` with a throwaway key of
+/// 2^`log_num_active_epochs` active epochs.
+pub fn fuzz_verify(
+    scheme: &dyn SchemeHandle,
+    duration: Duration,
+    log_num_active_epochs: usize,
+) -> io::Result<()> {
+    let mut rng = rand::rng();
+
+    println!(
+        "🔑 Generating a throwaway {} key with 2^{} active epochs",
+        scheme.name(),
+        log_num_active_epochs
+    );
+    let pair = scheme.key_gen(&mut rng, 0, 1 << log_num_active_epochs);
+    let pk = pair.pk_bytes();
+    let sk = pair.sk_bytes();
+    let epochs = pair.prepared_interval();
+
+    println!(
+        "🎲 Fuzzing verification for {}...\n",
+        format_duration(duration)
+    );
+    let mut stats = FuzzStats::default();
+    let started = Instant::now();
+    while started.elapsed() < duration {
+        let epoch = rng.random_range(epochs.clone()) as u32;
+        let mut message = [0u8; MESSAGE_LENGTH];
+        rng.fill_bytes(&mut message);
+        let signature = scheme.sign(&sk, epoch, &message)?;
+
+        if verifies(scheme, &pk, epoch, &message, &signature) {
+            stats.valid_checked += 1;
+        } else {
+            stats.failures.push(format!(
+                "valid signature rejected at epoch {} (message {})",
+                epoch,
+                hex::encode(message)
+            ));
+        }
+
+        let (bit, flipped) = flip_random_bit(&mut rng, &signature);
+        if verifies(scheme, &pk, epoch, &message, &flipped) {
+            stats.failures.push(format!(
+                "signature with bit {} flipped accepted at epoch {}",
+                bit, epoch
+            ));
+        } else {
+            stats.signatures_rejected += 1;
+        }
+
+        let (bit, flipped) = flip_random_bit(&mut rng, &pk);
+        if verifies(scheme, &flipped, epoch, &message, &signature) {
+            stats.failures.push(format!(
+                "public key with bit {} flipped accepted at epoch {}",
+                bit, epoch
+            ));
+        } else {
+            stats.pubkeys_rejected += 1;
+        }
+
+        let (bit, flipped) = flip_random_bit(&mut rng, &message);
+        let flipped: [u8; MESSAGE_LENGTH] = flipped.try_into().expect("same length as message");
+        if verifies(scheme, &pk, epoch, &flipped, &signature) {
+            stats.failures.push(format!(
+                "message with bit {} flipped accepted at epoch {}",
+                bit, epoch
+            ));
+        } else {
+            stats.messages_rejected += 1;
+        }
+    }
+
+    println!("  Valid signatures verified:  {}", stats.valid_checked);
+    println!(
+        "  Mutated signatures rejected: {}",
+        stats.signatures_rejected
+    );
+    println!("  Mutated public keys rejected: {}", stats.pubkeys_rejected);
+    println!("  Mutated messages rejected:  {}", stats.messages_rejected);
+
+    if !stats.failures.is_empty() {
+        for failure in &stats.failures {
+            println!("  ❌ {}", failure);
+        }
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("Verification misbehaved {} times", stats.failures.len()),
+        ));
+    }
+
+    println!("\n✅ Verification accepted every valid signature and rejected every mutation");
+    Ok(())
+}
+
+/// Whether verification accepts the triple. Inputs that fail to decode are
+/// rejected.
+fn verifies(
+    scheme: &dyn SchemeHandle,
+    pk: &[u8],
+    epoch: u32,
+    message: &[u8; MESSAGE_LENGTH],
+    signature: &[u8],
+) -> bool {
+    scheme
+        .verify(pk, epoch, message, signature)
+        .unwrap_or(false)
+}
+
+/// Copy of `bytes` with one random bit flipped, and the index of that bit.
+fn flip_random_bit(rng: &mut impl Rng, bytes: &[u8]) -> (usize, Vec<u8>) {
+    let bit = rng.random_range(0..bytes.len() * 8);
+    let mut flipped = bytes.to_vec();
+    flipped[bit / 8] ^= 1 << (bit % 8);
+    (bit, flipped)
+}
@@ -0,0 +1,1488 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::checksum;
+use crate::entropy::{AuditedRng, EntropyProvenance, EntropySource, KeyEntropy, PROVENANCE_FILE};
+use crate::exit::{self, Failure};
+use crate::keys;
+use crate::keystore::KeyStore;
+use crate::metadata::{self, KeyMetadata};
+use crate::scheme::{self, KeyKind, KeyPair, SchemeHandle};
+use crate::shutdown;
+use crate::units::format_duration;
+
+/// File recording the key pairs written by a `generate` run stopped by
+/// SIGTERM, read back by `generate --resume`.
+pub const CHECKPOINT_FILE: &str = "generate-checkpoint.json";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// Export only SSZ-encoded binary files (`.ssz`)
+    Ssz,
+    /// Export SSZ-encoded binaries (`.ssz`) and legacy JSON public keys
+    /// (secret keys too with `export_secret_json`)
+    Both,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum VerifyWrites {
+    /// Don't read back written files
+    None,
+    /// Read back and decode secret key files
+    Secret,
+    /// Read back and decode every key file
+    All,
+}
+
+pub struct ValidatorInfo {
+    pub index: usize,
+    pub pubkey_hex: String,
+    pub privkey_file: String,
+    pub derivation_path: Option<String>,
+    /// Generated with `--with-canary` as a canary, never to be used.
+    pub canary: bool,
+}
+
+/// How key generation is spread across threads.
+///
+/// Compute threads run `key_gen` and hand finished key pairs to writer threads
+/// through a bounded queue, so slow disks never stall key generation until
+/// `queue_depth` keys are waiting. With [`ExportFormat::Both`], writer threads
+/// only write the SSZ files and pass each key on to JSON threads through a
+/// second such queue, as serializing multi-MB keys to JSON is far slower.
+pub struct PipelineConfig {
+    pub compute_threads: usize,
+    pub writer_threads: usize,
+    /// Threads writing the legacy JSON export and metadata, with
+    /// [`ExportFormat::Both`] only.
+    pub json_threads: usize,
+    pub queue_depth: usize,
+    /// Start at most this many key generations a minute, across all compute
+    /// threads, to keep long runs cool or within burstable CPU credits.
+    pub max_keys_per_minute: Option<u32>,
+    /// Give up on a key pair whose generation takes longer than this, and
+    /// carry on with the rest.
+    pub key_timeout: Option<Duration>,
+    /// Retry a key pair whose generation or writing failed this many times,
+    /// so a transient error (a full disk for a moment, a flaky device) does
+    /// not cost the run.
+    pub retries: u32,
+    /// Skip up to this many key pairs that still fail after their retries
+    /// and carry on with the rest; one more and the run gives up.
+    pub max_failures: usize,
+    /// Indices of validators generated and written before all others, in
+    /// this order, so they exist even if the run is cut short.
+    pub priority: Vec<usize>,
+}
+
+/// Wait before the first retry of a failed key pair, doubling for each
+/// further one.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Spaces the starts of key generations evenly, at most `per_minute` a
+/// minute.
+struct Throttle {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / per_minute,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Sleep until the next free start slot and claim it.
+    fn wait(&self) {
+        let start = {
+            let mut next = self.next.lock().expect("throttle lock poisoned");
+            let start = (*next).max(Instant::now());
+            *next = start + self.interval;
+            start
+        };
+        thread::sleep(start.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Keys written so far and the estimated time to finish, printed as each key
+/// pair is written.
+struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    started: Instant,
+    /// The throttle's interval: remaining keys can't finish faster than this.
+    throttle_interval: Option<Duration>,
+}
+
+impl Progress {
+    fn key_written(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let remaining = self.total.saturating_sub(done);
+        let elapsed = self.started.elapsed();
+        let mut eta = elapsed.mul_f64(remaining as f64 / done as f64);
+        if let Some(interval) = self.throttle_interval {
+            eta = eta.max(interval * remaining as u32);
+        }
+        let keys_per_minute = done as f64 / elapsed.as_secs_f64().max(f64::EPSILON) * 60.0;
+        println!(
+            "  ⏱️  {}/{} written, {:.1} keys/min, ETA {}",
+            done,
+            self.total,
+            keys_per_minute,
+            format_duration(eta)
+        );
+    }
+}
+
+/// Options controlling which files are written for each key pair.
+pub struct OutputOptions<'a> {
+    pub store: &'a dyn KeyStore,
+    pub scheme: &'static dyn SchemeHandle,
+    pub export_format: ExportFormat,
+    /// Also write secret keys as JSON; only valid with [`ExportFormat::Both`].
+    pub export_secret_json: bool,
+    /// Indent the legacy JSON export; compact by default, as pretty-printing
+    /// multi-MB secret keys is slow.
+    pub pretty_json: bool,
+    pub distributed: bool,
+    pub write_metadata: bool,
+    pub verify_writes: VerifyWrites,
+    /// ID of the `generate` run, recorded in every key's metadata.
+    pub ceremony_id: Option<&'a str>,
+    /// Index from which keys are canaries (see `--with-canary`).
+    pub canaries_from: Option<usize>,
+    /// Index of the first key pair, after the ones of earlier `--batch`
+    /// groups.
+    pub first_index: usize,
+    /// Tenant prefix of every file name (see `--namespace`), recorded in
+    /// each key's metadata.
+    pub namespace: Option<&'a str>,
+}
+
+impl OutputOptions<'_> {
+    fn check(&self) -> io::Result<()> {
+        if self.export_secret_json && self.export_format != ExportFormat::Both {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--export-secret-json requires --export-format both",
+            ));
+        }
+        Ok(())
+    }
+
+    fn is_canary(&self, index: usize) -> bool {
+        self.canaries_from.is_some_and(|from| index >= from)
+    }
+}
+
+/// Outcome of [`generate_keys`].
+pub struct Generated {
+    /// Every key pair written, including ones from the resumed run, by index.
+    pub validators: Vec<ValidatorInfo>,
+    /// Stopped by SIGTERM before every key pair was written; the written
+    /// ones are recorded in [`CHECKPOINT_FILE`].
+    pub interrupted: bool,
+    /// Key pairs given up on, after `key_timeout` or after failing past
+    /// their retries, also recorded in [`CHECKPOINT_FILE`] so `--resume`
+    /// retries them.
+    pub failed: Vec<FailedKey>,
+    /// The failure that made the run give up, once more than `max_failures`
+    /// key pairs failed. The key pairs written until then are listed in
+    /// `validators` and recorded in [`CHECKPOINT_FILE`].
+    pub aborted: Option<io::Error>,
+}
+
+/// A key pair whose generation was given up on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FailedKey {
+    pub index: usize,
+    pub reason: String,
+    /// Given up on after `key_timeout`, rather than after an error.
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+/// Key pairs given up on during a run, shared by its threads.
+struct Failures {
+    /// Key pairs failing with an error that are skipped before the run
+    /// gives up; timeouts are always skipped.
+    max: usize,
+    keys: Mutex<Vec<FailedKey>>,
+    /// The failure that made the run give up.
+    fatal: Mutex<Option<io::Error>>,
+}
+
+impl Failures {
+    fn timed_out(&self, index: usize, reason: String) {
+        println!("  ⏲️  validator {}: {}, skipped", index, reason);
+        self.keys
+            .lock()
+            .expect("failure list lock poisoned")
+            .push(FailedKey {
+                index,
+                reason,
+                timed_out: true,
+            });
+    }
+
+    /// Record that key pair `index` failed with `error`, returning whether
+    /// the run carries on.
+    fn failed(&self, index: usize, error: io::Error) -> bool {
+        let mut keys = self.keys.lock().expect("failure list lock poisoned");
+        keys.push(FailedKey {
+            index,
+            reason: error.to_string(),
+            timed_out: false,
+        });
+        let count = keys.iter().filter(|key| !key.timed_out).count();
+        if count > self.max {
+            self.fatal
+                .lock()
+                .expect("failure lock poisoned")
+                .get_or_insert(error);
+            return false;
+        }
+        println!(
+            "  ❌ validator {}: {}, skipped ({} of at most {} failures)",
+            index, error, count, self.max
+        );
+        true
+    }
+}
+
+/// Run `attempt` up to `1 + retries` times, waiting [`RETRY_BACKOFF`] and
+/// then twice as long before each next try, and return the last error if
+/// every attempt failed.
+fn with_retries<T>(
+    retries: u32,
+    index: usize,
+    mut attempt: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = RETRY_BACKOFF;
+    for retry in 1..=retries {
+        match attempt() {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                println!(
+                    "  🔁 validator {}: {}; retry {} of {} in {}",
+                    index,
+                    e,
+                    retry,
+                    retries,
+                    format_duration(backoff)
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+        }
+    }
+    attempt()
+}
+
+/// Contents of [`CHECKPOINT_FILE`]: the run's parameters, which a resumed
+/// run must repeat, and the key pairs written so far.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(default)]
+    ceremony_id: Option<String>,
+    key_scheme: String,
+    num_validators: usize,
+    log_num_active_epochs: usize,
+    distributed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    entropy_source: String,
+    audit_entropy: bool,
+    keys: Vec<CheckpointKey>,
+    /// Key pairs given up on, retried by the resumed run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<FailedKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointKey {
+    index: usize,
+    pubkey_hex: String,
+    privkey_file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    derivation_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entropy_sha256: Option<String>,
+}
+
+impl Checkpoint {
+    /// ID of the interrupted run, which the resumed run continues.
+    pub fn ceremony_id(&self) -> Option<&str> {
+        self.ceremony_id.as_deref()
+    }
+
+    /// Read the checkpoint an interrupted run left in `store`.
+    pub fn load(store: &dyn KeyStore) -> io::Result<Self> {
+        let json = store.load(CHECKPOINT_FILE).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Nothing to resume: cannot read {} in {}: {}",
+                    CHECKPOINT_FILE,
+                    store.location(),
+                    e
+                ),
+            )
+        })?;
+        serde_json::from_slice(&json).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", CHECKPOINT_FILE, e),
+            )
+        })
+    }
+
+    /// Refuse to resume with parameters other than the interrupted run's.
+    fn check(
+        &self,
+        num_validators: usize,
+        log_num_active_epochs: usize,
+        options: &OutputOptions,
+        entropy: &dyn EntropySource,
+        audit_entropy: bool,
+    ) -> io::Result<()> {
+        let mismatches: Vec<String> = [
+            (
+                "--scheme",
+                self.key_scheme.clone(),
+                options.scheme.name().to_string(),
+            ),
+            (
+                "--num-validators",
+                self.num_validators.to_string(),
+                num_validators.to_string(),
+            ),
+            (
+                "--log-num-active-epochs",
+                self.log_num_active_epochs.to_string(),
+                log_num_active_epochs.to_string(),
+            ),
+            (
+                "--distributed",
+                self.distributed.to_string(),
+                options.distributed.to_string(),
+            ),
+            (
+                "--namespace",
+                self.namespace.clone().unwrap_or_default(),
+                options.namespace.unwrap_or_default().to_string(),
+            ),
+            (
+                "entropy source",
+                self.entropy_source.clone(),
+                entropy.describe(),
+            ),
+            (
+                "--audit-entropy",
+                self.audit_entropy.to_string(),
+                audit_entropy.to_string(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, recorded, given)| recorded != given)
+        .map(|(name, recorded, given)| format!("{} was {}, not {}", name, recorded, given))
+        .collect();
+        if !mismatches.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Cannot resume with different parameters: {}",
+                    mismatches.join("; ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+struct GeneratedKey {
+    index: usize,
+    pair: Box<dyn KeyPair>,
+    /// SHA-256 of the entropy consumed by `key_gen`, when auditing.
+    entropy_sha256: Option<String>,
+    derivation_path: Option<String>,
+}
+
+/// Generate and write `num_validators` key pairs, or with `resume` only the
+/// ones the interrupted run did not write.
+///
+/// Once SIGTERM arrives (see [`shutdown`]), no further key pair is started;
+/// the ones in flight are finished and written, and the run is recorded in
+/// [`CHECKPOINT_FILE`]. The same happens when the run gives up after more
+/// than `pipeline.max_failures` failed key pairs.
+pub fn generate_keys(
+    num_validators: usize,
+    log_num_active_epochs: usize,
+    options: &OutputOptions,
+    pipeline: &PipelineConfig,
+    entropy: &Arc<dyn EntropySource>,
+    audit_entropy: bool,
+    resume: Option<Checkpoint>,
+) -> io::Result<Generated> {
+    if pipeline.compute_threads == 0
+        || pipeline.writer_threads == 0
+        || pipeline.json_threads == 0
+        || pipeline.queue_depth == 0
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--threads, --writer-threads, --json-threads and --queue-depth must be at least 1",
+        ));
+    }
+    if let Some(index) = pipeline
+        .priority
+        .iter()
+        .find(|&&index| index >= num_validators)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--priority names validator {}, but only {} are generated",
+                index, num_validators
+            ),
+        ));
+    }
+    if pipeline.max_keys_per_minute == Some(0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--max-keys-per-minute must be at least 1",
+        ));
+    }
+    options.check()?;
+    if let Some(checkpoint) = &resume {
+        checkpoint.check(
+            num_validators,
+            log_num_active_epochs,
+            options,
+            entropy.as_ref(),
+            audit_entropy,
+        )?;
+    }
+
+    let activation_duration = 1 << log_num_active_epochs;
+    let resumed: Vec<(ValidatorInfo, Option<String>)> = resume
+        .map(|checkpoint| checkpoint.keys)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|key| {
+            let info = ValidatorInfo {
+                index: key.index,
+                pubkey_hex: key.pubkey_hex,
+                privkey_file: key.privkey_file,
+                derivation_path: key.derivation_path,
+                canary: options.is_canary(key.index),
+            };
+            (info, key.entropy_sha256)
+        })
+        .collect();
+    let done: BTreeSet<usize> = resumed.iter().map(|(info, _)| info.index).collect();
+    let mut pending: Vec<usize> = (options.first_index..options.first_index + num_validators)
+        .filter(|i| !done.contains(i))
+        .collect();
+    // Stable, so the rest stay in index order
+    pending.sort_by_key(|index| {
+        pipeline
+            .priority
+            .iter()
+            .position(|priority| priority == index)
+            .unwrap_or(usize::MAX)
+    });
+
+    println!(
+        "Generating {} validator keys with 2^{} active epochs ({} total) in: {}\n",
+        num_validators,
+        log_num_active_epochs,
+        activation_duration,
+        options.store.location()
+    );
+
+    println!("🔐 Keys will be formatted for validator integration");
+    println!("⚠️  Note: Secret keys are large files (~several MB each)");
+    let export_json = options.export_format == ExportFormat::Both;
+    if export_json {
+        println!(
+            "⚙️  {} compute threads, {} writer threads, {} JSON threads, queue depth {}\n",
+            pipeline.compute_threads,
+            pipeline.writer_threads,
+            pipeline.json_threads,
+            pipeline.queue_depth
+        );
+    } else {
+        println!(
+            "⚙️  {} compute threads, {} writer threads, queue depth {}\n",
+            pipeline.compute_threads, pipeline.writer_threads, pipeline.queue_depth
+        );
+    }
+    if let Some(per_minute) = pipeline.max_keys_per_minute {
+        println!("⏳ Throttled to at most {} keys per minute\n", per_minute);
+    }
+    if let Some(timeout) = pipeline.key_timeout {
+        println!(
+            "⏲️  Giving up on key pairs that take longer than {}\n",
+            format_duration(timeout)
+        );
+    }
+    let first: Vec<String> = pending
+        .iter()
+        .take_while(|&&index| pipeline.priority.contains(&index))
+        .map(usize::to_string)
+        .collect();
+    if !first.is_empty() {
+        println!(
+            "⭐ Generating {} priority validators first: {}\n",
+            first.len(),
+            first.join(", ")
+        );
+    }
+    if pipeline.retries > 0 || pipeline.max_failures > 0 {
+        println!(
+            "🔁 Retrying failed key pairs {} times, skipping up to {} that still fail\n",
+            pipeline.retries, pipeline.max_failures
+        );
+    }
+    println!("🎲 Entropy source: {}\n", entropy.describe());
+    if let Some(ceremony_id) = options.ceremony_id {
+        println!("🆔 Ceremony ID: {}\n", ceremony_id);
+    }
+    if !resumed.is_empty() {
+        println!(
+            "⏯️  Resuming: {} key pairs already written, {} to go\n",
+            resumed.len(),
+            pending.len()
+        );
+    }
+
+    let next_pending = AtomicUsize::new(0);
+    let abort = AtomicBool::new(false);
+    let failures = Failures {
+        max: pipeline.max_failures,
+        keys: Mutex::new(Vec::new()),
+        fatal: Mutex::new(None),
+    };
+    let (sender, receiver) = mpsc::sync_channel::<GeneratedKey>(pipeline.queue_depth);
+    let receiver = Mutex::new(receiver);
+    let (json_sender, json_receiver) =
+        mpsc::sync_channel::<(GeneratedKey, SszWritten)>(pipeline.queue_depth);
+    let json_receiver = Mutex::new(json_receiver);
+    let json_sender = export_json.then_some(json_sender);
+    let throttle = pipeline.max_keys_per_minute.map(Throttle::new);
+    let progress = Progress {
+        total: pending.len(),
+        done: AtomicUsize::new(0),
+        started: Instant::now(),
+        throttle_interval: throttle.as_ref().map(|t| t.interval),
+    };
+
+    let (writer_results, json_results) = thread::scope(|scope| {
+        let computes: Vec<_> = (0..pipeline.compute_threads)
+            .map(|_| {
+                let sender = sender.clone();
+                let next_pending = &next_pending;
+                let pending = &pending;
+                let abort = &abort;
+                let throttle = &throttle;
+                let failures = &failures;
+                let stopping = move || abort.load(Ordering::Relaxed) || shutdown::requested();
+                scope.spawn(move || {
+                    while !stopping() {
+                        let position = next_pending.fetch_add(1, Ordering::Relaxed);
+                        let Some(&index) = pending.get(position) else {
+                            break;
+                        };
+                        if let Some(throttle) = throttle {
+                            throttle.wait();
+                            if stopping() {
+                                break;
+                            }
+                        }
+
+                        let generated =
+                            with_retries(pipeline.retries, index, || match pipeline.key_timeout {
+                                Some(timeout) => generate_key_within(
+                                    options.scheme,
+                                    index,
+                                    activation_duration,
+                                    entropy,
+                                    audit_entropy,
+                                    timeout,
+                                ),
+                                None => generate_key(
+                                    options.scheme,
+                                    index,
+                                    activation_duration,
+                                    entropy.as_ref(),
+                                    audit_entropy,
+                                )
+                                .map(Some),
+                            });
+                        let key = match generated {
+                            Ok(Some(key)) => key,
+                            Ok(None) => {
+                                failures.timed_out(
+                                    index,
+                                    format!(
+                                        "key generation did not finish within {}",
+                                        format_duration(pipeline.key_timeout.unwrap_or_default())
+                                    ),
+                                );
+                                continue;
+                            }
+                            Err(e) => {
+                                if !failures.failed(index, e) {
+                                    abort.store(true, Ordering::Relaxed);
+                                }
+                                continue;
+                            }
+                        };
+                        if sender.send(key).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        // Writers stop once every compute thread has dropped its sender
+        drop(sender);
+
+        let writers: Vec<_> = (0..pipeline.writer_threads)
+            .map(|_| {
+                let receiver = &receiver;
+                let json_sender = json_sender.clone();
+                let abort = &abort;
+                let failures = &failures;
+                let options = &options;
+                let progress = &progress;
+                scope.spawn(move || {
+                    let mut written = Vec::new();
+                    loop {
+                        // Release the lock before writing so writers run concurrently
+                        let next = receiver.lock().expect("queue lock poisoned").recv();
+                        let Ok(key) = next else {
+                            break;
+                        };
+
+                        // Once the run gives up keep draining the queue so compute
+                        // threads never block on a full channel, but write nothing more
+                        if abort.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        let ssz = match with_retries(pipeline.retries, key.index, || {
+                            write_ssz(&key, options)
+                        }) {
+                            Ok(ssz) => ssz,
+                            Err(e) => {
+                                if !failures.failed(key.index, e) {
+                                    abort.store(true, Ordering::Relaxed);
+                                }
+                                continue;
+                            }
+                        };
+                        if let Some(json_sender) = &json_sender {
+                            // JSON threads drain their queue until every
+                            // writer is done, so this only blocks while it is full
+                            let _ = json_sender.send((key, ssz));
+                            continue;
+                        }
+                        match with_retries(pipeline.retries, key.index, || {
+                            finish_key(&key, &ssz, options)
+                        }) {
+                            Ok(info) => {
+                                progress.key_written();
+                                written.push((info, key.entropy_sha256));
+                            }
+                            Err(e) => {
+                                if !failures.failed(key.index, e) {
+                                    abort.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
+                    written
+                })
+            })
+            .collect();
+        // JSON threads stop once every writer has dropped its sender
+        let json_threads = if json_sender.is_some() {
+            pipeline.json_threads
+        } else {
+            0
+        };
+        drop(json_sender);
+
+        let json_writers: Vec<_> = (0..json_threads)
+            .map(|_| {
+                let json_receiver = &json_receiver;
+                let abort = &abort;
+                let failures = &failures;
+                let options = &options;
+                let progress = &progress;
+                scope.spawn(move || {
+                    let mut written = Vec::new();
+                    loop {
+                        let next = json_receiver.lock().expect("queue lock poisoned").recv();
+                        let Ok((key, ssz)) = next else {
+                            break;
+                        };
+                        if abort.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        match with_retries(pipeline.retries, key.index, || {
+                            finish_key(&key, &ssz, options)
+                        }) {
+                            Ok(info) => {
+                                progress.key_written();
+                                written.push((info, key.entropy_sha256));
+                            }
+                            Err(e) => {
+                                if !failures.failed(key.index, e) {
+                                    abort.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
+                    written
+                })
+            })
+            .collect();
+
+        let writer_results: Vec<Vec<_>> = writers
+            .into_iter()
+            .map(|writer| writer.join().expect("writer thread panicked"))
+            .collect();
+        let json_results: Vec<Vec<_>> = json_writers
+            .into_iter()
+            .map(|writer| writer.join().expect("JSON thread panicked"))
+            .collect();
+        for compute in computes {
+            compute.join().expect("compute thread panicked");
+        }
+        (writer_results, json_results)
+    });
+
+    let mut validator_info_list = Vec::with_capacity(num_validators);
+    validator_info_list.extend(resumed);
+    for written in writer_results.into_iter().chain(json_results) {
+        validator_info_list.extend(written);
+    }
+    let mut aborted = failures.fatal.into_inner().expect("failure lock poisoned");
+    if validator_info_list.is_empty() {
+        if let Some(e) = aborted.take() {
+            return Err(e);
+        }
+    }
+    validator_info_list.sort_by_key(|(info, _)| info.index);
+    let mut failed = failures
+        .keys
+        .into_inner()
+        .expect("failure list lock poisoned");
+    failed.sort_by_key(|key| key.index);
+
+    if validator_info_list.len() < num_validators {
+        let checkpoint = Checkpoint {
+            ceremony_id: options.ceremony_id.map(str::to_string),
+            key_scheme: options.scheme.name().to_string(),
+            num_validators,
+            log_num_active_epochs,
+            distributed: options.distributed,
+            namespace: options.namespace.map(str::to_string),
+            entropy_source: entropy.describe(),
+            audit_entropy,
+            keys: validator_info_list
+                .iter()
+                .map(|(info, entropy_sha256)| CheckpointKey {
+                    index: info.index,
+                    pubkey_hex: info.pubkey_hex.clone(),
+                    privkey_file: info.privkey_file.clone(),
+                    derivation_path: info.derivation_path.clone(),
+                    entropy_sha256: entropy_sha256.clone(),
+                })
+                .collect(),
+            failed: failed.clone(),
+        };
+        let json = serde_json::to_vec_pretty(&checkpoint).map_err(io::Error::other)?;
+        options.store.store(CHECKPOINT_FILE, &json).map_err(|e| {
+            exit::error(
+                Failure::Partial,
+                format!(
+                    "Stopped after {} of {} key pairs, but {} was not written: {}",
+                    validator_info_list.len(),
+                    num_validators,
+                    CHECKPOINT_FILE,
+                    e
+                ),
+            )
+        })?;
+        let interrupted =
+            aborted.is_none() && validator_info_list.len() + failed.len() < num_validators;
+        if let Some(e) = &aborted {
+            println!(
+                "\n🛑 Gave up after {} of {} key pairs: {}; progress saved to {}",
+                validator_info_list.len(),
+                num_validators,
+                e,
+                CHECKPOINT_FILE
+            );
+        }
+        if interrupted {
+            println!(
+                "\n⏸️  Stopped by SIGTERM after {} of {} key pairs; progress saved to {}",
+                validator_info_list.len(),
+                num_validators,
+                CHECKPOINT_FILE
+            );
+        }
+        if !failed.is_empty() {
+            println!(
+                "\n⚠️  {} key pairs failed or timed out and were skipped; recorded in {}:",
+                failed.len(),
+                CHECKPOINT_FILE
+            );
+            for key in &failed {
+                println!("  ❌ validator {}: {}", key.index, key.reason);
+            }
+        }
+        return Ok(Generated {
+            validators: validator_info_list
+                .into_iter()
+                .map(|(info, _)| info)
+                .collect(),
+            interrupted,
+            failed,
+            aborted,
+        });
+    }
+
+    println!(
+        "\n✅ Successfully generated and saved {} validator key pairs in {}.",
+        num_validators,
+        format_duration(progress.started.elapsed())
+    );
+
+    if audit_entropy {
+        let keys = validator_info_list
+            .iter()
+            .map(|(info, entropy_sha256)| KeyEntropy {
+                privkey_file: info.privkey_file.clone(),
+                entropy_sha256: entropy_sha256.clone().expect("recorded when auditing"),
+            })
+            .collect();
+        let provenance = EntropyProvenance::new(entropy.as_ref(), options.ceremony_id, keys);
+        provenance.write(options.store).map_err(|e| {
+            exit::error(
+                Failure::Partial,
+                format!("Keys were written, but {} was not: {}", PROVENANCE_FILE, e),
+            )
+        })?;
+        println!(
+            "  ✅ {} (entropy commitment {})",
+            PROVENANCE_FILE, provenance.entropy_commitment
+        );
+    }
+
+    Ok(Generated {
+        validators: validator_info_list
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect(),
+        interrupted: false,
+        failed,
+        aborted: None,
+    })
+}
+
+/// One `generate --batch` group: `count` key pairs of `scheme` with
+/// 2^`log_num_active_epochs` active epochs.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pub scheme: String,
+    pub count: usize,
+    pub log_num_active_epochs: usize,
+}
+
+impl FromStr for Batch {
+    type Err = String;
+
+    /// Parse `scheme=NAME,count=N,epochs=LOG2`, in any order.
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let (mut scheme, mut count, mut epochs) = (None, None, None);
+        for field in spec.split(',') {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, found {:?}", field))?;
+            let number = |value: &str| {
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("{} {:?}: {}", key.trim(), value, e))
+            };
+            match key.trim() {
+                "scheme" => scheme = Some(value.trim().to_string()),
+                "count" => count = Some(number(value)?),
+                "epochs" => epochs = Some(number(value)?),
+                other => {
+                    return Err(format!(
+                        "unknown field {:?}; expected scheme, count and epochs",
+                        other
+                    ))
+                }
+            }
+        }
+        let count = count.ok_or("missing count=")?;
+        if count == 0 {
+            return Err("count must be at least 1".to_string());
+        }
+        Ok(Batch {
+            scheme: scheme.ok_or("missing scheme=")?,
+            count,
+            log_num_active_epochs: epochs.ok_or("missing epochs= (log2 of the active epochs)")?,
+        })
+    }
+}
+
+/// Key pairs written for one batch of [`generate_batches`].
+pub struct GeneratedBatch {
+    pub scheme: &'static dyn SchemeHandle,
+    pub log_num_active_epochs: usize,
+    pub validators: Vec<ValidatorInfo>,
+}
+
+/// Outcome of [`generate_batches`], or of [`generate_keys`] as one batch.
+pub struct BatchesGenerated {
+    /// The batches generated, the last one possibly incomplete.
+    pub batches: Vec<GeneratedBatch>,
+    /// As for [`Generated`], of the batch that stopped the run.
+    pub interrupted: bool,
+    pub failed: Vec<FailedKey>,
+    pub aborted: Option<io::Error>,
+}
+
+impl BatchesGenerated {
+    /// The key pairs of a single-scheme run as one batch.
+    pub fn single(
+        scheme: &'static dyn SchemeHandle,
+        log_num_active_epochs: usize,
+        generated: Generated,
+    ) -> Self {
+        Self {
+            batches: vec![GeneratedBatch {
+                scheme,
+                log_num_active_epochs,
+                validators: generated.validators,
+            }],
+            interrupted: generated.interrupted,
+            failed: generated.failed,
+            aborted: generated.aborted,
+        }
+    }
+
+    /// Number of key pairs written, over all batches.
+    pub fn written(&self) -> usize {
+        self.batches
+            .iter()
+            .map(|batch| batch.validators.len())
+            .sum()
+    }
+}
+
+/// Generate the `batches` one after another into one key set, numbering
+/// validators on from one batch to the next. The first batch not generated
+/// in full stops the run.
+///
+/// Batches cannot be resumed; a batch's failed key pairs are recorded in
+/// [`CHECKPOINT_FILE`] for reference only.
+pub fn generate_batches(
+    batches: &[Batch],
+    options: &OutputOptions,
+    pipeline: &PipelineConfig,
+    entropy: &Arc<dyn EntropySource>,
+) -> io::Result<BatchesGenerated> {
+    // Every scheme is checked before any key is generated
+    let schemes = batches
+        .iter()
+        .map(|batch| scheme::lookup(&batch.scheme))
+        .collect::<io::Result<Vec<_>>>()?;
+    let total: usize = batches.iter().map(|batch| batch.count).sum();
+    println!(
+        "🧩 Generating {} validators in {} batches\n",
+        total,
+        batches.len()
+    );
+
+    let started = Instant::now();
+    let mut generated = Vec::new();
+    let mut first_index = 0;
+    for ((number, batch), scheme) in (1..).zip(batches).zip(schemes) {
+        println!(
+            "📦 Batch {} of {}: {} validators of {}, first index {}\n",
+            number,
+            batches.len(),
+            batch.count,
+            scheme.name(),
+            first_index
+        );
+        let batch_options = OutputOptions {
+            scheme,
+            first_index,
+            ..*options
+        };
+        let stop = |written: usize| {
+            println!(
+                "\n⚠️  Batch {} ({}) stopped after {} of {} key pairs; the batches before it are complete, and batches cannot be resumed",
+                number,
+                scheme.name(),
+                written,
+                batch.count
+            );
+        };
+        let keys = match generate_keys(
+            batch.count,
+            batch.log_num_active_epochs,
+            &batch_options,
+            pipeline,
+            entropy,
+            false,
+            None,
+        ) {
+            Ok(keys) => keys,
+            Err(e) if generated.is_empty() => return Err(e),
+            Err(e) => {
+                stop(0);
+                return Ok(BatchesGenerated {
+                    batches: generated,
+                    interrupted: false,
+                    failed: Vec::new(),
+                    aborted: Some(e),
+                });
+            }
+        };
+        let written = keys.validators.len();
+        let complete = keys.aborted.is_none() && keys.failed.is_empty() && !keys.interrupted;
+        generated.push(GeneratedBatch {
+            scheme,
+            log_num_active_epochs: batch.log_num_active_epochs,
+            validators: keys.validators,
+        });
+        if !complete {
+            stop(written);
+            return Ok(BatchesGenerated {
+                batches: generated,
+                interrupted: keys.interrupted,
+                failed: keys.failed,
+                aborted: keys.aborted,
+            });
+        }
+        first_index += batch.count;
+        println!();
+    }
+
+    println!(
+        "✅ Generated {} validators of {} batches in {}.",
+        total,
+        batches.len(),
+        format_duration(started.elapsed())
+    );
+    Ok(BatchesGenerated {
+        batches: generated,
+        interrupted: false,
+        failed: Vec::new(),
+        aborted: None,
+    })
+}
+
+/// Generate the key pair with the given index from its own RNG.
+fn generate_key(
+    scheme: &dyn SchemeHandle,
+    index: usize,
+    num_active_epochs: usize,
+    entropy: &dyn EntropySource,
+    audit_entropy: bool,
+) -> io::Result<GeneratedKey> {
+    let mut rng = entropy.rng_for(index)?;
+    let (pair, entropy_sha256) = if audit_entropy {
+        let mut rng = AuditedRng::new(rng);
+        let pair = scheme.key_gen(&mut rng, 0, num_active_epochs);
+        (pair, Some(rng.hex_digest()))
+    } else {
+        (scheme.key_gen(rng.as_mut(), 0, num_active_epochs), None)
+    };
+    Ok(GeneratedKey {
+        index,
+        pair,
+        entropy_sha256,
+        derivation_path: entropy.derivation_path(index),
+    })
+}
+
+/// Like [`generate_key`], but give up after `timeout`, returning `None`. The
+/// key pair is generated on a thread of its own, which is left behind if it
+/// hangs: a thread cannot be stopped from outside, and it ends with the
+/// process.
+fn generate_key_within(
+    scheme: &'static dyn SchemeHandle,
+    index: usize,
+    num_active_epochs: usize,
+    entropy: &Arc<dyn EntropySource>,
+    audit_entropy: bool,
+    timeout: Duration,
+) -> io::Result<Option<GeneratedKey>> {
+    let (sender, receiver) = mpsc::channel();
+    let entropy = Arc::clone(entropy);
+    thread::Builder::new()
+        .name(format!("keygen-{}", index))
+        .spawn(move || {
+            let key = generate_key(
+                scheme,
+                index,
+                num_active_epochs,
+                entropy.as_ref(),
+                audit_entropy,
+            );
+            // Nobody waits any more if it timed out
+            let _ = sender.send(key);
+        })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(key) => key.map(Some),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        Err(RecvTimeoutError::Disconnected) => Err(io::Error::other(format!(
+            "Key generation for validator {} panicked",
+            index
+        ))),
+    }
+}
+
+/// Generate and write the single key pair with the given index, e.g. to
+/// recover one validator from a master seed without regenerating the rest,
+/// or to replace one.
+pub fn generate_one(
+    index: usize,
+    log_num_active_epochs: usize,
+    options: &OutputOptions,
+    entropy: &dyn EntropySource,
+) -> io::Result<ValidatorInfo> {
+    options.check()?;
+    let key = generate_key(
+        options.scheme,
+        index,
+        1 << log_num_active_epochs,
+        entropy,
+        false,
+    )?;
+    let ssz = write_ssz(&key, options)?;
+    finish_key(&key, &ssz, options)
+}
+
+/// Parse a `--namespace`: letters, digits, `-` and `_`, so it is safe in
+/// file names everywhere.
+pub fn parse_namespace(namespace: &str) -> Result<String, String> {
+    if namespace.is_empty() {
+        return Err("Namespace must not be empty".to_string());
+    }
+    if !namespace
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid namespace {}: use only letters, digits, - and _",
+            namespace
+        ));
+    }
+    Ok(namespace.to_string())
+}
+
+/// What [`write_ssz`] wrote for a key pair, handed on to [`finish_key`].
+struct SszWritten {
+    key_prefix: String,
+    pk_bytes: Vec<u8>,
+    sk_bytes: Zeroizing<Vec<u8>>,
+    /// Checksums of the files written so far, for the metadata file.
+    checksums: BTreeMap<String, String>,
+    /// Output so far, printed by [`finish_key`] in one piece.
+    log: String,
+}
+
+/// Write the SSZ files of one key pair.
+fn write_ssz(key: &GeneratedKey, options: &OutputOptions) -> io::Result<SszWritten> {
+    let GeneratedKey { index, pair, .. } = key;
+    let store = options.store;
+    let scheme = options.scheme;
+
+    // Serialize the public key to SSZ bytes
+    let pk_bytes = pair.pk_bytes();
+
+    // Determine key prefix based on format
+    let name = if options.distributed {
+        // Extract first 3 and last 3 bytes from pk_bytes
+        if pk_bytes.len() < 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Public key bytes too short to extract first-3 last-3 bytes",
+            ));
+        }
+        let first_3 = &pk_bytes[0..3];
+        let last_3 = &pk_bytes[pk_bytes.len() - 3..];
+        let first_3_hex = hex::encode(first_3);
+        let last_3_hex = hex::encode(last_3);
+        format!("validator-{}-{}", first_3_hex, last_3_hex)
+    } else {
+        format!("validator_{}", index)
+    };
+    let key_prefix = match options.namespace {
+        Some(namespace) => format!("{}-{}", namespace, name),
+        None => name,
+    };
+
+    // Collected and printed at once so output of concurrent writers doesn't interleave
+    let mut log = format!("Generated {}:\n", key_prefix);
+
+    let verify_pk = options.verify_writes == VerifyWrites::All;
+    let verify_sk = options.verify_writes != VerifyWrites::None;
+
+    // Write public key to SSZ file
+    let pk_name = format!("{}_pk.ssz", key_prefix);
+    store.store(&pk_name, &pk_bytes)?;
+    if verify_pk {
+        verify_ssz_write(scheme, KeyKind::Public, store, &pk_name, &pk_bytes)?;
+    }
+
+    // Serialize the secret key to SSZ bytes and write to a binary .ssz file
+    let sk_bytes = Zeroizing::new(pair.sk_bytes());
+    let sk_name = format!("{}_sk.ssz", key_prefix);
+    store.store(&sk_name, &sk_bytes)?;
+    if verify_sk {
+        verify_ssz_write(scheme, KeyKind::Secret, store, &sk_name, &sk_bytes)?;
+    }
+
+    log.push_str(&format!("  ✅ {}_pk.ssz\n", key_prefix));
+    log.push_str(&format!("  ✅ {}_sk.ssz\n", key_prefix));
+
+    // Checksums of every file written for this validator, for the metadata file
+    let mut checksums = BTreeMap::new();
+    if options.write_metadata {
+        checksums.insert(
+            format!("{}_pk.ssz", key_prefix),
+            checksum::sha256_hex(&pk_bytes),
+        );
+        checksums.insert(
+            format!("{}_sk.ssz", key_prefix),
+            checksum::sha256_hex(&sk_bytes),
+        );
+    }
+
+    Ok(SszWritten {
+        key_prefix,
+        pk_bytes,
+        sk_bytes,
+        checksums,
+        log,
+    })
+}
+
+/// Write the legacy JSON export and metadata of a key pair whose SSZ files
+/// are written, returning its manifest entry.
+fn finish_key(
+    key: &GeneratedKey,
+    ssz: &SszWritten,
+    options: &OutputOptions,
+) -> io::Result<ValidatorInfo> {
+    let GeneratedKey {
+        index,
+        pair,
+        derivation_path,
+        ..
+    } = key;
+    let SszWritten {
+        key_prefix,
+        pk_bytes,
+        sk_bytes,
+        checksums,
+        log,
+    } = ssz;
+    // Copies, so a failed attempt can be retried
+    let mut checksums = checksums.clone();
+    let mut log = log.clone();
+    let store = options.store;
+    let scheme = options.scheme;
+    let verify_pk = options.verify_writes == VerifyWrites::All;
+    let verify_sk = options.verify_writes != VerifyWrites::None;
+
+    if options.export_format == ExportFormat::Both {
+        // Also export legacy JSON representations for backwards compatibility
+        let pk_json = pair.pk_json(options.pretty_json);
+        let pk_json_name = format!("{}_pk.json", key_prefix);
+        store.store(&pk_json_name, pk_json.as_bytes())?;
+        if verify_pk {
+            verify_json_write(scheme, KeyKind::Public, store, &pk_json_name, pk_bytes)?;
+        }
+
+        log.push_str(&format!("  ⚠️  (legacy) {}_pk.json\n", key_prefix));
+        if options.write_metadata {
+            checksums.insert(
+                format!("{}_pk.json", key_prefix),
+                checksum::sha256_hex(pk_json.as_bytes()),
+            );
+        }
+
+        // A second plaintext copy of the secret key, so only on request
+        if options.export_secret_json {
+            let sk_json = Zeroizing::new(pair.sk_json(options.pretty_json));
+            let sk_json_name = format!("{}_sk.json", key_prefix);
+            store.store(&sk_json_name, sk_json.as_bytes())?;
+            if verify_sk {
+                verify_json_write(scheme, KeyKind::Secret, store, &sk_json_name, sk_bytes)?;
+            }
+
+            log.push_str(&format!("  ⚠️  (legacy) {}_sk.json\n", key_prefix));
+            if options.write_metadata {
+                checksums.insert(
+                    format!("{}_sk.json", key_prefix),
+                    checksum::sha256_hex(sk_json.as_bytes()),
+                );
+            }
+        }
+    }
+
+    let pubkey_hex = format!("0x{}", hex::encode(pk_bytes));
+
+    if options.write_metadata {
+        let activation = pair.activation_interval();
+        KeyMetadata {
+            pubkey_hex: pubkey_hex.clone(),
+            fingerprint: keys::fingerprint(pk_bytes),
+            key_scheme: scheme.name().to_string(),
+            hash_function: scheme.hash_function().name().to_string(),
+            scheme_fingerprint: Some(scheme.fingerprint()),
+            activation_epoch: activation.start,
+            end_epoch: activation.end,
+            derivation_path: derivation_path.clone(),
+            ceremony_id: options.ceremony_id.map(str::to_string),
+            namespace: options.namespace.map(str::to_string),
+            canary: options.is_canary(*index),
+            checksums,
+            created_at: metadata::unix_now(),
+        }
+        .write(store, key_prefix)?;
+        log.push_str(&format!("  ✅ {}.meta.json\n", key_prefix));
+    }
+
+    if verify_sk {
+        log.push_str(&format!(
+            "  🔍 read back and verified {} files\n",
+            if verify_pk { "all" } else { "secret key" }
+        ));
+    }
+
+    print!("{}", log);
+
+    Ok(ValidatorInfo {
+        index: *index,
+        pubkey_hex,
+        privkey_file: format!("{}_sk.ssz", key_prefix),
+        derivation_path: derivation_path.clone(),
+        canary: options.is_canary(*index),
+    })
+}
+
+/// Read back a freshly written SSZ file and check that it holds exactly
+/// `expected` and decodes to a key that re-serializes to the same bytes.
+fn verify_ssz_write(
+    scheme: &dyn SchemeHandle,
+    kind: KeyKind,
+    store: &dyn KeyStore,
+    name: &str,
+    expected: &[u8],
+) -> io::Result<()> {
+    let bytes = store.load(name)?;
+    if bytes != expected {
+        return Err(write_verification_error(
+            name,
+            "contents differ from what was written",
+        ));
+    }
+    let reencoded = scheme
+        .reencode_ssz(kind, &bytes)
+        .map_err(|reason| write_verification_error(name, &reason))?;
+    if reencoded != expected {
+        return Err(write_verification_error(
+            name,
+            "decoded key differs from the generated key",
+        ));
+    }
+    Ok(())
+}
+
+/// Read back a freshly written legacy JSON file and check that it decodes to
+/// the key whose SSZ encoding is `expected_ssz`.
+fn verify_json_write(
+    scheme: &dyn SchemeHandle,
+    kind: KeyKind,
+    store: &dyn KeyStore,
+    name: &str,
+    expected_ssz: &[u8],
+) -> io::Result<()> {
+    let json = store.load(name)?;
+    let ssz = scheme
+        .json_to_ssz(kind, &json)
+        .map_err(|reason| write_verification_error(name, &reason))?;
+    if ssz != expected_ssz {
+        return Err(write_verification_error(
+            name,
+            "decoded key differs from the generated key",
+        ));
+    }
+    Ok(())
+}
+
+fn write_verification_error(name: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Write verification failed for {}: {}", name, reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_batches() {
+        let batch: Batch = " epochs = 5 ,scheme=SIGTopLevelTargetSumLifetime32Dim64Base8, count=3"
+            .parse()
+            .unwrap();
+        assert_eq!(batch.scheme, "SIGTopLevelTargetSumLifetime32Dim64Base8");
+        assert_eq!(batch.count, 3);
+        assert_eq!(batch.log_num_active_epochs, 5);
+    }
+
+    #[test]
+    fn rejects_bad_batches() {
+        let error = |spec: &str| spec.parse::<Batch>().unwrap_err();
+        assert!(error("scheme=S,count=1").starts_with("missing epochs="));
+        assert!(error("count=1,epochs=2").starts_with("missing scheme="));
+        assert!(error("scheme=S,epochs=2").starts_with("missing count="));
+        assert_eq!(
+            error("scheme=S,count=0,epochs=2"),
+            "count must be at least 1"
+        );
+        assert!(error("scheme=S,count=-1,epochs=2").starts_with("count \"-1\""));
+        assert!(error("scheme=S,count=1,epochs=2,seed=3").starts_with("unknown field \"seed\""));
+        assert!(error("scheme=S,count").starts_with("expected key=value"));
+        assert!(error("").starts_with("expected key=value"));
+    }
+}
@@ -0,0 +1,238 @@
+//! `interop run`: check that an external verifier agrees with this CLI on
+//! which signatures to accept.
+//!
+//! A throwaway key pair signs a random message, and a matrix of cases is
+//! derived from it: the valid signature, and ones with the signature,
+//! public key or message corrupted, the wrong epoch or another key's public
+//! key. Each case is written to a directory of its own:
+//!
+//! ```text
+//! scheme          scheme name, e.g. SIGTopLevelTargetSumLifetime32Dim64Base8
+//! epoch           epoch, in decimal
+//! message.hex     message, hex-encoded
+//! pk.ssz          public key, SSZ
+//! signature.ssz   signature, SSZ
+//! pk.json         the same in the reference format (see `convert`), when
+//! signature.json  the SSZ still decodes
+//! ```
+//!
+//! The external command is run through `sh -c` with the case directory as
+//! its last argument, and must exit with 0 to accept, 1 to reject, and
+//! anything else if it could not decide. Every case it decides differently
+//! from this CLI is a disagreement.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use leansig::MESSAGE_LENGTH;
+use rand::RngCore;
+
+use crate::exit::{self, Failure};
+use crate::scheme::{KeyKind, SchemeHandle};
+
+/// One input of the matrix.
+struct Case {
+    name: &'static str,
+    pk: Vec<u8>,
+    epoch: u32,
+    message: [u8; MESSAGE_LENGTH],
+    signature: Vec<u8>,
+}
+
+/// A verifier's decision on a case.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Verdict {
+    Accept,
+    Reject,
+    /// Exited with neither 0 nor 1, with its exit code if it had one.
+    Undecided(Option<i32>),
+}
+
+impl Verdict {
+    fn describe(self) -> String {
+        match self {
+            Verdict::Accept => "accept".to_string(),
+            Verdict::Reject => "reject".to_string(),
+            Verdict::Undecided(Some(code)) => format!("error (exit {})", code),
+            Verdict::Undecided(None) => "error (killed)".to_string(),
+        }
+    }
+}
+
+/// Run the case matrix of `scheme`, with keys of 2^`log_num_active_epochs`
+/// active epochs, against `external_cmd`. The cases are written to
+/// `work_dir`, which is kept, else to a temporary directory removed after
+/// a run without disagreements.
+pub fn run(
+    scheme: &dyn SchemeHandle,
+    external_cmd: &str,
+    log_num_active_epochs: usize,
+    work_dir: Option<&Path>,
+) -> io::Result<()> {
+    let (dir, temporary) = match work_dir {
+        Some(dir) => (dir.to_path_buf(), false),
+        None => (
+            std::env::temp_dir().join(format!("hashsig-interop-{}", uuid::Uuid::new_v4())),
+            true,
+        ),
+    };
+    fs::create_dir_all(&dir)?;
+
+    println!(
+        "🔑 Generating throwaway {} keys with 2^{} active epochs",
+        scheme.name(),
+        log_num_active_epochs
+    );
+    let cases = cases(scheme, log_num_active_epochs)?;
+    println!(
+        "🤝 Running {} cases against `{}` in {}\n",
+        cases.len(),
+        external_cmd,
+        dir.display()
+    );
+
+    let mut disagreements = 0;
+    for case in &cases {
+        let case_dir = write_case(scheme, &dir, case)?;
+        let ours = if scheme
+            .verify(&case.pk, case.epoch, &case.message, &case.signature)
+            .unwrap_or(false)
+        {
+            Verdict::Accept
+        } else {
+            Verdict::Reject
+        };
+        let theirs = run_external(external_cmd, &case_dir)?;
+        if ours == theirs {
+            println!("  ✅ {:<24} both {}", case.name, ours.describe());
+        } else {
+            println!(
+                "  ❌ {:<24} hashsig: {}, external: {}",
+                case.name,
+                ours.describe(),
+                theirs.describe()
+            );
+            disagreements += 1;
+        }
+    }
+
+    if disagreements > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "The external verifier disagreed on {} of {} cases; they are kept in {}",
+                disagreements,
+                cases.len(),
+                dir.display()
+            ),
+        ));
+    }
+    if temporary {
+        fs::remove_dir_all(&dir)?;
+    }
+    println!(
+        "\n✅ The external verifier agreed on all {} cases",
+        cases.len()
+    );
+    Ok(())
+}
+
+/// The case matrix: a valid signature and its corruptions.
+fn cases(scheme: &dyn SchemeHandle, log_num_active_epochs: usize) -> io::Result<Vec<Case>> {
+    let mut rng = rand::rng();
+    let pair = scheme.key_gen(&mut rng, 0, 1 << log_num_active_epochs);
+    let other = scheme.key_gen(&mut rng, 0, 1 << log_num_active_epochs);
+    let prepared = pair.prepared_interval();
+    if prepared.end - prepared.start < 2 {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            "The key needs at least two prepared epochs; raise --log-num-active-epochs",
+        ));
+    }
+    let epoch = prepared.start as u32;
+    let mut message = [0u8; MESSAGE_LENGTH];
+    rng.fill_bytes(&mut message);
+    let pk = pair.pk_bytes();
+    let signature = scheme.sign(&pair.sk_bytes(), epoch, &message)?;
+
+    let valid = |name: &'static str| Case {
+        name,
+        pk: pk.clone(),
+        epoch,
+        message,
+        signature: signature.clone(),
+    };
+    let mut signature_flipped = valid("signature-bit-flipped");
+    flip_middle_bit(&mut signature_flipped.signature);
+    let mut signature_truncated = valid("signature-truncated");
+    signature_truncated.signature.truncate(signature.len() - 1);
+    let mut pubkey_flipped = valid("pubkey-bit-flipped");
+    flip_middle_bit(&mut pubkey_flipped.pk);
+    let mut message_flipped = valid("message-bit-flipped");
+    flip_middle_bit(&mut message_flipped.message);
+    let mut wrong_epoch = valid("wrong-epoch");
+    wrong_epoch.epoch += 1;
+    let mut other_key = valid("other-key");
+    other_key.pk = other.pk_bytes();
+
+    Ok(vec![
+        valid("valid"),
+        signature_flipped,
+        signature_truncated,
+        pubkey_flipped,
+        message_flipped,
+        wrong_epoch,
+        other_key,
+    ])
+}
+
+fn flip_middle_bit(bytes: &mut [u8]) {
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 1;
+}
+
+/// Write `case` to its directory under `dir`, following the file protocol.
+fn write_case(scheme: &dyn SchemeHandle, dir: &Path, case: &Case) -> io::Result<PathBuf> {
+    let case_dir = dir.join(case.name);
+    fs::create_dir_all(&case_dir)?;
+    fs::write(case_dir.join("scheme"), format!("{}\n", scheme.name()))?;
+    fs::write(case_dir.join("epoch"), format!("{}\n", case.epoch))?;
+    fs::write(
+        case_dir.join("message.hex"),
+        format!("{}\n", hex::encode(case.message)),
+    )?;
+    fs::write(case_dir.join("pk.ssz"), &case.pk)?;
+    fs::write(case_dir.join("signature.ssz"), &case.signature)?;
+    // Corrupted SSZ may not decode, and then has no JSON form
+    if let Ok(json) = scheme.ssz_to_json(KeyKind::Public, &case.pk) {
+        fs::write(
+            case_dir.join("pk.json"),
+            serde_json::to_vec(&json).map_err(io::Error::other)?,
+        )?;
+    }
+    if let Ok(json) = scheme.signature_json(&case.signature) {
+        fs::write(
+            case_dir.join("signature.json"),
+            serde_json::to_vec(&json).map_err(io::Error::other)?,
+        )?;
+    }
+    Ok(case_dir)
+}
+
+/// Run `external_cmd` on `case_dir` and read its verdict from the exit code.
+fn run_external(external_cmd: &str, case_dir: &Path) -> io::Result<Verdict> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", external_cmd))
+        .arg("sh")
+        .arg(case_dir)
+        .status()
+        .map_err(|e| io::Error::other(format!("Failed to run `{}`: {}", external_cmd, e)))?;
+    Ok(match status.code() {
+        Some(0) => Verdict::Accept,
+        Some(1) => Verdict::Reject,
+        code => Verdict::Undecided(code),
+    })
+}
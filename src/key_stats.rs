@@ -0,0 +1,124 @@
+//! `stats key`: what a secret key file is made of.
+//!
+//! leansig does not expose the layout of its secret keys, so the breakdown
+//! comes from their JSON representation: for each field, its shape, the
+//! number of values it holds and its estimated share of the SSZ bytes. Only
+//! counts and sizes are printed, never key material.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::scheme::{self, KeyKind};
+use crate::units::format_size;
+
+/// Nesting shown in a field's shape before it is abbreviated.
+const SHAPE_DEPTH: usize = 4;
+
+/// Print the structure of the SSZ secret key `sk`.
+pub fn key_stats(sk: &Path, scheme_name: Option<&str>) -> io::Result<()> {
+    let scheme = scheme::for_key(scheme_name, sk)?;
+    let bytes = Zeroizing::new(fs::read(sk)?);
+    let (active, prepared) = scheme.secret_key_intervals(&bytes)?;
+    let json = scheme
+        .ssz_to_json(KeyKind::Secret, &bytes)
+        .map_err(|reason| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to decode {}: {}", sk.display(), reason),
+            )
+        })?;
+
+    println!("🔑 {} ({})\n", sk.display(), scheme.name());
+    println!(
+        "  Size:             {} ({} bytes)",
+        format_size(bytes.len() as u64),
+        bytes.len()
+    );
+    println!(
+        "  Lifetime:         {} epochs (2^{})",
+        scheme.lifetime(),
+        scheme.lifetime().trailing_zeros()
+    );
+    println!(
+        "  Active epochs:    {}..{} ({})",
+        active.start,
+        active.end,
+        active.end - active.start
+    );
+    println!(
+        "  Prepared epochs:  {}..{} ({} one-time keys materialized)",
+        prepared.start,
+        prepared.end,
+        prepared.end - prepared.start
+    );
+
+    let fields: Vec<(String, &Value)> = match &json {
+        Value::Object(map) => map
+            .iter()
+            .map(|(name, value)| (name.clone(), value))
+            .collect(),
+        other => vec![("(key)".to_string(), other)],
+    };
+    let total: usize = fields.iter().map(|(_, value)| count_values(value)).sum();
+    println!("\n  Structure (from the JSON representation, byte shares estimated):");
+    println!(
+        "    {:<24} {:>12} {:>12} {:>7}  shape",
+        "field", "values", "≈ bytes", "share"
+    );
+    for (name, value) in &fields {
+        let values = count_values(value);
+        let share = if total == 0 {
+            0.0
+        } else {
+            values as f64 / total as f64
+        };
+        println!(
+            "    {:<24} {:>12} {:>12} {:>6.1}%  {}",
+            name,
+            values,
+            format_size((share * bytes.len() as f64) as u64),
+            share * 100.0,
+            shape(value, SHAPE_DEPTH)
+        );
+    }
+    Ok(())
+}
+
+/// Number of scalar values (numbers, strings, booleans) in `value`.
+fn count_values(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => items.iter().map(count_values).sum(),
+        Value::Object(map) => map.values().map(count_values).sum(),
+        Value::Null => 0,
+        _ => 1,
+    }
+}
+
+/// Shape of `value`, e.g. `[32 × {nodes: [64 × n]}]`. Arrays show their
+/// first item, so layers of shrinking size show the largest.
+fn shape(value: &Value, depth: usize) -> String {
+    if depth == 0 {
+        return "…".to_string();
+    }
+    match value {
+        Value::Array(items) => match items.first() {
+            Some(first) => format!("[{} × {}]", items.len(), shape(first, depth - 1)),
+            None => "[]".to_string(),
+        },
+        Value::Object(map) => {
+            let fields: Vec<String> = map
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, shape(value, depth - 1)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        Value::String(_) => "str".to_string(),
+        Value::Number(_) => "n".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::Null => "null".to_string(),
+    }
+}
@@ -0,0 +1,49 @@
+use std::io;
+
+use clap::ValueEnum;
+use leansig::MESSAGE_LENGTH;
+
+use crate::checksum;
+
+/// Hash functions of the instantiations leansig exposes. Only Poseidon2 ones
+/// exist so far; new variants go here once upstream adds e.g. SHA3.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum HashFunction {
+    Poseidon2,
+}
+
+impl HashFunction {
+    /// Name recorded in manifests and metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashFunction::Poseidon2 => "Poseidon2",
+        }
+    }
+}
+
+/// Short identifier of a public key: the first 8 bytes of the SHA-256 of its
+/// SSZ encoding, hex-encoded.
+pub fn fingerprint(pk_bytes: &[u8]) -> String {
+    checksum::sha256_hex(pk_bytes)[..16].to_string()
+}
+
+/// Parse a hex-encoded message (with or without `0x`) of exactly
+/// `MESSAGE_LENGTH` bytes.
+pub fn parse_message_hex(message_hex: &str) -> io::Result<[u8; MESSAGE_LENGTH]> {
+    let bytes = hex::decode(message_hex.trim().trim_start_matches("0x")).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid message hex: {}", e),
+        )
+    })?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Message must be exactly {} bytes, got {}",
+                MESSAGE_LENGTH,
+                bytes.len()
+            ),
+        )
+    })
+}
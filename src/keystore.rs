@@ -0,0 +1,652 @@
+//! Storage backends for key files.
+//!
+//! Key material is read and written through [`KeyStore`] so the filesystem,
+//! in-memory and encrypted-archive backends (and any future ones) share a
+//! single code path. Files are addressed by bare names such as
+//! `validator_0_sk.ssz`.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rand::RngCore;
+
+use crate::backup;
+use crate::checksum;
+use crate::layout::OBJECTS_DIR;
+use crate::manifest::{self, Manifest, ManifestPaths};
+
+pub trait KeyStore: Send + Sync {
+    /// Human-readable location of the store, for messages.
+    fn location(&self) -> String;
+
+    /// Names of all stored files, sorted by [`manifest::compare_names`].
+    fn list(&self) -> io::Result<Vec<String>>;
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>>;
+
+    /// Create or replace a file.
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()>;
+
+    fn delete(&self, name: &str) -> io::Result<()>;
+
+    /// Filesystem path of the file `name`, for stores backed by files.
+    fn file_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// How a manifest stored in `store` refers to the stored file `name`. Stores
+/// not backed by files always use bare names.
+pub fn manifest_reference(
+    store: &dyn KeyStore,
+    name: &str,
+    paths: ManifestPaths,
+) -> io::Result<String> {
+    let (Some(manifest), Some(file)) = (
+        store.file_path(manifest::MANIFEST_FILE),
+        store.file_path(name),
+    ) else {
+        return Ok(name.to_string());
+    };
+    let file = fs::canonicalize(file)?;
+    let path = match paths {
+        ManifestPaths::Absolute => file,
+        ManifestPaths::Relative => {
+            let manifest_dir = manifest.parent().unwrap_or(Path::new("."));
+            relative_path(&fs::canonicalize(manifest_dir)?, &file)
+        }
+    };
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Reject names that would escape the store.
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid key file name {:?}", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Files in a directory on the local filesystem.
+pub struct FsKeyStore {
+    dir: PathBuf,
+}
+
+impl FsKeyStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Open a store, creating the directory if it doesn't exist.
+    pub fn create(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let store = Self::new(dir);
+        fs::create_dir_all(&store.dir)?;
+        Ok(store)
+    }
+
+    fn path(&self, name: &str) -> io::Result<PathBuf> {
+        check_name(name)?;
+        Ok(self.dir.join(name))
+    }
+
+    /// Overwrite a file in place with random bytes, sync it and delete it.
+    ///
+    /// This is best effort: journaling and copy-on-write filesystems or SSD
+    /// wear levelling may keep older copies of the data.
+    pub fn shred(&self, name: &str) -> io::Result<()> {
+        shred_file(&self.path(name)?)
+    }
+}
+
+/// Like [`FsKeyStore::shred`], for any file.
+pub fn shred_file(path: &Path) -> io::Result<()> {
+    overwrite(OpenOptions::new().write(true).open(path)?)?;
+    fs::remove_file(path)
+}
+
+/// Overwrite the open `file` with random bytes and sync it. The file may
+/// already be unlinked or replaced: its contents stay reachable through the
+/// handle until it is dropped.
+pub fn overwrite(mut file: File) -> io::Result<()> {
+    let mut remaining = file.metadata()?.len();
+    let mut rng = rand::rng();
+    let mut buffer = vec![0u8; 1 << 16];
+
+    while remaining > 0 {
+        let n = remaining.min(buffer.len() as u64) as usize;
+        rng.fill_bytes(&mut buffer[..n]);
+        file.write_all(&buffer[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+impl KeyStore for FsKeyStore {
+    fn location(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort_by(|a, b| manifest::compare_names(a, b));
+        Ok(names)
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(name)?)
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        File::create(self.path(name)?)?.write_all(contents)
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.path(name)?)
+    }
+
+    fn file_path(&self, name: &str) -> Option<PathBuf> {
+        self.path(name).ok()
+    }
+}
+
+/// Public files in one directory and secret key files in another, so
+/// secret keys can land on a separate (e.g. encrypted) volume.
+///
+/// The manifest lives with the public files and refers to secret keys by
+/// path (see [`manifest_reference`]).
+pub struct SplitKeyStore {
+    public: FsKeyStore,
+    secret: FsKeyStore,
+}
+
+impl SplitKeyStore {
+    /// Open a store, creating both directories if they don't exist.
+    pub fn create(
+        public_dir: impl Into<PathBuf>,
+        secret_dir: impl Into<PathBuf>,
+    ) -> io::Result<Self> {
+        let public = FsKeyStore::create(public_dir)?;
+        let secret = FsKeyStore::create(secret_dir)?;
+        Ok(Self { public, secret })
+    }
+
+    fn is_secret(name: &str) -> bool {
+        name.contains("_sk.")
+    }
+
+    fn store_for(&self, name: &str) -> &FsKeyStore {
+        if Self::is_secret(name) {
+            &self.secret
+        } else {
+            &self.public
+        }
+    }
+}
+
+/// Path of `target` relative to `base`; both must be absolute.
+fn relative_path(base: &Path, target: &Path) -> PathBuf {
+    let base: Vec<_> = base.components().collect();
+    let target: Vec<_> = target.components().collect();
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..base.len() {
+        path.push("..");
+    }
+    for component in &target[common..] {
+        path.push(component);
+    }
+    path
+}
+
+impl KeyStore for SplitKeyStore {
+    fn location(&self) -> String {
+        format!(
+            "{} (secret keys in {})",
+            self.public.location(),
+            self.secret.location()
+        )
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = self
+            .public
+            .list()?
+            .into_iter()
+            .filter(|name| !Self::is_secret(name))
+            .collect();
+        names.extend(
+            self.secret
+                .list()?
+                .into_iter()
+                .filter(|name| Self::is_secret(name)),
+        );
+        names.sort_by(|a, b| manifest::compare_names(a, b));
+        Ok(names)
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.store_for(name).load(name)
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        self.store_for(name).store(name, contents)
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        self.store_for(name).delete(name)
+    }
+
+    fn file_path(&self, name: &str) -> Option<PathBuf> {
+        self.store_for(name).file_path(name)
+    }
+}
+
+/// A key directory whose key files are stored by content: each one as
+/// `objects/<sha256>/<name>`, named after the SHA-256 of its contents. Only
+/// the manifest and run records (checkpoint, statistics, provenance) sit
+/// directly in the directory; the manifest maps every public key to its
+/// secret key's object.
+///
+/// An object's path is its checksum, so unchanged files keep their path
+/// across copies and rsync runs, and `verify-dir` checks every object
+/// against its name.
+pub struct ContentAddressedKeyStore {
+    dir: FsKeyStore,
+    /// SHA-256 of every stored key file, by file name.
+    objects: Mutex<BTreeMap<String, String>>,
+}
+
+impl ContentAddressedKeyStore {
+    /// Open a store, creating the directory if it doesn't exist and picking
+    /// up the objects of an earlier (e.g. interrupted) run.
+    pub fn create(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = FsKeyStore::create(dir)?;
+        let objects = read_objects(&dir.dir)?
+            .into_iter()
+            .map(|(name, path)| (name, object_hash(&path).to_string()))
+            .collect();
+        Ok(Self {
+            dir,
+            objects: Mutex::new(objects),
+        })
+    }
+
+    fn objects(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.objects.lock().expect("key store lock poisoned")
+    }
+
+    /// Key files become objects; the manifest and run records don't.
+    fn is_object(name: &str) -> bool {
+        ["_sk.ssz", "_sk.json", "_pk.ssz", "_pk.json", ".meta.json"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+    }
+
+    fn object_path(&self, hash: &str, name: &str) -> PathBuf {
+        self.dir.dir.join(OBJECTS_DIR).join(hash).join(name)
+    }
+
+    fn not_found(name: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No such key file {}", name),
+        )
+    }
+}
+
+impl KeyStore for ContentAddressedKeyStore {
+    fn location(&self) -> String {
+        format!("{} (content-addressed)", self.dir.location())
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = self.dir.list()?;
+        names.extend(self.objects().keys().cloned());
+        names.sort_by(|a, b| manifest::compare_names(a, b));
+        Ok(names)
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        if !Self::is_object(name) {
+            return self.dir.load(name);
+        }
+        let hash = self.objects().get(name).cloned();
+        match hash {
+            Some(hash) => fs::read(self.object_path(&hash, name)),
+            None => Err(Self::not_found(name)),
+        }
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        if !Self::is_object(name) {
+            return self.dir.store(name, contents);
+        }
+        check_name(name)?;
+        let hash = checksum::sha256_hex(contents);
+        let path = self.object_path(&hash, name);
+        if !path.is_file() {
+            fs::create_dir_all(path.parent().expect("object paths have a parent"))?;
+            File::create(&path)?.write_all(contents)?;
+        }
+        let replaced = self.objects().insert(name.to_string(), hash.clone());
+        if let Some(old) = replaced.filter(|old| *old != hash) {
+            remove_object(&self.object_path(&old, name))?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        if !Self::is_object(name) {
+            return self.dir.delete(name);
+        }
+        let hash = self.objects().remove(name);
+        match hash {
+            Some(hash) => remove_object(&self.object_path(&hash, name)),
+            None => Err(Self::not_found(name)),
+        }
+    }
+
+    fn file_path(&self, name: &str) -> Option<PathBuf> {
+        if !Self::is_object(name) {
+            return self.dir.file_path(name);
+        }
+        let objects = self.objects();
+        objects.get(name).map(|hash| self.object_path(hash, name))
+    }
+}
+
+/// Every object of the content-addressed key directory `dir` (none if it is
+/// flat), as (file name, path).
+fn read_objects(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let objects_dir = dir.join(OBJECTS_DIR);
+    if !objects_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut objects = Vec::new();
+    for object_dir in fs::read_dir(objects_dir)? {
+        let object_dir = object_dir?;
+        if !object_dir.file_type()?.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(object_dir.path())? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                objects.push((
+                    entry.file_name().to_string_lossy().into_owned(),
+                    entry.path(),
+                ));
+            }
+        }
+    }
+    objects.sort_by(|(a, _), (b, _)| manifest::compare_names(a, b));
+    Ok(objects)
+}
+
+/// The content hash an object at `path` is stored under.
+pub fn object_hash(path: &Path) -> &str {
+    path.parent()
+        .and_then(Path::file_name)
+        .and_then(|hash| hash.to_str())
+        .unwrap_or_default()
+}
+
+/// Delete an object, and its directory once no other file shares it.
+fn remove_object(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)?;
+    if let Some(object_dir) = path.parent() {
+        // Fails while another file of the same contents is left
+        let _ = fs::remove_dir(object_dir);
+    }
+    Ok(())
+}
+
+/// A key directory whose manifest may refer to secret keys elsewhere, by
+/// relative or absolute path. Those files are addressed by their file name
+/// like the ones in the directory.
+pub struct ManifestKeyStore {
+    dir: FsKeyStore,
+    /// Secret key files outside the directory, by file name.
+    external: BTreeMap<String, PathBuf>,
+}
+
+impl ManifestKeyStore {
+    /// Open the key directory `dir`, resolving the paths in its manifest (if
+    /// any) against `dir`.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let mut external = BTreeMap::new();
+        if let Some(manifest) = Manifest::read_if_present(dir)? {
+            let privkey_files = manifest
+                .validators
+                .iter()
+                .map(|v| v.privkey_file.as_str())
+                .chain(manifest.revoked.iter().map(|t| t.privkey_file.as_str()));
+            for privkey_file in privkey_files {
+                let name = manifest::file_name(privkey_file);
+                if name == privkey_file {
+                    continue;
+                }
+                let path = dir.join(privkey_file);
+                // The legacy JSON copy, if one was exported, sits next to it
+                if let Some(prefix) = name.strip_suffix("_sk.ssz") {
+                    let json_name = format!("{}_sk.json", prefix);
+                    external.insert(json_name.clone(), path.with_file_name(json_name));
+                }
+                external.insert(name.to_string(), path);
+            }
+        }
+        // Key files of a content-addressed directory, public ones included
+        external.extend(read_objects(dir)?);
+        Ok(Self {
+            dir: FsKeyStore::new(dir),
+            external,
+        })
+    }
+
+    /// Like [`FsKeyStore::shred`].
+    pub fn shred(&self, name: &str) -> io::Result<()> {
+        match self.external.get(name) {
+            Some(path) => {
+                shred_file(path)?;
+                self.tidy_object(path);
+                Ok(())
+            }
+            None => self.dir.shred(name),
+        }
+    }
+
+    /// The content hash `name` is stored under, if it is an object of a
+    /// content-addressed directory.
+    pub fn object_hash(&self, name: &str) -> Option<&str> {
+        self.external
+            .get(name)
+            .filter(|path| self.is_object(path))
+            .map(|path| object_hash(path))
+    }
+
+    fn is_object(&self, path: &Path) -> bool {
+        path.parent().and_then(Path::parent) == Some(self.dir.dir.join(OBJECTS_DIR).as_path())
+    }
+
+    /// Remove the directory of a deleted object once it is empty.
+    fn tidy_object(&self, path: &Path) {
+        if let Some(object_dir) = path.parent().filter(|_| self.is_object(path)) {
+            let _ = fs::remove_dir(object_dir);
+        }
+    }
+}
+
+impl KeyStore for ManifestKeyStore {
+    fn location(&self) -> String {
+        self.dir.location()
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = self.dir.list()?;
+        for (name, path) in &self.external {
+            if path.is_file() && !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names.sort_by(|a, b| manifest::compare_names(a, b));
+        Ok(names)
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        match self.external.get(name) {
+            Some(path) => fs::read(path),
+            None => self.dir.load(name),
+        }
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        match self.external.get(name) {
+            // An object's path is its checksum; rewriting it would break that
+            Some(path) if self.is_object(path) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is a content-addressed object and cannot be rewritten",
+                    name
+                ),
+            )),
+            Some(path) => fs::write(path, contents),
+            None => self.dir.store(name, contents),
+        }
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        match self.external.get(name) {
+            Some(path) => {
+                fs::remove_file(path)?;
+                self.tidy_object(path);
+                Ok(())
+            }
+            None => self.dir.delete(name),
+        }
+    }
+
+    fn file_path(&self, name: &str) -> Option<PathBuf> {
+        match self.external.get(name) {
+            Some(path) => Some(path.clone()),
+            None => self.dir.file_path(name),
+        }
+    }
+}
+
+/// Files held in memory only.
+#[derive(Default)]
+pub struct MemoryKeyStore {
+    files: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryKeyStore {
+    fn files(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Vec<u8>>> {
+        self.files.lock().expect("key store lock poisoned")
+    }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn location(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = self.files().keys().cloned().collect();
+        names.sort_by(|a, b| manifest::compare_names(a, b));
+        Ok(names)
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.files().get(name).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such key file {}", name),
+            )
+        })
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        check_name(name)?;
+        self.files().insert(name.to_string(), contents.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        self.files().remove(name).map(|_| ()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such key file {}", name),
+            )
+        })
+    }
+}
+
+/// Files kept in memory and sealed into an encrypted backup bundle (see
+/// [`crate::backup`]) on [`ArchiveKeyStore::commit`]. The bundle can be
+/// unpacked with `restore`.
+pub struct ArchiveKeyStore {
+    path: PathBuf,
+    passphrase: String,
+    files: MemoryKeyStore,
+}
+
+impl ArchiveKeyStore {
+    /// Start a new archive at `path`, which must not exist yet.
+    pub fn create(path: impl Into<PathBuf>, passphrase: &str) -> io::Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Refusing to overwrite existing archive {}", path.display()),
+            ));
+        }
+        Ok(Self {
+            path,
+            passphrase: passphrase.to_string(),
+            files: MemoryKeyStore::default(),
+        })
+    }
+
+    /// Encrypt all stored files into the archive.
+    pub fn commit(&self) -> io::Result<()> {
+        backup::write_bundle_from_memory(
+            &self.path,
+            &self.passphrase,
+            "generate --archive",
+            &self.files.files(),
+        )
+    }
+}
+
+impl KeyStore for ArchiveKeyStore {
+    fn location(&self) -> String {
+        format!("{} (encrypted archive)", self.path.display())
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        self.files.list()
+    }
+
+    fn load(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.files.load(name)
+    }
+
+    fn store(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        self.files.store(name, contents)
+    }
+
+    fn delete(&self, name: &str) -> io::Result<()> {
+        self.files.delete(name)
+    }
+}
@@ -0,0 +1,125 @@
+//! Layout versioning of key directories.
+//!
+//! `generate` writes a [`LAYOUT_FILE`] marker into every key directory it
+//! creates, holding the layout version and the layout, e.g.
+//! `1 content-addressed`. Every manifest read checks it first, so a
+//! directory written by a newer hashsig with a layout this one does not
+//! know is refused instead of misread.
+//!
+//! Directories from before the marker have no marker. Every layout they
+//! can have is recognizable from the directory itself, so they are read as
+//! they are, and get a marker the next time their manifest is rewritten.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::exit::{self, Failure};
+use crate::explain::Cause;
+
+/// Marker file in a key directory.
+pub const LAYOUT_FILE: &str = "layout-version";
+
+/// Version of the key directory layouts this hashsig writes and reads.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// Directory of a content-addressed key directory holding the objects.
+pub const OBJECTS_DIR: &str = "objects";
+
+/// How `generate` lays out a key directory.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Layout {
+    /// Every file directly in the directory
+    Flat,
+    /// Key files under `objects/<sha256>/`, see `ContentAddressedKeyStore`
+    ContentAddressed,
+}
+
+impl Layout {
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Flat => "flat",
+            Layout::ContentAddressed => "content-addressed",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Layout::Flat, Layout::ContentAddressed]
+            .into_iter()
+            .find(|layout| layout.name() == name)
+    }
+
+    /// Layout of the unmarked directory `dir`, from its contents.
+    fn infer(dir: &Path) -> Self {
+        if dir.join(OBJECTS_DIR).is_dir() {
+            Layout::ContentAddressed
+        } else {
+            Layout::Flat
+        }
+    }
+}
+
+/// Layout of the key directory `dir`, refusing layout versions newer than
+/// [`LAYOUT_VERSION`] and markers the directory contradicts.
+pub fn check(dir: &Path) -> io::Result<Layout> {
+    let path = dir.join(LAYOUT_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Layout::infer(dir)),
+        Err(e) => return Err(e),
+    };
+    let corrupt = || {
+        exit::error(
+            Failure::CorruptData,
+            format!("{} is corrupted: {:?}", path.display(), contents.trim()),
+        )
+    };
+    let (version, name) = contents.trim().split_once(' ').ok_or_else(corrupt)?;
+    let version: u32 = version.parse().map_err(|_| corrupt())?;
+    if version > LAYOUT_VERSION {
+        return Err(exit::diagnosed(
+            Cause::UnsupportedLayout,
+            format!(
+                "{} has layout version {}, but this hashsig only reads up to version {}",
+                dir.display(),
+                version,
+                LAYOUT_VERSION
+            ),
+        ));
+    }
+    let layout = Layout::from_name(name).ok_or_else(corrupt)?;
+    if layout == Layout::Flat && dir.join(OBJECTS_DIR).is_dir() {
+        return Err(exit::error(
+            Failure::CorruptData,
+            format!(
+                "{} is marked flat but holds {}/; it may mix two layouts",
+                dir.display(),
+                OBJECTS_DIR
+            ),
+        ));
+    }
+    Ok(layout)
+}
+
+/// Mark `dir` as a key directory of `layout`, refusing one marked with
+/// another layout.
+pub fn mark(dir: &Path, layout: Layout) -> io::Result<()> {
+    let path = dir.join(LAYOUT_FILE);
+    if path.exists() {
+        let marked = check(dir)?;
+        if marked != layout {
+            return Err(exit::error(
+                Failure::InvalidArgs,
+                format!(
+                    "{} is a {} key directory; refusing to write a {} one into it",
+                    dir.display(),
+                    marked.name(),
+                    layout.name()
+                ),
+            ));
+        }
+    }
+    fs::write(path, format!("{} {}\n", LAYOUT_VERSION, layout.name()))
+}
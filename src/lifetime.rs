@@ -0,0 +1,276 @@
+//! `watch-lifetime`: warn before keys run out of active epochs.
+//!
+//! The current epoch follows from the chain's genesis time and epoch
+//! duration. Every interval, the active range of each key of the watched
+//! directories (from its metadata, else the manifest's full range) is
+//! checked against it, and an event is raised the first time a key has less
+//! than a threshold of lifetime left, and once more when it has none. Events
+//! are printed to stderr and, with a webhook, POSTed to it as JSON:
+//!
+//! ```json
+//! {
+//!   "event": "lifetime_threshold",
+//!   "dir": "./generated_keys",
+//!   "pubkey_hex": "0x...",
+//!   "current_epoch": 7342000,
+//!   "end_epoch": 7493453,
+//!   "remaining_epochs": 151453,
+//!   "remaining_secs": 605812,
+//!   "threshold_secs": 604800
+//! }
+//! ```
+//!
+//! `event` is `lifetime_expired`, without `threshold_secs`, once the range
+//! has ended. Manifests are re-read on every check, so rotated and revoked
+//! keys are picked up without a restart.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::exit::{self, Failure};
+use crate::manifest::{short_pubkey, Manifest};
+use crate::metadata::{self, unix_now};
+use crate::shutdown;
+use crate::units::format_duration;
+
+/// How the chain's epochs map to wall-clock time.
+#[derive(Copy, Clone)]
+pub struct Clock {
+    /// Start of epoch 0, in seconds since the Unix epoch.
+    pub genesis_time: u64,
+    pub seconds_per_epoch: u64,
+}
+
+impl Clock {
+    /// Epoch at `now`; 0 before genesis.
+    fn epoch_at(&self, now: u64) -> u64 {
+        now.saturating_sub(self.genesis_time) / self.seconds_per_epoch
+    }
+
+    /// Start of `epoch`, in seconds since the Unix epoch.
+    fn start_of(&self, epoch: u64) -> u64 {
+        self.genesis_time
+            .saturating_add(epoch.saturating_mul(self.seconds_per_epoch))
+    }
+}
+
+#[derive(Copy, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EventKind {
+    LifetimeThreshold,
+    LifetimeExpired,
+}
+
+#[derive(Serialize)]
+struct Event {
+    event: EventKind,
+    dir: String,
+    pubkey_hex: String,
+    current_epoch: u64,
+    end_epoch: u64,
+    remaining_epochs: u64,
+    remaining_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold_secs: Option<u64>,
+}
+
+/// Watch the keys of `dirs` until SIGTERM, checking every `interval`, or
+/// check once with `once`. `thresholds` are lifetimes left at which to warn.
+pub fn watch(
+    dirs: &[PathBuf],
+    clock: Clock,
+    thresholds: &[Duration],
+    webhook: Option<&str>,
+    interval: Duration,
+    once: bool,
+) -> io::Result<()> {
+    if clock.seconds_per_epoch == 0 {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            "--seconds-per-epoch must be at least 1",
+        ));
+    }
+    // Longest first, so a key's level is the number of thresholds crossed
+    let mut thresholds: Vec<u64> = thresholds.iter().map(Duration::as_secs).collect();
+    thresholds.sort_unstable_by(|a, b| b.cmp(a));
+    thresholds.dedup();
+
+    if !once {
+        shutdown::install()?;
+        println!(
+            "👀 Watching {} key directories every {}; warning at {} left",
+            dirs.len(),
+            format_duration(interval),
+            thresholds
+                .iter()
+                .map(|&secs| format_duration(Duration::from_secs(secs)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    // Level last reported per key: thresholds crossed, one more once expired
+    let mut reported: HashMap<String, usize> = HashMap::new();
+    let mut first = true;
+    loop {
+        match check(dirs, clock, &thresholds, webhook, &mut reported) {
+            Ok(undelivered) if once && undelivered > 0 => {
+                return Err(exit::error(
+                    Failure::Io,
+                    format!(
+                        "{} events could not be delivered to the webhook",
+                        undelivered
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            // A directory being rewritten should not stop the watch
+            Err(e) if !first => eprintln!("⚠️  Check failed: {}", e),
+            Err(e) => return Err(e),
+        }
+        first = false;
+        if once {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < interval {
+            if shutdown::requested() {
+                println!("🛑 Stopping on SIGTERM");
+                return Ok(());
+            }
+            thread::sleep(Duration::from_secs(1).min(interval));
+        }
+    }
+}
+
+/// Check every key of `dirs` once, raising the events not reported yet.
+/// Returns how many events the webhook did not accept; those are raised
+/// again on the next check.
+fn check(
+    dirs: &[PathBuf],
+    clock: Clock,
+    thresholds: &[u64],
+    webhook: Option<&str>,
+    reported: &mut HashMap<String, usize>,
+) -> io::Result<usize> {
+    let now = unix_now();
+    let current_epoch = clock.epoch_at(now);
+    let mut keys = 0;
+    let mut soonest: Option<(u64, String)> = None;
+    let mut undelivered = 0;
+
+    for dir in dirs {
+        for (pubkey_hex, end_epoch) in key_ranges(dir)? {
+            keys += 1;
+            let remaining_secs = clock.start_of(end_epoch).saturating_sub(now);
+            if soonest
+                .as_ref()
+                .is_none_or(|(secs, _)| remaining_secs < *secs)
+            {
+                soonest = Some((remaining_secs, pubkey_hex.clone()));
+            }
+
+            let crossed = thresholds
+                .iter()
+                .filter(|&&threshold| remaining_secs <= threshold)
+                .count();
+            let expired = end_epoch <= current_epoch;
+            let level = if expired {
+                thresholds.len() + 1
+            } else {
+                crossed
+            };
+            let previous = reported.get(&pubkey_hex).copied().unwrap_or(0);
+            if level <= previous {
+                continue;
+            }
+
+            let event = Event {
+                event: if expired {
+                    EventKind::LifetimeExpired
+                } else {
+                    EventKind::LifetimeThreshold
+                },
+                dir: dir.display().to_string(),
+                pubkey_hex: pubkey_hex.clone(),
+                current_epoch,
+                end_epoch,
+                remaining_epochs: end_epoch.saturating_sub(current_epoch),
+                remaining_secs,
+                // The tightest threshold crossed
+                threshold_secs: (!expired).then(|| thresholds[crossed - 1]),
+            };
+            warn(&event);
+            match webhook.map(|url| post(url, &event)).transpose() {
+                Ok(_) => {
+                    reported.insert(pubkey_hex, level);
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Webhook delivery failed: {}", e);
+                    undelivered += 1;
+                }
+            }
+        }
+    }
+
+    match soonest {
+        Some((secs, pubkey_hex)) => println!(
+            "🕒 Epoch {}: {} keys watched, {} ends soonest, in {}",
+            current_epoch,
+            keys,
+            short_pubkey(&pubkey_hex),
+            format_duration(Duration::from_secs(secs))
+        ),
+        None => println!("🕒 Epoch {}: no keys to watch", current_epoch),
+    }
+    Ok(undelivered)
+}
+
+/// Public key and end of the active range (exclusive) of every validator of
+/// `dir`, canaries aside.
+fn key_ranges(dir: &Path) -> io::Result<Vec<(String, u64)>> {
+    let manifest = Manifest::read(dir)?;
+    manifest
+        .validators
+        .iter()
+        .filter(|entry| !entry.canary)
+        .map(|entry| {
+            let num_active_epochs = 1u64 << manifest.log_num_active_epochs_of(entry);
+            let (_, end_epoch) =
+                metadata::activation_window(dir, &entry.privkey_file, num_active_epochs)?;
+            Ok((entry.pubkey_hex.clone(), end_epoch))
+        })
+        .collect()
+}
+
+fn warn(event: &Event) {
+    let key = format!("{} ({})", short_pubkey(&event.pubkey_hex), event.dir);
+    match event.threshold_secs {
+        Some(threshold) => eprintln!(
+            "⏳ {} has {} of active epochs left, less than {}; its range ends at epoch {}",
+            key,
+            format_duration(Duration::from_secs(event.remaining_secs)),
+            format_duration(Duration::from_secs(threshold)),
+            event.end_epoch
+        ),
+        None => eprintln!(
+            "🚨 {} is out of active epochs: its range ended at epoch {}, the current epoch is {}",
+            key, event.end_epoch, event.current_epoch
+        ),
+    }
+}
+
+fn post(url: &str, event: &Event) -> io::Result<()> {
+    let body = serde_json::to_vec(event).map_err(io::Error::other)?;
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_bytes(&body)
+        .map_err(|e| io::Error::other(format!("POST to {} failed: {}", url, e)))?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! `link-view`: present a key directory under the other naming convention.
+//!
+//! The view is a directory of symlinks to the original key files, renamed
+//! from `validator_<i>` to `validator-<first3>-<last3>` or back, with a
+//! manifest and metadata of its own, so tools expecting either naming can
+//! share one physical key set.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::assign;
+use crate::manifest::{self, normalize_pubkey, Manifest};
+use crate::metadata::KeyMetadata;
+
+/// Naming convention of a view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Naming {
+    /// `validator_<i>`, as `generate` writes by default
+    #[value(alias = "indexed")]
+    Old,
+    /// `validator-<first3>-<last3>` after the public key, as `generate --distributed` writes
+    #[value(alias = "distributed")]
+    New,
+}
+
+/// Create `output_dir` presenting the keys of `dir` under `naming`.
+pub fn link_view(dir: &Path, naming: Naming, output_dir: &Path) -> io::Result<()> {
+    if manifest::manifest_path(output_dir).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                output_dir.display()
+            ),
+        ));
+    }
+    let mut manifest = Manifest::read(dir)?;
+    fs::create_dir_all(output_dir)?;
+    println!(
+        "🔗 Linking {} validators of {} into {}\n",
+        manifest.validators.len(),
+        dir.display(),
+        output_dir.display()
+    );
+
+    for (position, validator) in manifest.validators.iter_mut().enumerate() {
+        let name = manifest::file_name(&validator.privkey_file);
+        let old_prefix = name.strip_suffix("_sk.ssz").unwrap_or(name).to_string();
+        let new_prefix = match naming {
+            // Distributed keys have no index; their manifest position stands in
+            Naming::Old => format!("validator_{}", validator.index.unwrap_or(position)),
+            Naming::New => distributed_prefix(&validator.pubkey_hex)?,
+        };
+        let rename = |file: &str| {
+            file.strip_prefix(old_prefix.as_str()).map_or_else(
+                || file.to_string(),
+                |rest| format!("{}{}", new_prefix, rest),
+            )
+        };
+
+        for (file, path) in assign::validator_files(dir, &validator.privkey_file)? {
+            let target = output_dir.join(rename(&file));
+            if file.ends_with(".meta.json") {
+                // Checksums are keyed by file name, so the view gets its own
+                let mut metadata: KeyMetadata =
+                    serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Failed to parse {}: {}", path.display(), e),
+                        )
+                    })?;
+                metadata.checksums = metadata
+                    .checksums
+                    .into_iter()
+                    .map(|(file, checksum)| (rename(&file), checksum))
+                    .collect();
+                let json = serde_json::to_vec_pretty(&metadata).map_err(io::Error::other)?;
+                fs::write(&target, json)?;
+            } else {
+                assign::symlink(&fs::canonicalize(&path)?, &target)?;
+            }
+        }
+
+        validator.index = match naming {
+            Naming::Old => Some(validator.index.unwrap_or(position)),
+            Naming::New => None,
+        };
+        validator.privkey_file = format!("{}_sk.ssz", new_prefix);
+        println!("  🔗 {} -> {}", old_prefix, new_prefix);
+    }
+    manifest.write(output_dir)?;
+
+    println!("\n✅ View written to {}", output_dir.display());
+    Ok(())
+}
+
+/// Prefix `generate --distributed` names a key with: the first and last 3
+/// bytes of its SSZ public key.
+fn distributed_prefix(pubkey_hex: &str) -> io::Result<String> {
+    let hex = normalize_pubkey(pubkey_hex);
+    if hex.len() < 12 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Public key {} is too short to name", pubkey_hex),
+        ));
+    }
+    Ok(format!("validator-{}-{}", &hex[..6], &hex[hex.len() - 6..]))
+}
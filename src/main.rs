@@ -1,246 +1,2196 @@
-use std::fs::{self, File};
-use std::io::Write;
+// The verify-only build drops every command touching secret keys, leaving
+// some shared helpers unused.
+#![cfg_attr(feature = "verify-only", allow(dead_code, unused_imports))]
+
+#[cfg(not(feature = "verify-only"))]
+mod assign;
+#[cfg(not(feature = "verify-only"))]
+mod backup;
+#[cfg(not(feature = "verify-only"))]
+mod bench;
+mod build_info;
+#[cfg(not(feature = "verify-only"))]
+mod ceremony;
+mod checksum;
+mod codegen;
+mod config;
+#[cfg(not(feature = "verify-only"))]
+mod convert;
+#[cfg(not(feature = "verify-only"))]
+mod copy;
+#[cfg(not(feature = "verify-only"))]
+mod crosscheck;
+mod diff;
+#[cfg(not(feature = "verify-only"))]
+mod entropy;
+#[cfg(not(feature = "verify-only"))]
+mod escrow;
+mod exit;
+mod explain;
+mod fetch;
+#[cfg(not(feature = "verify-only"))]
+mod fuzz;
+#[cfg(not(feature = "verify-only"))]
+mod generate;
+mod inspect_sig;
+#[cfg(not(feature = "verify-only"))]
+mod interop;
+#[cfg(not(feature = "verify-only"))]
+mod key_stats;
+mod keys;
+#[cfg(not(feature = "verify-only"))]
+mod keystore;
+mod layout;
+mod lifetime;
+#[cfg(not(feature = "verify-only"))]
+mod link_view;
+mod manifest;
+mod manifest_info;
+mod metadata;
+#[cfg(not(feature = "verify-only"))]
+mod migrate;
+mod offline_bundle;
+#[cfg(not(feature = "verify-only"))]
+mod prune;
+mod registry;
+#[cfg(not(feature = "verify-only"))]
+mod report;
+#[cfg(not(feature = "verify-only"))]
+mod revoke;
+#[cfg(not(feature = "verify-only"))]
+mod rotate;
+mod scheme;
+#[cfg(not(feature = "verify-only"))]
+mod shamir;
+mod shutdown;
+mod sign;
+#[cfg(not(feature = "verify-only"))]
+mod signer;
+mod stats;
+mod tar;
+#[cfg(not(feature = "verify-only"))]
+mod testnet;
+#[cfg(not(feature = "verify-only"))]
+mod transfer;
+mod translog;
+mod units;
+#[cfg(not(feature = "verify-only"))]
+mod verify_dir;
+#[cfg(not(feature = "verify-only"))]
+mod wrap;
+
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand, ValueEnum};
-use leansig::serialization::Serializable;
-use leansig::signature::{
-    generalized_xmss::instantiations_poseidon_top_level::lifetime_2_to_the_32::hashing_optimized::SIGTopLevelTargetSumLifetime32Dim64Base8,
-    SignatureScheme,
-};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+#[cfg(not(feature = "verify-only"))]
+use crate::assign::AssignMode;
+#[cfg(not(feature = "verify-only"))]
+use crate::ceremony::{TranscriptFormat, TranscriptInputs};
+#[cfg(not(feature = "verify-only"))]
+use crate::convert::{Format, ObjectKind};
+#[cfg(not(feature = "verify-only"))]
+use crate::entropy::EntropyKind;
+#[cfg(not(feature = "verify-only"))]
+use crate::generate::{
+    Batch, BatchesGenerated, Checkpoint, ExportFormat, GeneratedBatch, OutputOptions,
+    PipelineConfig, VerifyWrites, CHECKPOINT_FILE,
+};
+#[cfg(not(feature = "verify-only"))]
+use crate::keys::HashFunction;
+#[cfg(not(feature = "verify-only"))]
+use crate::keystore::{
+    ArchiveKeyStore, ContentAddressedKeyStore, FsKeyStore, KeyStore, SplitKeyStore,
+};
+#[cfg(not(feature = "verify-only"))]
+use crate::layout::Layout;
+use crate::manifest::SortOrder;
+#[cfg(not(feature = "verify-only"))]
+use crate::manifest::{Manifest, ManifestEntry, ManifestPaths, MANIFEST_FILE, MANIFEST_VERSION};
+use crate::sign::{MessageSource, PreHash};
+#[cfg(not(feature = "verify-only"))]
+use crate::signer::KeySource;
+#[cfg(not(feature = "verify-only"))]
+use crate::stats::RunStats;
+
+/// Name of the installed binary, used in completions and man pages.
+const BIN_NAME: &str = "hashsig";
+
+/// A CLI tool to generate cryptographic keys for hash-based signatures.
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Run `hashsig --version --verbose` for the build metadata (rustc, commit, Cargo.lock hash, target)."
+)]
+struct Args {
+    /// Profile from the config file (`$HASHSIG_CONFIG`, else ~/.config/hashsig/config.toml) supplying option defaults; flags given here override it
+    #[arg(long)]
+    profile: Option<String>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Generate validator key pairs for hash-based signatures
+    #[cfg(not(feature = "verify-only"))]
+    Generate {
+        /// Number of validator keys to generate (required unless `--batch` is given)
+        #[arg(long)]
+        num_validators: Option<usize>,
+
+        /// Log2 of the number of active epochs, e.g. 18 for 2^18 active epochs (required unless `--batch` is given)
+        #[arg(long)]
+        log_num_active_epochs: Option<usize>,
+
+        /// Generate a group of validators of one scheme, `scheme=NAME,count=N,epochs=LOG2` (epochs as for `--log-num-active-epochs`); repeat for a mixed key set whose manifest records each validator's scheme. Validators are numbered on across groups
+        #[arg(
+            long,
+            conflicts_with_all = ["num_validators", "log_num_active_epochs", "scheme", "resume", "with_canary", "priority", "audit_entropy"]
+        )]
+        batch: Vec<Batch>,
+
+        /// Directory to save the keys to (required unless `--archive` or `--pubkey-dir` is given)
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+
+        /// Write the keys straight into an encrypted backup bundle instead of a directory, so no key material touches the disk unencrypted (unpack with `restore`)
+        #[arg(long, conflicts_with = "output_dir")]
+        archive: Option<PathBuf>,
+
+        /// Directory for public keys, the manifest and other public files, with secret keys written to `--secret-dir`
+        #[arg(long, requires = "secret_dir", conflicts_with_all = ["output_dir", "archive"])]
+        pubkey_dir: Option<PathBuf>,
+
+        /// Directory for secret keys when `--pubkey-dir` is given, e.g. on an encrypted volume. The manifest refers to them by their path relative to `--pubkey-dir`
+        #[arg(long, requires = "pubkey_dir")]
+        secret_dir: Option<PathBuf>,
+
+        /// File containing the archive passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long, requires = "archive")]
+        passphrase_file: Option<PathBuf>,
+
+        /// Layout of `--output-dir`: `flat`, or `content-addressed` with every key file stored under `objects/<sha256>/`
+        #[arg(long, value_enum, default_value_t = Layout::Flat, conflicts_with_all = ["archive", "pubkey_dir"])]
+        layout: Layout,
+
+        /// Signature scheme to generate keys for (defaults to the first scheme using `--hash-function`)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Hash function of the signature scheme (only `poseidon2` is available so far)
+        #[arg(long, value_enum, default_value_t = HashFunction::Poseidon2)]
+        hash_function: HashFunction,
+
+        /// Export format for keys: `ssz` (binary only) or `both` (SSZ + JSON public keys, legacy)
+        #[arg(long, value_enum, default_value_t = ExportFormat::Both)]
+        export_format: ExportFormat,
+
+        /// Also write secret keys as JSON (requires `--export-format both`). Doubles the plaintext copies of every secret key on disk
+        #[arg(long)]
+        export_secret_json: bool,
+
+        /// Indent the legacy JSON export (compact by default, since pretty-printing multi-MB secret keys is slow)
+        #[arg(long)]
+        pretty_json: bool,
+
+        /// Create a manifest file for validator keys
+        #[arg(long, default_value = "true")]
+        create_manifest: bool,
+
+        /// How the manifest refers to secret keys: `relative` to the manifest's directory (bare names for keys next to it) or `absolute`
+        #[arg(long, value_enum, default_value_t = ManifestPaths::Relative)]
+        manifest_paths: ManifestPaths,
+
+        /// Use distributed format: name validators with first-3 last-3 bytes of public key
+        #[arg(long)]
+        distributed: bool,
+
+        /// Write a `<name>.meta.json` file with pubkey, fingerprint, scheme, activation range and checksums next to each key pair
+        #[arg(long)]
+        write_metadata: bool,
+
+        /// Read back and decode written files before moving on: `secret` (secret keys only), `all` or `none`
+        #[arg(long, value_enum, default_value_t = VerifyWrites::Secret)]
+        verify_writes: VerifyWrites,
+
+        /// Number of threads generating keys (defaults to the tuning profile from `keygen-bench --profile`, else the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
+
+        /// Number of threads serializing and writing SSZ keys to disk
+        #[arg(long, default_value_t = 2)]
+        writer_threads: usize,
+
+        /// Number of threads writing the legacy JSON export and metadata with `--export-format both`, overlapping with later keys' SSZ writes
+        #[arg(long, default_value_t = 2)]
+        json_threads: usize,
+
+        /// Maximum number of generated keys waiting to be written before key generation pauses
+        #[arg(long, default_value_t = 4)]
+        queue_depth: usize,
+
+        /// Start at most this many key generations per minute, to limit heat on laptops or CPU credit use on burstable cloud instances
+        #[arg(long)]
+        max_keys_per_minute: Option<u32>,
+
+        /// Give up on a key pair whose generation takes longer than this many seconds, record it in `generate-checkpoint.json` and carry on with the rest (retry with `--resume`)
+        #[arg(long, conflicts_with = "archive")]
+        key_timeout: Option<u64>,
+
+        /// Retry a key pair whose generation or writing fails (a full disk for a moment, an I/O error on one file) this many times, waiting 1s, 2s, 4s, ... in between
+        #[arg(long, default_value_t = 0)]
+        retry: u32,
+
+        /// Skip up to this many key pairs that still fail after `--retry`, record them in `generate-checkpoint.json` and carry on (retry with `--resume`); one more and the run stops, with the manifest listing the keys written until then
+        #[arg(long, default_value_t = 0, conflicts_with = "archive")]
+        max_failures: usize,
+
+        /// Comma-separated indices of validators to generate and write before all others, in that order, so the most critical keys exist even if the run is cut short
+        #[arg(long, value_delimiter = ',')]
+        priority: Vec<usize>,
+
+        /// Where key generation randomness comes from: `os`, `deterministic` (from `--seed-file`) or `device` (from `--entropy-device`)
+        #[arg(long, value_enum, default_value_t = EntropyKind::Os)]
+        entropy_source: EntropyKind,
+
+        /// File containing the hex-encoded master seed (at least 32 bytes) for `--entropy-source deterministic`
+        #[arg(long, required_if_eq("entropy_source", "deterministic"))]
+        seed_file: Option<PathBuf>,
+
+        /// Hardware RNG or token device to read seeds from for `--entropy-source device`
+        #[arg(long, default_value = "/dev/hwrng")]
+        entropy_device: PathBuf,
+
+        /// Hash the entropy consumed by every key and record the commitments in `provenance.json`
+        #[arg(long)]
+        audit_entropy: bool,
+
+        /// Finish a run stopped by SIGTERM: generate only the key pairs missing from `generate-checkpoint.json`. Every other argument must repeat the interrupted run's
+        #[arg(long, conflicts_with = "archive")]
+        resume: bool,
+
+        /// Also generate this many canary validators after the real ones, labeled `canary` in the manifest and metadata; `sign` refuses them with a loud alert, so any request for one reveals a misrouted or compromised signer
+        #[arg(long, default_value_t = 0)]
+        with_canary: usize,
+
+        /// Prefix every key file name with `<namespace>-` and record the namespace in the manifest and metadata, so tenants sharing a bucket or signer host cannot mix up their keys
+        #[arg(long, value_parser = generate::parse_namespace)]
+        namespace: Option<String>,
+    },
+    /// Bootstrap a devnet: generate validators, deposit data, the genesis registry and per-node key directories in one go
+    #[cfg(not(feature = "verify-only"))]
+    GenTestNet {
+        /// Number of validator keys to generate
+        #[arg(long)]
+        num_validators: usize,
+
+        /// Number of nodes to split the validators across
+        #[arg(long, default_value_t = 1)]
+        nodes: usize,
+
+        /// Log2 of the number of active epochs (e.g., 18 for 2^18 active epochs)
+        #[arg(long)]
+        log_num_active_epochs: usize,
+
+        /// Directory to create the test network in
+        #[arg(long)]
+        output_dir: PathBuf,
+
+        /// Signature scheme to generate keys for (defaults to the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Export format for keys: `ssz` (binary only) or `both` (SSZ + JSON public keys, legacy)
+        #[arg(long, value_enum, default_value_t = ExportFormat::Both)]
+        export_format: ExportFormat,
+
+        /// Derive every key from this hex-encoded master seed, so the network can be recreated exactly
+        #[arg(long)]
+        seed_file: Option<PathBuf>,
+
+        /// Number of threads generating keys (defaults to the tuning profile from `keygen-bench --profile`, else the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Partition a key directory across several nodes' directories, each with its own manifest
+    #[cfg(not(feature = "verify-only"))]
+    Assign {
+        /// Key directory containing the manifest
+        dir: PathBuf,
+
+        /// Number of nodes to split the validators across
+        #[arg(long)]
+        nodes: usize,
+
+        /// Relative share of validators for each node, comma-separated (e.g. `2,1,1,1`; defaults to equal shares)
+        #[arg(long, value_delimiter = ',')]
+        weights: Vec<u32>,
+
+        /// How key files get into the node directories: `copy` or `symlink` (one physical copy)
+        #[arg(long, value_enum, default_value_t = AssignMode::Copy)]
+        mode: AssignMode,
+
+        /// Directory to create `node-<i>` directories and `assignment.json` in
+        #[arg(long)]
+        output_dir: PathBuf,
+    },
+    /// Create a directory of symlinks presenting a key directory under the other naming convention
+    #[cfg(not(feature = "verify-only"))]
+    LinkView {
+        /// Key directory containing the manifest
+        dir: PathBuf,
+
+        /// Naming of the view: `old` (`validator_<i>`) or `new` (`validator-<first3>-<last3>` after the public key)
+        #[arg(long, value_enum)]
+        naming: link_view::Naming,
+
+        /// Directory to create the view in
+        #[arg(long)]
+        output_dir: PathBuf,
+    },
+    /// Regenerate a single validator's keys from the master seed of a deterministic ceremony
+    #[cfg(not(feature = "verify-only"))]
+    Derive {
+        /// Derivation path of the validator, as recorded in the manifest (e.g. `m/validator/5`)
+        #[arg(long)]
+        path: String,
+
+        /// File containing the hex-encoded master seed the keys were generated from
+        #[arg(long)]
+        seed_file: PathBuf,
+
+        /// Directory to write the keys to
+        #[arg(long)]
+        output_dir: PathBuf,
+
+        /// Log2 of the number of active epochs the keys were generated with
+        #[arg(long)]
+        log_num_active_epochs: usize,
+
+        /// Signature scheme the keys were generated for (defaults to the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Export format for keys: `ssz` (binary only) or `both` (SSZ + JSON public keys, legacy)
+        #[arg(long, value_enum, default_value_t = ExportFormat::Both)]
+        export_format: ExportFormat,
+
+        /// Also write secret keys as JSON (requires `--export-format both`). Doubles the plaintext copies of every secret key on disk
+        #[arg(long)]
+        export_secret_json: bool,
+
+        /// Indent the legacy JSON export (compact by default, since pretty-printing multi-MB secret keys is slow)
+        #[arg(long)]
+        pretty_json: bool,
+
+        /// Name the validator with first-3 last-3 bytes of its public key, as `generate --distributed` does
+        #[arg(long)]
+        distributed: bool,
+
+        /// Write a `<name>.meta.json` file next to the key pair
+        #[arg(long)]
+        write_metadata: bool,
+    },
+    /// Export the validator set of a key directory as a single artifact
+    Export {
+        /// Key directory containing the manifest
+        dir: PathBuf,
+
+        /// Artifact to produce: `ssz-registry` (SSZ container of pubkeys and activation/exit epochs) or `code` (source file of pubkey constants)
+        #[arg(long, value_enum)]
+        format: registry::ExportKind,
+
+        /// Language of `--format code`: `rust`, `go` or `ts`
+        #[arg(long, value_enum, required_if_eq("format", "code"))]
+        lang: Option<codegen::Lang>,
+
+        /// File to write the artifact to
+        #[arg(long)]
+        output: PathBuf,
+
+        /// Order of the validators in the artifact: `index`, `pubkey` or `name` (secret key file name)
+        #[arg(long, value_enum, default_value_t = SortOrder::Index)]
+        sort: SortOrder,
+    },
+    /// Bundle a key directory into a single encrypted, checksummed backup file
+    #[cfg(not(feature = "verify-only"))]
+    Backup {
+        /// Key directory to back up
+        dir: PathBuf,
+
+        /// Path of the bundle file to write
+        #[arg(long)]
+        output: PathBuf,
+
+        /// File containing the bundle passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Verify and unpack a backup bundle created with `backup`
+    #[cfg(not(feature = "verify-only"))]
+    Restore {
+        /// Backup bundle to restore
+        bundle: PathBuf,
+
+        /// Directory to restore the keys into
+        #[arg(long)]
+        output_dir: PathBuf,
+
+        /// File containing the bundle passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+
+        /// Check that every restored public key matches its secret key
+        #[arg(long)]
+        verify_keys: bool,
+    },
+    /// Check an encrypted backup bundle or `generate --archive` keystore without unpacking it: its structure, and with the passphrase every checksum and that every secret key matches its public key
+    #[cfg(not(feature = "verify-only"))]
+    VerifyKeystore {
+        /// Bundle to check
+        bundle: PathBuf,
+
+        /// File containing the bundle passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+
+        /// Only check the structure, without the passphrase, e.g. on a machine that must never see it
+        #[arg(long, conflicts_with = "passphrase_file")]
+        structure_only: bool,
+
+        /// Signature scheme of the keys (defaults to the scheme in the bundled manifest)
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+    /// Re-encrypt every backup bundle and `generate --archive` keystore in a directory under a new passphrase, without writing decrypted keys to disk
+    #[cfg(not(feature = "verify-only"))]
+    Reencrypt {
+        /// Directory holding the bundles
+        dir: PathBuf,
+
+        /// File containing the current passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+
+        /// File containing the new passphrase
+        #[arg(long)]
+        new_passphrase_file: PathBuf,
+    },
+    /// Copy a key set with every file verified, resuming an interrupted copy when run again
+    #[cfg(not(feature = "verify-only"))]
+    Copy {
+        /// Key directory to copy
+        src: PathBuf,
+
+        /// Directory to copy it to. Must not hold a manifest yet, unless it is an unfinished copy of `src`
+        dst: PathBuf,
+    },
+    /// Copy a key directory to a signing host over SSH, verifying every file on arrival
+    #[cfg(not(feature = "verify-only"))]
+    Push {
+        /// Key directory to send
+        dir: PathBuf,
+
+        /// Remote directory, as `ssh://[user@]host[:port]/path` (`/~/path` for a path under the remote home). Must not hold a manifest yet
+        destination: String,
+    },
+    /// Copy a key directory from a remote host over SSH, verifying every file before it is written
+    #[cfg(not(feature = "verify-only"))]
+    Pull {
+        /// Remote directory, as `ssh://[user@]host[:port]/path` (`/~/path` for a path under the remote home)
+        source: String,
+
+        /// Directory to write the keys to. Must not hold a manifest yet
+        #[arg(long)]
+        output_dir: PathBuf,
+    },
+    /// Split a key directory among custodians, any threshold of whom can recover it
+    #[cfg(not(feature = "verify-only"))]
+    Escrow {
+        #[command(subcommand)]
+        command: EscrowCommand,
+    },
+    /// Create a short-lived transport key pair (`<output>.pub` and `<output>.key`) to receive wrapped keys with
+    #[cfg(not(feature = "verify-only"))]
+    TransportKeygen {
+        /// Path prefix of the key files
+        #[arg(long)]
+        output: PathBuf,
+
+        /// How long keys can be wrapped to and unwrapped with the pair, e.g. `8h`
+        #[arg(long, value_parser = units::parse_duration, default_value = "24h")]
+        valid_for: std::time::Duration,
+    },
+    /// Encrypt the secret keys of selected validators to a transport key, to carry them to another host
+    #[cfg(not(feature = "verify-only"))]
+    Wrap {
+        /// Key directory holding the validators
+        dir: PathBuf,
+
+        /// Public key of a validator to wrap (repeat for every validator)
+        #[arg(long = "pubkey", required = true)]
+        pubkeys: Vec<String>,
+
+        /// Transport public key from `transport-keygen`
+        #[arg(long)]
+        recipient: PathBuf,
+
+        /// File to write the wrapped keys to
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Open a file from `wrap` into a new key directory and shred the transport secret key
+    #[cfg(not(feature = "verify-only"))]
+    Unwrap {
+        /// Wrapped key file
+        file: PathBuf,
+
+        /// Transport secret key from `transport-keygen`
+        #[arg(long)]
+        key: PathBuf,
+
+        /// Directory to write the keys to. Must not hold a manifest yet
+        #[arg(long)]
+        output_dir: PathBuf,
+
+        /// Keep the transport secret key, to unwrap further files wrapped to it
+        #[arg(long)]
+        keep_key: bool,
+    },
+    /// Revoke a validator: delete its secret key and record a tombstone in the manifest
+    #[cfg(not(feature = "verify-only"))]
+    Revoke {
+        /// Public key of the validator to revoke (hex, with or without `0x`)
+        pubkey: String,
+
+        /// Key directory containing the manifest
+        #[arg(long)]
+        dir: PathBuf,
+
+        /// Reason recorded in the tombstone
+        #[arg(long)]
+        reason: String,
+
+        /// Overwrite the secret key files with random data before deleting them
+        #[arg(long)]
+        shred: bool,
+    },
+    /// Drop the one-time keys of passed epochs from secret keys, shredding the old files
+    #[cfg(not(feature = "verify-only"))]
+    Prune {
+        /// SSZ secret key file, or key directory whose secret keys to prune
+        path: PathBuf,
+
+        /// Epochs before this one will never be signed at again
+        #[arg(long)]
+        before_epoch: u64,
+
+        /// Signature scheme (defaults to the one in the manifest next to the keys, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+    /// Replace a validator's key: generate a replacement, then revoke the old key once the new one is registered
+    #[cfg(not(feature = "verify-only"))]
+    Rotate {
+        #[command(subcommand)]
+        command: RotateCommand,
+    },
+    /// Move the validators of a deprecated scheme to its replacement: generate replacement keys with `rotate start` and write the rotation plan to `migration-plan.json`
+    #[cfg(not(feature = "verify-only"))]
+    MigrateScheme {
+        /// Key directory containing the manifest
+        dir: PathBuf,
+
+        /// Migrate the validators of this scheme, deprecated or not (defaults to every deprecated scheme)
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+
+        /// Scheme to migrate to (defaults to the replacement the deprecation names)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Only print which validators would be migrated to what
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Append the public keys of a key directory to an append-only, hash-chained public log
+    PublishLog {
+        /// Key directory containing the manifest
+        dir: PathBuf,
+
+        /// Log file to append to (created if missing)
+        #[arg(long)]
+        log: PathBuf,
+
+        /// Order in which new keys are appended: `index`, `pubkey` or `name` (secret key file name)
+        #[arg(long, value_enum, default_value_t = SortOrder::Index)]
+        sort: SortOrder,
+    },
+    /// Verify the hash chain of a public key log and check that keys are included in it
+    VerifyLog {
+        /// Log file to verify
+        #[arg(long)]
+        log: PathBuf,
+
+        /// Trusted log head (the `entry_hash` of an entry), obtained out of band. The log must contain it, and inclusion is checked up to it
+        #[arg(long)]
+        head: Option<String>,
+
+        /// Public key that must be included (hex, with or without `0x`); may be repeated
+        #[arg(long = "pubkey")]
+        pubkeys: Vec<String>,
+
+        /// Key directory whose manifest's public keys must all be included
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Watch key directories and warn, on stderr and optionally a webhook, when keys get close to the end of their active epochs
+    WatchLifetime {
+        /// Key directories containing a manifest
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+
+        /// Start of epoch 0, in seconds since the Unix epoch
+        #[arg(long)]
+        genesis_time: u64,
+
+        /// Duration of an epoch in seconds
+        #[arg(long)]
+        seconds_per_epoch: u64,
+
+        /// Warn when a key has less than this much lifetime left, e.g. `30d` or `12h`; may be repeated
+        #[arg(long = "threshold", value_parser = units::parse_duration, default_values = ["30d", "7d", "1d"])]
+        thresholds: Vec<std::time::Duration>,
+
+        /// Also POST every event as JSON to this URL
+        #[arg(long)]
+        webhook: Option<String>,
+
+        /// How often to check, e.g. `10m` or `1h`
+        #[arg(long, value_parser = units::parse_duration, default_value = "1h")]
+        interval: std::time::Duration,
+
+        /// Check once and exit instead of watching
+        #[arg(long)]
+        once: bool,
+    },
+    /// Download a published public key set and keep it only if the manifest signature and every public key and checksum verify
+    Fetch {
+        /// URL of the published manifest (`http://`, `https://` or `file://`), with `<manifest>.sig` and `<manifest>.sig.json` from `sign` and the public key files next to it
+        #[arg(long)]
+        manifest: String,
+
+        /// Public key of the publisher that signed the manifest (SSZ), obtained out of band
+        #[arg(long)]
+        signer_pk: PathBuf,
+
+        /// Signature scheme of `--signer-pk` (defaults to the one in the manifest next to it, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Directory to write the verified public key set to. Must not hold a manifest yet
+        #[arg(long)]
+        output_dir: PathBuf,
+    },
+    /// Decode a signature and print its structure, optionally verifying it
+    InspectSig {
+        /// SSZ-encoded signature file
+        file: PathBuf,
+
+        /// Signature scheme (defaults to the one in the manifest next to `--pubkey`, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Public key (SSZ) to verify the signature against
+        #[arg(long, requires_all = ["message_hex", "epoch"])]
+        pubkey: Option<PathBuf>,
+
+        /// Signed message, hex-encoded (32 bytes)
+        #[arg(long)]
+        message_hex: Option<String>,
+
+        /// Epoch the signature was made for (signatures do not encode it)
+        #[arg(long)]
+        epoch: Option<u32>,
+    },
+    /// Sign a message with a hash-based secret key
+    #[cfg(not(feature = "verify-only"))]
+    Sign {
+        /// File whose pre-hash is signed, or `-` to read it from stdin
+        message: Option<PathBuf>,
+
+        /// Secret key (SSZ) to sign with
+        #[arg(long)]
+        sk: Option<PathBuf>,
+
+        /// Encrypted archive or backup bundle holding the key to sign with, instead of `--sk`. Decrypted in memory for every signature
+        #[arg(long, requires = "key_name")]
+        archive: Option<PathBuf>,
+
+        /// File prefix of the key in `--archive` (e.g. `validator_0`)
+        #[arg(long, requires = "archive")]
+        key_name: Option<String>,
+
+        /// File containing the `--archive` passphrase (defaults to the HASHSIG_PASSPHRASE environment variable)
+        #[arg(long, requires = "archive")]
+        passphrase_file: Option<PathBuf>,
+
+        /// File containing the hex-encoded master seed to regenerate the key from in memory, instead of `--sk`
+        #[arg(long, requires_all = ["derivation_path", "log_num_active_epochs"])]
+        seed_file: Option<PathBuf>,
+
+        /// Derivation path of the key in `--seed-file` (e.g. `m/validator/5`)
+        #[arg(long, requires = "seed_file")]
+        derivation_path: Option<String>,
+
+        /// Log2 of the number of active epochs the `--seed-file` key was generated with
+        #[arg(long, requires = "seed_file")]
+        log_num_active_epochs: Option<usize>,
+
+        /// Epoch to sign at. Never reuse an epoch of the signing key
+        #[arg(long)]
+        epoch: u32,
+
+        /// Signature scheme (defaults to the one recorded with the key: the manifest next to `--sk` or in `--archive`, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Message to sign, hex-encoded (32 bytes, signed as-is)
+        #[arg(long)]
+        message_hex: Option<String>,
+
+        /// File whose pre-hash is signed (same as the positional argument). Streamed, so it may be of any size
+        #[arg(long)]
+        message_file: Option<PathBuf>,
+
+        /// Hash turning a message file or stdin into the signed 32-byte message, recorded in the `<output>.json` sidecar
+        #[arg(long, value_enum, default_value = "sha256")]
+        prehash: PreHash,
+
+        /// File to write the SSZ-encoded signature to
+        #[arg(long, required_unless_present = "stdout")]
+        output: Option<PathBuf>,
+
+        /// Write the SSZ-encoded signature to stdout (must be redirected or piped) and status to stderr
+        #[arg(long, conflicts_with = "output")]
+        stdout: bool,
+
+        /// Also write a verification bundle (message, signature, public key and scheme parameters in one archive) that anyone can check with `verify-bundle`
+        #[arg(long)]
+        offline_bundle: Option<PathBuf>,
+
+        /// Abort with exit code 10 if signing takes longer than this many seconds, instead of hanging
+        #[arg(long)]
+        timeout: Option<u64>,
+
+        /// Also refuse keys revoked in this manifest, e.g. that of the live key directory when signing with a key restored elsewhere, kept in an archive or derived from a seed
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    /// Verify a signature made with `sign`
+    Verify {
+        /// File whose pre-hash was signed, or `-` to read it from stdin
+        message: Option<PathBuf>,
+
+        /// Public key (SSZ) to verify against
+        #[arg(long, required_unless_present = "batch_file")]
+        pk: Option<PathBuf>,
+
+        /// Epoch the signature was made for
+        #[arg(long, required_unless_present = "batch_file")]
+        epoch: Option<u32>,
+
+        /// SSZ-encoded signature file, or `-` to read it from stdin
+        #[arg(long, required_unless_present = "batch_file")]
+        signature: Option<PathBuf>,
+
+        /// Verify the JSONL jobs in this file (`-` for stdin) and stream one JSON result per line to stdout. `--scheme` and `--prehash` are defaults for the jobs
+        #[arg(long, conflicts_with_all = ["message", "pk", "epoch", "signature", "message_hex", "message_file"])]
+        batch_file: Option<PathBuf>,
+
+        /// Signature scheme (defaults to the one in the manifest next to `--pk`, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Signed message, hex-encoded (32 bytes)
+        #[arg(long)]
+        message_hex: Option<String>,
+
+        /// File whose pre-hash was signed (same as the positional argument)
+        #[arg(long)]
+        message_file: Option<PathBuf>,
+
+        /// Pre-hash of a message file or stdin (defaults to the one in the signature's `.json` sidecar, else sha256)
+        #[arg(long, value_enum)]
+        prehash: Option<PreHash>,
+    },
+    /// Verify a bundle written by `sign --offline-bundle`, with nothing else needed
+    VerifyBundle {
+        /// Verification bundle
+        bundle: PathBuf,
+
+        /// Public key the bundle must be signed by (hex, with or without `0x`)
+        #[arg(long)]
+        pubkey: Option<String>,
+    },
+    /// Check that keys exported as both SSZ and JSON decode to identical keys
+    #[cfg(not(feature = "verify-only"))]
+    Crosscheck {
+        /// Key directory to check
+        dir: PathBuf,
+    },
+    /// Convert a key or signature between SSZ and the serde JSON of the leansig reference implementation, for interop testing
+    #[cfg(not(feature = "verify-only"))]
+    Convert {
+        /// Key or signature file to convert
+        input: PathBuf,
+
+        /// File to write the converted key or signature to; must not exist yet
+        #[arg(long, required_unless_present = "stdout")]
+        output: Option<PathBuf>,
+
+        /// Write the converted key or signature to stdout (must be redirected or piped for SSZ and secret keys) and status to stderr
+        #[arg(long, conflicts_with = "output")]
+        stdout: bool,
+
+        /// Format of the input
+        #[arg(long, value_enum)]
+        from: Format,
+
+        /// Format to convert to
+        #[arg(long, value_enum)]
+        to: Format,
+
+        /// What the input holds (defaults to what its name says: `*_pk.*`, `*_sk.*` or `*.sig`)
+        #[arg(long, value_enum)]
+        kind: Option<ObjectKind>,
+
+        /// Signature scheme of the input (defaults to the scheme recorded in the manifest next to it)
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+    /// Benchmark key generation at increasing thread counts and recommend `--threads`
+    #[cfg(not(feature = "verify-only"))]
+    KeygenBench {
+        /// Signature scheme to benchmark (defaults to the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Log2 of the number of active epochs of each benchmark key
+        #[arg(long, default_value_t = 10)]
+        log_num_active_epochs: usize,
+
+        /// Number of keys each thread generates per measurement
+        #[arg(long, default_value_t = 1)]
+        keys_per_thread: usize,
+
+        /// Save the result as the tuning profile used by later `generate` runs
+        #[arg(long)]
+        profile: bool,
+
+        /// Where to save the tuning profile (defaults to $HASHSIG_PROFILE or ~/.config/hashsig/profile.json)
+        #[arg(long, requires = "profile")]
+        profile_path: Option<PathBuf>,
+    },
+    /// Measure signing latency across a sweep of epochs and report its distribution
+    #[cfg(not(feature = "verify-only"))]
+    SignBench {
+        /// Epochs to sign at, as `start..end` (end exclusive)
+        #[arg(long, value_parser = bench::parse_epoch_range)]
+        epochs: std::ops::Range<u32>,
+
+        /// Sign at every `step`-th epoch of the range only
+        #[arg(long, default_value_t = 1)]
+        step: usize,
+
+        /// Secret key (SSZ) to benchmark (defaults to a throwaway key generated for the range). Signatures are discarded
+        #[arg(long)]
+        sk: Option<PathBuf>,
+
+        /// Signature scheme (defaults to the one in the manifest next to `--sk`, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+    /// Compare key and signature sizes, timings and lifetimes of every supported scheme using throwaway keys
+    #[cfg(not(feature = "verify-only"))]
+    CompareSchemes {
+        /// Log2 of the number of active epochs of each throwaway key
+        #[arg(long, default_value_t = 8)]
+        log_num_active_epochs: usize,
+
+        /// Number of epochs to sign and verify at per scheme
+        #[arg(long, default_value_t = 10)]
+        signatures: usize,
+
+        /// Also write the comparison as JSON to this file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that verification accepts valid signatures and rejects bit-flipped signatures, public keys and messages
+    #[cfg(not(feature = "verify-only"))]
+    FuzzVerify {
+        /// How long to fuzz, e.g. `60s`, `5m` or `1h`
+        #[arg(long, value_parser = units::parse_duration, default_value = "60s")]
+        duration: std::time::Duration,
+
+        /// Signature scheme to fuzz (defaults to the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Log2 of the number of active epochs of the throwaway key
+        #[arg(long, default_value_t = 8)]
+        log_num_active_epochs: usize,
+    },
+    /// Cross-check an external verifier against this CLI on a matrix of valid and corrupted signatures
+    #[cfg(not(feature = "verify-only"))]
+    Interop {
+        #[command(subcommand)]
+        command: InteropCommand,
+    },
+    /// Fully validate a key directory (keys, key pairs, checksums and manifest) and optionally write a signed report
+    #[cfg(not(feature = "verify-only"))]
+    VerifyDir {
+        /// Key directory to validate
+        dir: PathBuf,
+
+        /// Number of threads validating key pairs (defaults to the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
-enum ExportFormat {
-    /// Export only SSZ-encoded binary files (`.ssz`)
-    Ssz,
-    /// Export both SSZ-encoded binaries (`.ssz`) and legacy JSON files
-    Both,
+        /// Fully validate only this many randomly chosen key pairs and check the rest by metadata checksum
+        #[arg(long)]
+        sample: Option<usize>,
+
+        /// Move files that fail to decode or mismatch their checksum to `quarantine/` and list them in the report
+        #[arg(long)]
+        quarantine: bool,
+
+        /// Write a JSON validation report to this file
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        /// Secret key (SSZ) to sign the report with; the signature is written to `<report>.sig`
+        #[arg(long, requires_all = ["report", "sign_epoch"])]
+        sign_key: Option<PathBuf>,
+
+        /// Epoch to sign the report at. Never reuse an epoch of the signing key
+        #[arg(long, requires = "sign_key")]
+        sign_epoch: Option<u32>,
+    },
+    /// Compare two key directories (manifests and file checksums)
+    Diff {
+        /// First key directory
+        dir_a: PathBuf,
+
+        /// Second key directory
+        dir_b: PathBuf,
+
+        /// Order of the validators listed: `index`, `pubkey` or `name` (secret key file name)
+        #[arg(long, value_enum, default_value_t = SortOrder::Index)]
+        sort: SortOrder,
+    },
+    /// Inspect a validator manifest
+    Manifest {
+        #[command(subcommand)]
+        command: ManifestCommand,
+    },
+    /// Local statistics of past runs
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommand,
+    },
+    /// Explain an error: its likely cause and how to fix it
+    Explain {
+        /// Exit code (e.g. `4`) or cause named under an error message (e.g. `epoch-out-of-window`)
+        code: String,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page to stdout, or write one per subcommand to a directory
+    Manpage {
+        /// Directory to write `hashsig.1` and a page per subcommand to
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Key ceremony records
+    #[cfg(not(feature = "verify-only"))]
+    Ceremony {
+        #[command(subcommand)]
+        command: CeremonyCommand,
+    },
 }
 
-/// A CLI tool to generate cryptographic keys for hash-based signatures.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    #[command(subcommand)]
-    command: Commands,
+#[cfg(not(feature = "verify-only"))]
+#[derive(Subcommand, Debug)]
+enum RotateCommand {
+    /// Generate a replacement key, mark the old one as rotating and write the registration file
+    Start {
+        /// Public key of the validator to rotate (hex, with or without `0x`)
+        pubkey: String,
+
+        /// Key directory containing the manifest
+        #[arg(long)]
+        dir: PathBuf,
+    },
+    /// Revoke the old key of a rotation once its replacement is registered
+    Finalize {
+        /// Public key of the validator being rotated (hex, with or without `0x`)
+        pubkey: String,
+
+        /// Key directory containing the manifest
+        #[arg(long)]
+        dir: PathBuf,
+
+        /// Overwrite the old secret key files with random data before deleting them
+        #[arg(long)]
+        shred: bool,
+    },
 }
 
+#[cfg(not(feature = "verify-only"))]
 #[derive(Subcommand, Debug)]
-enum Commands {
-    /// Generate validator key pairs for hash-based signatures
-    Generate {
-        /// Number of validator keys to generate
+enum EscrowCommand {
+    /// Create a custodian key pair (`<output>.pub` and `<output>.key`)
+    Keygen {
+        /// Path prefix of the key files
         #[arg(long)]
-        num_validators: usize,
+        output: PathBuf,
+    },
+    /// Write one escrow bundle per custodian, each holding the encrypted key set and the custodian's share
+    Create {
+        /// Key directory to escrow
+        dir: PathBuf,
 
-        /// Log2 of the number of active epochs (e.g., 18 for 2^18 active epochs)
+        /// Custodian public key from `escrow keygen` (repeat for every custodian). The bundle is named after the file
+        #[arg(long = "recipient", required = true)]
+        recipients: Vec<PathBuf>,
+
+        /// Number of custodians needed to recover the keys
         #[arg(long)]
-        log_num_active_epochs: usize,
+        threshold: u8,
 
-        /// Directory to save the keys to
+        /// Directory to write the `<name>.escrow` bundles to
         #[arg(long)]
         output_dir: PathBuf,
+    },
+    /// Recover an escrowed key set from at least threshold custodian bundles
+    Recover {
+        /// Custodian bundle (repeat; at least the threshold)
+        #[arg(long = "bundle", required = true)]
+        bundles: Vec<PathBuf>,
 
-        /// Export format for keys: `ssz` (binary only) or `both` (SSZ + JSON, legacy)
-        #[arg(long, value_enum, default_value_t = ExportFormat::Both)]
-        export_format: ExportFormat,
+        /// Custodian secret key opening the `--bundle` at the same position
+        #[arg(long = "key", required = true)]
+        keys: Vec<PathBuf>,
 
-        /// Create a manifest file for validator keys
-        #[arg(long, default_value = "true")]
-        create_manifest: bool,
+        /// Directory to restore the keys into
+        #[arg(long)]
+        output_dir: PathBuf,
 
-        /// Use distributed format: name validators with first-3 last-3 bytes of public key
+        /// Check that every restored public key matches its secret key
         #[arg(long)]
-        distributed: bool,
+        verify_keys: bool,
+    },
+    /// Check custodian bundles without writing any key material
+    Verify {
+        /// Custodian bundle (repeat)
+        #[arg(long = "bundle", required = true)]
+        bundles: Vec<PathBuf>,
+
+        /// Custodian secret key opening the `--bundle` at the same position. With keys for at least the threshold, reconstruction is tested in memory
+        #[arg(long = "key")]
+        keys: Vec<PathBuf>,
+
+        /// Key directory whose manifest holds the published pubkeys the escrow must cover
+        #[arg(long)]
+        published_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ManifestCommand {
+    /// Summarize a manifest and check that it is internally consistent
+    Info {
+        /// Manifest file, or the key directory containing it
+        file: PathBuf,
+    },
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[derive(Subcommand, Debug)]
+enum InteropCommand {
+    /// Write each case to a directory and run the external verifier on it; every case it decides differently is a failure
+    Run {
+        /// Verifier command, run through `sh -c` with the case directory appended; exit 0 accepts, 1 rejects
+        #[arg(long)]
+        external_cmd: String,
+
+        /// Signature scheme to test (defaults to the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+
+        /// Log2 of the number of active epochs of the throwaway keys
+        #[arg(long, default_value_t = 8)]
+        log_num_active_epochs: usize,
+
+        /// Directory to write the cases to, kept afterwards (defaults to a temporary directory, removed unless a case disagrees)
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsCommand {
+    /// Combine the `run-stats.json` files of several key directories
+    Aggregate {
+        /// Key directories to combine
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+
+        /// Also write the combined statistics to this file as JSON
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Show what a secret key file is made of: active and prepared epochs and a breakdown of its structure
+    #[cfg(not(feature = "verify-only"))]
+    Key {
+        /// Secret key (SSZ) to inspect
+        sk: PathBuf,
+
+        /// Signature scheme (defaults to the one in the manifest next to the key, else the default scheme)
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+}
+
+#[cfg(not(feature = "verify-only"))]
+#[derive(Subcommand, Debug)]
+enum CeremonyCommand {
+    /// Compile provenance, validation results, checksums, annotations and the manifest into one transcript
+    Report {
+        /// Key directory produced by the ceremony
+        dir: PathBuf,
+
+        /// File to write the transcript to
+        #[arg(long)]
+        output: PathBuf,
+
+        /// Transcript format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: TranscriptFormat,
+
+        /// Ceremony operator or witness to record (repeatable)
+        #[arg(long = "operator")]
+        operators: Vec<String>,
+
+        /// Free-form annotation to record (repeatable)
+        #[arg(long = "note")]
+        notes: Vec<String>,
+
+        /// JSON report from `verify-dir --report` to include
+        #[arg(long)]
+        validation_report: Option<PathBuf>,
+
+        /// Secret key (SSZ) to sign the transcript with; the signature is written to `<output>.sig`
+        #[arg(long, requires = "sign_epoch")]
+        sign_key: Option<PathBuf>,
+
+        /// Epoch to sign the transcript at. Never reuse an epoch of the signing key
+        #[arg(long, requires = "sign_key")]
+        sign_epoch: Option<u32>,
     },
 }
 
-fn main() -> std::io::Result<()> {
-    let args = Args::parse();
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            explain::hint(&e);
+            exit::Failure::of(&e).into()
+        }
+    }
+}
+
+fn run() -> std::io::Result<()> {
+    if build_info::wants_verbose_version() {
+        build_info::print_verbose(BIN_NAME);
+        return Ok(());
+    }
+    let args = parse_args()?;
+    if let Some(profile) = &args.profile {
+        // stderr, as stdout may carry a command's output (e.g. `sign --stdout`)
+        eprintln!("⚙️  Using profile {}", profile);
+    }
 
     match args.command {
+        #[cfg(not(feature = "verify-only"))]
         Commands::Generate {
             num_validators,
             log_num_active_epochs,
+            batch,
             output_dir,
+            archive,
+            pubkey_dir,
+            secret_dir,
+            passphrase_file,
+            layout,
+            scheme,
+            hash_function,
             export_format,
+            export_secret_json,
+            pretty_json,
             create_manifest,
+            manifest_paths,
             distributed,
+            write_metadata,
+            verify_writes,
+            threads,
+            writer_threads,
+            json_threads,
+            queue_depth,
+            max_keys_per_minute,
+            key_timeout,
+            retry,
+            max_failures,
+            priority,
+            entropy_source,
+            seed_file,
+            entropy_device,
+            audit_entropy,
+            resume,
+            with_canary,
+            namespace,
         } => {
-            let validator_info = generate_keys(
+            // Checked here rather than by clap, so `batch` may come from a profile
+            let (num_validators, log_num_active_epochs) =
+                match (num_validators, log_num_active_epochs) {
+                    // The first batch's active epochs, as in the manifest
+                    _ if !batch.is_empty() => (
+                        batch.iter().map(|batch| batch.count).sum(),
+                        batch[0].log_num_active_epochs,
+                    ),
+                    (Some(num_validators), Some(log_num_active_epochs)) => {
+                        (num_validators, log_num_active_epochs)
+                    }
+                    _ => {
+                        return Err(exit::error(
+                            exit::Failure::InvalidArgs,
+                            "--num-validators and --log-num-active-epochs are required unless --batch is given",
+                        ))
+                    }
+                };
+            // Likewise the first batch's scheme, for the run statistics
+            let scheme = match batch.first() {
+                Some(first) => scheme::lookup(&first.scheme)?,
+                None => scheme::select(scheme.as_deref(), hash_function)?,
+            };
+            let pipeline = PipelineConfig {
+                compute_threads: threads.unwrap_or_else(bench::default_threads),
+                writer_threads,
+                json_threads,
+                queue_depth,
+                max_keys_per_minute,
+                key_timeout: key_timeout.map(std::time::Duration::from_secs),
+                retries: retry,
+                max_failures,
+                priority,
+            };
+            let archive = match archive {
+                Some(path) => {
+                    let passphrase = backup::read_passphrase(passphrase_file.as_deref())?;
+                    Some(ArchiveKeyStore::create(path, &passphrase)?)
+                }
+                None => None,
+            };
+            let dir_store;
+            let content_store;
+            let split_store;
+            let store: &dyn KeyStore = match (&archive, pubkey_dir, secret_dir) {
+                (Some(archive), _, _) => archive,
+                (None, Some(pubkey_dir), Some(secret_dir)) => {
+                    split_store = SplitKeyStore::create(&pubkey_dir, secret_dir)?;
+                    layout::mark(&pubkey_dir, Layout::Flat)?;
+                    &split_store
+                }
+                _ => {
+                    let output_dir = output_dir.ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "--output-dir is required unless --archive or --pubkey-dir and --secret-dir are given",
+                        )
+                    })?;
+                    match layout {
+                        Layout::Flat => {
+                            dir_store = FsKeyStore::create(&output_dir)?;
+                            layout::mark(&output_dir, Layout::Flat)?;
+                            &dir_store
+                        }
+                        Layout::ContentAddressed => {
+                            content_store = ContentAddressedKeyStore::create(&output_dir)?;
+                            layout::mark(&output_dir, Layout::ContentAddressed)?;
+                            &content_store
+                        }
+                    }
+                }
+            };
+
+            let checkpoint = if resume {
+                Some(Checkpoint::load(store)?)
+            } else {
+                None
+            };
+            // A resumed run continues the interrupted run's ceremony
+            let ceremony_id = match checkpoint.as_ref().and_then(Checkpoint::ceremony_id) {
+                Some(ceremony_id) => ceremony_id.to_string(),
+                None => uuid::Uuid::new_v4().to_string(),
+            };
+
+            let entropy: std::sync::Arc<dyn entropy::EntropySource> =
+                entropy::open_source(entropy_source, seed_file.as_deref(), &entropy_device)?.into();
+            let options = OutputOptions {
+                store,
+                scheme,
+                export_format,
+                export_secret_json,
+                pretty_json,
+                distributed,
+                write_metadata,
+                verify_writes,
+                ceremony_id: Some(&ceremony_id),
+                canaries_from: (with_canary > 0).then_some(num_validators),
+                first_index: 0,
+                namespace: namespace.as_deref(),
+            };
+            let num_validators = num_validators + with_canary;
+            // A half-written archive cannot be resumed, so only directories
+            // are checkpointed
+            if archive.is_none() {
+                shutdown::install()?;
+            }
+
+            let started = std::time::Instant::now();
+            let result = if batch.is_empty() {
+                generate::generate_keys(
+                    num_validators,
+                    log_num_active_epochs,
+                    &options,
+                    &pipeline,
+                    &entropy,
+                    audit_entropy,
+                    checkpoint,
+                )
+                .map(|generated| BatchesGenerated::single(scheme, log_num_active_epochs, generated))
+            } else {
+                generate::generate_batches(&batch, &options, &pipeline, &entropy)
+            }
+            .and_then(|generated| {
+                let written = generated.written();
+                // Batches cannot be resumed, so there is nothing to rerun
+                let rerun = |hint: &str| {
+                    if batch.is_empty() {
+                        format!("; rerun with --resume {}", hint)
+                    } else {
+                        String::new()
+                    }
+                };
+                // Written for interrupted runs too, listing the keys so far
+                if create_manifest && !generated.batches.is_empty() {
+                    create_validator_manifest(
+                        store,
+                        &ceremony_id,
+                        distributed,
+                        namespace.as_deref(),
+                        manifest_paths,
+                        &generated.batches,
+                    )
+                    .map_err(|e| {
+                        exit::error(
+                            exit::Failure::Partial,
+                            format!("Keys were written, but the manifest was not: {}", e),
+                        )
+                    })?;
+                }
+                if let Some(e) = generated.aborted {
+                    return Err(exit::error(
+                        exit::Failure::Partial,
+                        format!(
+                            "{} of {} key pairs were written before giving up: {}{}",
+                            written,
+                            num_validators,
+                            e,
+                            rerun("once that is fixed")
+                        ),
+                    ));
+                }
+                if generated.failed.iter().any(|key| !key.timed_out) {
+                    return Err(exit::error(
+                        exit::Failure::Partial,
+                        format!(
+                            "{} of {} key pairs failed or timed out{}",
+                            generated.failed.len(),
+                            num_validators,
+                            rerun("to retry them")
+                        ),
+                    ));
+                }
+                if !generated.failed.is_empty() {
+                    return Err(exit::error(
+                        exit::Failure::TimedOut,
+                        format!(
+                            "{} of {} key pairs timed out{}",
+                            generated.failed.len(),
+                            num_validators,
+                            rerun("to retry them")
+                        ),
+                    ));
+                }
+                if generated.interrupted {
+                    return Err(exit::error(
+                        exit::Failure::Resumable,
+                        format!(
+                            "Stopped after {} of {} key pairs{}",
+                            written,
+                            num_validators,
+                            rerun("to finish")
+                        ),
+                    ));
+                }
+                if resume {
+                    store.delete(CHECKPOINT_FILE)?;
+                }
+                Ok(written)
+            });
+
+            // Failed runs are recorded too, for the error tallies
+            let run_stats = RunStats::generate(
+                &ceremony_id,
+                scheme,
                 num_validators,
                 log_num_active_epochs,
+                &pipeline,
+                started.elapsed(),
+                result.as_ref().copied(),
+            );
+            if let Err(e) = run_stats.write(store) {
+                println!("⚠️  Failed to write {}: {}", stats::RUN_STATS_FILE, e);
+            }
+            result?;
+
+            if let Some(archive) = &archive {
+                archive.commit()?;
+                println!("\n🔒 Keys sealed into: {}", store.location());
+            }
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::GenTestNet {
+            num_validators,
+            nodes,
+            log_num_active_epochs,
+            output_dir,
+            scheme,
+            export_format,
+            seed_file,
+            threads,
+        } => {
+            testnet::check_nodes(nodes, num_validators)?;
+            let scheme = match scheme {
+                Some(name) => scheme::lookup(&name)?,
+                None => scheme::default_scheme(),
+            };
+            let entropy: std::sync::Arc<dyn entropy::EntropySource> = match &seed_file {
+                Some(seed_file) => {
+                    std::sync::Arc::new(entropy::DeterministicEntropy::from_seed_file(seed_file)?)
+                }
+                None => std::sync::Arc::new(entropy::OsEntropy),
+            };
+            let store = FsKeyStore::create(output_dir.join(testnet::KEYS_DIR))?;
+            layout::mark(&output_dir.join(testnet::KEYS_DIR), Layout::Flat)?;
+            let ceremony_id = uuid::Uuid::new_v4().to_string();
+            let options = OutputOptions {
+                store: &store,
+                scheme,
                 export_format,
-                output_dir.clone(),
-                distributed,
+                export_secret_json: false,
+                pretty_json: false,
+                distributed: false,
+                write_metadata: true,
+                verify_writes: VerifyWrites::Secret,
+                ceremony_id: Some(&ceremony_id),
+                canaries_from: None,
+                first_index: 0,
+                namespace: None,
+            };
+            let pipeline = PipelineConfig {
+                compute_threads: threads.unwrap_or_else(bench::default_threads),
+                writer_threads: 2,
+                json_threads: 2,
+                queue_depth: 4,
+                max_keys_per_minute: None,
+                key_timeout: None,
+                retries: 0,
+                max_failures: 0,
+                priority: Vec::new(),
+            };
+
+            println!(
+                "🧪 Bootstrapping a test network of {} validators on {} nodes in {}\n",
+                num_validators,
+                nodes,
+                output_dir.display()
+            );
+            let generated = generate::generate_keys(
+                num_validators,
+                log_num_active_epochs,
+                &options,
+                &pipeline,
+                &entropy,
+                false,
+                None,
             )?;
-            
-            if create_manifest {
-                create_validator_manifest(
-                    &output_dir,
-                    num_validators,
+            // A devnet is generated in full or not at all
+            if let Some(e) = generated.aborted {
+                return Err(e);
+            }
+            create_validator_manifest(
+                &store,
+                &ceremony_id,
+                false,
+                None,
+                ManifestPaths::Relative,
+                &[GeneratedBatch {
+                    scheme,
                     log_num_active_epochs,
-                    distributed,
-                    &validator_info,
+                    validators: generated.validators,
+                }],
+            )?;
+            testnet::finish(&output_dir, nodes)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Assign {
+            dir,
+            nodes,
+            weights,
+            mode,
+            output_dir,
+        } => assign::assign(&dir, nodes, &weights, mode, &output_dir)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::LinkView {
+            dir,
+            naming,
+            output_dir,
+        } => link_view::link_view(&dir, naming, &output_dir)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Derive {
+            path,
+            seed_file,
+            output_dir,
+            log_num_active_epochs,
+            scheme,
+            export_format,
+            export_secret_json,
+            pretty_json,
+            distributed,
+            write_metadata,
+        } => {
+            let index = entropy::parse_derivation_path(&path)
+                .map_err(|reason| std::io::Error::new(std::io::ErrorKind::InvalidInput, reason))?;
+            let scheme = match scheme {
+                Some(name) => scheme::lookup(&name)?,
+                None => scheme::default_scheme(),
+            };
+            let entropy = entropy::DeterministicEntropy::from_seed_file(&seed_file)?;
+            let store = FsKeyStore::create(output_dir)?;
+            let options = OutputOptions {
+                store: &store,
+                scheme,
+                export_format,
+                export_secret_json,
+                pretty_json,
+                distributed,
+                write_metadata,
+                verify_writes: VerifyWrites::Secret,
+                ceremony_id: None,
+                canaries_from: None,
+                first_index: 0,
+                namespace: None,
+            };
+            println!("🌱 Deriving {} from {}\n", path, seed_file.display());
+            let info = generate::generate_one(index, log_num_active_epochs, &options, &entropy)?;
+            println!(
+                "\n✅ Derived {} at {}: {}",
+                info.privkey_file, path, info.pubkey_hex
+            );
+            println!(
+                "   Compare the pubkey with the manifest entry for {} before using it.",
+                path
+            );
+        }
+        Commands::Export {
+            dir,
+            format,
+            lang,
+            output,
+            sort,
+        } => {
+            registry::export(&dir, format, &output, sort, lang)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Backup {
+            dir,
+            output,
+            passphrase_file,
+        } => {
+            let passphrase = backup::read_passphrase(passphrase_file.as_deref())?;
+            backup::backup(&dir, &output, &passphrase)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Restore {
+            bundle,
+            output_dir,
+            passphrase_file,
+            verify_keys,
+        } => {
+            let passphrase = backup::read_passphrase(passphrase_file.as_deref())?;
+            backup::restore(&bundle, &output_dir, &passphrase, verify_keys)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::VerifyKeystore {
+            bundle,
+            passphrase_file,
+            structure_only,
+            scheme,
+        } => {
+            let passphrase = if structure_only {
+                None
+            } else {
+                Some(backup::read_passphrase(passphrase_file.as_deref())?)
+            };
+            backup::verify_keystore(&bundle, passphrase.as_deref(), scheme.as_deref())?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Reencrypt {
+            dir,
+            passphrase_file,
+            new_passphrase_file,
+        } => {
+            let passphrase = backup::read_passphrase(passphrase_file.as_deref())?;
+            let new_passphrase = backup::read_passphrase(Some(&new_passphrase_file))?;
+            backup::reencrypt(&dir, &passphrase, &new_passphrase)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Copy { src, dst } => copy::copy(&src, &dst)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Push { dir, destination } => {
+            transfer::push(&dir, &transfer::SshTarget::parse(&destination)?)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Pull { source, output_dir } => {
+            transfer::pull(&transfer::SshTarget::parse(&source)?, &output_dir)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Escrow { command } => match command {
+            EscrowCommand::Keygen { output } => escrow::keygen(&output)?,
+            EscrowCommand::Create {
+                dir,
+                recipients,
+                threshold,
+                output_dir,
+            } => escrow::create(&dir, &recipients, threshold, &output_dir)?,
+            EscrowCommand::Recover {
+                bundles,
+                keys,
+                output_dir,
+                verify_keys,
+            } => escrow::recover(&bundles, &keys, &output_dir, verify_keys)?,
+            EscrowCommand::Verify {
+                bundles,
+                keys,
+                published_dir,
+            } => escrow::verify(&bundles, &keys, published_dir.as_deref())?,
+        },
+        #[cfg(not(feature = "verify-only"))]
+        Commands::TransportKeygen { output, valid_for } => wrap::keygen(&output, valid_for)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Wrap {
+            dir,
+            pubkeys,
+            recipient,
+            output,
+        } => wrap::wrap(&dir, &pubkeys, &recipient, &output)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Unwrap {
+            file,
+            key,
+            output_dir,
+            keep_key,
+        } => wrap::unwrap(&file, &key, &output_dir, keep_key)?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Revoke {
+            pubkey,
+            dir,
+            reason,
+            shred,
+        } => {
+            revoke::revoke(&dir, &pubkey, &reason, shred)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Prune {
+            path,
+            before_epoch,
+            scheme,
+        } => prune::prune(&path, before_epoch, scheme.as_deref())?,
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Rotate { command } => match command {
+            RotateCommand::Start { pubkey, dir } => {
+                rotate::start(&dir, &pubkey, None)?;
+            }
+            RotateCommand::Finalize { pubkey, dir, shred } => {
+                rotate::finalize(&dir, &pubkey, shred)?
+            }
+        },
+        #[cfg(not(feature = "verify-only"))]
+        Commands::MigrateScheme {
+            dir,
+            from,
+            to,
+            dry_run,
+        } => migrate::migrate_scheme(&dir, from.as_deref(), to.as_deref(), dry_run)?,
+        Commands::PublishLog { dir, log, sort } => translog::publish_log(&dir, &log, sort)?,
+        Commands::VerifyLog {
+            log,
+            head,
+            pubkeys,
+            dir,
+        } => translog::verify_log(&log, head.as_deref(), &pubkeys, dir.as_deref())?,
+        Commands::WatchLifetime {
+            dirs,
+            genesis_time,
+            seconds_per_epoch,
+            thresholds,
+            webhook,
+            interval,
+            once,
+        } => lifetime::watch(
+            &dirs,
+            lifetime::Clock {
+                genesis_time,
+                seconds_per_epoch,
+            },
+            &thresholds,
+            webhook.as_deref(),
+            interval,
+            once,
+        )?,
+        Commands::Fetch {
+            manifest,
+            signer_pk,
+            scheme,
+            output_dir,
+        } => fetch::fetch(&manifest, &signer_pk, scheme.as_deref(), &output_dir)?,
+        Commands::InspectSig {
+            file,
+            scheme,
+            pubkey,
+            message_hex,
+            epoch,
+        } => {
+            inspect_sig::inspect_sig(
+                &file,
+                scheme.as_deref(),
+                pubkey.as_deref(),
+                message_hex.as_deref(),
+                epoch,
+            )?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Sign {
+            message,
+            sk,
+            archive,
+            key_name,
+            passphrase_file,
+            seed_file,
+            derivation_path,
+            log_num_active_epochs,
+            epoch,
+            scheme,
+            message_hex,
+            message_file,
+            prehash,
+            output,
+            stdout: _,
+            offline_bundle,
+            timeout,
+            manifest,
+        } => {
+            let message = MessageSource {
+                message: message.as_deref(),
+                message_hex: message_hex.as_deref(),
+                message_file: message_file.as_deref(),
+            };
+            let source = match (
+                &sk,
+                &archive,
+                &key_name,
+                &seed_file,
+                &derivation_path,
+                log_num_active_epochs,
+            ) {
+                (Some(sk), None, None, None, None, None) => KeySource::File(sk),
+                (None, Some(bundle), Some(name), None, None, None) => KeySource::Archive {
+                    bundle,
+                    name,
+                    passphrase: backup::read_passphrase(passphrase_file.as_deref())?,
+                },
+                (None, None, None, Some(seed_file), Some(path), Some(log_num_active_epochs)) => {
+                    KeySource::Derived {
+                        seed_file,
+                        path,
+                        log_num_active_epochs,
+                    }
+                }
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Give exactly one key: --sk, --archive with --key-name, or --seed-file with --derivation-path",
+                    ))
+                }
+            };
+            let signer = source.open(scheme.as_deref())?;
+            if let Some(manifest) = manifest {
+                crate::signer::refuse_revoked(
+                    signer.as_ref(),
+                    Some(&Manifest::read_file(&manifest)?),
                 )?;
             }
+            sign::sign(
+                signer.as_ref(),
+                epoch,
+                &message,
+                prehash,
+                output.as_deref(),
+                offline_bundle.as_deref(),
+                timeout.map(std::time::Duration::from_secs),
+            )?;
+        }
+        Commands::Verify {
+            message,
+            pk,
+            epoch,
+            signature,
+            scheme,
+            message_hex,
+            message_file,
+            prehash,
+            batch_file,
+        } => match (batch_file, pk, epoch, signature) {
+            (Some(batch_file), ..) => {
+                let defaults = sign::BatchDefaults {
+                    scheme_name: scheme.as_deref(),
+                    prehash,
+                };
+                sign::verify_batch(&batch_file, &defaults)?;
+            }
+            (None, Some(pk), Some(epoch), Some(signature)) => {
+                let message = MessageSource {
+                    message: message.as_deref(),
+                    message_hex: message_hex.as_deref(),
+                    message_file: message_file.as_deref(),
+                };
+                sign::verify(&pk, epoch, scheme.as_deref(), &message, prehash, &signature)?;
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--pk, --epoch and --signature are required without --batch-file",
+                ))
+            }
+        },
+        Commands::VerifyBundle { bundle, pubkey } => {
+            offline_bundle::verify_bundle(&bundle, pubkey.as_deref())?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Crosscheck { dir } => {
+            crosscheck::crosscheck(&dir)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Convert {
+            input,
+            output,
+            stdout: _,
+            from,
+            to,
+            kind,
+            scheme,
+        } => {
+            convert::convert(&input, output.as_deref(), from, to, kind, scheme.as_deref())?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::KeygenBench {
+            scheme,
+            log_num_active_epochs,
+            keys_per_thread,
+            profile,
+            profile_path,
+        } => {
+            let profile_path = if profile {
+                Some(
+                    profile_path
+                        .or_else(bench::default_profile_path)
+                        .ok_or_else(|| {
+                            std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "No default profile location (HOME is not set), pass --profile-path",
+                    )
+                        })?,
+                )
+            } else {
+                None
+            };
+            let scheme = match scheme {
+                Some(name) => scheme::lookup(&name)?,
+                None => scheme::default_scheme(),
+            };
+            bench::keygen_bench(
+                scheme,
+                log_num_active_epochs,
+                keys_per_thread,
+                profile_path.as_deref(),
+            )?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::SignBench {
+            epochs,
+            step,
+            sk,
+            scheme,
+        } => {
+            let scheme = match (&sk, scheme) {
+                (Some(sk), scheme) => scheme::for_key(scheme.as_deref(), sk)?,
+                (None, Some(name)) => scheme::lookup(&name)?,
+                (None, None) => scheme::default_scheme(),
+            };
+            bench::sign_bench(scheme, sk.as_deref(), epochs, step)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::CompareSchemes {
+            log_num_active_epochs,
+            signatures,
+            output,
+        } => {
+            bench::compare_schemes(log_num_active_epochs, signatures, output.as_deref())?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::FuzzVerify {
+            duration,
+            scheme,
+            log_num_active_epochs,
+        } => {
+            let scheme = match scheme {
+                Some(name) => scheme::lookup(&name)?,
+                None => scheme::default_scheme(),
+            };
+            fuzz::fuzz_verify(scheme, duration, log_num_active_epochs)?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Interop {
+            command:
+                InteropCommand::Run {
+                    external_cmd,
+                    scheme,
+                    log_num_active_epochs,
+                    work_dir,
+                },
+        } => {
+            let scheme = match scheme {
+                Some(name) => scheme::lookup(&name)?,
+                None => scheme::default_scheme(),
+            };
+            interop::run(
+                scheme,
+                &external_cmd,
+                log_num_active_epochs,
+                work_dir.as_deref(),
+            )?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::VerifyDir {
+            dir,
+            threads,
+            sample,
+            quarantine,
+            report,
+            sign_key,
+            sign_epoch,
+        } => {
+            let signer = match (&sign_key, sign_epoch) {
+                (Some(sk), Some(epoch)) => Some(report::ReportSigner {
+                    signer: KeySource::File(sk).open(None)?,
+                    epoch,
+                }),
+                _ => None,
+            };
+            let threads = threads
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+            verify_dir::verify_dir(&dir, threads, sample, quarantine, report.as_deref(), signer)?;
+        }
+        Commands::Diff { dir_a, dir_b, sort } => {
+            diff::diff(&dir_a, &dir_b, sort)?;
+        }
+        Commands::Manifest {
+            command: ManifestCommand::Info { file },
+        } => {
+            manifest_info::info(&file)?;
+        }
+        Commands::Stats {
+            command: StatsCommand::Aggregate { dirs, output },
+        } => {
+            stats::aggregate(&dirs, output.as_deref())?;
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Stats {
+            command: StatsCommand::Key { sk, scheme },
+        } => {
+            key_stats::key_stats(&sk, scheme.as_deref())?;
+        }
+        Commands::Explain { code } => {
+            explain::explain(&code)?;
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                BIN_NAME,
+                &mut std::io::stdout(),
+            );
+        }
+        Commands::Manpage { output_dir } => {
+            let command = Args::command().name(BIN_NAME);
+            match output_dir {
+                Some(dir) => {
+                    std::fs::create_dir_all(&dir)?;
+                    clap_mangen::generate_to(command, &dir)?;
+                    println!("📄 Man pages written to: {}", dir.display());
+                }
+                None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
+            }
+        }
+        #[cfg(not(feature = "verify-only"))]
+        Commands::Ceremony {
+            command:
+                CeremonyCommand::Report {
+                    dir,
+                    output,
+                    format,
+                    operators,
+                    notes,
+                    validation_report,
+                    sign_key,
+                    sign_epoch,
+                },
+        } => {
+            let signer = match (&sign_key, sign_epoch) {
+                (Some(sk), Some(epoch)) => Some(report::ReportSigner {
+                    signer: KeySource::File(sk).open(None)?,
+                    epoch,
+                }),
+                _ => None,
+            };
+            let inputs = TranscriptInputs {
+                operators: &operators,
+                notes: &notes,
+                validation_report: validation_report.as_deref(),
+            };
+            ceremony::report(&dir, &inputs, format, &output, signer)?;
         }
     }
 
     Ok(())
 }
 
-struct ValidatorInfo {
-    pubkey_hex: String,
-    privkey_file: String,
+/// Parse the command line, with the defaults of `--profile` applied.
+fn parse_args() -> std::io::Result<Args> {
+    // A first, lenient pass only to find the profile, whose values may be
+    // what makes the command line complete
+    let profile = Args::command()
+        .ignore_errors(true)
+        .try_get_matches()
+        .ok()
+        .and_then(|matches| matches.get_one::<String>("profile").cloned());
+    let command = match profile {
+        Some(name) => config::apply_profile(Args::command(), &name)?,
+        None => Args::command(),
+    };
+    Ok(Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|e| e.exit()))
 }
 
-fn generate_keys(
-    num_validators: usize,
-    log_num_active_epochs: usize,
-    export_format: ExportFormat,
-    output_dir: PathBuf,
+/// Write the manifest of the key pairs of `batches`. The first batch's
+/// scheme and active epochs are the manifest's; validators of later batches
+/// that differ record their own.
+#[cfg(not(feature = "verify-only"))]
+fn create_validator_manifest(
+    store: &dyn KeyStore,
+    ceremony_id: &str,
     distributed: bool,
-) -> std::io::Result<Vec<ValidatorInfo>> {
-    // Create the output directory if it doesn't exist
-    fs::create_dir_all(&output_dir)?;
-
-    let activation_duration = 1 << log_num_active_epochs;
-    
-    println!(
-        "Generating {} validator keys with 2^{} active epochs ({} total) in directory: {}\n",
-        num_validators,
-        log_num_active_epochs,
-        activation_duration,
-        output_dir.display()
-    );
+    namespace: Option<&str>,
+    paths: ManifestPaths,
+    batches: &[GeneratedBatch],
+) -> std::io::Result<()> {
+    println!("\n📄 Creating validator manifest...");
 
-    println!("🔐 Keys will be formatted for validator integration");
-    println!("⚠️  Note: Secret keys are large files (~several MB each)\n");
-
-    let mut rng = rand::rng();
-
-    let write_json = matches!(export_format, ExportFormat::Both);
-    let mut validator_info_list = Vec::new();
-
-    for i in 0..num_validators {
-        // Generate the key pair
-        let (pk, sk) = SIGTopLevelTargetSumLifetime32Dim64Base8::key_gen(
-            &mut rng,
-            0,
-            activation_duration,
-        );
-
-        // Serialize the public key to SSZ bytes
-        let pk_bytes = pk.to_bytes();
-        
-        // Determine key prefix based on format
-        let key_prefix = if distributed {
-            // Extract first 3 and last 3 bytes from pk_bytes
-            if pk_bytes.len() < 3 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Public key bytes too short to extract first-3 last-3 bytes"
-                ));
-            }
-            let first_3 = &pk_bytes[0..3];
-            let last_3 = &pk_bytes[pk_bytes.len() - 3..];
-            let first_3_hex = hex::encode(first_3);
-            let last_3_hex = hex::encode(last_3);
-            format!("validator-{}-{}", first_3_hex, last_3_hex)
-        } else {
-            format!("validator_{}", i)
-        };
-        
-        println!("Generating {}...", key_prefix);
-
-        // Write public key to SSZ file
-        let mut pk_file = File::create(output_dir.join(format!("{}_pk.ssz", key_prefix)))?;
-        pk_file.write_all(&pk_bytes)?;
-
-        // Serialize the secret key to SSZ bytes and write to a binary .ssz file
-        let sk_bytes = sk.to_bytes();
-        let mut sk_file = File::create(output_dir.join(format!("{}_sk.ssz", key_prefix)))?;
-        sk_file.write_all(&sk_bytes)?;
-
-        println!("  ✅ {}_pk.ssz", key_prefix);
-        println!("  ✅ {}_sk.ssz", key_prefix);
-
-        if write_json {
-            // Also export legacy JSON representations for backwards compatibility
-            let pk_json =
-                serde_json::to_string_pretty(&pk).expect("Failed to serialize public key to JSON");
-            let mut pk_json_file =
-                File::create(output_dir.join(format!("{}_pk.json", key_prefix)))?;
-            pk_json_file.write_all(pk_json.as_bytes())?;
-
-            let sk_json =
-                serde_json::to_string_pretty(&sk).expect("Failed to serialize secret key to JSON");
-            let mut sk_json_file =
-                File::create(output_dir.join(format!("{}_sk.json", key_prefix)))?;
-            sk_json_file.write_all(sk_json.as_bytes())?;
-
-            println!("  ⚠️  (legacy) {}_pk.json", key_prefix);
-            println!("  ⚠️  (legacy) {}_sk.json", key_prefix);
-        }
-
-        // Store validator info for manifest
-        let pubkey_hex = format!("0x{}", hex::encode(&pk_bytes));
-        let privkey_file = format!("{}_sk.ssz", key_prefix);
-        validator_info_list.push(ValidatorInfo {
-            pubkey_hex,
-            privkey_file,
-        });
-    }
+    let scheme = batches[0].scheme;
+    let log_num_active_epochs = batches[0].log_num_active_epochs;
+    let validators: Vec<ManifestEntry> = batches
+        .iter()
+        .flat_map(|batch| batch.validators.iter().map(move |info| (batch, info)))
+        .map(|(batch, info)| {
+            Ok(ManifestEntry {
+                // Distributed format: no index field
+                index: (!distributed).then_some(info.index),
+                pubkey_hex: info.pubkey_hex.clone(),
+                privkey_file: keystore::manifest_reference(store, &info.privkey_file, paths)?,
+                derivation_path: info.derivation_path.clone(),
+                rotating_to: None,
+                canary: info.canary,
+                key_scheme: (batch.scheme.name() != scheme.name())
+                    .then(|| batch.scheme.name().to_string()),
+                log_num_active_epochs: (batch.log_num_active_epochs != log_num_active_epochs)
+                    .then_some(batch.log_num_active_epochs),
+            })
+        })
+        .collect::<std::io::Result<_>>()?;
 
-    println!("\n✅ Successfully generated and saved {} validator key pairs.", num_validators);
+    let manifest = Manifest {
+        manifest_version: Some(MANIFEST_VERSION),
+        ceremony_id: Some(ceremony_id.to_string()),
+        namespace: namespace.map(str::to_string),
+        key_scheme: scheme.name().to_string(),
+        hash_function: scheme.hash_function().name().to_string(),
+        encoding: scheme.encoding().to_string(),
+        scheme_fingerprint: Some(scheme.fingerprint()),
+        lifetime: scheme.lifetime(),
+        log_num_active_epochs,
+        num_active_epochs: 1 << log_num_active_epochs,
+        num_validators: validators.len(),
+        validators,
+        revoked: Vec::new(),
+    };
+    store.store(MANIFEST_FILE, manifest.to_yaml().as_bytes())?;
 
-    Ok(validator_info_list)
-}
+    println!("  ✅ {}", MANIFEST_FILE);
+    println!(
+        "\n📋 Manifest created successfully in: {}",
+        store.location()
+    );
 
-fn create_validator_manifest(
-    output_dir: &PathBuf,
-    num_validators: usize,
-    log_num_active_epochs: usize,
-    distributed: bool,
-    validator_info: &[ValidatorInfo],
-) -> std::io::Result<()> {
-    println!("\n📄 Creating validator manifest...");
-    
-    let manifest_path = output_dir.join("validator-keys-manifest.yaml");
-    let mut manifest_file = File::create(&manifest_path)?;
-    
-    // Write YAML manifest
-    writeln!(manifest_file, "# Hash-Signature Validator Keys Manifest")?;
-    writeln!(manifest_file, "# Generated by hash-sig-cli\n")?;
-    writeln!(manifest_file, "key_scheme: SIGTopLevelTargetSumLifetime32Dim64Base8")?;
-    writeln!(manifest_file, "hash_function: Poseidon2")?;
-    writeln!(manifest_file, "encoding: TargetSum")?;
-    writeln!(manifest_file, "lifetime: {}", 1u64 << 32)?;
-    writeln!(manifest_file, "log_num_active_epochs: {}", log_num_active_epochs)?;
-    writeln!(manifest_file, "num_active_epochs: {}", 1 << log_num_active_epochs)?;
-    writeln!(manifest_file, "num_validators: {}\n", num_validators)?;
-    writeln!(manifest_file, "validators:")?;
-    
-    for (i, info) in validator_info.iter().enumerate() {
-        if distributed {
-            // Distributed format: no index field
-            writeln!(manifest_file, "  - pubkey_hex: {}", info.pubkey_hex)?;
-            writeln!(manifest_file, "    privkey_file: {}", info.privkey_file)?;
-        } else {
-            // Indexed format: include index field
-            writeln!(manifest_file, "  - index: {}", i)?;
-            writeln!(manifest_file, "    pubkey_hex: {}", info.pubkey_hex)?;
-            writeln!(manifest_file, "    privkey_file: {}", info.privkey_file)?;
-        }
-        if i < validator_info.len() - 1 {
-            writeln!(manifest_file)?;
-        }
-    }
-    
-    println!("  ✅ validator-keys-manifest.yaml");
-    println!("\n📋 Manifest created successfully at: {}", manifest_path.display());
-    
     Ok(())
 }
-
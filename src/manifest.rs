@@ -0,0 +1,481 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::layout;
+
+/// File name of the manifest written next to the keys by `generate`.
+pub const MANIFEST_FILE: &str = "validator-keys-manifest.yaml";
+
+/// Layout version written to new manifests.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Validator keys manifest as written by `generate`.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    /// Missing from manifests written before the layout was versioned.
+    #[serde(default)]
+    pub manifest_version: Option<u32>,
+    /// ID of the `generate` run that created the key set.
+    #[serde(default)]
+    pub ceremony_id: Option<String>,
+    /// Tenant of the key set, the prefix of its key file names (`generate
+    /// --namespace`).
+    #[serde(default)]
+    pub namespace: Option<String>,
+    pub key_scheme: String,
+    pub hash_function: String,
+    pub encoding: String,
+    /// Missing from manifests written before parameters were fingerprinted.
+    #[serde(default)]
+    pub scheme_fingerprint: Option<String>,
+    pub lifetime: u64,
+    pub log_num_active_epochs: usize,
+    pub num_active_epochs: u64,
+    pub num_validators: usize,
+    pub validators: Vec<ManifestEntry>,
+    /// Validators removed with `revoke`.
+    #[serde(default)]
+    pub revoked: Vec<Tombstone>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestEntry {
+    /// Only present for the indexed naming format.
+    #[serde(default)]
+    pub index: Option<usize>,
+    pub pubkey_hex: String,
+    pub privkey_file: String,
+    /// Only present for keys derived from a master seed.
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+    /// Pubkey of the replacement while the validator is being rotated.
+    #[serde(default)]
+    pub rotating_to: Option<String>,
+    /// A canary from `generate --with-canary`, never to be registered or
+    /// signed with.
+    #[serde(default)]
+    pub canary: bool,
+    /// Only present in mixed key sets (`generate --batch`), for validators
+    /// of another scheme than the manifest's.
+    #[serde(default)]
+    pub key_scheme: Option<String>,
+    /// Only present in mixed key sets, for validators with other active
+    /// epochs than the manifest's.
+    #[serde(default)]
+    pub log_num_active_epochs: Option<usize>,
+}
+
+/// Record of a revoked validator. Its pubkey must never be used again.
+#[derive(Debug, Deserialize)]
+pub struct Tombstone {
+    #[serde(default)]
+    pub index: Option<usize>,
+    pub pubkey_hex: String,
+    pub privkey_file: String,
+    pub reason: String,
+    /// Revocation time in seconds since the Unix epoch.
+    pub revoked_at: u64,
+}
+
+/// How the manifest refers to secret key files.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ManifestPaths {
+    /// Relative to the manifest's directory (a bare name for keys next to it)
+    Relative,
+    /// Absolute paths
+    Absolute,
+}
+
+/// Order of validators in listings.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum SortOrder {
+    /// By validator index; validators without one (distributed format)
+    /// after the others, by name
+    #[default]
+    Index,
+    /// By public key
+    Pubkey,
+    /// By secret key file name, with numbers in names compared by value
+    Name,
+}
+
+/// Compare file names with runs of digits compared by value, so
+/// `validator_2` sorts before `validator_10`. Byte-wise otherwise, so the
+/// order is the same whatever the locale or filesystem.
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits_a = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let digits_b = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (number_a, rest_a) = a.split_at(digits_a);
+                let (number_b, rest_b) = b.split_at(digits_b);
+                let trim = |n: &[u8]| -> usize { n.iter().take_while(|&&c| c == b'0').count() };
+                let (value_a, value_b) = (&number_a[trim(number_a)..], &number_b[trim(number_b)..]);
+                let ordering = value_a
+                    .len()
+                    .cmp(&value_b.len())
+                    .then_with(|| value_a.cmp(value_b))
+                    // Equal values: fewer leading zeros first, so the order is total
+                    .then_with(|| digits_a.cmp(&digits_b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+/// File name of a manifest's `privkey_file`, which may be a bare name or a
+/// relative or absolute path. Key file names are unique within a key set.
+pub fn file_name(privkey_file: &str) -> &str {
+    Path::new(privkey_file)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(privkey_file)
+}
+
+/// Name of a validator's key files without the suffix, e.g. `validator_3`
+/// for `validator_3_pk.ssz`.
+pub fn key_prefix(name: &str) -> Option<&str> {
+    ["_sk.ssz", "_pk.ssz", "_sk.json", "_pk.json", ".meta.json"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+}
+
+pub fn manifest_path(dir: &Path) -> PathBuf {
+    dir.join(MANIFEST_FILE)
+}
+
+impl Manifest {
+    /// Read the manifest stored in the key directory `dir`.
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        Self::read_file(&manifest_path(dir))
+    }
+
+    /// Read a manifest from the file `path`, after checking the layout of
+    /// its key directory (see [`layout::check`]).
+    pub fn read_file(path: &Path) -> io::Result<Self> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        layout::check(dir)?;
+        Self::parse(&fs::read_to_string(path)?, &path.display().to_string())
+    }
+
+    /// Parse a manifest read from `source`.
+    pub fn parse(contents: &str, source: &str) -> io::Result<Self> {
+        serde_yaml::from_str(contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse manifest {}: {}", source, e),
+            )
+        })
+    }
+
+    /// Like [`Manifest::read`], but returns `None` if `dir` has no manifest.
+    pub fn read_if_present(dir: &Path) -> io::Result<Option<Self>> {
+        if manifest_path(dir).exists() {
+            Self::read(dir).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Scheme of the validator `entry`: its own in a mixed key set, else the
+    /// manifest's.
+    pub fn scheme_of<'a>(&'a self, entry: &'a ManifestEntry) -> &'a str {
+        entry.key_scheme.as_deref().unwrap_or(&self.key_scheme)
+    }
+
+    /// Log2 of the active epochs of the validator `entry`.
+    pub fn log_num_active_epochs_of(&self, entry: &ManifestEntry) -> usize {
+        entry
+            .log_num_active_epochs
+            .unwrap_or(self.log_num_active_epochs)
+    }
+
+    /// Whether validators of more than one scheme are listed.
+    pub fn is_mixed(&self) -> bool {
+        self.validators
+            .iter()
+            .any(|entry| entry.key_scheme.is_some())
+    }
+
+    /// The validator whose public or secret key file is named `name`.
+    pub fn entry_for_file(&self, name: &str) -> Option<&ManifestEntry> {
+        let prefix = key_prefix(name)?;
+        self.validators
+            .iter()
+            .find(|entry| key_prefix(file_name(&entry.privkey_file)) == Some(prefix))
+    }
+
+    /// Sort the validator entries by `order`. Ties keep their order.
+    pub fn sort_validators(&mut self, order: SortOrder) {
+        match order {
+            SortOrder::Index => self.validators.sort_by(|a, b| {
+                // Indexed validators first
+                (a.index.is_none(), a.index)
+                    .cmp(&(b.index.is_none(), b.index))
+                    .then_with(|| {
+                        compare_names(file_name(&a.privkey_file), file_name(&b.privkey_file))
+                    })
+            }),
+            SortOrder::Pubkey => self
+                .validators
+                .sort_by_key(|v| normalize_pubkey(&v.pubkey_hex)),
+            SortOrder::Name => self.validators.sort_by(|a, b| {
+                compare_names(file_name(&a.privkey_file), file_name(&b.privkey_file))
+            }),
+        }
+    }
+
+    /// Write the manifest into the key directory `dir`, returning its path.
+    /// A directory from before layout markers gets one.
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        layout::mark(dir, layout::check(dir)?)?;
+        let path = manifest_path(dir);
+        fs::write(&path, self.to_yaml())?;
+        Ok(path)
+    }
+
+    /// The manifest in the YAML layout written by `generate`.
+    pub fn to_yaml(&self) -> String {
+        // Writing to a `String` cannot fail
+        let mut yaml = String::new();
+
+        writeln!(yaml, "# Hash-Signature Validator Keys Manifest").unwrap();
+        writeln!(yaml, "# Generated by hash-sig-cli\n").unwrap();
+        if let Some(version) = self.manifest_version {
+            writeln!(yaml, "manifest_version: {}", version).unwrap();
+        }
+        if let Some(ceremony_id) = &self.ceremony_id {
+            writeln!(yaml, "ceremony_id: {}", ceremony_id).unwrap();
+        }
+        if let Some(namespace) = &self.namespace {
+            writeln!(yaml, "namespace: {}", namespace).unwrap();
+        }
+        writeln!(yaml, "key_scheme: {}", self.key_scheme).unwrap();
+        writeln!(yaml, "hash_function: {}", self.hash_function).unwrap();
+        writeln!(yaml, "encoding: {}", self.encoding).unwrap();
+        if let Some(fingerprint) = &self.scheme_fingerprint {
+            writeln!(yaml, "scheme_fingerprint: {}", fingerprint).unwrap();
+        }
+        writeln!(yaml, "lifetime: {}", self.lifetime).unwrap();
+        writeln!(
+            yaml,
+            "log_num_active_epochs: {}",
+            self.log_num_active_epochs
+        )
+        .unwrap();
+        writeln!(yaml, "num_active_epochs: {}", self.num_active_epochs).unwrap();
+        writeln!(yaml, "num_validators: {}\n", self.num_validators).unwrap();
+
+        if self.validators.is_empty() {
+            writeln!(yaml, "validators: []").unwrap();
+        } else {
+            writeln!(yaml, "validators:").unwrap();
+        }
+        for (i, entry) in self.validators.iter().enumerate() {
+            match entry.index {
+                Some(index) => {
+                    writeln!(yaml, "  - index: {}", index).unwrap();
+                    writeln!(yaml, "    pubkey_hex: {}", entry.pubkey_hex).unwrap();
+                }
+                None => writeln!(yaml, "  - pubkey_hex: {}", entry.pubkey_hex).unwrap(),
+            }
+            // Paths may contain `: `, ` #` or quotes
+            writeln!(yaml, "    privkey_file: {}", quote(&entry.privkey_file)).unwrap();
+            if let Some(derivation_path) = &entry.derivation_path {
+                writeln!(yaml, "    derivation_path: {}", derivation_path).unwrap();
+            }
+            if let Some(rotating_to) = &entry.rotating_to {
+                writeln!(yaml, "    rotating_to: {}", rotating_to).unwrap();
+            }
+            if entry.canary {
+                writeln!(yaml, "    canary: true").unwrap();
+            }
+            if let Some(key_scheme) = &entry.key_scheme {
+                writeln!(yaml, "    key_scheme: {}", key_scheme).unwrap();
+            }
+            if let Some(log_num_active_epochs) = entry.log_num_active_epochs {
+                writeln!(yaml, "    log_num_active_epochs: {}", log_num_active_epochs).unwrap();
+            }
+            if i + 1 < self.validators.len() {
+                writeln!(yaml).unwrap();
+            }
+        }
+
+        if !self.revoked.is_empty() {
+            writeln!(yaml, "\nrevoked:").unwrap();
+            for (i, tombstone) in self.revoked.iter().enumerate() {
+                match tombstone.index {
+                    Some(index) => {
+                        writeln!(yaml, "  - index: {}", index).unwrap();
+                        writeln!(yaml, "    pubkey_hex: {}", tombstone.pubkey_hex).unwrap();
+                    }
+                    None => writeln!(yaml, "  - pubkey_hex: {}", tombstone.pubkey_hex).unwrap(),
+                }
+                writeln!(yaml, "    privkey_file: {}", quote(&tombstone.privkey_file)).unwrap();
+                writeln!(yaml, "    reason: {}", quote(&tombstone.reason)).unwrap();
+                writeln!(yaml, "    revoked_at: {}", tombstone.revoked_at).unwrap();
+                if i + 1 < self.revoked.len() {
+                    writeln!(yaml).unwrap();
+                }
+            }
+        }
+
+        yaml
+    }
+}
+
+/// `value` as a YAML double-quoted scalar, which a JSON string is.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("string serialization")
+}
+
+/// Canonical form of a hex public key for comparisons: lowercase, no `0x`.
+pub fn normalize_pubkey(pubkey_hex: &str) -> String {
+    pubkey_hex.trim_start_matches("0x").to_ascii_lowercase()
+}
+
+/// Abbreviate a `0x`-prefixed public key for display.
+pub fn short_pubkey(pubkey_hex: &str) -> String {
+    let hex = pubkey_hex.trim_start_matches("0x");
+    if hex.len() <= 16 {
+        return pubkey_hex.to_string();
+    }
+    format!("0x{}…{}", &hex[..8], &hex[hex.len() - 8..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        names.sort_by(|a, b| compare_names(a, b));
+        names
+    }
+
+    #[test]
+    fn round_trips_odd_paths() {
+        let mut manifest = Manifest::parse(
+            "key_scheme: SIGTopLevelTargetSumLifetime32Dim64Base8
+hash_function: Poseidon2
+encoding: TargetSum
+lifetime: 4294967296
+log_num_active_epochs: 2
+num_active_epochs: 4
+num_validators: 1
+validators:
+  - index: 0
+    pubkey_hex: 0x00
+    privkey_file: validator_0_sk.ssz
+revoked:
+  - index: 1
+    pubkey_hex: 0x01
+    privkey_file: validator_1_sk.ssz
+    reason: exited
+    revoked_at: 1760000000
+",
+            "test manifest",
+        )
+        .unwrap();
+        let paths = [
+            "/mnt/keys: prod/validator_0_sk.ssz",
+            "keys #2/validator_0_sk.ssz",
+            "*validator_0_sk.ssz",
+            "&anchor/validator_0_sk.ssz",
+            "- \"quoted\" \\ 'single'.ssz",
+            "",
+            "null",
+            "0x123",
+            "  padded  ",
+        ];
+        for path in paths {
+            manifest.validators[0].privkey_file = path.to_string();
+            manifest.revoked[0].privkey_file = path.to_string();
+            let parsed = Manifest::parse(&manifest.to_yaml(), "written manifest").unwrap();
+            assert_eq!(parsed.validators[0].privkey_file, path);
+            assert_eq!(parsed.revoked[0].privkey_file, path);
+        }
+    }
+
+    #[test]
+    fn orders_numbers_by_value() {
+        assert_eq!(
+            sorted(&[
+                "validator_10_sk.ssz",
+                "validator_2_sk.ssz",
+                "validator_1_sk.ssz"
+            ]),
+            [
+                "validator_1_sk.ssz",
+                "validator_2_sk.ssz",
+                "validator_10_sk.ssz"
+            ]
+        );
+        assert_eq!(compare_names("a9b", "a10a"), Ordering::Less);
+        assert_eq!(compare_names("a2b3", "a2b20"), Ordering::Less);
+    }
+
+    #[test]
+    fn breaks_ties_on_leading_zeros() {
+        // Equal values: fewer leading zeros first, and never Equal for
+        // different names
+        assert_eq!(compare_names("a1", "a01"), Ordering::Less);
+        assert_eq!(compare_names("a01", "a001"), Ordering::Less);
+        assert_eq!(compare_names("a007", "a8"), Ordering::Less);
+        assert_eq!(compare_names("a0", "a00"), Ordering::Less);
+        assert_eq!(compare_names("a01b", "a1c"), Ordering::Greater);
+    }
+
+    #[test]
+    fn orders_prefixes_first() {
+        assert_eq!(compare_names("key", "key_sk"), Ordering::Less);
+        assert_eq!(compare_names("key1", "key1a"), Ordering::Less);
+        assert_eq!(compare_names("", "a"), Ordering::Less);
+        assert_eq!(compare_names("abc", "abd"), Ordering::Less);
+        assert_eq!(compare_names("B", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn is_a_total_order() {
+        let names = [
+            "", "a", "a0", "a00", "a1", "a01", "a10", "a1b", "a01b", "b", "b2", "B", "_9", "9",
+        ];
+        for a in names {
+            assert_eq!(compare_names(a, a), Ordering::Equal);
+            for b in names {
+                assert_eq!(compare_names(a, b), compare_names(b, a).reverse());
+                if a != b {
+                    assert_ne!(compare_names(a, b), Ordering::Equal, "{:?} {:?}", a, b);
+                }
+                for c in names {
+                    if compare_names(a, b).is_lt() && compare_names(b, c).is_lt() {
+                        assert!(compare_names(a, c).is_lt(), "{:?} {:?} {:?}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! `manifest info`: a summary of a validator manifest and the key set it
+//! describes, with a check that the two agree.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::exit::{self, Failure};
+use crate::manifest::{self, normalize_pubkey, short_pubkey, Manifest, ManifestEntry};
+use crate::metadata::KeyMetadata;
+use crate::scheme;
+use crate::units::format_size;
+
+/// Files of one validator, resolved against the manifest's directory.
+struct ValidatorFiles {
+    /// Every key file that exists, as (file name, path).
+    keys: Vec<(String, PathBuf)>,
+    metadata: Option<KeyMetadata>,
+}
+
+/// Print a summary of the manifest `path` (or of the manifest in the key
+/// directory `path`) and fail if it is not internally consistent.
+pub fn info(path: &Path) -> io::Result<()> {
+    let file = if path.is_dir() {
+        manifest::manifest_path(path)
+    } else {
+        path.to_path_buf()
+    };
+    let dir = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let manifest = Manifest::read_file(&file)?;
+    let mut problems = Vec::new();
+
+    println!("📄 {}\n", file.display());
+    match manifest.manifest_version {
+        Some(version) => println!("  Manifest version:  {}", version),
+        None => println!("  Manifest version:  unversioned"),
+    }
+    if let Some(ceremony_id) = &manifest.ceremony_id {
+        println!("  Ceremony ID:       {}", ceremony_id);
+    }
+    if let Some(namespace) = &manifest.namespace {
+        println!("  Namespace:         {}", namespace);
+    }
+    println!(
+        "  Scheme:            {} ({}, {})",
+        manifest.key_scheme, manifest.hash_function, manifest.encoding
+    );
+    println!("  Lifetime:          {} epochs", manifest.lifetime);
+    println!(
+        "  Active epochs:     2^{} ({})",
+        manifest.log_num_active_epochs, manifest.num_active_epochs
+    );
+    if manifest.is_mixed() {
+        let mut schemes: BTreeMap<(&str, usize), usize> = BTreeMap::new();
+        for validator in &manifest.validators {
+            *schemes
+                .entry((
+                    manifest.scheme_of(validator),
+                    manifest.log_num_active_epochs_of(validator),
+                ))
+                .or_default() += 1;
+        }
+        println!("  Mixed key set:");
+        for ((scheme, log_num_active_epochs), count) in schemes {
+            println!(
+                "    {} validators of {}, 2^{} active epochs",
+                count, scheme, log_num_active_epochs
+            );
+        }
+    }
+    check_scheme(&manifest, &mut problems);
+
+    let indexed = manifest
+        .validators
+        .iter()
+        .filter(|v| v.index.is_some())
+        .count();
+    let rotating = manifest
+        .validators
+        .iter()
+        .filter(|v| v.rotating_to.is_some())
+        .count();
+    let derived = manifest
+        .validators
+        .iter()
+        .filter(|v| v.derivation_path.is_some())
+        .count();
+    println!(
+        "  Validators:        {} ({} indexed, {} distributed, {} derived, {} rotating)",
+        manifest.validators.len(),
+        indexed,
+        manifest.validators.len() - indexed,
+        derived,
+        rotating
+    );
+    println!("  Revoked:           {}", manifest.revoked.len());
+    check_entries(&manifest, &mut problems);
+
+    let mut windows: BTreeMap<(u64, u64), usize> = BTreeMap::new();
+    let mut without_metadata = 0;
+    let mut key_files = 0;
+    let mut covered = 0;
+    let mut paths: BTreeSet<PathBuf> = BTreeSet::new();
+    for validator in &manifest.validators {
+        let files = validator_files(&dir, &validator.privkey_file, &mut problems)?;
+        key_files += files.keys.len();
+        match &files.metadata {
+            Some(metadata) => {
+                *windows
+                    .entry((metadata.activation_epoch, metadata.end_epoch))
+                    .or_default() += 1;
+                covered += files
+                    .keys
+                    .iter()
+                    .filter(|(name, _)| metadata.checksums.contains_key(name))
+                    .count();
+                check_metadata(&manifest, validator, metadata, &mut problems);
+            }
+            None => without_metadata += 1,
+        }
+        paths.extend(files.keys.into_iter().map(|(_, path)| path));
+    }
+
+    println!("  Epoch windows:");
+    for ((activation, end), count) in &windows {
+        println!(
+            "    {}..{}  {} validator{}",
+            activation,
+            end,
+            count,
+            if *count == 1 { "" } else { "s" }
+        );
+    }
+    if without_metadata > 0 {
+        println!(
+            "    0..{}  {} without metadata (as generated)",
+            manifest.num_active_epochs, without_metadata
+        );
+    }
+
+    // Everything in the directory, plus secret keys kept elsewhere
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.insert(entry.path());
+        }
+    }
+    let mut total_size = 0;
+    for path in &paths {
+        total_size += fs::metadata(path)?.len();
+    }
+    println!(
+        "  Size on disk:      {} in {} files",
+        format_size(total_size),
+        paths.len()
+    );
+    println!(
+        "  Checksum coverage: {} of {} key files",
+        covered, key_files
+    );
+
+    if !problems.is_empty() {
+        println!();
+        for problem in &problems {
+            println!("  ❌ {}", problem);
+        }
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} is inconsistent ({} problems)",
+                file.display(),
+                problems.len()
+            ),
+        ));
+    }
+
+    println!("\n✅ Manifest is consistent");
+    Ok(())
+}
+
+/// Check the recorded scheme parameters against the registered scheme.
+fn check_scheme(manifest: &Manifest, problems: &mut Vec<String>) {
+    let scheme = match scheme::lookup(&manifest.key_scheme) {
+        Ok(scheme) => scheme,
+        Err(e) => {
+            problems.push(e.to_string());
+            return;
+        }
+    };
+    if manifest.hash_function != scheme.hash_function().name() {
+        problems.push(format!(
+            "hash function {} is not {}'s ({})",
+            manifest.hash_function,
+            scheme.name(),
+            scheme.hash_function().name()
+        ));
+    }
+    if let Err(e) =
+        scheme::check_fingerprint(scheme, manifest.scheme_fingerprint.as_deref(), "manifest")
+    {
+        problems.push(e.to_string());
+    }
+    if manifest.lifetime != scheme.lifetime() {
+        problems.push(format!(
+            "lifetime {} is not {}'s ({})",
+            manifest.lifetime,
+            scheme.name(),
+            scheme.lifetime()
+        ));
+    }
+    if 1u64.checked_shl(manifest.log_num_active_epochs as u32) != Some(manifest.num_active_epochs) {
+        problems.push(format!(
+            "num_active_epochs {} is not 2^{}",
+            manifest.num_active_epochs, manifest.log_num_active_epochs
+        ));
+    }
+    if manifest.num_active_epochs > manifest.lifetime {
+        problems.push(format!(
+            "num_active_epochs {} exceeds the lifetime {}",
+            manifest.num_active_epochs, manifest.lifetime
+        ));
+    }
+}
+
+/// Check the validator and tombstone entries against each other.
+fn check_entries(manifest: &Manifest, problems: &mut Vec<String>) {
+    if manifest.num_validators != manifest.validators.len() {
+        problems.push(format!(
+            "num_validators is {}, but {} validators are listed",
+            manifest.num_validators,
+            manifest.validators.len()
+        ));
+    }
+
+    let mut pubkeys = BTreeSet::new();
+    let mut indices = BTreeSet::new();
+    let mut files = BTreeSet::new();
+    for validator in &manifest.validators {
+        if !pubkeys.insert(normalize_pubkey(&validator.pubkey_hex)) {
+            problems.push(format!(
+                "{} is listed more than once",
+                short_pubkey(&validator.pubkey_hex)
+            ));
+        }
+        if let Some(index) = validator.index {
+            if !indices.insert(index) {
+                problems.push(format!("index {} is used more than once", index));
+            }
+        }
+        if !files.insert(&validator.privkey_file) {
+            problems.push(format!(
+                "{} is the secret key of more than one validator",
+                validator.privkey_file
+            ));
+        }
+    }
+    for validator in &manifest.validators {
+        if let Some(rotating_to) = &validator.rotating_to {
+            if !pubkeys.contains(&normalize_pubkey(rotating_to)) {
+                problems.push(format!(
+                    "{} is rotating to {}, which is not listed",
+                    short_pubkey(&validator.pubkey_hex),
+                    short_pubkey(rotating_to)
+                ));
+            }
+        }
+    }
+    for tombstone in &manifest.revoked {
+        if pubkeys.contains(&normalize_pubkey(&tombstone.pubkey_hex)) {
+            problems.push(format!(
+                "{} is revoked but still listed as a validator",
+                short_pubkey(&tombstone.pubkey_hex)
+            ));
+        }
+    }
+}
+
+/// Check a validator's metadata against its manifest entry and the key set.
+fn check_metadata(
+    manifest: &Manifest,
+    validator: &ManifestEntry,
+    metadata: &KeyMetadata,
+    problems: &mut Vec<String>,
+) {
+    let pubkey_hex = &validator.pubkey_hex;
+    // A validator of another scheme in a mixed key set is checked against
+    // that scheme's parameters
+    let (fingerprint, lifetime) = match &validator.key_scheme {
+        Some(name) => match scheme::lookup(name) {
+            Ok(scheme) => (Some(scheme.fingerprint()), scheme.lifetime()),
+            Err(e) => {
+                problems.push(format!("{}: {}", short_pubkey(pubkey_hex), e));
+                return;
+            }
+        },
+        None => (manifest.scheme_fingerprint.clone(), manifest.lifetime),
+    };
+    if normalize_pubkey(&metadata.pubkey_hex) != normalize_pubkey(pubkey_hex) {
+        problems.push(format!(
+            "metadata of {} records pubkey {}",
+            short_pubkey(pubkey_hex),
+            short_pubkey(&metadata.pubkey_hex)
+        ));
+    }
+    if let (Some(recorded), Some(expected)) = (&metadata.ceremony_id, &manifest.ceremony_id) {
+        if recorded != expected {
+            problems.push(format!(
+                "metadata of {} records ceremony {}, not {}",
+                short_pubkey(pubkey_hex),
+                recorded,
+                expected
+            ));
+        }
+    }
+    // A key of another tenant, or of none, in a namespaced key set
+    if metadata.namespace != manifest.namespace {
+        problems.push(format!(
+            "metadata of {} records namespace {}, not {}",
+            short_pubkey(pubkey_hex),
+            metadata.namespace.as_deref().unwrap_or("none"),
+            manifest.namespace.as_deref().unwrap_or("none")
+        ));
+    }
+    if let (Some(recorded), Some(expected)) = (&metadata.scheme_fingerprint, &fingerprint) {
+        if recorded != expected {
+            problems.push(format!(
+                "metadata of {} records scheme parameters {}, not {}",
+                short_pubkey(pubkey_hex),
+                recorded,
+                expected
+            ));
+        }
+    }
+    if metadata.key_scheme != manifest.scheme_of(validator) {
+        problems.push(format!(
+            "metadata of {} records scheme {}",
+            short_pubkey(pubkey_hex),
+            metadata.key_scheme
+        ));
+    }
+    if metadata.activation_epoch >= metadata.end_epoch || metadata.end_epoch > lifetime {
+        problems.push(format!(
+            "epoch window {}..{} of {} is empty or outside the lifetime",
+            metadata.activation_epoch,
+            metadata.end_epoch,
+            short_pubkey(pubkey_hex)
+        ));
+    }
+}
+
+/// Resolve the files of the validator whose secret key is `privkey_file`,
+/// recording the ones that are missing.
+fn validator_files(
+    dir: &Path,
+    privkey_file: &str,
+    problems: &mut Vec<String>,
+) -> io::Result<ValidatorFiles> {
+    let name = manifest::file_name(privkey_file);
+    let prefix = name.strip_suffix("_sk.ssz").unwrap_or(name);
+    let secret = dir.join(privkey_file);
+    let secret_dir = secret.parent().unwrap_or(dir);
+
+    let mut keys = Vec::new();
+    for (path, required) in [
+        (secret.clone(), true),
+        (dir.join(format!("{}_pk.ssz", prefix)), true),
+        (secret_dir.join(format!("{}_sk.json", prefix)), false),
+        (dir.join(format!("{}_pk.json", prefix)), false),
+    ] {
+        if path.is_file() {
+            let file_name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            keys.push((file_name, path));
+        } else if required {
+            problems.push(format!("{} is missing", path.display()));
+        }
+    }
+
+    let metadata_path = dir.join(format!("{}.meta.json", prefix));
+    let metadata = if metadata_path.is_file() {
+        let metadata = serde_json::from_slice(&fs::read(&metadata_path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", metadata_path.display(), e),
+            )
+        })?;
+        Some(metadata)
+    } else {
+        None
+    };
+    Ok(ValidatorFiles { keys, metadata })
+}
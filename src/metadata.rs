@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "verify-only"))]
+use crate::keystore::KeyStore;
+use crate::manifest;
+
+/// Per-validator metadata written next to each key pair as `<name>.meta.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyMetadata {
+    pub pubkey_hex: String,
+    pub fingerprint: String,
+    pub key_scheme: String,
+    /// Missing from metadata written before hash functions were recorded.
+    #[serde(default)]
+    pub hash_function: String,
+    /// Missing from metadata written before parameters were fingerprinted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme_fingerprint: Option<String>,
+    /// First active epoch (inclusive).
+    pub activation_epoch: u64,
+    /// End of the active range (exclusive).
+    pub end_epoch: u64,
+    /// Derivation path from the master seed, for deterministic keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    /// ID of the `generate` run that created the key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ceremony_id: Option<String>,
+    /// Tenant the key was generated for (`generate --namespace`), also the
+    /// prefix of its file names.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// A canary from `generate --with-canary`: `sign` refuses it and raises
+    /// an alert.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canary: bool,
+    /// SHA-256 of every file written for this validator, keyed by file name.
+    pub checksums: BTreeMap<String, String>,
+    /// Creation time in seconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl KeyMetadata {
+    pub fn write(&self, store: &dyn KeyStore, key_prefix: &str) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        store.store(&format!("{}.meta.json", key_prefix), &json)
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Active epoch range of the key `privkey_file` of `dir`: from its metadata
+/// if it was written, else `num_active_epochs` from epoch 0, the range
+/// `generate` gives keys.
+pub fn activation_window(
+    dir: &Path,
+    privkey_file: &str,
+    num_active_epochs: u64,
+) -> io::Result<(u64, u64)> {
+    let name = manifest::file_name(privkey_file);
+    let prefix = name.strip_suffix("_sk.ssz").unwrap_or(name);
+    let path = dir.join(format!("{}.meta.json", prefix));
+    if !path.exists() {
+        return Ok((0, num_active_epochs));
+    }
+    let metadata: KeyMetadata = serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        )
+    })?;
+    Ok((metadata.activation_epoch, metadata.end_epoch))
+}
@@ -0,0 +1,186 @@
+//! `migrate-scheme`: move the validators of a deprecated scheme to its
+//! replacement.
+//!
+//! Every validator of the scheme gets a replacement key of the new scheme
+//! from `rotate start`, and what is left to do, registering each
+//! replacement and finalizing its rotation, is written to [`PLAN_FILE`].
+//! The old keys stay in service until their rotation is finalized.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::exit::{self, Failure};
+use crate::manifest::{short_pubkey, Manifest};
+use crate::metadata::unix_now;
+use crate::rotate;
+use crate::scheme::{self, SchemeHandle};
+
+/// Rotation plan written to the key directory.
+pub const PLAN_FILE: &str = "migration-plan.json";
+
+#[derive(Serialize)]
+struct MigrationPlan {
+    created_at: u64,
+    dir: String,
+    steps: Vec<MigrationStep>,
+}
+
+#[derive(Serialize)]
+struct MigrationStep {
+    old_pubkey_hex: String,
+    old_scheme: String,
+    new_pubkey_hex: String,
+    new_scheme: String,
+    /// Submit this to register the replacement.
+    registration_file: String,
+    /// Run once the replacement is registered.
+    finalize: String,
+}
+
+/// A validator to move to another scheme.
+struct Move {
+    pubkey_hex: String,
+    from: String,
+    to: &'static dyn SchemeHandle,
+}
+
+/// Generate replacements for the validators of `dir` whose scheme is
+/// deprecated, or with `from`, of that scheme, in scheme `to` (by default
+/// the deprecated scheme's replacement), and write the rotation plan. With
+/// `dry_run`, only print what would be done.
+pub fn migrate_scheme(
+    dir: &Path,
+    from: Option<&str>,
+    to: Option<&str>,
+    dry_run: bool,
+) -> io::Result<()> {
+    let manifest = Manifest::read(dir)?;
+    let to = to.map(scheme::lookup).transpose()?;
+    if let Some(to) = to {
+        if scheme::deprecation(to.name()).is_some() {
+            return Err(exit::error(
+                Failure::InvalidArgs,
+                format!("{} is deprecated itself; pick another --to", to.name()),
+            ));
+        }
+    }
+
+    let mut moves = Vec::new();
+    let mut rotating = 0;
+    for entry in manifest.validators.iter().filter(|entry| !entry.canary) {
+        let current = manifest.scheme_of(entry);
+        let target = match (from, to, scheme::deprecation(current)) {
+            (Some(from), Some(to), _) if from == current => to,
+            (None, Some(to), Some(_)) => to,
+            (None, None, Some(deprecation)) => scheme::lookup(deprecation.replacement)?,
+            _ => continue,
+        };
+        if target.name() == current {
+            return Err(exit::error(
+                Failure::InvalidArgs,
+                format!(
+                    "{} already uses {}; nothing to migrate to",
+                    short_pubkey(&entry.pubkey_hex),
+                    current
+                ),
+            ));
+        }
+        // Already on its way to a replacement
+        if entry.rotating_to.is_some() {
+            rotating += 1;
+            continue;
+        }
+        moves.push(Move {
+            pubkey_hex: entry.pubkey_hex.clone(),
+            from: current.to_string(),
+            to: target,
+        });
+    }
+
+    if rotating > 0 {
+        println!(
+            "⏭️  {} validators are already being rotated; finalize or check those first",
+            rotating
+        );
+    }
+    if moves.is_empty() {
+        println!("✅ No validator of {} needs migrating", dir.display());
+        return Ok(());
+    }
+
+    println!(
+        "🧭 Migrating {} validators of {}:\n",
+        moves.len(),
+        dir.display()
+    );
+    for planned in &moves {
+        println!(
+            "  {} {} → {}",
+            short_pubkey(&planned.pubkey_hex),
+            planned.from,
+            planned.to.name()
+        );
+    }
+    if dry_run {
+        println!("\n🔎 Dry run: rerun without --dry-run to generate the replacement keys");
+        return Ok(());
+    }
+    println!();
+
+    let mut steps = Vec::with_capacity(moves.len());
+    let mut failure = None;
+    for planned in &moves {
+        match rotate::start(dir, &planned.pubkey_hex, Some(planned.to)) {
+            Ok(replacement) => steps.push(MigrationStep {
+                old_pubkey_hex: planned.pubkey_hex.clone(),
+                old_scheme: planned.from.clone(),
+                new_pubkey_hex: replacement.pubkey_hex,
+                new_scheme: planned.to.name().to_string(),
+                registration_file: replacement.registration_file,
+                finalize: format!(
+                    "hashsig rotate finalize {} --dir {}",
+                    planned.pubkey_hex,
+                    dir.display()
+                ),
+            }),
+            Err(e) => {
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+
+    // Written after a failure too, so the replacements made are not lost
+    let plan = MigrationPlan {
+        created_at: unix_now(),
+        dir: dir.display().to_string(),
+        steps,
+    };
+    let json = serde_json::to_vec_pretty(&plan).map_err(io::Error::other)?;
+    fs::write(dir.join(PLAN_FILE), json)?;
+    println!(
+        "\n📋 Rotation plan written to {}",
+        dir.join(PLAN_FILE).display()
+    );
+
+    if let Some(e) = failure {
+        return Err(exit::error(
+            Failure::Partial,
+            format!(
+                "Migrated {} of {} validators before failing: {}",
+                plan.steps.len(),
+                moves.len(),
+                e
+            ),
+        ));
+    }
+    println!(
+        "\n✅ {} replacements generated. Register each with its registration file, then finalize its rotation as listed in {}",
+        plan.steps.len(),
+        PLAN_FILE
+    );
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! `sign --offline-bundle` and `verify-bundle`: a signature packed with
+//! everything needed to verify it, for third parties without the key
+//! directory or any other context.
+//!
+//! A bundle is a tar archive, so it can also be unpacked and checked by
+//! hand:
+//!
+//! ```text
+//! bundle.json     what was signed, by which key and scheme, and the SHA-256 of every other file
+//! README.txt      how to verify it, with and without hashsig
+//! message         the signed file, unless the message was given as hex
+//! signature.ssz
+//! pubkey.ssz
+//! ```
+//!
+//! `verify-bundle` checks every file against `bundle.json`, the scheme
+//! parameters against this build and the message against its pre-hash, and
+//! then the signature. A bundle only proves that its public key made the
+//! signature, so the key is printed, and `--pubkey` checks it against one
+//! the verifier trusts.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::checksum;
+use crate::exit::{self, Failure};
+use crate::keys;
+use crate::manifest::{normalize_pubkey, short_pubkey};
+use crate::metadata::unix_now;
+use crate::scheme::{self, SchemeHandle};
+use crate::sign::{Message, PreHash};
+use crate::tar;
+
+const FORMAT: &str = "hashsig-verification-bundle";
+const VERSION: u32 = 1;
+
+const BUNDLE_FILE: &str = "bundle.json";
+const README_FILE: &str = "README.txt";
+const MESSAGE_FILE: &str = "message";
+const SIGNATURE_FILE: &str = "signature.ssz";
+const PUBKEY_FILE: &str = "pubkey.ssz";
+
+/// `bundle.json`.
+#[derive(Serialize, Deserialize)]
+struct BundleManifest {
+    format: String,
+    version: u32,
+    tool_version: String,
+    created_at: u64,
+    key_scheme: String,
+    scheme_fingerprint: String,
+    epoch: u32,
+    pubkey_hex: String,
+    /// The signed 32-byte message, hex-encoded.
+    message: String,
+    /// Pre-hash of the bundled message file that `message` is, if it was
+    /// signed from a file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prehash: Option<String>,
+    /// SHA-256 of every other file of the bundle, by name.
+    files: BTreeMap<String, String>,
+}
+
+/// A signature made by `sign`, with what it was made over.
+pub struct SignedMessage<'a> {
+    pub scheme: &'static dyn SchemeHandle,
+    pub pubkey: &'a [u8],
+    pub epoch: u32,
+    pub message: &'a Message,
+    /// File `message` is the pre-hash of, if it was signed from one.
+    pub message_file: Option<&'a Path>,
+    pub signature: &'a [u8],
+}
+
+/// Write the verification bundle of `signed` to `output`.
+pub fn write(output: &Path, signed: &SignedMessage) -> io::Result<()> {
+    let mut files: Vec<(&str, Vec<u8>)> = Vec::new();
+    if let (Some(path), Some(prehash)) = (signed.message_file, signed.message.prehash) {
+        let contents = fs::read(path)?;
+        let (digest, _) = prehash.digest(&mut &contents[..])?;
+        if digest != signed.message.bytes {
+            return Err(io::Error::other(format!(
+                "{} changed while it was signed; not bundling it",
+                path.display()
+            )));
+        }
+        files.push((MESSAGE_FILE, contents));
+    }
+    files.push((SIGNATURE_FILE, signed.signature.to_vec()));
+    files.push((PUBKEY_FILE, signed.pubkey.to_vec()));
+
+    let manifest = BundleManifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: unix_now(),
+        key_scheme: signed.scheme.name().to_string(),
+        scheme_fingerprint: signed.scheme.fingerprint(),
+        epoch: signed.epoch,
+        pubkey_hex: format!("0x{}", hex::encode(signed.pubkey)),
+        message: hex::encode(signed.message.bytes),
+        prehash: signed
+            .message
+            .prehash
+            .filter(|_| signed.message_file.is_some())
+            .map(|prehash| prehash.name().to_string()),
+        files: files
+            .iter()
+            .map(|(name, contents)| (name.to_string(), checksum::sha256_hex(contents)))
+            .collect(),
+    };
+    let json = serde_json::to_vec_pretty(&manifest).map_err(io::Error::other)?;
+    let readme = readme(&manifest);
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    tar::write_file(&mut writer, BUNDLE_FILE, &json, tar::MODE_PUBLIC)?;
+    tar::write_file(
+        &mut writer,
+        README_FILE,
+        readme.as_bytes(),
+        tar::MODE_PUBLIC,
+    )?;
+    for (name, contents) in &files {
+        tar::write_file(&mut writer, name, contents, tar::MODE_PUBLIC)?;
+    }
+    tar::write_end(&mut writer)?;
+    writer.flush()
+}
+
+fn readme(manifest: &BundleManifest) -> String {
+    let signed = match &manifest.prehash {
+        Some(prehash) => format!(
+            "the {} of the file `{}`, {}",
+            prehash, MESSAGE_FILE, manifest.message
+        ),
+        None => format!("the message {}", manifest.message),
+    };
+    format!(
+        "This is a hashsig verification bundle: a {scheme} signature made at
+epoch {epoch} by the public key
+
+    {pubkey}
+
+over {signed}.
+
+To verify it with hashsig:
+
+    hashsig verify-bundle <this file> --pubkey <the key you expect>
+
+To verify it by hand, unpack it with `tar -xf`, check every file against
+its SHA-256 in {bundle}, recompute the signed message if there is a
+`{message}` file, and verify {signature} over it against {pk} at epoch
+{epoch} with {scheme} (parameter fingerprint {fingerprint}).
+
+The bundle only proves that this public key made the signature. Check the
+key against one you obtained from a source you trust.
+",
+        scheme = manifest.key_scheme,
+        epoch = manifest.epoch,
+        pubkey = manifest.pubkey_hex,
+        signed = signed,
+        bundle = BUNDLE_FILE,
+        message = MESSAGE_FILE,
+        signature = SIGNATURE_FILE,
+        pk = PUBKEY_FILE,
+        fingerprint = manifest.scheme_fingerprint,
+    )
+}
+
+/// Verify the verification bundle `path`. With `pubkey`, the bundle's
+/// public key must be that one.
+pub fn verify_bundle(path: &Path, pubkey: Option<&str>) -> io::Result<()> {
+    let source = path.display().to_string();
+    let bytes = fs::read(path)?;
+    let files: BTreeMap<String, &[u8]> = tar::read_files(&bytes, &source)?.into_iter().collect();
+    let file = |name: &str| {
+        files.get(name).copied().ok_or_else(|| {
+            exit::error(
+                Failure::CorruptData,
+                format!("{} holds no {}", source, name),
+            )
+        })
+    };
+
+    let manifest: BundleManifest = serde_json::from_slice(file(BUNDLE_FILE)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {} in {}: {}", BUNDLE_FILE, source, e),
+        )
+    })?;
+    if manifest.format != FORMAT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a verification bundle", source),
+        ));
+    }
+    if manifest.version > VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has bundle version {}, but this hashsig only reads up to version {}",
+                source, manifest.version, VERSION
+            ),
+        ));
+    }
+    for (name, sha256) in &manifest.files {
+        if checksum::sha256_hex(file(name)?) != *sha256 {
+            return Err(exit::error(
+                Failure::CorruptData,
+                format!("{} in {} does not match its checksum", name, source),
+            ));
+        }
+    }
+
+    let scheme = scheme::lookup(&manifest.key_scheme)?;
+    scheme::check_fingerprint(scheme, Some(&manifest.scheme_fingerprint), &source)?;
+    let pk = file(PUBKEY_FILE)?;
+    if hex::encode(pk) != normalize_pubkey(&manifest.pubkey_hex) {
+        return Err(exit::error(
+            Failure::CorruptData,
+            format!(
+                "{} in {} is not the public key {} names",
+                PUBKEY_FILE, source, BUNDLE_FILE
+            ),
+        ));
+    }
+    if let Some(expected) = pubkey {
+        if normalize_pubkey(expected) != hex::encode(pk) {
+            return Err(exit::error(
+                Failure::VerificationFailed,
+                format!(
+                    "❌ {} is signed by {}, not by {}",
+                    source,
+                    short_pubkey(&manifest.pubkey_hex),
+                    short_pubkey(expected)
+                ),
+            ));
+        }
+    }
+
+    let message = keys::parse_message_hex(&manifest.message)?;
+    if let Some(name) = &manifest.prehash {
+        let prehash = PreHash::from_str(name, false).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid prehash in {}: {}", source, e),
+            )
+        })?;
+        let (digest, _) = prehash.digest(&mut &file(MESSAGE_FILE)?[..])?;
+        if digest != message {
+            return Err(exit::error(
+                Failure::VerificationFailed,
+                format!(
+                    "❌ The {} of the message in {} is not the signed message",
+                    name, source
+                ),
+            ));
+        }
+    }
+    if !scheme.verify(pk, manifest.epoch, &message, file(SIGNATURE_FILE)?)? {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("❌ The signature in {} is INVALID", source),
+        ));
+    }
+
+    println!("✅ {} holds a valid {} signature", source, scheme.name());
+    println!("   Public key: {}", manifest.pubkey_hex);
+    println!("   Epoch:      {}", manifest.epoch);
+    match &manifest.prehash {
+        Some(prehash) => println!(
+            "   Message:    {} bytes, {} {}",
+            file(MESSAGE_FILE)?.len(),
+            prehash,
+            manifest.message
+        ),
+        None => println!("   Message:    {}", manifest.message),
+    }
+    if pubkey.is_none() {
+        println!("⚠️  Check the public key against one you trust, or pass it as --pubkey");
+    }
+    Ok(())
+}
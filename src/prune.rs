@@ -0,0 +1,244 @@
+//! `prune`: drop the one-time keys of epochs that have passed.
+//!
+//! A secret key holds its one-time keys for a window of prepared epochs
+//! spanning two bottom trees. Pruning moves that window forward one bottom
+//! tree at a time, for as long as the tree dropped only covers epochs
+//! before `--before-epoch`, and rewrites the file with the old copy shredded.
+//! The key then can no longer sign at the pruned epochs, even by mistake.
+//!
+//! This is as far as the scheme structure permits: the window keeps its
+//! size, so files do not shrink, and the key's PRF seed, which every
+//! one-time key derives from, stays in the file.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroizing;
+
+use crate::checksum;
+use crate::keystore::{self, KeyStore, ManifestKeyStore};
+use crate::layout::OBJECTS_DIR;
+use crate::metadata::KeyMetadata;
+use crate::scheme::{self, KeyKind};
+
+/// Prune the secret key file `path`, or every secret key of the key
+/// directory `path`, to the epochs from `before_epoch` on.
+pub fn prune(path: &Path, before_epoch: u64, scheme_name: Option<&str>) -> io::Result<()> {
+    let content_addressed = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is content-addressed, and pruning would change its objects; `copy` it to a flat directory first",
+                path.display()
+            ),
+        )
+    };
+    // Each secret key with the directory of its public key and metadata
+    let keys: Vec<(PathBuf, PathBuf)> = if path.is_dir() {
+        let store = ManifestKeyStore::open(path)?;
+        let mut keys = Vec::new();
+        for name in store
+            .list()?
+            .iter()
+            .filter(|name| name.ends_with("_sk.ssz"))
+        {
+            if store.object_hash(name).is_some() {
+                return Err(content_addressed());
+            }
+            if let Some(sk) = store.file_path(name) {
+                keys.push((sk, path.to_path_buf()));
+            }
+        }
+        keys
+    } else {
+        if is_object(path)? {
+            return Err(content_addressed());
+        }
+        let public_dir = path.parent().unwrap_or(Path::new("."));
+        vec![(path.to_path_buf(), public_dir.to_path_buf())]
+    };
+    if keys.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No secret keys in {}", path.display()),
+        ));
+    }
+
+    println!(
+        "✂️  Pruning {} secret keys to epochs from {} on\n",
+        keys.len(),
+        before_epoch
+    );
+    let mut pruned = 0;
+    for (sk, public_dir) in &keys {
+        if prune_key(sk, public_dir, before_epoch, scheme_name)? {
+            pruned += 1;
+        }
+    }
+    println!("\n✅ Pruned {} of {} secret keys", pruned, keys.len());
+    Ok(())
+}
+
+/// Whether the file `path` is an object of a content-addressed key
+/// directory, `objects/<sha256>/<name>`.
+fn is_object(path: &Path) -> io::Result<bool> {
+    let path = fs::canonicalize(path)?;
+    Ok(path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .is_some_and(|name| name == OBJECTS_DIR))
+}
+
+/// Prune one key, whose public key and metadata are in `public_dir`,
+/// returning whether anything was dropped.
+fn prune_key(
+    sk: &Path,
+    public_dir: &Path,
+    before_epoch: u64,
+    scheme_name: Option<&str>,
+) -> io::Result<bool> {
+    let scheme = scheme::for_key(scheme_name, sk)?;
+    let name = file_name(sk);
+    let bytes = Zeroizing::new(fs::read(sk)?);
+    let (_, before) = scheme.secret_key_intervals(&bytes)?;
+    let advanced = Zeroizing::new(scheme.advance_secret_key(&bytes, before_epoch)?);
+    let (_, after) = scheme.secret_key_intervals(&advanced)?;
+    if after == before {
+        println!(
+            "  ⏭️  {}: prepared epochs {}..{} already start at the earliest tree still needed",
+            name, before.start, before.end
+        );
+        return Ok(false);
+    }
+
+    // The pruned key must still belong to its public key
+    let prefix = name.strip_suffix("_sk.ssz").unwrap_or(&name).to_string();
+    let pk_path = public_dir.join(format!("{}_pk.ssz", prefix));
+    if pk_path.is_file() && !scheme.keypair_matches(&fs::read(&pk_path)?, &advanced)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The pruned {} no longer matches {}; the original is left untouched",
+                name,
+                pk_path.display()
+            ),
+        ));
+    }
+
+    let dir = sk.parent().unwrap_or(Path::new("."));
+    let mut rewritten = vec![(name.clone(), checksum::sha256_hex(&advanced))];
+    replace(dir, &name, &advanced)?;
+    // The legacy JSON copy holds the same one-time keys. Compact, as
+    // `generate` writes it by default
+    let json_name = format!("{}_sk.json", prefix);
+    if dir.join(&json_name).is_file() {
+        let json = scheme
+            .ssz_to_json(KeyKind::Secret, &advanced)
+            .and_then(|json| serde_json::to_vec(&json).map_err(|e| e.to_string()))
+            .map_err(io::Error::other)?;
+        let json = Zeroizing::new(json);
+        rewritten.push((json_name.clone(), checksum::sha256_hex(&json)));
+        replace(dir, &json_name, &json)?;
+    }
+    update_metadata(
+        &public_dir.join(format!("{}.meta.json", prefix)),
+        &rewritten,
+    )?;
+
+    println!(
+        "  ✂️  {}: prepared epochs {}..{} -> {}..{}",
+        name, before.start, before.end, after.start, after.end
+    );
+    Ok(true)
+}
+
+/// Replace `name` in `dir` with `contents`, shredding the old copy. The new
+/// copy is synced and renamed over the old one before the old one is
+/// overwritten, so at every point one of them is intact under `name`.
+fn replace(dir: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let path = dir.join(name);
+    let pruned = dir.join(format!("{}.pruned", name));
+    let mut file = File::create(&pruned)?;
+    file.set_permissions(fs::metadata(&path)?.permissions())?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    // Held open, so the old copy can still be overwritten once it is replaced
+    let old = OpenOptions::new().write(true).open(&path)?;
+    fs::rename(&pruned, &path)?;
+    File::open(dir)?.sync_all()?;
+    keystore::overwrite(old)
+}
+
+/// Record the new checksums of `rewritten` files in the metadata at `path`,
+/// if there is any.
+fn update_metadata(path: &Path, rewritten: &[(String, String)]) -> io::Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let mut metadata: KeyMetadata = serde_json::from_slice(&fs::read(path)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        )
+    })?;
+    for (name, checksum) in rewritten {
+        if let Some(recorded) = metadata.checksums.get_mut(name) {
+            recorded.clone_from(checksum);
+        }
+    }
+    let json = serde_json::to_vec_pretty(&metadata).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn replace_leaves_only_the_new_copy() {
+        let dir = std::env::temp_dir().join(format!("hashsig-prune-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("validator_0_sk.ssz");
+        fs::write(&path, b"old secret key").unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        // The old copy, as a crash would leave it reachable
+        let old = File::open(&path).unwrap();
+
+        replace(&dir, "validator_0_sk.ssz", b"new key").unwrap();
+        let contents = fs::read(&path).unwrap();
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        #[cfg(unix)]
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        let mut old_contents = Vec::new();
+        (&old).read_to_end(&mut old_contents).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contents, b"new key");
+        assert_eq!(names, ["validator_0_sk.ssz"]);
+        #[cfg(unix)]
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(old_contents.len(), b"old secret key".len());
+        assert_ne!(old_contents, b"old secret key");
+    }
+}
@@ -0,0 +1,357 @@
+//! `export`: the validator set for genesis tooling.
+//!
+//! `--format ssz-registry` writes it as a single SSZ object:
+//!
+//! ```text
+//! RegistryEntry = Container {
+//!     pubkey: ByteList[MAX_PUBKEY_BYTES],
+//!     activation_epoch: uint64,
+//!     exit_epoch: uint64,              # FAR_FUTURE_EPOCH placeholder
+//! }
+//! ValidatorRegistry = Container {
+//!     validators: List[RegistryEntry, VALIDATOR_REGISTRY_LIMIT],
+//! }
+//! ```
+//!
+//! Encoding and hash tree root follow the SSZ specification, so genesis
+//! tooling can check the artifact against the printed root.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+
+use crate::codegen::{self, Lang};
+use crate::exit::{self, Failure};
+use crate::manifest::{normalize_pubkey, Manifest, SortOrder};
+use crate::metadata;
+
+/// Maximum SSZ-encoded public key length of any registered scheme.
+const MAX_PUBKEY_BYTES: usize = 1024;
+
+/// Maximum number of validators, as in the beacon chain.
+const VALIDATOR_REGISTRY_LIMIT: u64 = 1 << 40;
+
+/// Exit epoch of validators that have not exited.
+const FAR_FUTURE_EPOCH: u64 = u64::MAX;
+
+const BYTES_PER_CHUNK: usize = 32;
+const BYTES_PER_OFFSET: usize = 4;
+
+type Chunk = [u8; BYTES_PER_CHUNK];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ExportKind {
+    /// One SSZ `ValidatorRegistry` container of every active validator
+    SszRegistry,
+    /// A source file of public key constants in the language `--lang` picks
+    Code,
+}
+
+struct RegistryEntry {
+    pubkey: Vec<u8>,
+    activation_epoch: u64,
+    exit_epoch: u64,
+}
+
+/// Export the validators in the manifest of `dir`, in `order`, to `output`,
+/// as source code in `lang` for [`ExportKind::Code`].
+pub fn export(
+    dir: &Path,
+    kind: ExportKind,
+    output: &Path,
+    order: SortOrder,
+    lang: Option<Lang>,
+) -> io::Result<()> {
+    match (kind, lang) {
+        (ExportKind::SszRegistry, _) => export_ssz_registry(dir, output, order),
+        (ExportKind::Code, Some(lang)) => codegen::export_code(dir, output, order, lang),
+        (ExportKind::Code, None) => Err(exit::error(
+            Failure::InvalidArgs,
+            "--format code needs --lang",
+        )),
+    }
+}
+
+/// Write the SSZ registry, canaries aside, and print its hash tree root.
+fn export_ssz_registry(dir: &Path, output: &Path, order: SortOrder) -> io::Result<()> {
+    let mut manifest = Manifest::read(dir)?;
+    // Canaries are never registered
+    manifest.validators.retain(|entry| !entry.canary);
+    manifest.sort_validators(order);
+    let mut entries = Vec::with_capacity(manifest.validators.len());
+    for validator in &manifest.validators {
+        let pubkey = hex::decode(normalize_pubkey(&validator.pubkey_hex)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid pubkey_hex for {}: {}", validator.privkey_file, e),
+            )
+        })?;
+        if pubkey.len() > MAX_PUBKEY_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Public key of {} is {} bytes, more than the registry allows ({})",
+                    validator.privkey_file,
+                    pubkey.len(),
+                    MAX_PUBKEY_BYTES
+                ),
+            ));
+        }
+        entries.push(RegistryEntry {
+            pubkey,
+            activation_epoch: metadata::activation_window(
+                dir,
+                &validator.privkey_file,
+                1 << manifest.log_num_active_epochs_of(validator),
+            )?
+            .0,
+            exit_epoch: FAR_FUTURE_EPOCH,
+        });
+    }
+
+    fs::write(output, encode_registry(&entries))?;
+    println!(
+        "📦 Exported {} validators as an SSZ registry: {}",
+        entries.len(),
+        output.display()
+    );
+    println!(
+        "🌳 Hash tree root: 0x{}",
+        hex::encode(registry_root(&entries))
+    );
+    Ok(())
+}
+
+fn offset(value: usize) -> [u8; BYTES_PER_OFFSET] {
+    (value as u32).to_le_bytes()
+}
+
+fn encode_entry(entry: &RegistryEntry) -> Vec<u8> {
+    // Fixed part: pubkey offset, activation_epoch, exit_epoch
+    let fixed_len = BYTES_PER_OFFSET + 8 + 8;
+    let mut bytes = Vec::with_capacity(fixed_len + entry.pubkey.len());
+    bytes.extend_from_slice(&offset(fixed_len));
+    bytes.extend_from_slice(&entry.activation_epoch.to_le_bytes());
+    bytes.extend_from_slice(&entry.exit_epoch.to_le_bytes());
+    bytes.extend_from_slice(&entry.pubkey);
+    bytes
+}
+
+fn encode_registry(entries: &[RegistryEntry]) -> Vec<u8> {
+    let encoded: Vec<Vec<u8>> = entries.iter().map(encode_entry).collect();
+
+    // The container's only field is variable-size: its offset, then the list
+    let mut bytes = offset(BYTES_PER_OFFSET).to_vec();
+    let mut next = encoded.len() * BYTES_PER_OFFSET;
+    for entry in &encoded {
+        bytes.extend_from_slice(&offset(next));
+        next += entry.len();
+    }
+    for entry in encoded {
+        bytes.extend(entry);
+    }
+    bytes
+}
+
+fn hash_pair(left: &Chunk, right: &Chunk) -> Chunk {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Merkle root of `chunks` padded with zero chunks to the next power of two
+/// of `limit`.
+fn merkleize(mut chunks: Vec<Chunk>, limit: u64) -> Chunk {
+    let depth = limit.max(1).next_power_of_two().trailing_zeros();
+    let mut zero = [0u8; BYTES_PER_CHUNK];
+    for _ in 0..depth {
+        if chunks.len() % 2 == 1 {
+            chunks.push(zero);
+        }
+        chunks = chunks
+            .chunks_exact(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+        zero = hash_pair(&zero, &zero);
+    }
+    chunks.first().copied().unwrap_or(zero)
+}
+
+fn mix_in_length(root: Chunk, length: usize) -> Chunk {
+    let mut length_chunk = [0u8; BYTES_PER_CHUNK];
+    length_chunk[..8].copy_from_slice(&(length as u64).to_le_bytes());
+    hash_pair(&root, &length_chunk)
+}
+
+fn uint64_root(value: u64) -> Chunk {
+    let mut chunk = [0u8; BYTES_PER_CHUNK];
+    chunk[..8].copy_from_slice(&value.to_le_bytes());
+    chunk
+}
+
+fn byte_list_root(bytes: &[u8], max_len: usize) -> Chunk {
+    let chunks = bytes
+        .chunks(BYTES_PER_CHUNK)
+        .map(|part| {
+            let mut chunk = [0u8; BYTES_PER_CHUNK];
+            chunk[..part.len()].copy_from_slice(part);
+            chunk
+        })
+        .collect();
+    let chunk_limit = max_len.div_ceil(BYTES_PER_CHUNK) as u64;
+    mix_in_length(merkleize(chunks, chunk_limit), bytes.len())
+}
+
+fn entry_root(entry: &RegistryEntry) -> Chunk {
+    let fields = vec![
+        byte_list_root(&entry.pubkey, MAX_PUBKEY_BYTES),
+        uint64_root(entry.activation_epoch),
+        uint64_root(entry.exit_epoch),
+    ];
+    let field_count = fields.len() as u64;
+    merkleize(fields, field_count)
+}
+
+fn registry_root(entries: &[RegistryEntry]) -> Chunk {
+    let validators = mix_in_length(
+        merkleize(
+            entries.iter().map(entry_root).collect(),
+            VALIDATOR_REGISTRY_LIMIT,
+        ),
+        entries.len(),
+    );
+    // Container of one field
+    merkleize(vec![validators], 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected encodings and roots were computed independently with the
+    // `ssz_rs` crate, from the same container definitions.
+
+    fn entry(pubkey: Vec<u8>, activation_epoch: u64) -> RegistryEntry {
+        RegistryEntry {
+            pubkey,
+            activation_epoch,
+            exit_epoch: FAR_FUTURE_EPOCH,
+        }
+    }
+
+    fn check(entries: &[RegistryEntry], encoding: &str, root: &str) {
+        assert_eq!(hex::encode(encode_registry(entries)), encoding);
+        assert_eq!(hex::encode(registry_root(entries)), root);
+    }
+
+    #[test]
+    fn empty_registry() {
+        check(
+            &[],
+            "04000000",
+            "ea569bcb4fbb2ed26d30e997d7337e7e12a43ac115793e9cbe25da401fcbb725",
+        );
+    }
+
+    #[test]
+    fn single_entry() {
+        check(
+            &[entry((0..52).collect(), 0)],
+            concat!(
+                "0400000004000000140000000000000000000000ffffffffffffffff",
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "202122232425262728292a2b2c2d2e2f30313233",
+            ),
+            "f4d3721b787ecb61164657a3d31a66987b4eceb16a1bde6e7ad18e39815c5340",
+        );
+    }
+
+    #[test]
+    fn non_power_of_two_count() {
+        check(
+            &[
+                entry(vec![0xaa; 32], 0),
+                entry((0..33).collect(), 7),
+                entry(Vec::new(), 1 << 20),
+            ],
+            concat!(
+                "040000000c0000004000000075000000",
+                "140000000000000000000000ffffffffffffffff",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                "140000000700000000000000ffffffffffffffff",
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+                "140000000000100000000000ffffffffffffffff",
+            ),
+            "576418520b214d54523c4848fdd274d8510a2cc4c8bf81f35436b943bbbad041",
+        );
+    }
+
+    #[test]
+    fn exports_leave_canaries_out() {
+        let dir =
+            std::env::temp_dir().join(format!("hashsig-registry-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = format!(
+            "key_scheme: SIGTopLevelTargetSumLifetime32Dim64Base8
+hash_function: Poseidon2
+encoding: TargetSum
+lifetime: 4294967296
+log_num_active_epochs: 2
+num_active_epochs: 4
+num_validators: 2
+
+validators:
+  - index: 0
+    pubkey_hex: 0x{}
+    privkey_file: validator_0_sk.ssz
+
+  - index: 1
+    pubkey_hex: 0x{}
+    privkey_file: validator_1_sk.ssz
+    canary: true
+",
+            "aa".repeat(32),
+            "bb".repeat(32)
+        );
+        fs::write(dir.join(crate::manifest::MANIFEST_FILE), manifest).unwrap();
+
+        let registry = dir.join("registry.ssz");
+        export(
+            &dir,
+            ExportKind::SszRegistry,
+            &registry,
+            SortOrder::Index,
+            None,
+        )
+        .unwrap();
+        let code = dir.join("pubkeys.rs");
+        export(
+            &dir,
+            ExportKind::Code,
+            &code,
+            SortOrder::Index,
+            Some(Lang::Rust),
+        )
+        .unwrap();
+        let log = dir.join("pubkeys.log");
+        crate::translog::publish_log(&dir, &log, SortOrder::Index).unwrap();
+
+        let registry = fs::read(&registry).unwrap();
+        let code = fs::read_to_string(&code).unwrap();
+        let log = fs::read_to_string(&log).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(registry, encode_registry(&[entry(vec![0xaa; 32], 0)]));
+        assert!(code.contains("validator_0"));
+        assert!(!code.contains("validator_1"));
+        assert!(!code.contains("0xbb"));
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains(&"aa".repeat(32)));
+        assert!(!log.contains(&"bb".repeat(32)));
+    }
+}
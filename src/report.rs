@@ -0,0 +1,44 @@
+//! Signing of reports and transcripts with a hash-based key.
+//!
+//! The signed message is the SHA-256 of the file, so a signature can be
+//! checked with `inspect-sig --message-hex $(sha256sum <file>)`.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use leansig::MESSAGE_LENGTH;
+use sha2::{Digest, Sha256};
+
+use crate::signer::Signer;
+
+/// Hash-based key used to sign a report, and the epoch to sign at. Every
+/// epoch must only ever be used once.
+pub struct ReportSigner {
+    pub signer: Box<dyn Signer>,
+    pub epoch: u32,
+}
+
+/// Sign the SHA-256 of `contents`, which were written to `path`, and write
+/// the signature to `<path>.sig`.
+pub fn sign(contents: &[u8], path: &Path, signer: &ReportSigner) -> io::Result<()> {
+    crate::signer::refuse_canary(signer.signer.as_ref())?;
+    crate::signer::refuse_revoked(signer.signer.as_ref(), None)?;
+    let message: [u8; MESSAGE_LENGTH] = Sha256::digest(contents).into();
+    let signature = signer.signer.sign(signer.epoch, &message)?;
+
+    let mut sig_path = OsString::from(path);
+    sig_path.push(".sig");
+    let sig_path = PathBuf::from(sig_path);
+    fs::write(&sig_path, signature)?;
+
+    println!(
+        "🖊️  Signed with {} at epoch {} (message = SHA-256 of {}): {}",
+        signer.signer.describe(),
+        signer.epoch,
+        path.display(),
+        sig_path.display()
+    );
+    Ok(())
+}
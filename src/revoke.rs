@@ -0,0 +1,154 @@
+use std::io;
+use std::path::Path;
+
+use crate::keystore::{KeyStore, ManifestKeyStore};
+use crate::manifest::{self, normalize_pubkey, short_pubkey, Manifest, Tombstone};
+use crate::metadata::unix_now;
+
+/// Remove a validator's secret key and record a tombstone for it in the
+/// manifest of `dir`. Public key files are left in place.
+pub fn revoke(dir: &Path, pubkey: &str, reason: &str, shred: bool) -> io::Result<()> {
+    let mut manifest = Manifest::read(dir)?;
+    let store = ManifestKeyStore::open(dir)?;
+    let wanted = normalize_pubkey(pubkey);
+
+    if manifest
+        .revoked
+        .iter()
+        .any(|t| normalize_pubkey(&t.pubkey_hex) == wanted)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is already revoked", short_pubkey(pubkey)),
+        ));
+    }
+
+    let position = manifest
+        .validators
+        .iter()
+        .position(|v| normalize_pubkey(&v.pubkey_hex) == wanted)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in the manifest", short_pubkey(pubkey)),
+            )
+        })?;
+    let entry = manifest.validators.remove(position);
+
+    println!(
+        "🪦 Revoking {} ({})",
+        short_pubkey(&entry.pubkey_hex),
+        entry.privkey_file
+    );
+
+    // The SSZ secret key plus its legacy JSON copy, if one was exported
+    let privkey_name = manifest::file_name(&entry.privkey_file);
+    let mut sk_files = vec![privkey_name.to_string()];
+    if let Some(prefix) = privkey_name.strip_suffix("_sk.ssz") {
+        sk_files.push(format!("{}_sk.json", prefix));
+    }
+    let existing = store.list()?;
+    for name in sk_files {
+        if !existing.contains(&name) {
+            continue;
+        }
+        if shred {
+            store.shred(&name)?;
+        } else {
+            store.delete(&name)?;
+        }
+        println!(
+            "  🗑️  {} {}",
+            if shred { "shredded" } else { "deleted" },
+            name
+        );
+    }
+
+    // Written only after the key is gone, so a failed deletion can be retried
+    manifest.num_validators = manifest.validators.len();
+    manifest.revoked.push(Tombstone {
+        index: entry.index,
+        pubkey_hex: entry.pubkey_hex,
+        privkey_file: entry.privkey_file,
+        reason: reason.to_string(),
+        revoked_at: unix_now(),
+    });
+    let manifest_path = manifest.write(dir)?;
+
+    println!("\n✅ Tombstone recorded in: {}", manifest_path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::manifest::MANIFEST_FILE;
+    use crate::sign::{self, MessageSource, PreHash};
+    use crate::signer::{self, KeySource};
+
+    const PUBKEY: [u8; 32] = [7; 32];
+
+    fn manifest_yaml() -> String {
+        format!(
+            "key_scheme: SIGTopLevelTargetSumLifetime32Dim64Base8
+hash_function: Poseidon2
+encoding: TargetSum
+lifetime: 4294967296
+log_num_active_epochs: 2
+num_active_epochs: 4
+num_validators: 1
+
+validators:
+  - index: 0
+    pubkey_hex: 0x{}
+    privkey_file: validator_0_sk.ssz
+",
+            hex::encode(PUBKEY)
+        )
+    }
+
+    #[test]
+    fn refuses_to_sign_with_a_restored_revoked_key() {
+        let dir = std::env::temp_dir().join(format!("hashsig-revoke-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(MANIFEST_FILE), manifest_yaml()).unwrap();
+        fs::write(dir.join("validator_0_sk.ssz"), b"secret key").unwrap();
+        fs::write(dir.join("validator_0_pk.ssz"), PUBKEY).unwrap();
+
+        revoke(&dir, &hex::encode(PUBKEY), "lost laptop", false).unwrap();
+        assert!(!dir.join("validator_0_sk.ssz").exists());
+        // As a restore from a backup taken before the revocation would
+        fs::write(dir.join("validator_0_sk.ssz"), b"secret key").unwrap();
+
+        let sk = dir.join("validator_0_sk.ssz");
+        let signer = KeySource::File(&sk).open(None).unwrap();
+        let message = MessageSource {
+            message: None,
+            message_hex: Some(&"00".repeat(32)),
+            message_file: None,
+        };
+        let output = dir.join("message.sig");
+        let result = sign::sign(
+            signer.as_ref(),
+            0,
+            &message,
+            PreHash::Sha256,
+            Some(&output),
+            None,
+            None,
+        );
+        let manifest = Manifest::read(&dir).unwrap();
+        let checked = signer::refuse_revoked(signer.as_ref(), Some(&manifest));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("revoked"), "{}", err);
+        assert!(!output.exists());
+        assert!(checked.is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! `rotate`: replace a validator's key in two steps.
+//!
+//! `rotate start` generates the replacement next to the old key, marks the
+//! old validator as rotating in the manifest and writes a registration file
+//! for the new key. Once the new key is registered, `rotate finalize`
+//! revokes the old one.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::entropy::OsEntropy;
+use crate::generate::{self, ExportFormat, OutputOptions, VerifyWrites};
+use crate::keys;
+use crate::keystore::{self, FsKeyStore, KeyStore, ManifestKeyStore, SplitKeyStore};
+use crate::manifest::{
+    self, normalize_pubkey, short_pubkey, Manifest, ManifestEntry, ManifestPaths,
+};
+use crate::metadata::unix_now;
+use crate::revoke;
+use crate::scheme::{self, SchemeHandle};
+
+/// What has to be submitted to register the replacement key, written as
+/// `<name>.registration.json`.
+#[derive(Serialize)]
+struct Registration {
+    pubkey_hex: String,
+    fingerprint: String,
+    pubkey_file: String,
+    key_scheme: String,
+    hash_function: String,
+    num_active_epochs: u64,
+    /// Pubkey of the validator this key replaces.
+    replaces_pubkey_hex: String,
+    created_at: u64,
+}
+
+/// A replacement key generated by [`start`].
+pub struct Replacement {
+    pub pubkey_hex: String,
+    pub registration_file: String,
+}
+
+/// Generate a replacement for the validator `pubkey` of the key directory
+/// `dir` and mark the validator as rotating.
+///
+/// The replacement uses the validator's active epochs, its scheme unless
+/// `to` is given, and the directory's naming and export format, with fresh
+/// OS entropy.
+pub fn start(
+    dir: &Path,
+    pubkey: &str,
+    to: Option<&'static dyn SchemeHandle>,
+) -> io::Result<Replacement> {
+    let mut manifest = Manifest::read(dir)?;
+    let source = manifest::manifest_path(dir).display().to_string();
+    let position = find_validator(&manifest, pubkey)?;
+    let old = &manifest.validators[position];
+    // In a mixed key set, the validator's own scheme and active epochs
+    let scheme = match to {
+        Some(scheme) => scheme,
+        None => scheme::for_entry(&manifest, old, &source)?,
+    };
+    let log_num_active_epochs = manifest.log_num_active_epochs_of(old);
+    let key_scheme = (scheme.name() != manifest.key_scheme).then(|| scheme.name().to_string());
+    let log_override = old.log_num_active_epochs;
+    if let Some(rotating_to) = &old.rotating_to {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} is already being rotated to {} (run rotate finalize)",
+                short_pubkey(&old.pubkey_hex),
+                short_pubkey(rotating_to)
+            ),
+        ));
+    }
+
+    // Match the files the old key was written with, and put the new secret
+    // key where the old one is
+    let existing = ManifestKeyStore::open(dir)?.list()?;
+    let old_name = manifest::file_name(&old.privkey_file);
+    let old_prefix = old_name.strip_suffix("_sk.ssz").unwrap_or(old_name);
+    let old_path = Path::new(&old.privkey_file);
+    let manifest_paths = if old_path.is_absolute() {
+        ManifestPaths::Absolute
+    } else {
+        ManifestPaths::Relative
+    };
+    let dir_store;
+    let split_store;
+    let store: &dyn KeyStore = match old_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(secret_dir) => {
+            split_store = SplitKeyStore::create(dir, dir.join(secret_dir))?;
+            &split_store
+        }
+        None => {
+            dir_store = FsKeyStore::new(dir);
+            &dir_store
+        }
+    };
+    let export_format = if existing.contains(&format!("{}_pk.json", old_prefix)) {
+        ExportFormat::Both
+    } else {
+        ExportFormat::Ssz
+    };
+    let distributed = old.index.is_none();
+    let index = manifest
+        .validators
+        .iter()
+        .filter_map(|v| v.index)
+        .chain(manifest.revoked.iter().filter_map(|t| t.index))
+        .max()
+        .map_or(0, |max| max + 1);
+
+    println!(
+        "🔄 Rotating {} ({})\n",
+        short_pubkey(&old.pubkey_hex),
+        old.privkey_file
+    );
+    let options = OutputOptions {
+        store,
+        scheme,
+        export_format,
+        export_secret_json: existing.contains(&format!("{}_sk.json", old_prefix)),
+        pretty_json: false,
+        distributed,
+        write_metadata: existing.contains(&format!("{}.meta.json", old_prefix)),
+        verify_writes: VerifyWrites::Secret,
+        ceremony_id: None,
+        canaries_from: None,
+        first_index: 0,
+        namespace: manifest.namespace.as_deref(),
+    };
+    let info = generate::generate_one(index, log_num_active_epochs, &options, &OsEntropy)?;
+
+    let new_prefix = info
+        .privkey_file
+        .strip_suffix("_sk.ssz")
+        .unwrap_or(&info.privkey_file);
+    let pk_bytes = hex::decode(normalize_pubkey(&info.pubkey_hex)).map_err(io::Error::other)?;
+    let registration = Registration {
+        pubkey_hex: info.pubkey_hex.clone(),
+        fingerprint: keys::fingerprint(&pk_bytes),
+        pubkey_file: format!("{}_pk.ssz", new_prefix),
+        key_scheme: scheme.name().to_string(),
+        hash_function: scheme.hash_function().name().to_string(),
+        num_active_epochs: 1 << log_num_active_epochs,
+        replaces_pubkey_hex: old.pubkey_hex.clone(),
+        created_at: unix_now(),
+    };
+    let registration_file = format!("{}.registration.json", new_prefix);
+    let json = serde_json::to_vec_pretty(&registration).map_err(io::Error::other)?;
+    store.store(&registration_file, &json)?;
+    println!("  📄 {}", registration_file);
+
+    manifest.validators[position].rotating_to = Some(info.pubkey_hex.clone());
+    manifest.validators.push(ManifestEntry {
+        index: (!distributed).then_some(index),
+        pubkey_hex: info.pubkey_hex.clone(),
+        privkey_file: keystore::manifest_reference(store, &info.privkey_file, manifest_paths)?,
+        derivation_path: None,
+        rotating_to: None,
+        canary: false,
+        key_scheme,
+        log_num_active_epochs: log_override,
+    });
+    manifest.num_validators = manifest.validators.len();
+    manifest.write(dir)?;
+
+    println!(
+        "\n✅ Replacement {} generated; register it with {}, then run `rotate finalize {}`",
+        short_pubkey(&info.pubkey_hex),
+        registration_file,
+        pubkey
+    );
+    Ok(Replacement {
+        pubkey_hex: info.pubkey_hex,
+        registration_file,
+    })
+}
+
+/// Revoke the validator `pubkey` of `dir` after its replacement has been
+/// registered.
+pub fn finalize(dir: &Path, pubkey: &str, shred: bool) -> io::Result<()> {
+    let manifest = Manifest::read(dir)?;
+    let old = &manifest.validators[find_validator(&manifest, pubkey)?];
+    let rotating_to = old.rotating_to.as_deref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not being rotated (run rotate start first)",
+                short_pubkey(pubkey)
+            ),
+        )
+    })?;
+    let wanted = normalize_pubkey(rotating_to);
+    if !manifest
+        .validators
+        .iter()
+        .any(|v| normalize_pubkey(&v.pubkey_hex) == wanted)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Replacement {} is no longer in the manifest; refusing to revoke {}",
+                short_pubkey(rotating_to),
+                short_pubkey(pubkey)
+            ),
+        ));
+    }
+
+    revoke::revoke(dir, pubkey, &format!("rotated to {}", rotating_to), shred)
+}
+
+fn find_validator(manifest: &Manifest, pubkey: &str) -> io::Result<usize> {
+    let wanted = normalize_pubkey(pubkey);
+    manifest
+        .validators
+        .iter()
+        .position(|v| normalize_pubkey(&v.pubkey_hex) == wanted)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in the manifest", short_pubkey(pubkey)),
+            )
+        })
+}
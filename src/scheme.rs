@@ -0,0 +1,657 @@
+//! Registry of the signature schemes this build supports.
+//!
+//! Subcommands never name a leansig instantiation directly. They look one
+//! up by its identifier and work with it through [`SchemeHandle`], which
+//! deals in SSZ bytes and JSON only, so supporting another instantiation
+//! takes one more entry in [`REGISTRY`].
+
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use leansig::serialization::Serializable;
+use leansig::signature::{
+    generalized_xmss::instantiations_poseidon_top_level::lifetime_2_to_the_32::hashing_optimized::SIGTopLevelTargetSumLifetime32Dim64Base8,
+    SignatureScheme, SignatureSchemeSecretKey,
+};
+use leansig::MESSAGE_LENGTH;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::exit;
+use crate::explain::Cause;
+use crate::keys::HashFunction;
+use crate::manifest::{self, Manifest, ManifestEntry};
+
+static TARGET_SUM_LIFETIME_32_DIM_64_BASE_8: LeanSig<SIGTopLevelTargetSumLifetime32Dim64Base8> =
+    LeanSig::new(
+        "SIGTopLevelTargetSumLifetime32Dim64Base8",
+        HashFunction::Poseidon2,
+        "TargetSum",
+        Parameters {
+            dimension: 64,
+            base: 8,
+            target_sum: 375,
+        },
+    );
+
+/// Every selectable scheme. The first one is the default.
+static REGISTRY: &[&dyn SchemeHandle] = &[&TARGET_SUM_LIFETIME_32_DIM_64_BASE_8];
+
+/// A registered scheme whose keys should be replaced.
+pub struct Deprecation {
+    pub scheme: &'static str,
+    /// Why, e.g. a weakened parameter set.
+    pub reason: &'static str,
+    /// Scheme `migrate-scheme` moves its validators to.
+    pub replacement: &'static str,
+}
+
+/// Deprecated instantiations. Any command meeting one of their keys warns,
+/// and `migrate-scheme` replaces them. Keep an entry until the scheme is
+/// removed from [`REGISTRY`], so fleets are warned for a whole release
+/// cycle first. None is deprecated yet.
+static DEPRECATED: &[Deprecation] = &[];
+
+/// Schemes already warned about by this process.
+static WARNED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Message signed (in memory only) when checking that a secret key belongs
+/// to a public key and when benchmarking signing.
+#[cfg(not(feature = "verify-only"))]
+const TEST_MESSAGE: [u8; MESSAGE_LENGTH] = [0x42; MESSAGE_LENGTH];
+
+/// Which half of a key pair an encoded key is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyKind {
+    Public,
+    #[cfg(not(feature = "verify-only"))]
+    Secret,
+}
+
+impl KeyKind {
+    fn name(self) -> &'static str {
+        match self {
+            KeyKind::Public => "public key",
+            #[cfg(not(feature = "verify-only"))]
+            KeyKind::Secret => "secret key",
+        }
+    }
+}
+
+/// A freshly generated key pair of some registered scheme.
+#[cfg(not(feature = "verify-only"))]
+pub trait KeyPair: Send {
+    fn pk_bytes(&self) -> Vec<u8>;
+
+    fn sk_bytes(&self) -> Vec<u8>;
+
+    /// Legacy JSON representation of the public key, indented if `pretty`.
+    fn pk_json(&self, pretty: bool) -> String;
+
+    /// Legacy JSON representation of the secret key, indented if `pretty`.
+    fn sk_json(&self, pretty: bool) -> String;
+
+    /// Epochs the secret key is active for (end exclusive).
+    fn activation_interval(&self) -> Range<u64>;
+
+    /// Epochs the secret key can sign at without further preparation (end
+    /// exclusive).
+    fn prepared_interval(&self) -> Range<u64>;
+}
+
+/// A signature scheme, with keys and signatures in their SSZ encoding.
+///
+/// Decoding failures of the codec methods are returned as a reason string
+/// so callers can report them alongside the file they came from.
+pub trait SchemeHandle: Sync {
+    /// Identifier used on the command line and recorded in manifests.
+    fn name(&self) -> &'static str;
+
+    fn hash_function(&self) -> HashFunction;
+
+    /// Name of the incomparable encoding, recorded in manifests.
+    fn encoding(&self) -> &'static str;
+
+    /// Number of epochs covered by a key.
+    fn lifetime(&self) -> u64;
+
+    /// Short hash over every parameter of the instantiation, recorded in
+    /// manifests, metadata and signature sidecars so they are never read
+    /// with parameters they were not made with.
+    fn fingerprint(&self) -> String;
+
+    #[cfg(not(feature = "verify-only"))]
+    fn key_gen(
+        &self,
+        rng: &mut dyn RngCore,
+        activation_epoch: usize,
+        num_active_epochs: usize,
+    ) -> Box<dyn KeyPair>;
+
+    /// Decode an SSZ-encoded key and encode it again.
+    fn reencode_ssz(&self, kind: KeyKind, bytes: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Decode a legacy JSON key and return its SSZ encoding.
+    fn json_to_ssz(&self, kind: KeyKind, json: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Decode an SSZ-encoded key and return its JSON representation.
+    fn ssz_to_json(&self, kind: KeyKind, bytes: &[u8]) -> Result<Value, String>;
+
+    /// JSON representation of an SSZ-encoded signature.
+    fn signature_json(&self, signature: &[u8]) -> Result<Value, String>;
+
+    /// Check that `sk` belongs to `pk` by signing a fixed message at the
+    /// first prepared epoch and verifying it. The signature never leaves
+    /// memory.
+    #[cfg(not(feature = "verify-only"))]
+    fn keypair_matches(&self, pk: &[u8], sk: &[u8]) -> io::Result<bool>;
+
+    /// Active and prepared epochs of an SSZ-encoded secret key (end
+    /// exclusive).
+    #[cfg(not(feature = "verify-only"))]
+    fn secret_key_intervals(&self, sk: &[u8]) -> io::Result<(Range<u64>, Range<u64>)>;
+
+    /// Move the prepared epochs of an SSZ-encoded secret key forward, one
+    /// bottom tree at a time, while the tree dropped only covers epochs
+    /// before `before_epoch` and the key has epochs left to prepare. Returns
+    /// the re-encoded key.
+    #[cfg(not(feature = "verify-only"))]
+    fn advance_secret_key(&self, sk: &[u8], before_epoch: u64) -> io::Result<Vec<u8>>;
+
+    /// Sign `message` at `epoch`, returning the SSZ-encoded signature.
+    /// Signing twice at the same epoch breaks the scheme's security, so
+    /// callers must never reuse an epoch.
+    #[cfg(not(feature = "verify-only"))]
+    fn sign(&self, sk: &[u8], epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>>;
+
+    fn verify(
+        &self,
+        pk: &[u8],
+        epoch: u32,
+        message: &[u8; MESSAGE_LENGTH],
+        signature: &[u8],
+    ) -> io::Result<bool>;
+
+    /// Time signing a fixed message at each of `epochs` with `sk`, which is
+    /// decoded only once. The signatures never leave memory.
+    #[cfg(not(feature = "verify-only"))]
+    fn sign_latencies(&self, sk: &[u8], epochs: &[u32]) -> io::Result<Vec<Duration>>;
+}
+
+/// The scheme used when none is selected.
+pub fn default_scheme() -> &'static dyn SchemeHandle {
+    REGISTRY[0]
+}
+
+/// Every registered scheme, the default first.
+pub fn all() -> &'static [&'static dyn SchemeHandle] {
+    REGISTRY
+}
+
+/// Deprecation of the scheme `name`, if it is deprecated.
+pub fn deprecation(name: &str) -> Option<&'static Deprecation> {
+    DEPRECATED
+        .iter()
+        .find(|deprecation| deprecation.scheme == name)
+}
+
+/// Warn on stderr, once per scheme, if `scheme` is deprecated.
+fn warn_if_deprecated(scheme: &'static dyn SchemeHandle) {
+    let Some(deprecation) = deprecation(scheme.name()) else {
+        return;
+    };
+    let mut warned = WARNED.lock().expect("warning lock poisoned");
+    if warned.contains(&deprecation.scheme) {
+        return;
+    }
+    warned.push(deprecation.scheme);
+    eprintln!(
+        "\n⚠️  ⚠️  ⚠️  {} is DEPRECATED: {}",
+        deprecation.scheme, deprecation.reason
+    );
+    eprintln!(
+        "   Replace its keys with {}: `hashsig migrate-scheme <dir> --dry-run` shows the plan\n",
+        deprecation.replacement
+    );
+}
+
+/// Look up a registered scheme by identifier, warning if it is deprecated.
+pub fn lookup(name: &str) -> io::Result<&'static dyn SchemeHandle> {
+    let scheme = REGISTRY
+        .iter()
+        .copied()
+        .find(|scheme| scheme.name() == name)
+        .ok_or_else(|| {
+            let available: Vec<_> = REGISTRY.iter().map(|scheme| scheme.name()).collect();
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Unknown signature scheme {} (available: {})",
+                    name,
+                    available.join(", ")
+                ),
+            )
+        })?;
+    warn_if_deprecated(scheme);
+    Ok(scheme)
+}
+
+/// Scheme for new keys: `name` if given, which must use `hash_function`,
+/// else the first registered scheme using `hash_function`.
+pub fn select(
+    name: Option<&str>,
+    hash_function: HashFunction,
+) -> io::Result<&'static dyn SchemeHandle> {
+    let scheme = match name {
+        Some(name) => lookup(name)?,
+        None => REGISTRY
+            .iter()
+            .copied()
+            .find(|scheme| scheme.hash_function() == hash_function)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "No {} signature scheme is available in this build",
+                        hash_function.name()
+                    ),
+                )
+            })?,
+    };
+    if scheme.hash_function() != hash_function {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} uses {}, not {}",
+                scheme.name(),
+                scheme.hash_function().name(),
+                hash_function.name()
+            ),
+        ));
+    }
+    Ok(scheme)
+}
+
+/// Scheme recorded in a manifest (read from `source`). Refuses manifests
+/// whose recorded hash function is not the scheme's, so keys are never
+/// verified across hash functions.
+pub fn for_manifest(manifest: &Manifest, source: &str) -> io::Result<&'static dyn SchemeHandle> {
+    let scheme = lookup(&manifest.key_scheme)?;
+    check_fingerprint(scheme, manifest.scheme_fingerprint.as_deref(), source)?;
+    if manifest.hash_function != scheme.hash_function().name() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} records hash function {}, but {} uses {}; refusing to verify across hash functions",
+                source,
+                manifest.hash_function,
+                scheme.name(),
+                scheme.hash_function().name()
+            ),
+        ));
+    }
+    Ok(scheme)
+}
+
+/// Scheme of the validator `entry` of `manifest` (read from `source`): its
+/// own in a mixed key set, else the manifest's.
+pub fn for_entry(
+    manifest: &Manifest,
+    entry: &ManifestEntry,
+    source: &str,
+) -> io::Result<&'static dyn SchemeHandle> {
+    match &entry.key_scheme {
+        Some(name) => lookup(name),
+        None => for_manifest(manifest, source),
+    }
+}
+
+/// Refuse an artifact read from `source` whose recorded parameter
+/// fingerprint is not `scheme`'s. Artifacts written before fingerprints were
+/// recorded have none and pass.
+pub fn check_fingerprint(
+    scheme: &dyn SchemeHandle,
+    recorded: Option<&str>,
+    source: &str,
+) -> io::Result<()> {
+    match recorded {
+        Some(recorded) if recorded != scheme.fingerprint() => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} was made with scheme parameters {}, but {} in this build has {}; refusing to use it",
+                source,
+                recorded,
+                scheme.name(),
+                scheme.fingerprint()
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Scheme of the key directory `dir` according to its manifest, or `None`
+/// if it has no manifest.
+pub fn for_dir(dir: &Path) -> io::Result<Option<&'static dyn SchemeHandle>> {
+    match Manifest::read_if_present(dir)? {
+        Some(manifest) => {
+            let source = manifest::manifest_path(dir).display().to_string();
+            for_manifest(&manifest, &source).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Scheme for using the key file `key`: `name` if given, else the one the
+/// manifest next to the key records for it, else the default. Refuses a
+/// `name` that contradicts the manifest.
+pub fn for_key(name: Option<&str>, key: &Path) -> io::Result<&'static dyn SchemeHandle> {
+    let dir = key.parent().unwrap_or(Path::new("."));
+    let key_dir_scheme = match Manifest::read_if_present(dir)? {
+        Some(manifest) => {
+            let source = manifest::manifest_path(dir).display().to_string();
+            let entry = key
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| manifest.entry_for_file(name));
+            Some(match entry {
+                Some(entry) => for_entry(&manifest, entry, &source)?,
+                None => for_manifest(&manifest, &source)?,
+            })
+        }
+        None => None,
+    };
+    let scheme = match name {
+        Some(name) => lookup(name)?,
+        None => key_dir_scheme.unwrap_or_else(default_scheme),
+    };
+    if let Some(key_dir_scheme) = key_dir_scheme {
+        if key_dir_scheme.name() != scheme.name() {
+            return Err(exit::diagnosed(
+                Cause::SchemeMismatch,
+                format!(
+                    "{} belongs to {}, not {}; refusing to use it across schemes",
+                    key.display(),
+                    key_dir_scheme.name(),
+                    scheme.name()
+                ),
+            ));
+        }
+    }
+    Ok(scheme)
+}
+
+/// Encoding parameters of a leansig instantiation, as defined in leansig.
+/// The lifetime is read from the instantiation itself.
+struct Parameters {
+    dimension: u32,
+    base: u32,
+    target_sum: u32,
+}
+
+/// [`SchemeHandle`] for a leansig instantiation.
+struct LeanSig<S> {
+    name: &'static str,
+    hash_function: HashFunction,
+    encoding: &'static str,
+    parameters: Parameters,
+    scheme: PhantomData<fn() -> S>,
+}
+
+impl<S> LeanSig<S> {
+    const fn new(
+        name: &'static str,
+        hash_function: HashFunction,
+        encoding: &'static str,
+        parameters: Parameters,
+    ) -> Self {
+        Self {
+            name,
+            hash_function,
+            encoding,
+            parameters,
+            scheme: PhantomData,
+        }
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+struct LeanSigKeyPair<S: SignatureScheme> {
+    pk: S::PublicKey,
+    sk: S::SecretKey,
+}
+
+fn decode_ssz<T: Serializable>(bytes: &[u8]) -> Result<T, String> {
+    T::from_bytes(bytes).map_err(|e| format!("SSZ decoding failed: {:?}", e))
+}
+
+fn decode_json<T: DeserializeOwned>(json: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(json).map_err(|e| format!("JSON decoding failed: {}", e))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| format!("JSON encoding failed: {}", e))
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn json_string<T: Serialize>(value: &T, pretty: bool) -> serde_json::Result<String> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+fn decoding_error(what: &str, reason: String) -> io::Error {
+    exit::diagnosed(
+        Cause::CorruptSsz,
+        format!("Failed to decode {}: {}", what, reason),
+    )
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl<S> KeyPair for LeanSigKeyPair<S>
+where
+    S: SignatureScheme,
+    S::PublicKey: Serializable + Serialize + Send,
+    S::SecretKey: Serializable + Serialize + Send,
+{
+    fn pk_bytes(&self) -> Vec<u8> {
+        self.pk.to_bytes()
+    }
+
+    fn sk_bytes(&self) -> Vec<u8> {
+        self.sk.to_bytes()
+    }
+
+    fn pk_json(&self, pretty: bool) -> String {
+        json_string(&self.pk, pretty).expect("Failed to serialize public key to JSON")
+    }
+
+    fn sk_json(&self, pretty: bool) -> String {
+        json_string(&self.sk, pretty).expect("Failed to serialize secret key to JSON")
+    }
+
+    fn activation_interval(&self) -> Range<u64> {
+        self.sk.get_activation_interval()
+    }
+
+    fn prepared_interval(&self) -> Range<u64> {
+        self.sk.get_prepared_interval()
+    }
+}
+
+impl<S> SchemeHandle for LeanSig<S>
+where
+    S: SignatureScheme + 'static,
+    S::PublicKey: Serializable + Serialize + DeserializeOwned + Send,
+    S::SecretKey: Serializable + Serialize + DeserializeOwned + Send,
+    S::Signature: Serializable + Serialize,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn hash_function(&self) -> HashFunction {
+        self.hash_function
+    }
+
+    fn encoding(&self) -> &'static str {
+        self.encoding
+    }
+
+    fn lifetime(&self) -> u64 {
+        S::LIFETIME
+    }
+
+    fn fingerprint(&self) -> String {
+        let Parameters {
+            dimension,
+            base,
+            target_sum,
+        } = self.parameters;
+        let canonical = format!(
+            "lifetime={};dimension={};base={};target_sum={};hash_function={};encoding={}",
+            S::LIFETIME,
+            dimension,
+            base,
+            target_sum,
+            self.hash_function.name(),
+            self.encoding
+        );
+        hex::encode(&Sha256::digest(canonical)[..8])
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn key_gen(
+        &self,
+        mut rng: &mut dyn RngCore,
+        activation_epoch: usize,
+        num_active_epochs: usize,
+    ) -> Box<dyn KeyPair> {
+        let (pk, sk) = S::key_gen(&mut rng, activation_epoch, num_active_epochs);
+        Box::new(LeanSigKeyPair::<S> { pk, sk })
+    }
+
+    fn reencode_ssz(&self, kind: KeyKind, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        match kind {
+            KeyKind::Public => decode_ssz::<S::PublicKey>(bytes).map(|pk| pk.to_bytes()),
+            #[cfg(not(feature = "verify-only"))]
+            KeyKind::Secret => decode_ssz::<S::SecretKey>(bytes).map(|sk| sk.to_bytes()),
+        }
+    }
+
+    fn json_to_ssz(&self, kind: KeyKind, json: &[u8]) -> Result<Vec<u8>, String> {
+        match kind {
+            KeyKind::Public => decode_json::<S::PublicKey>(json).map(|pk| pk.to_bytes()),
+            #[cfg(not(feature = "verify-only"))]
+            KeyKind::Secret => decode_json::<S::SecretKey>(json).map(|sk| sk.to_bytes()),
+        }
+    }
+
+    fn ssz_to_json(&self, kind: KeyKind, bytes: &[u8]) -> Result<Value, String> {
+        match kind {
+            KeyKind::Public => to_json(&decode_ssz::<S::PublicKey>(bytes)?),
+            #[cfg(not(feature = "verify-only"))]
+            KeyKind::Secret => to_json(&decode_ssz::<S::SecretKey>(bytes)?),
+        }
+    }
+
+    fn signature_json(&self, signature: &[u8]) -> Result<Value, String> {
+        to_json(&decode_ssz::<S::Signature>(signature)?)
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn keypair_matches(&self, pk: &[u8], sk: &[u8]) -> io::Result<bool> {
+        let pk = decode_ssz::<S::PublicKey>(pk)
+            .map_err(|e| decoding_error(KeyKind::Public.name(), e))?;
+        let sk = decode_ssz::<S::SecretKey>(sk)
+            .map_err(|e| decoding_error(KeyKind::Secret.name(), e))?;
+
+        let epoch = sk.get_prepared_interval().start as u32;
+        let signature = S::sign(&sk, epoch, &TEST_MESSAGE)
+            .map_err(|e| io::Error::other(format!("Failed to sign test message: {:?}", e)))?;
+        Ok(S::verify(&pk, epoch, &TEST_MESSAGE, &signature))
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn secret_key_intervals(&self, sk: &[u8]) -> io::Result<(Range<u64>, Range<u64>)> {
+        let sk = decode_ssz::<S::SecretKey>(sk)
+            .map_err(|e| decoding_error(KeyKind::Secret.name(), e))?;
+        Ok((sk.get_activation_interval(), sk.get_prepared_interval()))
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn advance_secret_key(&self, sk: &[u8], before_epoch: u64) -> io::Result<Vec<u8>> {
+        let mut sk = decode_ssz::<S::SecretKey>(sk)
+            .map_err(|e| decoding_error(KeyKind::Secret.name(), e))?;
+        loop {
+            let prepared = sk.get_prepared_interval();
+            // The prepared epochs span two bottom trees; the first is dropped
+            let first_tree_end = prepared.start + (prepared.end - prepared.start) / 2;
+            if first_tree_end > before_epoch || prepared.end >= sk.get_activation_interval().end {
+                break;
+            }
+            sk.advance_preparation();
+            if sk.get_prepared_interval() == prepared {
+                break;
+            }
+        }
+        Ok(sk.to_bytes())
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn sign(&self, sk: &[u8], epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>> {
+        let sk = decode_ssz::<S::SecretKey>(sk)
+            .map_err(|e| decoding_error(KeyKind::Secret.name(), e))?;
+        let prepared = sk.get_prepared_interval();
+        if !prepared.contains(&(epoch as u64)) {
+            return Err(exit::diagnosed(
+                Cause::EpochOutOfWindow,
+                format!(
+                    "Epoch {} is outside the key's prepared epochs {}..{}; refusing to sign",
+                    epoch, prepared.start, prepared.end
+                ),
+            ));
+        }
+        let signature = S::sign(&sk, epoch, message)
+            .map_err(|e| io::Error::other(format!("Failed to sign at epoch {}: {:?}", epoch, e)))?;
+        Ok(signature.to_bytes())
+    }
+
+    fn verify(
+        &self,
+        pk: &[u8],
+        epoch: u32,
+        message: &[u8; MESSAGE_LENGTH],
+        signature: &[u8],
+    ) -> io::Result<bool> {
+        let pk = decode_ssz::<S::PublicKey>(pk)
+            .map_err(|e| decoding_error(KeyKind::Public.name(), e))?;
+        let signature =
+            decode_ssz::<S::Signature>(signature).map_err(|e| decoding_error("signature", e))?;
+        Ok(S::verify(&pk, epoch, message, &signature))
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn sign_latencies(&self, sk: &[u8], epochs: &[u32]) -> io::Result<Vec<Duration>> {
+        let sk = decode_ssz::<S::SecretKey>(sk)
+            .map_err(|e| decoding_error(KeyKind::Secret.name(), e))?;
+        epochs
+            .iter()
+            .map(|&epoch| {
+                let start = Instant::now();
+                S::sign(&sk, epoch, &TEST_MESSAGE).map_err(|e| {
+                    io::Error::other(format!("Failed to sign at epoch {}: {:?}", epoch, e))
+                })?;
+                Ok(start.elapsed())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,203 @@
+//! Shamir secret sharing over GF(2^8), applied byte by byte.
+//!
+//! Share `x` (1-based) holds, for every secret byte, the value at `x` of a
+//! random polynomial of degree `threshold - 1` whose constant term is that
+//! byte. Any `threshold` shares recover the secret by Lagrange interpolation
+//! at 0; fewer reveal nothing about it.
+
+use std::io;
+
+use rand::RngCore;
+
+/// Multiplication in GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse of a non-zero element (a^254 = a^-1).
+fn inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = mul(result, power);
+        }
+        power = mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Split `secret` into `shares` shares, any `threshold` of which recover it.
+/// Share `i` of the result has x coordinate `i + 1`.
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> io::Result<Vec<Vec<u8>>> {
+    if threshold == 0 || threshold > shares {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Threshold must be between 1 and the number of shares ({}), not {}",
+                shares, threshold
+            ),
+        ));
+    }
+
+    let mut rng = rand::rng();
+    let mut result = vec![Vec::with_capacity(secret.len()); shares as usize];
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+        for (share, x) in result.iter_mut().zip(1..=shares) {
+            // Horner's rule, highest coefficient first
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, &coefficient| mul(acc, x) ^ coefficient);
+            share.push(y);
+        }
+    }
+    coefficients.fill(0);
+    Ok(result)
+}
+
+/// Recover the secret from `shares`, given as (x coordinate, share). Needs at
+/// least the threshold the shares were split with; that is not checked here.
+pub fn combine(shares: &[(u8, &[u8])]) -> io::Result<Vec<u8>> {
+    let Some((_, first)) = shares.first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "No shares"));
+    };
+    for (i, (x, share)) in shares.iter().enumerate() {
+        if *x == 0 || shares[..i].iter().any(|(other, _)| other == x) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid or repeated share index {}", x),
+            ));
+        }
+        if share.len() != first.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Shares have different lengths",
+            ));
+        }
+    }
+
+    // Lagrange basis polynomials at 0: prod x_j / (x_j - x_i), and - is ^
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|(xi, _)| {
+            shares
+                .iter()
+                .filter(|(xj, _)| xj != xi)
+                .fold(1, |acc, (xj, _)| mul(acc, mul(*xj, inv(xj ^ xi))))
+        })
+        .collect();
+    Ok((0..first.len())
+        .map(|position| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0, |acc, ((_, share), &weight)| {
+                    acc ^ mul(share[position], weight)
+                })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every subset of `0..n` of size `k`, as indices.
+    fn subsets(n: usize, k: usize) -> Vec<Vec<usize>> {
+        (0u32..1 << n)
+            .filter(|mask| mask.count_ones() as usize == k)
+            .map(|mask| (0..n).filter(|i| mask & (1 << i) != 0).collect())
+            .collect()
+    }
+
+    fn pick<'a>(shares: &'a [Vec<u8>], indices: &[usize]) -> Vec<(u8, &'a [u8])> {
+        indices
+            .iter()
+            .map(|&i| (i as u8 + 1, shares[i].as_slice()))
+            .collect()
+    }
+
+    #[test]
+    fn field_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inv(a)), 1, "{}", a);
+        }
+    }
+
+    #[test]
+    fn recovers_from_every_threshold_subset() {
+        let secret: [u8; 32] = rand::random();
+        for (threshold, count) in [(1, 1), (1, 3), (2, 3), (3, 5), (5, 5)] {
+            let shares = split(&secret, threshold, count).unwrap();
+            assert_eq!(shares.len(), usize::from(count));
+            for size in usize::from(threshold)..=usize::from(count) {
+                for subset in subsets(usize::from(count), size) {
+                    let recovered = combine(&pick(&shares, &subset)).unwrap();
+                    assert_eq!(
+                        recovered, secret,
+                        "{} of {}: {:?}",
+                        threshold, count, subset
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fails_below_threshold() {
+        let secret: [u8; 32] = rand::random();
+        let shares = split(&secret, 3, 5).unwrap();
+        for size in 1..3 {
+            for subset in subsets(5, size) {
+                let recovered = combine(&pick(&shares, &subset)).unwrap();
+                assert_ne!(recovered, secret, "{:?}", subset);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_and_invalid_indices() {
+        let shares = split(b"secret", 2, 3).unwrap();
+        let duplicate = [(1, shares[0].as_slice()), (1, shares[0].as_slice())];
+        assert!(combine(&duplicate).is_err());
+        let zero = [(0, shares[0].as_slice()), (2, shares[1].as_slice())];
+        assert!(combine(&zero).is_err());
+        let short = [(1, shares[0].as_slice()), (2, &shares[1][1..])];
+        assert!(combine(&short).is_err());
+        assert!(combine(&[]).is_err());
+    }
+
+    #[test]
+    fn tampered_share_changes_the_secret() {
+        let secret: [u8; 32] = rand::random();
+        let mut shares = split(&secret, 2, 3).unwrap();
+        shares[1][0] ^= 1;
+        let recovered = combine(&pick(&shares, &[0, 1])).unwrap();
+        assert_ne!(recovered, secret);
+        assert_eq!(recovered[1..], secret[1..]);
+    }
+
+    #[test]
+    fn rejects_invalid_thresholds() {
+        assert!(split(b"secret", 0, 3).is_err());
+        assert!(split(b"secret", 4, 3).is_err());
+    }
+}
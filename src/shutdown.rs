@@ -0,0 +1,26 @@
+//! Graceful stop on SIGTERM, which spot and preemptible cloud instances get
+//! shortly before they are reclaimed.
+//!
+//! Long-running commands [`install`] the handler and poll [`requested`]
+//! between units of work, so what is in flight is finished and recorded
+//! instead of being cut off.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Catch SIGTERM from now on instead of being terminated by it.
+pub fn install() -> io::Result<()> {
+    let flag = REQUESTED.get_or_init(|| Arc::new(AtomicBool::new(false)));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(flag))?;
+    Ok(())
+}
+
+/// Whether SIGTERM has arrived since [`install`].
+pub fn requested() -> bool {
+    REQUESTED
+        .get()
+        .is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
@@ -0,0 +1,674 @@
+//! `sign` and `verify`: sign arbitrary messages with a hash-based key.
+//!
+//! The scheme signs 32-byte messages. `--message-hex` gives that message
+//! directly; a message read from a file or stdin is signed by its pre-hash
+//! (SHA-256 unless `--prehash` says otherwise, as for reports). Messages are
+//! streamed through the pre-hash, so their size is not limited by memory.
+//!
+//! Next to a signature file, `sign` writes `<signature>.json` recording the
+//! pre-hash, which `verify` uses when `--prehash` is not given.
+//!
+//! `verify --batch-file` reads one JSON job per line and writes one JSON
+//! result per line as it goes, so any number of signatures is verified in
+//! constant memory.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(not(feature = "verify-only"))]
+use std::sync::mpsc::{self, RecvTimeoutError};
+#[cfg(not(feature = "verify-only"))]
+use std::thread;
+#[cfg(not(feature = "verify-only"))]
+use std::time::Duration;
+
+use clap::ValueEnum;
+use leansig::MESSAGE_LENGTH;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha512_256};
+
+use crate::exit::{self, Failure};
+use crate::keys;
+use crate::metadata::unix_now;
+#[cfg(not(feature = "verify-only"))]
+use crate::offline_bundle::{self, SignedMessage};
+use crate::scheme::{self, SchemeHandle};
+#[cfg(not(feature = "verify-only"))]
+use crate::signer::Signer;
+
+/// Hash turning a message file into the 32-byte message that is signed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum PreHash {
+    Sha256,
+    #[value(name = "sha512-256")]
+    Sha512_256,
+}
+
+impl PreHash {
+    pub fn name(self) -> &'static str {
+        match self {
+            PreHash::Sha256 => "sha256",
+            PreHash::Sha512_256 => "sha512-256",
+        }
+    }
+
+    /// Digest of everything `reader` yields, and the number of bytes read.
+    pub fn digest(self, reader: &mut dyn Read) -> io::Result<([u8; MESSAGE_LENGTH], u64)> {
+        match self {
+            PreHash::Sha256 => {
+                let mut hasher = Sha256::new();
+                let size = io::copy(reader, &mut hasher)?;
+                Ok((hasher.finalize().into(), size))
+            }
+            PreHash::Sha512_256 => {
+                let mut hasher = Sha512_256::new();
+                let size = io::copy(reader, &mut hasher)?;
+                Ok((hasher.finalize().into(), size))
+            }
+        }
+    }
+}
+
+/// A resolved message and how it was obtained.
+pub struct Message {
+    pub bytes: [u8; MESSAGE_LENGTH],
+    /// Pre-hash `bytes` is the digest of, or `None` for `--message-hex`.
+    pub prehash: Option<PreHash>,
+    /// Size of the pre-hashed input.
+    pub input_size: Option<u64>,
+}
+
+/// Written next to a signature file as `<signature>.json`.
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    key_scheme: String,
+    /// Missing from sidecars written before parameters were fingerprinted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheme_fingerprint: Option<String>,
+    epoch: u32,
+    /// The signed 32-byte message, hex-encoded.
+    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prehash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_size: Option<u64>,
+    created_at: u64,
+}
+
+fn sidecar_path(signature: &Path) -> PathBuf {
+    let mut path = OsString::from(signature);
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Where the message to sign or verify comes from. Exactly one source may
+/// be given, so a message is never silently taken from the wrong one.
+pub struct MessageSource<'a> {
+    /// Positional argument: a file, or `-` for stdin.
+    pub message: Option<&'a Path>,
+    pub message_hex: Option<&'a str>,
+    pub message_file: Option<&'a Path>,
+}
+
+impl MessageSource<'_> {
+    /// Resolve the 32-byte message, pre-hashing a file or stdin with
+    /// `prehash`.
+    pub fn read(&self, prehash: PreHash) -> io::Result<Message> {
+        match (self.message_hex, self.message_file, self.message) {
+            (Some(message_hex), None, None) => Ok(Message {
+                bytes: keys::parse_message_hex(message_hex)?,
+                prehash: None,
+                input_size: None,
+            }),
+            (None, Some(path), None) | (None, None, Some(path)) => {
+                let (bytes, input_size) = if path == Path::new("-") {
+                    prehash.digest(&mut io::stdin().lock())?
+                } else {
+                    prehash.digest(&mut File::open(path)?)?
+                };
+                Ok(Message {
+                    bytes,
+                    prehash: Some(prehash),
+                    input_size: Some(input_size),
+                })
+            }
+            (None, None, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "No message given (pass a file, `-` for stdin, --message-hex or --message-file)",
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Give only one of a message argument, --message-hex and --message-file",
+            )),
+        }
+    }
+}
+
+/// Exit the process with [`Failure::TimedOut`] unless the returned sender is
+/// dropped within `timeout`. A hung signing call cannot be interrupted, so
+/// ending the process is the only way out.
+#[cfg(not(feature = "verify-only"))]
+fn watchdog(timeout: Duration, key: String) -> mpsc::Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        if receiver.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+            eprintln!(
+                "Error: signing with {} did not finish within {}s; aborting",
+                key,
+                timeout.as_secs()
+            );
+            std::process::exit(Failure::TimedOut as i32);
+        }
+    });
+    sender
+}
+
+/// Contents of `path`, or of stdin if `path` is `-`.
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut contents = Vec::new();
+        io::stdin().lock().read_to_end(&mut contents)?;
+        Ok(contents)
+    } else {
+        fs::read(path)
+    }
+}
+
+/// Sign the message at `epoch` with `signer` and write the signature to
+/// `output` with its sidecar, or to stdout if `output` is `None`. When the
+/// signer's public key is available, the signature is verified against it
+/// before it is written. With `timeout`, a signature taking longer ends the
+/// process with [`Failure::TimedOut`]. With `offline_bundle`, a verification
+/// bundle of the signature is written there too.
+#[cfg(not(feature = "verify-only"))]
+pub fn sign(
+    signer: &dyn Signer,
+    epoch: u32,
+    message: &MessageSource,
+    prehash: PreHash,
+    output: Option<&Path>,
+    offline_bundle: Option<&Path>,
+    timeout: Option<Duration>,
+) -> io::Result<()> {
+    // Checked before signing, so no signature is made only to be discarded
+    if output.is_none() && io::stdout().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Refusing to write a binary signature to a terminal; redirect or pipe stdout",
+        ));
+    }
+
+    let pk = signer.pubkey()?;
+    // The bundle holds the message file, so it cannot be streamed from stdin
+    let message_file = message.message_file.or(message.message);
+    if offline_bundle.is_some() {
+        if pk.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "--offline-bundle needs the public key of {}; put its _pk.ssz next to it",
+                    signer.describe()
+                ),
+            ));
+        }
+        if message_file == Some(Path::new("-")) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--offline-bundle needs the message as a file or --message-hex, not stdin",
+            ));
+        }
+    }
+
+    crate::signer::refuse_canary(signer)?;
+    crate::signer::refuse_revoked(signer, None)?;
+    let scheme = signer.scheme();
+    let message = message.read(prehash)?;
+    let watchdog = timeout.map(|timeout| watchdog(timeout, signer.describe()));
+    let signature = signer.sign(epoch, &message.bytes)?;
+    drop(watchdog);
+    if let Some(pk) = &pk {
+        if !scheme.verify(pk, epoch, &message.bytes, &signature)? {
+            return Err(exit::error(
+                Failure::VerificationFailed,
+                format!(
+                    "The signature made with {} does not verify against its public key; not writing it",
+                    signer.describe()
+                ),
+            ));
+        }
+    }
+
+    // With the signature on stdout, status goes to stderr so it cannot
+    // corrupt the piped bytes
+    let status = match output {
+        Some(output) => {
+            fs::write(output, &signature)?;
+            let sidecar = Sidecar {
+                key_scheme: scheme.name().to_string(),
+                scheme_fingerprint: Some(scheme.fingerprint()),
+                epoch,
+                message: hex::encode(message.bytes),
+                prehash: message.prehash.map(|prehash| prehash.name().to_string()),
+                input_size: message.input_size,
+                created_at: unix_now(),
+            };
+            let json = serde_json::to_vec_pretty(&sidecar).map_err(io::Error::other)?;
+            fs::write(sidecar_path(output), json)?;
+            output.display().to_string()
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&signature)?;
+            stdout.flush()?;
+            "stdout".to_string()
+        }
+    };
+    let mut lines = vec![format!(
+        "🖊️  Signed message {} with {} at epoch {}: {}",
+        hex::encode(message.bytes),
+        signer.describe(),
+        epoch,
+        status
+    )];
+    if let (Some(bundle), Some(pk)) = (offline_bundle, &pk) {
+        offline_bundle::write(
+            bundle,
+            &SignedMessage {
+                scheme,
+                pubkey: pk,
+                epoch,
+                message: &message,
+                message_file,
+                signature: &signature,
+            },
+        )?;
+        lines.push(format!(
+            "📦 Verification bundle written to {}; anyone can check it with `hashsig verify-bundle`",
+            bundle.display()
+        ));
+    }
+    lines.push(format!(
+        "⚠️  Never sign again at epoch {} with this key.",
+        epoch
+    ));
+    for line in lines {
+        if output.is_some() {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Verify the signature in `signature` (`-` for stdin) against the public
+/// key `pk`. A file or stdin message is pre-hashed with `prehash`, else
+/// with the one recorded in the signature's sidecar, else with SHA-256.
+pub fn verify(
+    pk: &Path,
+    epoch: u32,
+    scheme_name: Option<&str>,
+    message: &MessageSource,
+    prehash: Option<PreHash>,
+    signature: &Path,
+) -> io::Result<()> {
+    let stdin = Path::new("-");
+    if signature == stdin && (message.message == Some(stdin) || message.message_file == Some(stdin))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The message and the signature cannot both be read from stdin",
+        ));
+    }
+
+    let scheme = scheme::for_key(scheme_name, pk)?;
+    let recorded = recorded_prehash(signature, scheme)?;
+    let prehash = prehash.or(recorded).unwrap_or(PreHash::Sha256);
+    let message = message.read(prehash)?;
+
+    if !scheme.verify(
+        &fs::read(pk)?,
+        epoch,
+        &message.bytes,
+        &read_input(signature)?,
+    )? {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "❌ Signature {} is INVALID for {} at epoch {}",
+                signature.display(),
+                pk.display(),
+                epoch
+            ),
+        ));
+    }
+    println!(
+        "✅ Signature {} is valid for {} at epoch {}",
+        signature.display(),
+        pk.display(),
+        epoch
+    );
+    Ok(())
+}
+
+/// One line of a `verify --batch-file`. Keys and signatures are given either
+/// as files or inline as hex, and the message as for `verify`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchJob {
+    /// Anything identifying the job, echoed in its result.
+    #[serde(default)]
+    id: Option<Value>,
+    pk: Option<PathBuf>,
+    pk_hex: Option<String>,
+    epoch: u32,
+    message_hex: Option<String>,
+    message_file: Option<PathBuf>,
+    prehash: Option<String>,
+    signature: Option<PathBuf>,
+    signature_hex: Option<String>,
+    scheme: Option<String>,
+}
+
+/// One line of `verify --batch-file` output.
+#[derive(Serialize)]
+struct BatchResult {
+    /// Line of the job in the batch, 1-based.
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    valid: bool,
+    /// Why the job could not be verified, if it could not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Defaults for the jobs of a batch, from the command line.
+pub struct BatchDefaults<'a> {
+    pub scheme_name: Option<&'a str>,
+    pub prehash: Option<PreHash>,
+}
+
+/// Verify every job of the JSONL batch `batch` (`-` for stdin), writing a
+/// JSON result per job to stdout as soon as it is known. Fails after the
+/// last job if any signature was invalid or could not be verified.
+pub fn verify_batch(batch: &Path, defaults: &BatchDefaults) -> io::Result<()> {
+    let reader: Box<dyn BufRead> = if batch == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(batch)?))
+    };
+    let mut stdout = io::stdout().lock();
+    // Schemes by (name, key directory), so manifests are read once per
+    // directory rather than once per job
+    let mut schemes = BTreeMap::new();
+    let mut jobs = 0;
+    let mut valid = 0;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        jobs += 1;
+        let (id, outcome) = match serde_json::from_str::<BatchJob>(&line) {
+            Ok(job) => {
+                let outcome = verify_job(&job, defaults, &mut schemes);
+                (job.id, outcome)
+            }
+            Err(e) => (
+                None,
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid job: {}", e),
+                )),
+            ),
+        };
+        let result = BatchResult {
+            line: number + 1,
+            id,
+            valid: matches!(outcome, Ok(true)),
+            error: outcome.err().map(|e| e.to_string()),
+        };
+        if result.valid {
+            valid += 1;
+        }
+        serde_json::to_writer(&mut stdout, &result).map_err(io::Error::other)?;
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()?;
+
+    // Status goes to stderr, so stdout stays valid JSONL
+    if valid < jobs {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "❌ {} of {} signatures in {} are invalid or could not be verified",
+                jobs - valid,
+                jobs,
+                batch.display()
+            ),
+        ));
+    }
+    eprintln!(
+        "✅ All {} signatures in {} are valid",
+        jobs,
+        batch.display()
+    );
+    Ok(())
+}
+
+/// Verify one batch job, returning whether its signature is valid.
+fn verify_job(
+    job: &BatchJob,
+    defaults: &BatchDefaults,
+    schemes: &mut BTreeMap<(Option<String>, PathBuf), &'static dyn SchemeHandle>,
+) -> io::Result<bool> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, reason.to_string());
+    let stdin = Path::new("-");
+    if job.message_file.as_deref() == Some(stdin) || job.signature.as_deref() == Some(stdin) {
+        return Err(invalid("Batch jobs cannot read from stdin"));
+    }
+
+    let scheme_name = job.scheme.as_deref().or(defaults.scheme_name);
+    let (pk, scheme) = match (&job.pk, &job.pk_hex) {
+        (Some(path), None) => {
+            let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            let scheme = match schemes.get(&(scheme_name.map(str::to_string), dir.clone())) {
+                Some(scheme) => *scheme,
+                None => {
+                    let scheme = scheme::for_key(scheme_name, path)?;
+                    schemes.insert((scheme_name.map(str::to_string), dir), scheme);
+                    scheme
+                }
+            };
+            (fs::read(path)?, scheme)
+        }
+        (None, Some(pk_hex)) => {
+            let scheme = match scheme_name {
+                Some(name) => scheme::lookup(name)?,
+                None => scheme::default_scheme(),
+            };
+            (decode_hex("pk_hex", pk_hex)?, scheme)
+        }
+        _ => return Err(invalid("Give exactly one of pk and pk_hex")),
+    };
+    let signature = match (&job.signature, &job.signature_hex) {
+        (Some(path), None) => fs::read(path)?,
+        (None, Some(signature_hex)) => decode_hex("signature_hex", signature_hex)?,
+        _ => return Err(invalid("Give exactly one of signature and signature_hex")),
+    };
+
+    let recorded = match &job.signature {
+        Some(path) => recorded_prehash(path, scheme)?,
+        None => None,
+    };
+    let prehash = match &job.prehash {
+        Some(name) => PreHash::from_str(name, false)
+            .map_err(|e| invalid(&format!("Invalid prehash: {}", e)))?,
+        None => defaults.prehash.or(recorded).unwrap_or(PreHash::Sha256),
+    };
+    let message = MessageSource {
+        message: None,
+        message_hex: job.message_hex.as_deref(),
+        message_file: job.message_file.as_deref(),
+    }
+    .read(prehash)?;
+    scheme.verify(&pk, job.epoch, &message.bytes, &signature)
+}
+
+fn decode_hex(field: &str, value: &str) -> io::Result<Vec<u8>> {
+    hex::decode(value.trim().trim_start_matches("0x")).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid {}: {}", field, e),
+        )
+    })
+}
+
+/// Pre-hash recorded in the sidecar of `signature`, if it has one. Fails if
+/// the sidecar records parameters other than `scheme`'s.
+fn recorded_prehash(signature: &Path, scheme: &dyn SchemeHandle) -> io::Result<Option<PreHash>> {
+    let path = sidecar_path(signature);
+    if signature == Path::new("-") || !path.exists() {
+        return Ok(None);
+    }
+    let source = path.display().to_string();
+    parse_sidecar(&fs::read(&path)?, scheme, &source)?
+        .prehash
+        .map(|name| PreHash::from_str(&name, false).map_err(|e| sidecar_error(&source, e)))
+        .transpose()
+}
+
+fn parse_sidecar(contents: &[u8], scheme: &dyn SchemeHandle, source: &str) -> io::Result<Sidecar> {
+    let sidecar: Sidecar =
+        serde_json::from_slice(contents).map_err(|e| sidecar_error(source, e.to_string()))?;
+    scheme::check_fingerprint(scheme, sidecar.scheme_fingerprint.as_deref(), source)?;
+    Ok(sidecar)
+}
+
+fn sidecar_error(source: &str, reason: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Failed to parse {}: {}", source, reason),
+    )
+}
+
+/// Check `signature`, made by `sign` over the file `contents` and described
+/// by its `sidecar` (read from `source`), against the public key `pk`.
+/// Returns the epoch it was made at.
+pub fn verify_file_signature(
+    scheme: &dyn SchemeHandle,
+    pk: &[u8],
+    contents: &[u8],
+    signature: &[u8],
+    sidecar: &[u8],
+    source: &str,
+) -> io::Result<u32> {
+    let sidecar = parse_sidecar(sidecar, scheme, source)?;
+    if sidecar.key_scheme != scheme.name() {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} records a {} signature, not {}",
+                source,
+                sidecar.key_scheme,
+                scheme.name()
+            ),
+        ));
+    }
+    let prehash = match sidecar.prehash {
+        Some(name) => PreHash::from_str(&name, false).map_err(|e| sidecar_error(source, e))?,
+        None => PreHash::Sha256,
+    };
+    let (message, _) = prehash.digest(&mut &contents[..])?;
+    if !scheme.verify(pk, sidecar.epoch, &message, signature)? {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("❌ Signature described by {} is INVALID", source),
+        ));
+    }
+    Ok(sidecar.epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: BatchDefaults<'static> = BatchDefaults {
+        scheme_name: None,
+        prehash: None,
+    };
+
+    fn job(line: &str) -> Result<BatchJob, serde_json::Error> {
+        serde_json::from_str(line)
+    }
+
+    fn job_error(line: &str) -> String {
+        let job = job(line).unwrap();
+        verify_job(&job, &DEFAULTS, &mut BTreeMap::new())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn parses_jobs() {
+        let parsed = job(
+            r#"{"id": {"n": 7}, "pk_hex": "0x00", "epoch": 3, "message_hex": "11", "signature_hex": "22", "prehash": "sha256"}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.id, Some(serde_json::json!({"n": 7})));
+        assert_eq!(parsed.epoch, 3);
+        assert_eq!(parsed.pk_hex.as_deref(), Some("0x00"));
+        assert!(parsed.pk.is_none() && parsed.signature.is_none());
+
+        assert!(
+            job(r#"{"pk_hex": "00", "epoch": 1, "signature_hex": "00", "sig": "00"}"#).is_err()
+        );
+        assert!(job(r#"{"pk_hex": "00", "signature_hex": "00"}"#).is_err());
+        assert!(job(r#"{"pk_hex": "00", "epoch": -1, "signature_hex": "00"}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_ambiguous_jobs() {
+        assert!(job_error(r#"{"epoch": 0, "signature_hex": "00"}"#).contains("pk and pk_hex"));
+        assert!(job_error(
+            r#"{"pk": "pk.ssz", "pk_hex": "00", "epoch": 0, "signature_hex": "00"}"#
+        )
+        .contains("pk and pk_hex"));
+        assert!(
+            job_error(r#"{"pk_hex": "00", "epoch": 0}"#).contains("signature and signature_hex")
+        );
+        assert!(job_error(
+            r#"{"pk_hex": "00", "epoch": 0, "message_file": "-", "signature_hex": "00"}"#
+        )
+        .contains("stdin"));
+        assert!(
+            job_error(r#"{"pk_hex": "zz", "epoch": 0, "signature_hex": "00"}"#)
+                .contains("Invalid pk_hex")
+        );
+        assert!(job_error(
+            r#"{"pk_hex": "00", "epoch": 0, "signature_hex": "00", "prehash": "md5"}"#
+        )
+        .contains("Invalid prehash"));
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex("pk_hex", " 0x0aFF\n").unwrap(), [0x0a, 0xff]);
+        assert!(decode_hex("pk_hex", "0x0").is_err());
+    }
+
+    #[test]
+    fn fails_batches_with_bad_lines() {
+        let path =
+            std::env::temp_dir().join(format!("hashsig-sign-batch-{}.jsonl", std::process::id()));
+        fs::write(&path, "\n{\"epoch\": 0}\nnot json\n").unwrap();
+        let err = verify_batch(&path, &DEFAULTS).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+        assert!(err.to_string().contains("2 of 2"));
+    }
+}
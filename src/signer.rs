@@ -0,0 +1,404 @@
+//! Where signing keys come from.
+//!
+//! Every command that signs does so through [`Signer`], so a new place to
+//! keep keys only needs an implementation here and a [`KeySource`] variant,
+//! not changes to each command. Secret keys held in memory are zeroized when
+//! they are dropped.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use leansig::MESSAGE_LENGTH;
+use zeroize::Zeroizing;
+
+use crate::backup;
+use crate::entropy::{self, DeterministicEntropy, EntropySource};
+use crate::exit::{self, Failure};
+use crate::explain::Cause;
+use crate::manifest::{self, Manifest, MANIFEST_FILE};
+use crate::metadata::KeyMetadata;
+use crate::scheme::{self, KeyPair, SchemeHandle};
+
+/// A hash-based key that can sign.
+pub trait Signer {
+    fn scheme(&self) -> &'static dyn SchemeHandle;
+
+    /// SSZ-encoded public key, if it is available.
+    fn pubkey(&self) -> io::Result<Option<Vec<u8>>>;
+
+    /// Sign `message` at `epoch`, returning the SSZ-encoded signature. Never
+    /// sign twice at the same epoch with one key.
+    fn sign(&self, epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>>;
+
+    /// Where the key comes from, for status output.
+    fn describe(&self) -> String;
+
+    /// Whether the key is a canary from `generate --with-canary`, per its
+    /// manifest entry or metadata.
+    fn is_canary(&self) -> bool;
+
+    /// Manifest kept with the key, if there is one.
+    fn manifest(&self) -> Option<&Manifest>;
+}
+
+/// Refuse to sign with a canary key, raising an alert on stderr. Call
+/// before every signature: a canary key is never registered, so any
+/// request for one comes from a misrouted or compromised client.
+pub fn refuse_canary(signer: &dyn Signer) -> io::Result<()> {
+    if !signer.is_canary() {
+        return Ok(());
+    }
+    eprintln!(
+        "🚨🚨🚨 CANARY TRIGGERED: a signature was requested from canary key {}. No legitimate request ever targets a canary; investigate who sent it before signing anything else.",
+        signer.describe()
+    );
+    Err(exit::error(
+        Failure::CanaryTriggered,
+        format!("{} is a canary key; refusing to sign", signer.describe()),
+    ))
+}
+
+/// Refuse to sign with a key whose public key is revoked in `manifest` or
+/// in the manifest kept with the key. Call before every signature: a
+/// revoked key may come back from a backup, an archive or its seed, and
+/// `revoke` only removes the copy in the key directory.
+pub fn refuse_revoked(signer: &dyn Signer, manifest: Option<&Manifest>) -> io::Result<()> {
+    let Some(pk) = signer.pubkey()? else {
+        return Ok(());
+    };
+    let pubkey = hex::encode(pk);
+    let tombstone = manifest
+        .into_iter()
+        .chain(signer.manifest())
+        .flat_map(|manifest| &manifest.revoked)
+        .find(|tombstone| manifest::normalize_pubkey(&tombstone.pubkey_hex) == pubkey);
+    match tombstone {
+        Some(tombstone) => Err(exit::error(
+            Failure::InvalidArgs,
+            format!(
+                "{} is revoked ({}); refusing to sign",
+                signer.describe(),
+                tombstone.reason
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// How to find the key to sign with.
+pub enum KeySource<'a> {
+    /// An SSZ secret key file.
+    File(&'a Path),
+    /// The key with file prefix `name` (e.g. `validator_0`) in an encrypted
+    /// archive or backup bundle.
+    Archive {
+        bundle: &'a Path,
+        name: &'a str,
+        passphrase: String,
+    },
+    /// The key at `path` of a deterministic ceremony's master seed.
+    Derived {
+        seed_file: &'a Path,
+        path: &'a str,
+        log_num_active_epochs: usize,
+    },
+}
+
+impl KeySource<'_> {
+    /// Open the key for `scheme_name`, defaulting to the scheme recorded with
+    /// the key, else the default scheme.
+    pub fn open(self, scheme_name: Option<&str>) -> io::Result<Box<dyn Signer>> {
+        Ok(match self {
+            KeySource::File(sk) => Box::new(KeySigner::open(sk, scheme_name)?),
+            KeySource::Archive {
+                bundle,
+                name,
+                passphrase,
+            } => Box::new(ArchiveSigner::open(
+                bundle,
+                name,
+                Zeroizing::new(passphrase),
+                scheme_name,
+            )?),
+            KeySource::Derived {
+                seed_file,
+                path,
+                log_num_active_epochs,
+            } => Box::new(DerivedSigner::derive(
+                seed_file,
+                path,
+                log_num_active_epochs,
+                scheme_name,
+            )?),
+        })
+    }
+}
+
+/// A secret key file, read into memory. Its public key is taken from the
+/// `_pk.ssz` file next to it, if there is one.
+pub struct KeySigner {
+    scheme: &'static dyn SchemeHandle,
+    sk: Zeroizing<Vec<u8>>,
+    pk: Option<Vec<u8>>,
+    path: PathBuf,
+    canary: bool,
+    manifest: Option<Manifest>,
+}
+
+impl KeySigner {
+    pub fn open(sk: &Path, scheme_name: Option<&str>) -> io::Result<Self> {
+        let scheme = scheme::for_key(scheme_name, sk)?;
+        let name = sk.file_name().unwrap_or_default().to_string_lossy();
+        let pk_path = name
+            .strip_suffix("_sk.ssz")
+            .map(|prefix| sk.with_file_name(format!("{}_pk.ssz", prefix)))
+            .filter(|path| path.is_file());
+        let dir = sk.parent().unwrap_or(Path::new("."));
+        let metadata_path = name
+            .strip_suffix("_sk.ssz")
+            .map(|prefix| dir.join(format!("{}.meta.json", prefix)))
+            .filter(|path| path.is_file());
+        let metadata = metadata_path.map(fs::read).transpose()?;
+        let manifest = Manifest::read_if_present(dir)?;
+        let canary = is_canary(manifest.as_ref(), &name)
+            || metadata.is_some_and(|metadata| metadata_is_canary(&metadata));
+        Ok(Self {
+            scheme,
+            sk: Zeroizing::new(fs::read(sk)?),
+            pk: pk_path.map(fs::read).transpose()?,
+            path: sk.to_path_buf(),
+            canary,
+            manifest,
+        })
+    }
+}
+
+impl Signer for KeySigner {
+    fn scheme(&self) -> &'static dyn SchemeHandle {
+        self.scheme
+    }
+
+    fn pubkey(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.pk.clone())
+    }
+
+    fn sign(&self, epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>> {
+        self.scheme.sign(&self.sk, epoch, message)
+    }
+
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn is_canary(&self) -> bool {
+        self.canary
+    }
+
+    fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+}
+
+/// A key kept in an encrypted archive or backup bundle. The bundle is
+/// decrypted on every signature, so the secret key is only in memory while
+/// signing.
+pub struct ArchiveSigner {
+    scheme: &'static dyn SchemeHandle,
+    bundle: PathBuf,
+    passphrase: Zeroizing<String>,
+    name: String,
+    pk: Vec<u8>,
+    canary: bool,
+    manifest: Option<Manifest>,
+}
+
+impl ArchiveSigner {
+    pub fn open(
+        bundle: &Path,
+        name: &str,
+        passphrase: Zeroizing<String>,
+        scheme_name: Option<&str>,
+    ) -> io::Result<Self> {
+        let sk_file = format!("{}_sk.ssz", name);
+        let pk_file = format!("{}_pk.ssz", name);
+        let meta_file = format!("{}.meta.json", name);
+        let mut recorded = None;
+        let mut pk = None;
+        let mut has_sk = false;
+        let mut canary_metadata = false;
+        backup::inspect_from(open_bundle(bundle)?, &passphrase, |file, contents| {
+            if file == MANIFEST_FILE {
+                recorded = Some(Manifest::parse(&String::from_utf8_lossy(contents), file)?);
+            } else if file == pk_file {
+                pk = Some(contents.to_vec());
+            } else if file == sk_file {
+                has_sk = true;
+            } else if file == meta_file {
+                canary_metadata = metadata_is_canary(contents);
+            }
+            Ok(())
+        })?;
+        let (Some(pk), true) = (pk, has_sk) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{} does not hold both {} and {}",
+                    bundle.display(),
+                    sk_file,
+                    pk_file
+                ),
+            ));
+        };
+
+        let canary = canary_metadata || is_canary(recorded.as_ref(), &sk_file);
+        let source = format!("{} in {}", MANIFEST_FILE, bundle.display());
+        let recorded_scheme = recorded
+            .as_ref()
+            .map(|manifest| scheme::for_manifest(manifest, &source))
+            .transpose()?;
+        let scheme = match scheme_name {
+            Some(name) => scheme::lookup(name)?,
+            None => recorded_scheme.unwrap_or_else(scheme::default_scheme),
+        };
+        if let Some(recorded) = recorded_scheme.filter(|r| r.name() != scheme.name()) {
+            return Err(exit::diagnosed(
+                Cause::SchemeMismatch,
+                format!(
+                    "{} belongs to {}, not {}; refusing to use it across schemes",
+                    bundle.display(),
+                    recorded.name(),
+                    scheme.name()
+                ),
+            ));
+        }
+
+        Ok(Self {
+            scheme,
+            bundle: bundle.to_path_buf(),
+            passphrase,
+            name: name.to_string(),
+            pk,
+            canary,
+            manifest: recorded,
+        })
+    }
+}
+
+impl Signer for ArchiveSigner {
+    fn scheme(&self) -> &'static dyn SchemeHandle {
+        self.scheme
+    }
+
+    fn pubkey(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(Some(self.pk.clone()))
+    }
+
+    fn sign(&self, epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>> {
+        let sk_file = format!("{}_sk.ssz", self.name);
+        let mut signature = None;
+        backup::inspect_from(open_bundle(&self.bundle)?, &self.passphrase, |file, sk| {
+            if file == sk_file {
+                signature = Some(self.scheme.sign(sk, epoch, message)?);
+            }
+            Ok(())
+        })?;
+        signature.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} no longer holds {}", self.bundle.display(), sk_file),
+            )
+        })
+    }
+
+    fn describe(&self) -> String {
+        format!("{} in {}", self.name, self.bundle.display())
+    }
+
+    fn is_canary(&self) -> bool {
+        self.canary
+    }
+
+    fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+}
+
+/// A key regenerated in memory from the master seed of a deterministic
+/// ceremony, as `derive` would write it.
+pub struct DerivedSigner {
+    scheme: &'static dyn SchemeHandle,
+    pair: Box<dyn KeyPair>,
+    path: String,
+}
+
+impl DerivedSigner {
+    pub fn derive(
+        seed_file: &Path,
+        path: &str,
+        log_num_active_epochs: usize,
+        scheme_name: Option<&str>,
+    ) -> io::Result<Self> {
+        let index = entropy::parse_derivation_path(path)
+            .map_err(|reason| io::Error::new(io::ErrorKind::InvalidInput, reason))?;
+        let scheme = match scheme_name {
+            Some(name) => scheme::lookup(name)?,
+            None => scheme::default_scheme(),
+        };
+        let mut rng = DeterministicEntropy::from_seed_file(seed_file)?.rng_for(index)?;
+        Ok(Self {
+            scheme,
+            pair: scheme.key_gen(rng.as_mut(), 0, 1 << log_num_active_epochs),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Signer for DerivedSigner {
+    fn scheme(&self) -> &'static dyn SchemeHandle {
+        self.scheme
+    }
+
+    fn pubkey(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(Some(self.pair.pk_bytes()))
+    }
+
+    fn sign(&self, epoch: u32, message: &[u8; MESSAGE_LENGTH]) -> io::Result<Vec<u8>> {
+        self.scheme
+            .sign(&Zeroizing::new(self.pair.sk_bytes()), epoch, message)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} of the master seed", self.path)
+    }
+
+    /// Canaries are only ever generated after a ceremony's real keys, and
+    /// `derive` does not know where that boundary was.
+    fn is_canary(&self) -> bool {
+        false
+    }
+
+    fn manifest(&self) -> Option<&Manifest> {
+        None
+    }
+}
+
+fn open_bundle(bundle: &Path) -> io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(bundle)?))
+}
+
+/// Whether `manifest` marks the key in `sk_file` as a canary.
+fn is_canary(manifest: Option<&Manifest>, sk_file: &str) -> bool {
+    manifest.is_some_and(|manifest| {
+        manifest
+            .validators
+            .iter()
+            .any(|v| v.canary && manifest::file_name(&v.privkey_file) == sk_file)
+    })
+}
+
+/// Whether the key metadata in `contents` marks a canary. Unreadable
+/// metadata does not block signing; `verify-dir` reports it.
+fn metadata_is_canary(contents: &[u8]) -> bool {
+    serde_json::from_slice::<KeyMetadata>(contents).is_ok_and(|metadata| metadata.canary)
+}
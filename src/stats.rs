@@ -0,0 +1,218 @@
+//! Local run statistics.
+//!
+//! `generate` records how a run went in [`RUN_STATS_FILE`] next to the keys.
+//! Nothing is ever sent anywhere; `stats aggregate` combines the files of
+//! several key directories into trend data across ceremonies.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(not(feature = "verify-only"))]
+use std::thread;
+#[cfg(not(feature = "verify-only"))]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::build_info::BuildInfo;
+#[cfg(not(feature = "verify-only"))]
+use crate::generate::PipelineConfig;
+#[cfg(not(feature = "verify-only"))]
+use crate::keystore::KeyStore;
+#[cfg(not(feature = "verify-only"))]
+use crate::metadata::unix_now;
+#[cfg(not(feature = "verify-only"))]
+use crate::scheme::SchemeHandle;
+use crate::units::format_secs;
+
+/// File written next to the keys after every `generate` run.
+pub const RUN_STATS_FILE: &str = "run-stats.json";
+
+/// The machine a run happened on. Deliberately nothing identifying.
+#[derive(Serialize, Deserialize)]
+pub struct MachineSpecs {
+    pub cpus: usize,
+    pub os: String,
+    pub arch: String,
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl MachineSpecs {
+    pub fn current() -> Self {
+        Self {
+            cpus: thread::available_parallelism().map_or(1, |n| n.get()),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+/// Contents of [`RUN_STATS_FILE`].
+#[derive(Serialize, Deserialize)]
+pub struct RunStats {
+    pub tool_version: String,
+    /// Absent from runs recorded before build metadata was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
+    pub created_at: u64,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ceremony_id: Option<String>,
+    pub key_scheme: String,
+    pub num_validators: usize,
+    pub log_num_active_epochs: usize,
+    pub compute_threads: usize,
+    pub writer_threads: usize,
+    /// Throttle of the run, if any; its rate is a limit, not the machine's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_keys_per_minute: Option<u32>,
+    pub duration_secs: f64,
+    /// Key pairs generated; 0 if the run failed.
+    pub keys_generated: usize,
+    pub keys_per_sec: f64,
+    pub error_count: usize,
+    pub errors: Vec<String>,
+    pub machine: MachineSpecs,
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl RunStats {
+    /// Statistics of a `generate` run that took `duration`. `outcome` is
+    /// the number of key pairs generated, or the error the run failed with.
+    pub fn generate(
+        ceremony_id: &str,
+        scheme: &dyn SchemeHandle,
+        num_validators: usize,
+        log_num_active_epochs: usize,
+        pipeline: &PipelineConfig,
+        duration: Duration,
+        outcome: Result<usize, &io::Error>,
+    ) -> Self {
+        let (keys_generated, errors) = match outcome {
+            Ok(keys_generated) => (keys_generated, Vec::new()),
+            Err(e) => (0, vec![e.to_string()]),
+        };
+        let duration_secs = duration.as_secs_f64();
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            build: Some(BuildInfo::current()),
+            created_at: unix_now(),
+            command: "generate".to_string(),
+            ceremony_id: Some(ceremony_id.to_string()),
+            key_scheme: scheme.name().to_string(),
+            num_validators,
+            log_num_active_epochs,
+            compute_threads: pipeline.compute_threads,
+            writer_threads: pipeline.writer_threads,
+            max_keys_per_minute: pipeline.max_keys_per_minute,
+            duration_secs,
+            keys_generated,
+            keys_per_sec: keys_generated as f64 / duration_secs,
+            error_count: errors.len(),
+            errors,
+            machine: MachineSpecs::current(),
+        }
+    }
+
+    pub fn write(&self, store: &dyn KeyStore) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        store.store(RUN_STATS_FILE, &json)
+    }
+}
+
+/// Combined statistics of several runs.
+#[derive(Serialize)]
+struct AggregateStats {
+    runs: usize,
+    keys_generated: usize,
+    duration_secs: f64,
+    keys_per_sec: f64,
+    error_count: usize,
+    /// Per-run statistics, oldest first.
+    per_run: Vec<AggregatedRun>,
+}
+
+#[derive(Serialize)]
+struct AggregatedRun {
+    dir: String,
+    #[serde(flatten)]
+    stats: RunStats,
+}
+
+/// Combine the run statistics of `dirs` into one summary, optionally
+/// written to `output` as JSON.
+pub fn aggregate(dirs: &[PathBuf], output: Option<&Path>) -> io::Result<()> {
+    let mut per_run = Vec::new();
+    for dir in dirs {
+        let path = dir.join(RUN_STATS_FILE);
+        if !path.exists() {
+            println!("⚠️  No {} in {}, skipping", RUN_STATS_FILE, dir.display());
+            continue;
+        }
+        let stats: RunStats = serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), e),
+            )
+        })?;
+        per_run.push(AggregatedRun {
+            dir: dir.display().to_string(),
+            stats,
+        });
+    }
+    if per_run.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No {} found in the given directories", RUN_STATS_FILE),
+        ));
+    }
+    per_run.sort_by_key(|run| run.stats.created_at);
+
+    println!("📊 {} runs:\n", per_run.len());
+    println!(
+        "  {:<32} {:>10} {:>6} {:>8} {:>10} {:>10} {:>6} {:>6}",
+        "Directory", "Created", "Keys", "Epochs", "Duration", "Keys/s", "Cpus", "Errors"
+    );
+    for run in &per_run {
+        let stats = &run.stats;
+        let epochs = format!("2^{}", stats.log_num_active_epochs);
+        println!(
+            "  {:<32} {:>10} {:>6} {:>8} {:>10} {:>10.3} {:>6} {:>6}",
+            run.dir,
+            stats.created_at,
+            stats.keys_generated,
+            epochs,
+            format_secs(stats.duration_secs),
+            stats.keys_per_sec,
+            stats.machine.cpus,
+            stats.error_count
+        );
+    }
+
+    let keys_generated: usize = per_run.iter().map(|run| run.stats.keys_generated).sum();
+    let duration_secs: f64 = per_run.iter().map(|run| run.stats.duration_secs).sum();
+    let aggregate = AggregateStats {
+        runs: per_run.len(),
+        keys_generated,
+        duration_secs,
+        keys_per_sec: keys_generated as f64 / duration_secs,
+        error_count: per_run.iter().map(|run| run.stats.error_count).sum(),
+        per_run,
+    };
+
+    println!(
+        "\n  Total: {} keys in {} ({:.3} keys/s), {} errors",
+        aggregate.keys_generated,
+        format_secs(aggregate.duration_secs),
+        aggregate.keys_per_sec,
+        aggregate.error_count
+    );
+
+    if let Some(output) = output {
+        let json = serde_json::to_vec_pretty(&aggregate).map_err(io::Error::other)?;
+        fs::write(output, json)?;
+        println!("\n📄 Aggregate written to: {}", output.display());
+    }
+
+    Ok(())
+}
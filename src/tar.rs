@@ -0,0 +1,197 @@
+//! Minimal ustar archives of regular files: written by `push` and by
+//! `sign --offline-bundle`, read by `verify-bundle`.
+
+use std::io::{self, Write};
+
+use crate::metadata::unix_now;
+
+pub const BLOCK: usize = 512;
+
+/// Mode of files only their owner may read.
+pub const MODE_PRIVATE: u32 = 0o600;
+
+/// Mode of files anyone may read.
+pub const MODE_PUBLIC: u32 = 0o644;
+
+/// Header block of a regular file of `size` bytes with permissions `mode`.
+pub fn write_header(writer: &mut impl Write, name: &str, size: u64, mode: u32) -> io::Result<()> {
+    if name.len() > 100 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("File name {} is too long for a tar archive", name),
+        ));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(format!("{:07o}\0", mode).as_bytes());
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", unix_now()).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    let sum = checksum(&header);
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    writer.write_all(&header)
+}
+
+/// Zeros filling a file of `size` bytes up to the next block.
+pub fn write_padding(writer: &mut impl Write, size: u64) -> io::Result<()> {
+    let padding = (BLOCK - (size % BLOCK as u64) as usize) % BLOCK;
+    writer.write_all(&[0; BLOCK][..padding])
+}
+
+/// A regular file with its header and padding.
+pub fn write_file(
+    writer: &mut impl Write,
+    name: &str,
+    contents: &[u8],
+    mode: u32,
+) -> io::Result<()> {
+    write_header(writer, name, contents.len() as u64, mode)?;
+    writer.write_all(contents)?;
+    write_padding(writer, contents.len() as u64)
+}
+
+/// The two zero blocks ending an archive.
+pub fn write_end(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(&[0; 2 * BLOCK])
+}
+
+/// Sum of the header bytes, with the checksum field counted as spaces.
+fn checksum(header: &[u8]) -> u32 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u32::from(b)
+            }
+        })
+        .sum()
+}
+
+/// The regular files of the archive `bytes`, by name, in archive order.
+/// Entries of other types are skipped.
+pub fn read_files<'a>(bytes: &'a [u8], source: &str) -> io::Result<Vec<(String, &'a [u8])>> {
+    let malformed = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a valid tar archive: {}", source, reason),
+        )
+    };
+    let octal = |field: &[u8]| {
+        let digits = String::from_utf8_lossy(field);
+        let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+        u64::from_str_radix(digits, 8).map_err(|_| malformed("bad number in a header"))
+    };
+
+    let mut files = Vec::new();
+    let mut offset = 0;
+    loop {
+        let header = bytes
+            .get(offset..offset + BLOCK)
+            .ok_or_else(|| malformed("truncated"))?;
+        if header.iter().all(|&b| b == 0) {
+            return Ok(files);
+        }
+        if octal(&header[148..156])? != u64::from(checksum(header)) {
+            return Err(malformed("header checksum mismatch"));
+        }
+        let size =
+            usize::try_from(octal(&header[124..136])?).map_err(|_| malformed("file too large"))?;
+        let start = offset + BLOCK;
+        let contents = bytes
+            .get(start..start + size)
+            .ok_or_else(|| malformed("truncated"))?;
+        if matches!(header[156], b'0' | b'\0') {
+            let end = header[..100].iter().position(|&b| b == 0).unwrap_or(100);
+            let name = std::str::from_utf8(&header[..end])
+                .map_err(|_| malformed("file name is not UTF-8"))?;
+            files.push((name.to_string(), contents));
+        }
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+
+    fn archive() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_file(&mut bytes, "bundle.json", b"{}", MODE_PUBLIC).unwrap();
+        write_file(&mut bytes, "empty", b"", MODE_PUBLIC).unwrap();
+        write_file(&mut bytes, "block", &[7; BLOCK], MODE_PRIVATE).unwrap();
+        write_file(&mut bytes, "odd", &[1; BLOCK + 3], MODE_PUBLIC).unwrap();
+        write_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let bytes = archive();
+        assert_eq!(bytes.len() % BLOCK, 0);
+        let files = read_files(&bytes, "test").unwrap();
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["bundle.json", "empty", "block", "odd"]);
+        assert_eq!(files[0].1, b"{}");
+        assert!(files[1].1.is_empty());
+        assert_eq!(files[2].1, &[7; BLOCK][..]);
+        assert_eq!(files[3].1, &[1; BLOCK + 3][..]);
+    }
+
+    #[test]
+    fn rejects_corrupt_and_truncated_archives() {
+        let mut bytes = archive();
+        bytes[0] ^= 1;
+        assert!(read_files(&bytes, "test").is_err());
+
+        let bytes = archive();
+        assert!(read_files(&bytes[..BLOCK + 1], "test").is_err());
+        assert!(read_files(&bytes[..3 * BLOCK], "test").is_err());
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        let name = "n".repeat(101);
+        assert!(write_header(&mut Vec::new(), &name, 0, MODE_PUBLIC).is_err());
+    }
+
+    #[test]
+    fn lists_with_system_tar() {
+        let path =
+            std::env::temp_dir().join(format!("hashsig-tar-test-{}.tar", std::process::id()));
+        std::fs::write(&path, archive()).unwrap();
+        let output = Command::new("tar").arg("-tvf").arg(&path).output();
+        std::fs::remove_file(&path).unwrap();
+        let Ok(output) = output else {
+            eprintln!("tar not found; skipping");
+            return;
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let listing = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(lines.len(), 4);
+        for (line, (name, size, mode)) in lines.iter().zip([
+            ("bundle.json", 2, "-rw-r--r--"),
+            ("empty", 0, "-rw-r--r--"),
+            ("block", BLOCK, "-rw-------"),
+            ("odd", BLOCK + 3, "-rw-r--r--"),
+        ]) {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            assert_eq!(fields[0], mode);
+            assert_eq!(fields[2], size.to_string());
+            assert_eq!(fields.last(), Some(&name));
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! `gen-test-net`: what a devnet needs from a fresh key set, in one command.
+//!
+//! ```text
+//! <output>/
+//!   keys/                  every key pair with the manifest and metadata
+//!   deposit-data.json      what registering each validator takes
+//!   genesis-registry.ssz   as written by `export --format ssz-registry`
+//!   node-0/ .. node-<n>/   equal shares of the validators, as `assign`
+//!   assignment.json        writes them
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::assign::{self, AssignMode};
+use crate::keys;
+use crate::manifest::{self, normalize_pubkey, Manifest, SortOrder};
+use crate::registry::{self, ExportKind};
+
+/// Directory of `<output>` holding every key.
+pub const KEYS_DIR: &str = "keys";
+
+const DEPOSIT_DATA_FILE: &str = "deposit-data.json";
+const REGISTRY_FILE: &str = "genesis-registry.ssz";
+
+/// One validator's entry in `deposit-data.json`.
+#[derive(Serialize)]
+struct DepositData {
+    index: Option<usize>,
+    pubkey_hex: String,
+    fingerprint: String,
+    pubkey_file: String,
+    key_scheme: String,
+    hash_function: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scheme_fingerprint: Option<String>,
+    num_active_epochs: u64,
+}
+
+/// Check the node count before any key is generated.
+pub fn check_nodes(nodes: usize, num_validators: usize) -> io::Result<()> {
+    if nodes == 0 || nodes > num_validators {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--nodes must be between 1 and the number of validators ({}), not {}",
+                num_validators, nodes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Write the deposit data, the genesis registry and the per-node
+/// directories for the key set generated into `<output_dir>/keys`.
+pub fn finish(output_dir: &Path, nodes: usize) -> io::Result<()> {
+    let keys_dir = output_dir.join(KEYS_DIR);
+    let manifest = Manifest::read(&keys_dir)?;
+
+    println!();
+    write_deposit_data(&manifest, &output_dir.join(DEPOSIT_DATA_FILE))?;
+    registry::export(
+        &keys_dir,
+        ExportKind::SszRegistry,
+        &output_dir.join(REGISTRY_FILE),
+        SortOrder::Index,
+        None,
+    )?;
+    println!();
+    assign::assign(&keys_dir, nodes, &[], AssignMode::Copy, output_dir)?;
+
+    println!(
+        "\n✅ Test network for {} validators on {} nodes ready in {}",
+        manifest.validators.len(),
+        nodes,
+        output_dir.display()
+    );
+    Ok(())
+}
+
+fn write_deposit_data(manifest: &Manifest, path: &Path) -> io::Result<()> {
+    let mut deposits = Vec::with_capacity(manifest.validators.len());
+    // Canaries are never registered
+    for validator in manifest.validators.iter().filter(|entry| !entry.canary) {
+        let pk_bytes = hex::decode(normalize_pubkey(&validator.pubkey_hex)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid pubkey_hex for {}: {}", validator.privkey_file, e),
+            )
+        })?;
+        let name = manifest::file_name(&validator.privkey_file);
+        let prefix = name.strip_suffix("_sk.ssz").unwrap_or(name);
+        deposits.push(DepositData {
+            index: validator.index,
+            pubkey_hex: validator.pubkey_hex.clone(),
+            fingerprint: keys::fingerprint(&pk_bytes),
+            pubkey_file: format!("{}_pk.ssz", prefix),
+            key_scheme: manifest.key_scheme.clone(),
+            hash_function: manifest.hash_function.clone(),
+            scheme_fingerprint: manifest.scheme_fingerprint.clone(),
+            num_active_epochs: manifest.num_active_epochs,
+        });
+    }
+    let json = serde_json::to_vec_pretty(&deposits).map_err(io::Error::other)?;
+    fs::write(path, json)?;
+    println!(
+        "📄 Deposit data for {} validators: {}",
+        deposits.len(),
+        path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,347 @@
+//! `push` and `pull`: move a key directory straight to or from a signing
+//! host over SSH, without intermediate copies.
+//!
+//! Transfers go through the system `ssh` client, so `~/.ssh/config`, agents
+//! and jump hosts apply as usual. The remote host only needs a POSIX shell,
+//! `tar` and `sha256sum`. Every file is checked against its SHA-256 on
+//! arrival: for `push`, the remote host hashes what it wrote; for `pull`, it
+//! hashes what it sends and the received bytes are checked before they are
+//! written.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use zeroize::Zeroizing;
+
+use crate::checksum::{self, HashingReader};
+use crate::exit::{self, Failure};
+use crate::keystore::{FsKeyStore, KeyStore, ManifestKeyStore};
+use crate::layout::LAYOUT_FILE;
+use crate::manifest::{self, Manifest, MANIFEST_FILE};
+use crate::tar;
+
+/// A directory on a remote host, from `ssh://[user@]host[:port]/path`.
+/// Paths starting with `/~/` are relative to the remote home directory.
+#[derive(Debug)]
+pub struct SshTarget {
+    destination: String,
+    port: Option<u16>,
+    path: String,
+}
+
+impl SshTarget {
+    pub fn parse(url: &str) -> io::Result<Self> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid SSH location {:?}: {}", url, reason),
+            )
+        };
+        let rest = url
+            .strip_prefix("ssh://")
+            .ok_or_else(|| invalid("expected ssh://[user@]host[:port]/path"))?;
+        let (authority, path) = rest
+            .split_once('/')
+            .ok_or_else(|| invalid("no path after the host"))?;
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(port.parse().map_err(|_| invalid("invalid port"))?),
+            ),
+            None => (host_port, None),
+        };
+        if host.is_empty() || path.is_empty() {
+            return Err(invalid("empty host or path"));
+        }
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => relative.to_string(),
+            None => format!("/{}", path),
+        };
+        Ok(Self {
+            destination: match user {
+                Some(user) => format!("{}@{}", user, host),
+                None => host.to_string(),
+            },
+            port,
+            path,
+        })
+    }
+
+    /// `ssh` running `script` in the remote shell.
+    fn command(&self, script: &str) -> Command {
+        let mut command = Command::new("ssh");
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        command.arg("--").arg(&self.destination).arg(script);
+        command
+    }
+
+    /// Run `script`, returning its exit code; fails if `ssh` itself fails.
+    fn run(&self, script: &str) -> io::Result<i32> {
+        let status = self.command(script).stdin(Stdio::null()).status()?;
+        match status.code() {
+            Some(255) | None => Err(io::Error::other(format!(
+                "ssh to {} failed",
+                self.destination
+            ))),
+            Some(code) => Ok(code),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{}:{}", self.destination, self.path)
+    }
+}
+
+/// Copy the key directory `dir` to `target`, which must not hold a manifest
+/// yet. Secret keys the manifest places elsewhere are sent along, and the
+/// remote manifest refers to every key by file name.
+pub fn push(dir: &Path, target: &SshTarget) -> io::Result<()> {
+    let remote_dir = shell_quote(&target.path);
+    if target.run(&format!(
+        "test ! -e {}/{}",
+        remote_dir,
+        shell_quote(MANIFEST_FILE)
+    ))? != 0
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                target.describe()
+            ),
+        ));
+    }
+
+    let store = ManifestKeyStore::open(dir)?;
+    // The copy is flat, so the source's layout marker stays behind
+    let names: Vec<String> = store
+        .list()?
+        .into_iter()
+        .filter(|name| name != LAYOUT_FILE)
+        .collect();
+    let manifest = match Manifest::read_if_present(dir)? {
+        Some(mut manifest) => {
+            for validator in &mut manifest.validators {
+                validator.privkey_file = manifest::file_name(&validator.privkey_file).to_string();
+            }
+            for tombstone in &mut manifest.revoked {
+                tombstone.privkey_file = manifest::file_name(&tombstone.privkey_file).to_string();
+            }
+            Some(manifest.to_yaml())
+        }
+        None => None,
+    };
+    println!(
+        "📤 Pushing {} files of {} to {}\n",
+        names.len(),
+        dir.display(),
+        target.describe()
+    );
+
+    let mut child = target
+        .command(&format!(
+            "set -e; mkdir -p -- {dir}; cd -- {dir}; umask 077; tar -xf -; \
+             for f in *; do if [ -f \"$f\" ]; then sha256sum -- \"$f\"; fi; done",
+            dir = remote_dir
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let sent = send_files(&mut child, &store, &names, manifest.as_deref());
+    let output = child.wait_with_output()?;
+    let sent = sent?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Writing the files on {} failed",
+            target.describe()
+        )));
+    }
+
+    let received: BTreeMap<&str, &str> = std::str::from_utf8(&output.stdout)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Unreadable remote checksums"))?
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(hash, name)| (name, hash))
+        .collect();
+    let mut failures = 0;
+    for (name, hash) in &sent {
+        if received.get(name.as_str()) == Some(&hash.as_str()) {
+            println!("  ✅ {}", name);
+        } else {
+            println!("  ❌ {}: checksum mismatch on arrival", name);
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} files arrived corrupted on {}",
+                failures,
+                sent.len(),
+                target.describe()
+            ),
+        ));
+    }
+
+    println!(
+        "\n✅ {} files pushed and verified on {}",
+        sent.len(),
+        target.describe()
+    );
+    Ok(())
+}
+
+/// Stream `names` to `child` as a tar archive, returning the SHA-256 of
+/// every file sent. The manifest is replaced by `manifest`, if given.
+fn send_files(
+    child: &mut Child,
+    store: &ManifestKeyStore,
+    names: &[String],
+    manifest: Option<&str>,
+) -> io::Result<BTreeMap<String, String>> {
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut sent = BTreeMap::new();
+    for name in names {
+        if name == MANIFEST_FILE {
+            if let Some(manifest) = manifest {
+                tar::write_file(&mut stdin, name, manifest.as_bytes(), tar::MODE_PRIVATE)?;
+                sent.insert(name.clone(), checksum::sha256_hex(manifest.as_bytes()));
+                continue;
+            }
+        }
+        let path = store.file_path(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such key file {}", name),
+            )
+        })?;
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        // Readable by their owner only
+        tar::write_header(&mut stdin, name, size, tar::MODE_PRIVATE)?;
+        let mut reader = HashingReader::new(file.take(size));
+        if io::copy(&mut reader, &mut stdin)? != size {
+            return Err(io::Error::other(format!(
+                "{} changed while it was sent",
+                path.display()
+            )));
+        }
+        tar::write_padding(&mut stdin, size)?;
+        sent.insert(name.clone(), reader.hex_digest());
+    }
+    tar::write_end(&mut stdin)?;
+    Ok(sent)
+}
+
+/// Copy the key directory at `source` into `dir`, which must not hold a
+/// manifest yet. Files whose contents don't match the checksum the remote
+/// host computed are not written.
+pub fn pull(source: &SshTarget, dir: &Path) -> io::Result<()> {
+    if manifest::manifest_path(dir).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                dir.display()
+            ),
+        ));
+    }
+    let store = FsKeyStore::create(dir)?;
+    println!("📥 Pulling {} into {}\n", source.describe(), dir.display());
+
+    // Every file as `<sha256> <size> <name>` followed by its contents
+    let mut child = source
+        .command(&format!(
+            "set -e; cd -- {}; for f in *; do if [ -f \"$f\" ]; then \
+             printf '%s %s %s\\n' \"$(sha256sum < \"$f\" | cut -d' ' -f1)\" \
+             \"$(wc -c < \"$f\" | tr -d ' ')\" \"$f\"; cat -- \"$f\"; fi; done",
+            shell_quote(&source.path)
+        ))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let received = receive_files(&mut child, &store);
+    let status = child.wait()?;
+    let (received, failures) = received?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "Reading the files on {} failed",
+            source.describe()
+        )));
+    }
+    if received == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} holds no files", source.describe()),
+        ));
+    }
+    if failures > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!(
+                "{} of {} files arrived corrupted and were not written",
+                failures,
+                received + failures
+            ),
+        ));
+    }
+
+    println!(
+        "\n✅ {} files pulled and verified into {}",
+        received,
+        dir.display()
+    );
+    Ok(())
+}
+
+/// Read the files sent by `child` into `store`, returning the number
+/// written and the number rejected.
+fn receive_files(child: &mut Child, store: &FsKeyStore) -> io::Result<(usize, usize)> {
+    let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let (mut received, mut failures) = (0, 0);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok((received, failures));
+        }
+        let malformed = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed transfer header {:?}", line.trim_end()),
+            )
+        };
+        let mut fields = line.trim_end_matches('\n').splitn(3, ' ');
+        let (Some(hash), Some(size), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(malformed());
+        };
+        let size: usize = size.parse().map_err(|_| malformed())?;
+
+        let mut contents = Zeroizing::new(vec![0u8; size]);
+        reader.read_exact(&mut contents)?;
+        if checksum::sha256_hex(&contents) == hash {
+            store.store(name, &contents)?;
+            println!("  ✅ {}", name);
+            received += 1;
+        } else {
+            println!("  ❌ {}: checksum mismatch on arrival", name);
+            failures += 1;
+        }
+    }
+}
+
+/// `value` as a single-quoted shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
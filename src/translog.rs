@@ -0,0 +1,416 @@
+//! `publish-log` and `verify-log`: an append-only, hash-chained public log
+//! of every validator key in existence.
+//!
+//! The log is JSON lines, one per public key. Every entry records the
+//! `entry_hash` of the one before it, and its own `entry_hash` is the
+//! SHA-256 of the entry without that field, serialized with sorted keys. The
+//! hash of the last entry (the head) thereby commits to the whole log, so a
+//! mirror only needs to be checked against a head obtained out of band, and
+//! any entry up to that head is proven to be included.
+
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checksum;
+use crate::exit::{self, Failure};
+use crate::manifest::{normalize_pubkey, short_pubkey, Manifest, SortOrder};
+use crate::metadata::{activation_window, unix_now};
+
+/// `prev_hash` of the first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LogEntry {
+    /// Position in the log, from 0.
+    seq: u64,
+    prev_hash: String,
+    pubkey_hex: String,
+    key_scheme: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheme_fingerprint: Option<String>,
+    /// First active epoch (inclusive).
+    activation_epoch: u64,
+    /// End of the active range (exclusive).
+    end_epoch: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ceremony_id: Option<String>,
+    logged_at: u64,
+    entry_hash: String,
+}
+
+impl LogEntry {
+    /// SHA-256 of the entry without `entry_hash`, with sorted keys.
+    fn compute_hash(&self) -> io::Result<String> {
+        let mut body = serde_json::to_value(self).map_err(io::Error::other)?;
+        if let Value::Object(map) = &mut body {
+            map.remove("entry_hash");
+        }
+        let canonical = serde_json::to_vec(&body).map_err(io::Error::other)?;
+        Ok(checksum::sha256_hex(&canonical))
+    }
+}
+
+/// Append every public key in the manifest of `dir`, canaries aside, that
+/// is not logged yet to `log`, in `order`, creating it if needed. The
+/// existing log is verified first, so a broken log is never extended.
+pub fn publish_log(dir: &Path, log: &Path, order: SortOrder) -> io::Result<()> {
+    let mut manifest = Manifest::read(dir)?;
+    // Canaries are never registered
+    manifest.validators.retain(|entry| !entry.canary);
+    manifest.sort_validators(order);
+    let entries = if log.exists() {
+        read_log(log)?
+    } else {
+        Vec::new()
+    };
+    let logged: BTreeSet<String> = entries
+        .iter()
+        .map(|entry| normalize_pubkey(&entry.pubkey_hex))
+        .collect();
+    let (mut seq, mut prev_hash) = match entries.last() {
+        Some(last) => (last.seq + 1, last.entry_hash.clone()),
+        None => (0, GENESIS_HASH.to_string()),
+    };
+
+    println!(
+        "📜 Publishing {} validators of {} to {}\n",
+        manifest.validators.len(),
+        dir.display(),
+        log.display()
+    );
+    let mut lines = String::new();
+    let mut skipped = 0;
+    for validator in &manifest.validators {
+        if logged.contains(&normalize_pubkey(&validator.pubkey_hex)) {
+            skipped += 1;
+            continue;
+        }
+        let (activation_epoch, end_epoch) = activation_window(
+            dir,
+            &validator.privkey_file,
+            1 << manifest.log_num_active_epochs_of(validator),
+        )?;
+        let mut entry = LogEntry {
+            seq,
+            prev_hash,
+            pubkey_hex: validator.pubkey_hex.clone(),
+            key_scheme: manifest.scheme_of(validator).to_string(),
+            // The manifest only fingerprints its own scheme
+            scheme_fingerprint: manifest
+                .scheme_fingerprint
+                .clone()
+                .filter(|_| validator.key_scheme.is_none()),
+            activation_epoch,
+            end_epoch,
+            ceremony_id: manifest.ceremony_id.clone(),
+            logged_at: unix_now(),
+            entry_hash: String::new(),
+        };
+        entry.entry_hash = entry.compute_hash()?;
+        lines.push_str(&serde_json::to_string(&entry).map_err(io::Error::other)?);
+        lines.push('\n');
+        println!("  ✅ #{} {}", seq, short_pubkey(&validator.pubkey_hex));
+        seq += 1;
+        prev_hash = entry.entry_hash;
+    }
+    // One write, so a reader never sees half of a run's entries
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)?
+        .write_all(lines.as_bytes())?;
+
+    if skipped > 0 {
+        println!("  ⏭️  {} already logged", skipped);
+    }
+    println!("\n📌 Log head: {} entries, {}", seq, prev_hash);
+    Ok(())
+}
+
+/// Verify the hash chain of `log`. With `head`, the log must contain the
+/// entry with that hash, and inclusion is checked up to it; with `pubkeys`
+/// and the manifest of `dir`, every given key must be included.
+pub fn verify_log(
+    log: &Path,
+    head: Option<&str>,
+    pubkeys: &[String],
+    dir: Option<&Path>,
+) -> io::Result<()> {
+    let mut entries = read_log(log)?;
+    println!(
+        "🔍 Verified the hash chain of {} ({} entries)",
+        log.display(),
+        entries.len()
+    );
+    if let Some(head) = head {
+        let head = head.trim_start_matches("0x").to_ascii_lowercase();
+        let position = entries
+            .iter()
+            .position(|entry| entry.entry_hash == head)
+            .ok_or_else(|| {
+                exit::error(
+                    Failure::VerificationFailed,
+                    format!(
+                        "{} does not contain head {}; it was rewritten or is not the log that head belongs to",
+                        log.display(),
+                        head
+                    ),
+                )
+            })?;
+        entries.truncate(position + 1);
+        println!("  📌 Head {} is entry #{}", head, position);
+    }
+
+    let mut wanted: Vec<String> = pubkeys.to_vec();
+    if let Some(dir) = dir {
+        wanted.extend(
+            Manifest::read(dir)?
+                .validators
+                .into_iter()
+                .map(|v| v.pubkey_hex),
+        );
+    }
+    let mut missing = 0;
+    for pubkey in &wanted {
+        let normalized = normalize_pubkey(pubkey);
+        match entries
+            .iter()
+            .find(|entry| normalize_pubkey(&entry.pubkey_hex) == normalized)
+        {
+            Some(entry) => println!(
+                "  ✅ {} included as #{} (epochs {}..{})",
+                short_pubkey(pubkey),
+                entry.seq,
+                entry.activation_epoch,
+                entry.end_epoch
+            ),
+            None => {
+                println!("  ❌ {} is not included", short_pubkey(pubkey));
+                missing += 1;
+            }
+        }
+    }
+    if missing > 0 {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("{} of {} keys are not in the log", missing, wanted.len()),
+        ));
+    }
+
+    match entries.last() {
+        Some(last) => println!("\n✅ Log is intact up to head {}", last.entry_hash),
+        None => println!("\n✅ Log is empty"),
+    }
+    Ok(())
+}
+
+/// Read `log`, checking every entry's hash, its link to the previous entry,
+/// its position and that no public key is logged twice.
+fn read_log(log: &Path) -> io::Result<Vec<LogEntry>> {
+    let contents = fs::read_to_string(log)?;
+    let broken = |line: usize, reason: String| {
+        exit::error(
+            Failure::VerificationFailed,
+            format!("{} line {}: {}", log.display(), line, reason),
+        )
+    };
+    let mut entries: Vec<LogEntry> = Vec::new();
+    let mut pubkeys = BTreeSet::new();
+    for (number, line) in contents.lines().enumerate() {
+        let number = number + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: LogEntry = serde_json::from_str(line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", log.display(), number, e),
+            )
+        })?;
+        let (seq, prev_hash) = match entries.last() {
+            Some(last) => (last.seq + 1, last.entry_hash.as_str()),
+            None => (0, GENESIS_HASH),
+        };
+        if entry.seq != seq {
+            return Err(broken(
+                number,
+                format!("expected entry #{}, found #{}", seq, entry.seq),
+            ));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(broken(
+                number,
+                "does not link to the previous entry".to_string(),
+            ));
+        }
+        if entry.compute_hash()? != entry.entry_hash {
+            return Err(broken(
+                number,
+                "entry hash does not match its contents".to_string(),
+            ));
+        }
+        if !pubkeys.insert(normalize_pubkey(&entry.pubkey_hex)) {
+            return Err(broken(
+                number,
+                format!("{} is logged twice", short_pubkey(&entry.pubkey_hex)),
+            ));
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::manifest::MANIFEST_FILE;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("hashsig-translog-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a manifest of validators with the public keys `0x<byte>…`.
+    fn write_manifest(dir: &Path, bytes: &[u8]) {
+        let mut manifest = format!(
+            "key_scheme: SIGTopLevelTargetSumLifetime32Dim64Base8
+hash_function: Poseidon2
+encoding: TargetSum
+lifetime: 4294967296
+log_num_active_epochs: 2
+num_active_epochs: 4
+num_validators: {}
+
+validators:
+",
+            bytes.len()
+        );
+        for (index, byte) in bytes.iter().enumerate() {
+            manifest.push_str(&format!(
+                "  - index: {}\n    pubkey_hex: 0x{}\n    privkey_file: validator_{}_sk.ssz\n",
+                index,
+                hex::encode([*byte; 32]),
+                index
+            ));
+        }
+        fs::write(dir.join(MANIFEST_FILE), manifest).unwrap();
+    }
+
+    fn entry(seq: u64, prev_hash: &str, pubkey: u8) -> LogEntry {
+        let mut entry = LogEntry {
+            seq,
+            prev_hash: prev_hash.to_string(),
+            pubkey_hex: format!("0x{}", hex::encode([pubkey; 32])),
+            key_scheme: "SIGTopLevelTargetSumLifetime32Dim64Base8".to_string(),
+            scheme_fingerprint: None,
+            activation_epoch: 0,
+            end_epoch: 4,
+            ceremony_id: None,
+            logged_at: 1760000000,
+            entry_hash: String::new(),
+        };
+        entry.entry_hash = entry.compute_hash().unwrap();
+        entry
+    }
+
+    fn write_log(path: &Path, entries: &[LogEntry]) {
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn round_trips() {
+        let dir = scratch("round-trip");
+        let log = dir.join("pubkeys.log");
+        write_manifest(&dir, &[0xaa, 0xbb]);
+        publish_log(&dir, &log, SortOrder::Index).unwrap();
+        // Logged keys are skipped, new ones appended to the chain
+        write_manifest(&dir, &[0xaa, 0xbb, 0xcc]);
+        publish_log(&dir, &log, SortOrder::Index).unwrap();
+        let entries = read_log(&log).unwrap();
+        let head = entries.last().unwrap().entry_hash.clone();
+        let verified = verify_log(&log, Some(&head), &[], Some(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+
+        verified.unwrap();
+        let pubkeys: Vec<_> = entries.iter().map(|e| &e.pubkey_hex[2..4]).collect();
+        assert_eq!(pubkeys, ["aa", "bb", "cc"]);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        for (i, pair) in entries.windows(2).enumerate() {
+            assert_eq!(pair[0].seq, i as u64);
+            assert_eq!(pair[1].prev_hash, pair[0].entry_hash);
+        }
+    }
+
+    #[test]
+    fn rejects_a_tampered_entry() {
+        let dir = scratch("tampered");
+        let log = dir.join("pubkeys.log");
+        let first = entry(0, GENESIS_HASH, 0xaa);
+        let mut second = entry(1, &first.entry_hash, 0xbb);
+        second.end_epoch = 1 << 20;
+        write_log(&log, &[first, second]);
+        let result = read_log(&log);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = result.err().unwrap();
+        assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_a_reordered_entry() {
+        let dir = scratch("reordered");
+        let log = dir.join("pubkeys.log");
+        let first = entry(0, GENESIS_HASH, 0xaa);
+        let second = entry(1, &first.entry_hash, 0xbb);
+        let third = entry(2, &second.entry_hash, 0xcc);
+        write_log(&log, &[first, third, second]);
+        let reordered = read_log(&log);
+        // Renumbered to hide the move, the chain still breaks
+        let first = entry(0, GENESIS_HASH, 0xaa);
+        let second = entry(1, &first.entry_hash, 0xbb);
+        let third = entry(2, &second.entry_hash, 0xcc);
+        let moved = entry(1, &third.entry_hash, 0xcc);
+        write_log(&log, &[first, moved]);
+        let relinked = read_log(&log);
+        fs::remove_dir_all(&dir).unwrap();
+
+        for result in [reordered, relinked] {
+            let err = result.err().unwrap();
+            assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+            assert!(err.to_string().contains("line 2"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn rejects_a_duplicate_pubkey() {
+        let dir = scratch("duplicate");
+        let log = dir.join("pubkeys.log");
+        let first = entry(0, GENESIS_HASH, 0xaa);
+        let second = entry(1, &first.entry_hash, 0xbb);
+        let third = entry(2, &second.entry_hash, 0xaa);
+        write_log(&log, &[first, second, third]);
+        let result = read_log(&log);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = result.err().unwrap();
+        assert_eq!(Failure::of(&err), Failure::VerificationFailed);
+        assert!(err.to_string().contains("logged twice"), "{}", err);
+    }
+}
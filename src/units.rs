@@ -0,0 +1,147 @@
+//! Sizes and durations as people read and write them, e.g. `3.2 MiB` or
+//! `1h 12m`.
+//!
+//! Only human output goes through these; JSON output keeps exact values
+//! (bytes, seconds) for tools to read.
+
+use std::time::Duration;
+
+/// `bytes` in binary units, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // Compared as printed, so 1023.96 KiB is 1.0 MiB rather than 1024.0 KiB
+    while (size * 10.0).round() >= 10240.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// `duration` in its two largest units, e.g. `850ms`, `42s`, `3m 07s`,
+/// `1h 12m` or `2d 05h`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    match (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60) {
+        (0, 0, 0, s) => format!("{}s", s),
+        (0, 0, m, s) => format!("{}m {:02}s", m, s),
+        (0, h, m, _) => format!("{}h {:02}m", h, m),
+        (d, h, _, _) => format!("{}d {:02}h", d, h),
+    }
+}
+
+/// Parse a duration written as seconds with an optional `s`, `m`, `h` or
+/// `d` suffix (e.g. `90`, `60s`, `5m`, `30d`).
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let (number, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => duration.split_at(index),
+        None => (duration, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|e| format!("Invalid duration {}: {}", duration, e))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("Unknown duration unit {} (use s, m, h or d)", unit)),
+    };
+    let seconds = number
+        .checked_mul(unit_secs)
+        .ok_or_else(|| format!("Duration {} is too long", duration))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// [`format_duration`] of a number of seconds, as recorded in JSON files.
+pub fn format_secs(secs: f64) -> String {
+    format_duration(Duration::try_from_secs_f64(secs).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_sizes() {
+        for (bytes, formatted) in [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (1048524, "1023.9 KiB"),
+            (1048525, "1.0 MiB"),
+            (1 << 20, "1.0 MiB"),
+            (3 << 30, "3.0 GiB"),
+            (5 << 40, "5.0 TiB"),
+            (2048 << 40, "2048.0 TiB"),
+        ] {
+            assert_eq!(format_size(bytes), formatted, "{}", bytes);
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        for (duration, formatted) in [
+            (Duration::ZERO, "0ms"),
+            (Duration::from_millis(850), "850ms"),
+            (Duration::from_millis(42_900), "42s"),
+            (Duration::from_secs(187), "3m 07s"),
+            (Duration::from_secs(3600), "1h 00m"),
+            (Duration::from_secs(4320), "1h 12m"),
+            (Duration::from_secs(86400 * 2 + 3600 * 5 + 59), "2d 05h"),
+        ] {
+            assert_eq!(format_duration(duration), formatted);
+        }
+        assert_eq!(format_secs(90.5), "1m 30s");
+        assert_eq!(format_secs(-1.0), "0ms");
+        assert_eq!(format_secs(f64::NAN), "0ms");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("60s"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_duration("0d"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for duration in ["", "d", "5w", "5 m", "-5m", "1.5h", "5mm"] {
+            assert!(parse_duration(duration).is_err(), "{:?}", duration);
+        }
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert!(parse_duration("999999999999999d").is_err());
+        assert!(parse_duration(&format!("{}m", u64::MAX / 60 + 1)).is_err());
+        assert_eq!(
+            parse_duration(&format!("{}m", u64::MAX / 60)),
+            Ok(Duration::from_secs(u64::MAX / 60 * 60))
+        );
+    }
+
+    #[test]
+    fn formats_parsed_durations() {
+        for (input, formatted) in [
+            ("42", "42s"),
+            ("187", "3m 07s"),
+            ("72m", "1h 12m"),
+            ("53h", "2d 05h"),
+        ] {
+            assert_eq!(format_duration(parse_duration(input).unwrap()), formatted);
+        }
+    }
+}
@@ -0,0 +1,556 @@
+//! `verify-dir`: full validation of a key directory, the last check before a
+//! key set goes into production, and the audit of key sets already in use.
+//!
+//! It only reads, unless `--quarantine` is given: then every file that
+//! fails to decode or does not match its checksum is moved to
+//! [`QUARANTINE_DIR`] and listed in the report, so what remains are the
+//! healthy keys. The key set still fails validation, as the manifest lists
+//! the keys moved out.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::checksum;
+use crate::exit::{self, Failure};
+use crate::keystore::{KeyStore, ManifestKeyStore};
+use crate::manifest::{self, normalize_pubkey, Manifest};
+use crate::metadata::{unix_now, KeyMetadata};
+use crate::report::{self, ReportSigner};
+use crate::scheme::{self, KeyKind, SchemeHandle};
+
+/// Subdirectory `--quarantine` moves corrupted files to.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+#[derive(Serialize)]
+struct ValidationReport {
+    tool_version: String,
+    created_at: u64,
+    dir: String,
+    /// ID of the `generate` run, as recorded in the manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ceremony_id: Option<String>,
+    key_scheme: String,
+    passed: bool,
+    key_pairs: usize,
+    public_keys: usize,
+    secret_keys: usize,
+    metadata_files: usize,
+    /// With `--sample`, the public keys whose pairs were fully validated;
+    /// the others were only checked against their metadata checksums.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sampled: Option<Vec<String>>,
+    failures: Vec<String>,
+    /// With `--quarantine`, where each corrupted file was moved to.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    quarantined: BTreeMap<String, String>,
+    /// SHA-256 of every file in the directory, keyed by file name.
+    checksums: BTreeMap<String, String>,
+}
+
+/// Validate every key, key pair, metadata checksum and the manifest of
+/// `dir`, optionally writing a (signed) report. Fails if anything is wrong.
+///
+/// Key pairs are decoded and checked on `threads` threads. With `sample`,
+/// only that many randomly chosen pairs are, and the rest are covered by
+/// their metadata checksums only. With `quarantine`, files that fail to
+/// decode or mismatch their checksum are moved to [`QUARANTINE_DIR`].
+pub fn verify_dir(
+    dir: &Path,
+    threads: usize,
+    sample: Option<usize>,
+    quarantine: bool,
+    report: Option<&Path>,
+    signer: Option<ReportSigner>,
+) -> io::Result<()> {
+    if threads == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--threads must be at least 1",
+        ));
+    }
+
+    let store = ManifestKeyStore::open(dir)?;
+    let files = store.list()?;
+    let mut checksums = checksum::sha256_dir(dir)?;
+    // Secret keys the manifest places outside the directory
+    for name in &files {
+        if !checksums.contains_key(name) {
+            if let Some(path) = store.file_path(name) {
+                checksums.insert(name.clone(), checksum::sha256_file(&path)?);
+            }
+        }
+    }
+    let scheme = scheme::for_dir(dir)?.unwrap_or_else(scheme::default_scheme);
+    let manifest = Manifest::read_if_present(dir)?;
+    let schemes = KeySchemes::new(scheme, manifest.as_ref())?;
+
+    println!("🔍 Validating key set in {}\n", dir.display());
+
+    let mut failures = Vec::new();
+    // Files that fail to decode or mismatch their checksum
+    let mut corrupted = BTreeSet::new();
+    // Objects of a content-addressed directory are named after their checksum
+    for name in &files {
+        if let Some(hash) = store.object_hash(name) {
+            if checksums.get(name).map(String::as_str) != Some(hash) {
+                failures.push(format!("{}: contents do not match object {}", name, hash));
+                corrupted.insert(name.clone());
+            }
+        }
+    }
+    let counts = check_keys(
+        &schemes,
+        &store,
+        &files,
+        threads,
+        sample,
+        &mut failures,
+        &mut corrupted,
+    )?;
+    let metadata_files = check_metadata(
+        &schemes,
+        &store,
+        &files,
+        &checksums,
+        &mut failures,
+        &mut corrupted,
+    )?;
+    match &manifest {
+        Some(manifest) => check_manifest(manifest, &store, &files, &mut failures)?,
+        None => failures.push("No manifest".to_string()),
+    }
+
+    println!("  Scheme:          {}", schemes.describe());
+    println!("  Public keys:     {}", counts.public_keys);
+    println!("  Secret keys:     {}", counts.secret_keys);
+    println!("  Matching pairs:  {}", counts.key_pairs);
+    if let Some(sampled) = &counts.sampled {
+        println!(
+            "  Sampled:         {} of {} pairs fully validated, the rest by checksum only",
+            sampled.len(),
+            counts.public_keys
+        );
+    }
+    println!("  Metadata files:  {}", metadata_files);
+    for failure in &failures {
+        println!("  ❌ {}", failure);
+    }
+    let mut quarantined = BTreeMap::new();
+    if quarantine && !corrupted.is_empty() {
+        println!();
+        for name in &corrupted {
+            let Some(path) = store.file_path(name) else {
+                continue;
+            };
+            let moved = quarantine_file(dir, &path)?;
+            println!("  🚧 Quarantined {} to {}", name, moved.display());
+            quarantined.insert(name.clone(), moved.display().to_string());
+        }
+    }
+
+    let passed = failures.is_empty();
+    if let Some(path) = report {
+        let validation = ValidationReport {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: unix_now(),
+            dir: dir.display().to_string(),
+            ceremony_id: manifest.and_then(|manifest| manifest.ceremony_id),
+            key_scheme: scheme.name().to_string(),
+            passed,
+            key_pairs: counts.key_pairs,
+            public_keys: counts.public_keys,
+            secret_keys: counts.secret_keys,
+            metadata_files,
+            sampled: counts.sampled,
+            failures: failures.clone(),
+            quarantined,
+            checksums,
+        };
+        let json = serde_json::to_vec_pretty(&validation).map_err(io::Error::other)?;
+        fs::write(path, &json)?;
+        println!("\n📄 Validation report written to: {}", path.display());
+
+        if let Some(signer) = signer {
+            report::sign(&json, path, &signer)?;
+        }
+    }
+
+    if !passed {
+        return Err(exit::error(
+            Failure::VerificationFailed,
+            format!("Key set failed validation ({} problems)", failures.len()),
+        ));
+    }
+
+    println!("\n✅ Key set passed validation");
+    Ok(())
+}
+
+/// Scheme of every key: the manifest's, or a validator's own in a mixed key
+/// set (`generate --batch`).
+struct KeySchemes {
+    default: &'static dyn SchemeHandle,
+    /// By key file prefix, e.g. `validator_3`, for validators of another
+    /// scheme than the manifest's.
+    validators: BTreeMap<String, &'static dyn SchemeHandle>,
+}
+
+impl KeySchemes {
+    fn new(default: &'static dyn SchemeHandle, manifest: Option<&Manifest>) -> io::Result<Self> {
+        let mut validators = BTreeMap::new();
+        if let Some(manifest) = manifest {
+            for entry in &manifest.validators {
+                let name = manifest::file_name(&entry.privkey_file);
+                if let (Some(_), Some(prefix)) = (&entry.key_scheme, manifest::key_prefix(name)) {
+                    let scheme = scheme::for_entry(manifest, entry, manifest::MANIFEST_FILE)?;
+                    validators.insert(prefix.to_string(), scheme);
+                }
+            }
+        }
+        Ok(Self {
+            default,
+            validators,
+        })
+    }
+
+    /// Scheme of the key or metadata file `name`.
+    fn of(&self, name: &str) -> &'static dyn SchemeHandle {
+        manifest::key_prefix(name)
+            .and_then(|prefix| self.validators.get(prefix))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// The schemes in use, the manifest's first.
+    fn describe(&self) -> String {
+        let mut names = vec![self.default.name()];
+        for scheme in self.validators.values() {
+            if !names.contains(&scheme.name()) {
+                names.push(scheme.name());
+            }
+        }
+        names.join(", ")
+    }
+}
+
+#[derive(Default)]
+struct KeyCounts {
+    public_keys: usize,
+    secret_keys: usize,
+    key_pairs: usize,
+    sampled: Option<Vec<String>>,
+}
+
+enum KeyCheck {
+    Pair,
+    /// Public key without a secret key, as left behind by `revoke`.
+    PublicOnly,
+    Failed(String),
+    /// The file named first does not decode.
+    Corrupt(String, String),
+}
+
+/// Decode SSZ keys and check that each secret key belongs to the public key
+/// next to it, for all public keys or a random sample of `sample` of them.
+fn check_keys(
+    schemes: &KeySchemes,
+    store: &dyn KeyStore,
+    files: &[String],
+    threads: usize,
+    sample: Option<usize>,
+    failures: &mut Vec<String>,
+    corrupted: &mut BTreeSet<String>,
+) -> io::Result<KeyCounts> {
+    let mut counts = KeyCounts::default();
+
+    let mut pk_names = Vec::new();
+    for name in files {
+        if let Some(prefix) = name.strip_suffix("_sk.ssz") {
+            counts.secret_keys += 1;
+            if !files.contains(&format!("{}_pk.ssz", prefix)) {
+                failures.push(format!("{}: no matching public key", name));
+            }
+        } else if name.ends_with("_pk.ssz") {
+            pk_names.push(name.as_str());
+        }
+    }
+    counts.public_keys = pk_names.len();
+
+    if let Some(sample) = sample.filter(|&n| n < pk_names.len()) {
+        let mut chosen =
+            rand::seq::index::sample(&mut rand::rng(), pk_names.len(), sample).into_vec();
+        chosen.sort_unstable();
+        pk_names = chosen.into_iter().map(|i| pk_names[i]).collect();
+        counts.sampled = Some(pk_names.iter().map(|name| name.to_string()).collect());
+    }
+
+    let next = AtomicUsize::new(0);
+    let progress = Progress::new(pk_names.len());
+    let results: Vec<io::Result<Vec<(usize, KeyCheck)>>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| -> io::Result<Vec<(usize, KeyCheck)>> {
+                    let mut checks = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(name) = pk_names.get(index) else {
+                            break;
+                        };
+                        let scheme = schemes.of(name);
+                        checks.push((index, check_key(scheme, store, files, name)?));
+                        progress.tick();
+                    }
+                    Ok(checks)
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("validation thread panicked"))
+            .collect()
+    });
+
+    // Report in file order regardless of which thread checked what
+    let mut checks = Vec::with_capacity(pk_names.len());
+    for result in results {
+        checks.extend(result?);
+    }
+    checks.sort_by_key(|(index, _)| *index);
+    for (_, check) in checks {
+        match check {
+            KeyCheck::Pair => counts.key_pairs += 1,
+            KeyCheck::PublicOnly => {}
+            KeyCheck::Failed(failure) => failures.push(failure),
+            KeyCheck::Corrupt(name, failure) => {
+                failures.push(failure);
+                corrupted.insert(name);
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+fn check_key(
+    scheme: &dyn SchemeHandle,
+    store: &dyn KeyStore,
+    files: &[String],
+    pk_name: &str,
+) -> io::Result<KeyCheck> {
+    let pk = store.load(pk_name)?;
+    if let Err(reason) = check_encoding(scheme, KeyKind::Public, &pk) {
+        let failure = format!("{}: {}", pk_name, reason);
+        return Ok(KeyCheck::Corrupt(pk_name.to_string(), failure));
+    }
+
+    let prefix = pk_name.trim_end_matches("_pk.ssz");
+    let sk_name = format!("{}_sk.ssz", prefix);
+    if !files.contains(&sk_name) {
+        return Ok(KeyCheck::PublicOnly);
+    }
+    let sk = store.load(&sk_name)?;
+    if let Err(reason) = check_encoding(scheme, KeyKind::Secret, &sk) {
+        let failure = format!("{}: {}", sk_name, reason);
+        return Ok(KeyCheck::Corrupt(sk_name, failure));
+    }
+    Ok(match scheme.keypair_matches(&pk, &sk) {
+        Ok(true) => KeyCheck::Pair,
+        Ok(false) => KeyCheck::Failed(format!("{} does not belong to {}", sk_name, pk_name)),
+        Err(e) => KeyCheck::Failed(format!("{}: {}", sk_name, e)),
+    })
+}
+
+/// Move the file `path` of the key directory `dir` to its
+/// [`QUARANTINE_DIR`], returning where it went. Files kept outside `dir`
+/// (`--secret-dir`) go to the one next to them, so the move never crosses
+/// filesystems, and a file already quarantined under the same name is kept.
+fn quarantine_file(dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let root = if path.starts_with(dir) {
+        dir
+    } else {
+        path.parent().unwrap_or(Path::new("."))
+    };
+    let quarantine = root.join(QUARANTINE_DIR);
+    fs::create_dir_all(&quarantine)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut target = quarantine.join(&name);
+    let mut n = 1;
+    while target.exists() {
+        target = quarantine.join(format!("{}.{}", name, n));
+        n += 1;
+    }
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+/// Progress line on stderr, only shown when stderr is a terminal.
+struct Progress {
+    done: AtomicUsize,
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Self {
+            done: AtomicUsize::new(0),
+            total,
+            enabled: io::stderr().is_terminal(),
+        }
+    }
+
+    fn tick(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.enabled {
+            eprint!("\r  ⏳ {}/{} key pairs validated", done, self.total);
+            if done == self.total {
+                eprintln!();
+            }
+        }
+    }
+}
+
+fn check_encoding(scheme: &dyn SchemeHandle, kind: KeyKind, bytes: &[u8]) -> Result<(), String> {
+    if scheme.reencode_ssz(kind, bytes)? != bytes {
+        return Err("does not re-serialize to the same bytes".to_string());
+    }
+    Ok(())
+}
+
+/// Check every `.meta.json` file against the files it describes, returning
+/// the number of metadata files.
+fn check_metadata(
+    schemes: &KeySchemes,
+    store: &dyn KeyStore,
+    files: &[String],
+    checksums: &BTreeMap<String, String>,
+    failures: &mut Vec<String>,
+    corrupted: &mut BTreeSet<String>,
+) -> io::Result<usize> {
+    let mut count = 0;
+
+    for name in files.iter().filter(|name| name.ends_with(".meta.json")) {
+        count += 1;
+        let metadata: KeyMetadata = match serde_json::from_slice(&store.load(name)?) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                failures.push(format!("{}: invalid metadata: {}", name, e));
+                corrupted.insert(name.clone());
+                continue;
+            }
+        };
+
+        let scheme = schemes.of(name);
+        if metadata.key_scheme != scheme.name() {
+            failures.push(format!(
+                "{}: key scheme {} differs from {}",
+                name,
+                metadata.key_scheme,
+                scheme.name()
+            ));
+        }
+        if let Err(e) =
+            scheme::check_fingerprint(scheme, metadata.scheme_fingerprint.as_deref(), name)
+        {
+            failures.push(e.to_string());
+        }
+        for (file, expected) in &metadata.checksums {
+            match checksums.get(file) {
+                Some(actual) if actual == expected => {}
+                Some(_) => {
+                    failures.push(format!("{}: checksum mismatch", file));
+                    corrupted.insert(file.clone());
+                }
+                // Secret keys of revoked validators are deleted on purpose
+                None if file.ends_with("_sk.ssz") || file.ends_with("_sk.json") => {}
+                None => failures.push(format!("{}: lists missing file {}", name, file)),
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Check that the manifest and the key files agree.
+fn check_manifest(
+    manifest: &Manifest,
+    store: &dyn KeyStore,
+    files: &[String],
+    failures: &mut Vec<String>,
+) -> io::Result<()> {
+    if manifest.num_validators != manifest.validators.len() {
+        failures.push(format!(
+            "Manifest: num_validators is {} but {} validators are listed",
+            manifest.num_validators,
+            manifest.validators.len()
+        ));
+    }
+
+    let mut pubkeys = BTreeSet::new();
+    let mut listed = BTreeSet::new();
+    for entry in &manifest.validators {
+        if !pubkeys.insert(normalize_pubkey(&entry.pubkey_hex)) {
+            failures.push(format!("Manifest: duplicate pubkey {}", entry.pubkey_hex));
+        }
+        let privkey_name = manifest::file_name(&entry.privkey_file);
+        listed.insert(privkey_name);
+
+        if !files.iter().any(|name| name == privkey_name) {
+            failures.push(format!(
+                "Manifest: secret key {} is missing",
+                entry.privkey_file
+            ));
+        }
+        let Some(prefix) = privkey_name.strip_suffix("_sk.ssz") else {
+            failures.push(format!(
+                "Manifest: unexpected secret key name {}",
+                entry.privkey_file
+            ));
+            continue;
+        };
+        let pk_name = format!("{}_pk.ssz", prefix);
+        if !files.contains(&pk_name) {
+            failures.push(format!("Manifest: public key {} is missing", pk_name));
+            continue;
+        }
+        if hex::encode(store.load(&pk_name)?) != normalize_pubkey(&entry.pubkey_hex) {
+            failures.push(format!(
+                "Manifest: pubkey of {} does not match {}",
+                entry.privkey_file, pk_name
+            ));
+        }
+    }
+
+    for tombstone in &manifest.revoked {
+        if pubkeys.contains(&normalize_pubkey(&tombstone.pubkey_hex)) {
+            failures.push(format!(
+                "Manifest: revoked pubkey {} is still listed",
+                tombstone.pubkey_hex
+            ));
+        }
+        let privkey_name = manifest::file_name(&tombstone.privkey_file);
+        if files.iter().any(|name| name == privkey_name) {
+            failures.push(format!(
+                "{}: secret key of a revoked validator is still present",
+                tombstone.privkey_file
+            ));
+        }
+    }
+
+    for name in files.iter().filter(|name| name.ends_with("_sk.ssz")) {
+        if !listed.contains(name.as_str()) {
+            failures.push(format!("{}: not listed in the manifest", name));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,917 @@
+//! `wrap` and `unwrap`: carry selected secret keys between ceremony stages,
+//! such as from the generation machine to the signing host, encrypted to a
+//! short-lived transport key.
+//!
+//! The receiving host creates a transport key pair with `transport-keygen`
+//! and hands over the public half, which records when the pair expires.
+//! `wrap` seals the chosen validators' key files, with a manifest of just
+//! those validators, to it:
+//!
+//! ```text
+//! magic (8) | version (1) | header length (u32 LE) | JSON header | sealed payload
+//! ```
+//!
+//! The payload is sealed with ChaCha20-Poly1305 under
+//! `SHA-256(context || X25519(ephemeral, recipient) || ephemeral || recipient)`,
+//! authenticating the header. `unwrap` checks that the payload holds exactly
+//! the validators the header names, each secret key matching its public key,
+//! opens it into a new key directory and shreds the transport secret key, so
+//! a transport key is used once.
+//!
+//! Both append an event to the [`AUDIT_LOG_FILE`] of the key directory they
+//! read from or write to. The two events of a transfer carry the same
+//! SHA-256 of the wrapped file.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::checksum;
+use crate::escrow;
+use crate::exit::{self, Failure};
+use crate::keystore::{self, FsKeyStore, KeyStore, ManifestKeyStore};
+use crate::layout::{self, Layout};
+use crate::manifest::{self, normalize_pubkey, short_pubkey, Manifest, MANIFEST_FILE};
+use crate::metadata::unix_now;
+use crate::scheme;
+use crate::units::format_duration;
+
+const MAGIC: &[u8; 8] = b"HSIGWRAP";
+const VERSION: u8 = 1;
+const PAYLOAD_KEY_CONTEXT: &[u8] = b"hashsig/wrap/payload-key/v1";
+
+/// Length of the magic, version and header length.
+const PREFIX_LEN: usize = 13;
+
+/// JSON lines of the wrap and unwrap events of a key directory.
+pub const AUDIT_LOG_FILE: &str = "audit-log.jsonl";
+
+/// Public half of a transport key pair, as written to `<output>.pub`.
+#[derive(Serialize, Deserialize)]
+struct TransportKey {
+    /// Hex X25519 public key.
+    pubkey: String,
+    expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrapHeader {
+    tool_version: String,
+    created_at: u64,
+    source_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ceremony_id: Option<String>,
+    /// Hex X25519 public key of the transport key.
+    recipient: String,
+    /// Expiry of the transport key; the file is not unwrapped after it.
+    expires_at: u64,
+    ephemeral_pubkey: String,
+    /// Public keys of the wrapped validators.
+    pubkeys: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AuditAction {
+    Wrap,
+    Unwrap,
+}
+
+#[derive(Serialize)]
+struct AuditEvent {
+    event: AuditAction,
+    at: u64,
+    wrapped_file: String,
+    wrapped_sha256: String,
+    /// Hex X25519 public key of the transport key.
+    recipient: String,
+    pubkeys: Vec<String>,
+}
+
+/// Write a transport key pair valid for `valid_for` to `<output>.pub` and
+/// `<output>.key`.
+pub fn keygen(output: &Path, valid_for: Duration) -> io::Result<()> {
+    let secret = StaticSecret::from(rand::random::<[u8; 32]>());
+    let public = PublicKey::from(&secret);
+
+    let pub_path = escrow::with_suffix(output, ".pub");
+    let key_path = escrow::with_suffix(output, ".key");
+    for path in [&pub_path, &key_path] {
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Refusing to overwrite existing file {}", path.display()),
+            ));
+        }
+    }
+    let transport = TransportKey {
+        pubkey: hex::encode(public.as_bytes()),
+        expires_at: unix_now().saturating_add(valid_for.as_secs()),
+    };
+    let mut json = serde_json::to_vec_pretty(&transport).map_err(io::Error::other)?;
+    json.push(b'\n');
+    fs::write(&key_path, format!("{}\n", hex::encode(secret.to_bytes())))?;
+    fs::write(&pub_path, json)?;
+
+    println!(
+        "🔑 Transport key pair written, valid for {}:",
+        format_duration(valid_for)
+    );
+    println!("  📄 {} (give to whoever runs `wrap`)", pub_path.display());
+    println!(
+        "  📄 {} (keep on this host; `unwrap` shreds it)",
+        key_path.display()
+    );
+    Ok(())
+}
+
+fn read_transport_key(path: &Path) -> io::Result<(TransportKey, [u8; 32])> {
+    let not_transport = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not a transport public key from `transport-keygen`",
+                path.display()
+            ),
+        )
+    };
+    let transport: TransportKey =
+        serde_json::from_slice(&fs::read(path)?).map_err(|_| not_transport())?;
+    let pubkey = hex::decode(&transport.pubkey)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(not_transport)?;
+    Ok((transport, pubkey))
+}
+
+fn payload_key(shared_secret: &[u8], ephemeral: &[u8; 32], recipient: &[u8; 32]) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(PAYLOAD_KEY_CONTEXT);
+    hasher.update(shared_secret);
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+    let mut key = Key::default();
+    key.copy_from_slice(&hasher.finalize());
+    key
+}
+
+/// Append `name` and `contents` to `payload`: the name's length (u16 LE),
+/// the name, the contents' length (u64 LE) and the contents.
+fn add_file(payload: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    payload.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    payload.extend_from_slice(name.as_bytes());
+    payload.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+    payload.extend_from_slice(contents);
+}
+
+/// Wrap the key files of the validators of `dir` with `pubkeys` to the
+/// transport public key `recipient`, writing the sealed file to `output`.
+pub fn wrap(dir: &Path, pubkeys: &[String], recipient: &Path, output: &Path) -> io::Result<()> {
+    let (transport, recipient_key) = read_transport_key(recipient)?;
+    let now = unix_now();
+    if transport.expires_at <= now {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            format!(
+                "The transport key {} expired {} ago; have the receiving host create a new one",
+                recipient.display(),
+                format_duration(Duration::from_secs(now - transport.expires_at))
+            ),
+        ));
+    }
+    if output.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite existing file {}", output.display()),
+        ));
+    }
+
+    let mut manifest = Manifest::read(dir)?;
+    let store = ManifestKeyStore::open(dir)?;
+    let wanted: BTreeSet<String> = pubkeys.iter().map(|pk| normalize_pubkey(pk)).collect();
+    manifest
+        .validators
+        .retain(|v| wanted.contains(&normalize_pubkey(&v.pubkey_hex)));
+    let found: BTreeSet<String> = manifest
+        .validators
+        .iter()
+        .map(|v| normalize_pubkey(&v.pubkey_hex))
+        .collect();
+    if let Some(missing) = wanted.difference(&found).next() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not in the manifest", short_pubkey(missing)),
+        ));
+    }
+
+    println!(
+        "📦 Wrapping {} validators of {} for transport\n",
+        manifest.validators.len(),
+        dir.display()
+    );
+    let existing = store.list()?;
+    let mut payload = Zeroizing::new(Vec::new());
+    for validator in &mut manifest.validators {
+        // The secret and public keys, plus metadata where written. `unwrap`
+        // checks every secret key against its public key
+        let privkey_name = manifest::file_name(&validator.privkey_file).to_string();
+        let prefix = privkey_name
+            .strip_suffix("_sk.ssz")
+            .unwrap_or(&privkey_name);
+        let mut names = vec![privkey_name.clone(), format!("{}_pk.ssz", prefix)];
+        let meta_name = format!("{}.meta.json", prefix);
+        if existing.contains(&meta_name) {
+            names.push(meta_name);
+        }
+        for name in &names {
+            add_file(&mut payload, name, &Zeroizing::new(store.load(name)?));
+        }
+        println!(
+            "  🔒 {} ({})",
+            short_pubkey(&validator.pubkey_hex),
+            names.join(", ")
+        );
+        validator.privkey_file = privkey_name;
+    }
+    manifest.revoked.clear();
+    manifest.num_validators = manifest.validators.len();
+    add_file(&mut payload, MANIFEST_FILE, manifest.to_yaml().as_bytes());
+
+    let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+    let header = WrapHeader {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now,
+        source_dir: dir.display().to_string(),
+        ceremony_id: manifest.ceremony_id.clone(),
+        recipient: transport.pubkey,
+        expires_at: transport.expires_at,
+        ephemeral_pubkey: hex::encode(PublicKey::from(&ephemeral).as_bytes()),
+        pubkeys: manifest
+            .validators
+            .iter()
+            .map(|v| v.pubkey_hex.clone())
+            .collect(),
+    };
+    let wrapped = seal(&header, &ephemeral, &recipient_key, &payload)?;
+    let mut file = File::create(output)?;
+    file.write_all(&wrapped)?;
+    file.sync_all()?;
+
+    let wrapped_sha256 = checksum::sha256_hex(&wrapped);
+    log_event(
+        dir,
+        &AuditEvent {
+            event: AuditAction::Wrap,
+            at: unix_now(),
+            wrapped_file: output.display().to_string(),
+            wrapped_sha256: wrapped_sha256.clone(),
+            recipient: header.recipient,
+            pubkeys: header.pubkeys,
+        },
+    )?;
+
+    println!("\n✅ Wrapped into: {}", output.display());
+    println!("   SHA-256 {}", wrapped_sha256);
+    println!(
+        "   Valid until the transport key expires, in {}; carry it to the host holding that key and run `hashsig unwrap` there",
+        format_duration(Duration::from_secs(transport.expires_at - now))
+    );
+    Ok(())
+}
+
+/// The wrapped file of `payload` under `header`, sealed to `recipient`
+/// with the ephemeral key `ephemeral`.
+fn seal(
+    header: &WrapHeader,
+    ephemeral: &StaticSecret,
+    recipient: &[u8; 32],
+    payload: &[u8],
+) -> io::Result<Vec<u8>> {
+    let header_json = serde_json::to_vec(header).map_err(io::Error::other)?;
+    let ephemeral_pubkey = PublicKey::from(ephemeral).to_bytes();
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    let cipher = ChaCha20Poly1305::new(&payload_key(
+        shared.as_bytes(),
+        &ephemeral_pubkey,
+        recipient,
+    ));
+    let sealed = cipher
+        .encrypt(
+            &Nonce::default(),
+            Payload {
+                msg: payload,
+                aad: &header_json,
+            },
+        )
+        .map_err(|_| io::Error::other("Payload encryption failed"))?;
+
+    let mut wrapped = Vec::with_capacity(PREFIX_LEN + header_json.len() + sealed.len());
+    wrapped.extend_from_slice(MAGIC);
+    wrapped.push(VERSION);
+    wrapped.extend_from_slice(&(header_json.len() as u32).to_le_bytes());
+    wrapped.extend_from_slice(&header_json);
+    wrapped.extend_from_slice(&sealed);
+    Ok(wrapped)
+}
+
+/// Split a wrapped file into its header, the header's bytes and the sealed
+/// payload.
+fn split_wrapped<'a>(
+    bytes: &'a [u8],
+    source: &str,
+) -> io::Result<(WrapHeader, &'a [u8], &'a [u8])> {
+    let not_wrapped = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a wrapped key file", source),
+        )
+    };
+    if bytes.len() < PREFIX_LEN || &bytes[..8] != MAGIC {
+        return Err(not_wrapped());
+    }
+    if bytes[8] != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported wrapped key file version {}", bytes[8]),
+        ));
+    }
+    let len = u32::from_le_bytes(bytes[9..PREFIX_LEN].try_into().expect("4 bytes")) as usize;
+    let header_json = bytes
+        .get(PREFIX_LEN..PREFIX_LEN + len)
+        .ok_or_else(not_wrapped)?;
+    let header = serde_json::from_slice(header_json).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Corrupted header in {}: {}", source, e),
+        )
+    })?;
+    Ok((header, header_json, &bytes[PREFIX_LEN + len..]))
+}
+
+/// The first `n` bytes of `bytes` and the rest, if it has that many.
+fn take(bytes: &[u8], n: usize) -> Option<(&[u8], &[u8])> {
+    (bytes.len() >= n).then(|| bytes.split_at(n))
+}
+
+/// The files of an opened payload, as written by [`add_file`].
+fn payload_files<'a>(payload: &'a [u8], source: &str) -> io::Result<Vec<(&'a str, &'a [u8])>> {
+    let malformed = || {
+        exit::error(
+            Failure::CorruptData,
+            format!("The payload of {} is malformed", source),
+        )
+    };
+    let mut files = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let (len, after) = take(rest, 2).ok_or_else(malformed)?;
+        let name_len = usize::from(u16::from_le_bytes([len[0], len[1]]));
+        let (name, after) = take(after, name_len).ok_or_else(malformed)?;
+        let (len, after) = take(after, 8).ok_or_else(malformed)?;
+        let len = u64::from_le_bytes(len.try_into().expect("8 bytes"));
+        let len = usize::try_from(len).map_err(|_| malformed())?;
+        let (contents, after) = take(after, len).ok_or_else(malformed)?;
+        let name = std::str::from_utf8(name).map_err(|_| malformed())?;
+        files.push((name, contents));
+        rest = after;
+    }
+    Ok(files)
+}
+
+/// Open the wrapped file `file` with the transport secret key `key` into the
+/// new key directory `output_dir`, then shred `key` unless `keep_key`.
+pub fn unwrap(file: &Path, key: &Path, output_dir: &Path, keep_key: bool) -> io::Result<()> {
+    if manifest::manifest_path(output_dir).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already holds a manifest; refusing to overwrite it",
+                output_dir.display()
+            ),
+        ));
+    }
+    let source = file.display().to_string();
+    let wrapped = fs::read(file)?;
+    let (header, header_json, sealed) = split_wrapped(&wrapped, &source)?;
+
+    let secret = StaticSecret::from(*Zeroizing::new(escrow::read_key(key)?));
+    let recipient = PublicKey::from(&secret).to_bytes();
+    if hex::encode(recipient) != header.recipient {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not wrapped to the transport key {}",
+                source,
+                key.display()
+            ),
+        ));
+    }
+    let now = unix_now();
+    if header.expires_at <= now {
+        return Err(exit::error(
+            Failure::InvalidArgs,
+            format!(
+                "{} was wrapped to a transport key that expired {} ago; wrap the keys again to a new one",
+                source,
+                format_duration(Duration::from_secs(now - header.expires_at))
+            ),
+        ));
+    }
+
+    let corrupt = || {
+        exit::error(
+            Failure::CorruptData,
+            format!("{} is corrupted or was tampered with", source),
+        )
+    };
+    let ephemeral: [u8; 32] = hex::decode(&header.ephemeral_pubkey)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(corrupt)?;
+    let shared = secret.diffie_hellman(&PublicKey::from(ephemeral));
+    let cipher = ChaCha20Poly1305::new(&payload_key(shared.as_bytes(), &ephemeral, &recipient));
+    let payload = cipher
+        .decrypt(
+            &Nonce::default(),
+            Payload {
+                msg: sealed,
+                aad: header_json,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| corrupt())?;
+    let files = payload_files(&payload, &source)?;
+    let Some(manifest) = files.iter().find(|(name, _)| *name == MANIFEST_FILE) else {
+        return Err(exit::error(
+            Failure::CorruptData,
+            format!("{} holds no manifest", source),
+        ));
+    };
+    check_payload(
+        &header,
+        &Manifest::parse(&String::from_utf8_lossy(manifest.1), &source)?,
+        &files,
+        &source,
+    )?;
+
+    println!(
+        "📥 Unwrapping {} validators into {}\n",
+        header.pubkeys.len(),
+        output_dir.display()
+    );
+    let store = FsKeyStore::create(output_dir)?;
+    // The manifest goes last, so a directory with a manifest is complete
+    for (name, contents) in files
+        .iter()
+        .filter(|(name, _)| *name != MANIFEST_FILE)
+        .chain([manifest])
+    {
+        store.store(name, contents)?;
+        println!("  ✅ {}", name);
+    }
+    layout::mark(output_dir, Layout::Flat)?;
+
+    log_event(
+        output_dir,
+        &AuditEvent {
+            event: AuditAction::Unwrap,
+            at: unix_now(),
+            wrapped_file: source,
+            wrapped_sha256: checksum::sha256_hex(&wrapped),
+            recipient: header.recipient,
+            pubkeys: header.pubkeys.clone(),
+        },
+    )?;
+    if !keep_key {
+        keystore::shred_file(key)?;
+        println!("\n🔥 Shredded the transport key {}", key.display());
+    }
+
+    println!(
+        "\n✅ {} validators unwrapped into: {}",
+        header.pubkeys.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+/// Check that the opened payload `files` hold what `header` says: a
+/// `manifest` of exactly the header's validators, and for each its secret
+/// key, its public key matching the manifest and the secret key, and at most
+/// its metadata. Nothing else is unwrapped.
+fn check_payload(
+    header: &WrapHeader,
+    manifest: &Manifest,
+    files: &[(&str, &[u8])],
+    source: &str,
+) -> io::Result<()> {
+    let mismatch = |reason: String| {
+        exit::error(
+            Failure::CorruptData,
+            format!("{} does not hold what its header says: {}", source, reason),
+        )
+    };
+    let mut named = BTreeSet::new();
+    if header
+        .pubkeys
+        .iter()
+        .any(|pk| !named.insert(normalize_pubkey(pk)))
+    {
+        return Err(mismatch("a pubkey is listed twice".to_string()));
+    }
+    let mut listed = BTreeSet::new();
+    for validator in &manifest.validators {
+        let pubkey = normalize_pubkey(&validator.pubkey_hex);
+        if !named.contains(&pubkey) || !listed.insert(pubkey) {
+            return Err(mismatch(format!(
+                "its manifest lists {}",
+                short_pubkey(&validator.pubkey_hex)
+            )));
+        }
+    }
+    if let Some(missing) = named.difference(&listed).next() {
+        return Err(mismatch(format!(
+            "its manifest does not list {}",
+            short_pubkey(missing)
+        )));
+    }
+
+    let mut contents = BTreeMap::new();
+    for (name, bytes) in files {
+        if contents.insert(*name, *bytes).is_some() {
+            return Err(mismatch(format!("{} is in it twice", name)));
+        }
+    }
+    let mut expected = BTreeSet::from([MANIFEST_FILE.to_string()]);
+    let scheme = scheme::for_manifest(manifest, source)?;
+    for validator in &manifest.validators {
+        let sk_name = manifest::file_name(&validator.privkey_file);
+        let prefix = sk_name.strip_suffix("_sk.ssz").unwrap_or(sk_name);
+        let pk_name = format!("{}_pk.ssz", prefix);
+        let (Some(sk), Some(pk)) = (contents.get(sk_name), contents.get(pk_name.as_str())) else {
+            return Err(mismatch(format!("it lacks {} or {}", sk_name, pk_name)));
+        };
+        if hex::encode(pk) != normalize_pubkey(&validator.pubkey_hex) {
+            return Err(mismatch(format!(
+                "{} is not {}",
+                pk_name,
+                short_pubkey(&validator.pubkey_hex)
+            )));
+        }
+        let scheme = match &validator.key_scheme {
+            Some(name) => scheme::lookup(name)?,
+            None => scheme,
+        };
+        if !scheme.keypair_matches(pk, sk)? {
+            return Err(exit::error(
+                Failure::VerificationFailed,
+                format!("{} in {} does not belong to {}", sk_name, source, pk_name),
+            ));
+        }
+        expected.extend([
+            sk_name.to_string(),
+            pk_name,
+            format!("{}.meta.json", prefix),
+        ]);
+    }
+    if let Some(name) = contents.keys().find(|name| !expected.contains(**name)) {
+        return Err(mismatch(format!(
+            "{} is not a file of a wrapped validator",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Append `event` to the audit log of `dir`.
+fn log_event(dir: &Path, event: &AuditEvent) -> io::Result<()> {
+    let mut line = serde_json::to_vec(event).map_err(io::Error::other)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(AUDIT_LOG_FILE))?
+        .write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// A scratch key directory of two validators and a transport key pair.
+    struct Transfer {
+        root: PathBuf,
+        dir: PathBuf,
+        pubkeys: Vec<String>,
+        transport: PathBuf,
+    }
+
+    impl Transfer {
+        fn new(name: &str, valid_for: Duration) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("hashsig-wrap-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let dir = root.join("keys");
+            fs::create_dir_all(&dir).unwrap();
+
+            let scheme = scheme::default_scheme();
+            let mut validators = String::new();
+            let mut pubkeys = Vec::new();
+            for index in 0..2 {
+                let pair = scheme.key_gen(&mut rand::rng(), 0, 4);
+                fs::write(
+                    dir.join(format!("validator_{}_sk.ssz", index)),
+                    pair.sk_bytes(),
+                )
+                .unwrap();
+                fs::write(
+                    dir.join(format!("validator_{}_pk.ssz", index)),
+                    pair.pk_bytes(),
+                )
+                .unwrap();
+                let pubkey = format!("0x{}", hex::encode(pair.pk_bytes()));
+                validators.push_str(&format!(
+                    "  - index: {}\n    pubkey_hex: {}\n    privkey_file: validator_{}_sk.ssz\n",
+                    index, pubkey, index
+                ));
+                pubkeys.push(pubkey);
+            }
+            let manifest = format!(
+                "key_scheme: {}
+hash_function: {}
+encoding: {}
+lifetime: {}
+log_num_active_epochs: 2
+num_active_epochs: 4
+num_validators: 2
+
+validators:
+{}",
+                scheme.name(),
+                scheme.hash_function().name(),
+                scheme.encoding(),
+                scheme.lifetime(),
+                validators
+            );
+            fs::write(dir.join(MANIFEST_FILE), manifest).unwrap();
+
+            let transport = root.join("transport");
+            keygen(&transport, valid_for).unwrap();
+            Transfer {
+                root,
+                dir,
+                pubkeys,
+                transport,
+            }
+        }
+
+        fn public(&self) -> PathBuf {
+            escrow::with_suffix(&self.transport, ".pub")
+        }
+
+        fn secret(&self) -> PathBuf {
+            escrow::with_suffix(&self.transport, ".key")
+        }
+
+        fn wrap(&self, pubkeys: &[String]) -> io::Result<PathBuf> {
+            let output = self.root.join("keys.wrapped");
+            let _ = fs::remove_file(&output);
+            wrap(&self.dir, pubkeys, &self.public(), &output).map(|()| output)
+        }
+
+        fn unwrap(&self, file: &Path) -> io::Result<PathBuf> {
+            let output = self.root.join("unwrapped");
+            unwrap(file, &self.secret(), &output, true).map(|()| output)
+        }
+
+        /// A file wrapped to the transport key with `header_pubkeys` in its
+        /// header and `files` as its payload, as a hostile wrapper could
+        /// write it.
+        fn forge(
+            &self,
+            header_pubkeys: &[String],
+            files: &[(&str, Vec<u8>)],
+            expires_at: u64,
+        ) -> PathBuf {
+            let (transport, recipient) = read_transport_key(&self.public()).unwrap();
+            let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+            let header = WrapHeader {
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                created_at: unix_now(),
+                source_dir: self.dir.display().to_string(),
+                ceremony_id: None,
+                recipient: transport.pubkey,
+                expires_at,
+                ephemeral_pubkey: hex::encode(PublicKey::from(&ephemeral).as_bytes()),
+                pubkeys: header_pubkeys.to_vec(),
+            };
+            let mut payload = Vec::new();
+            for (name, contents) in files {
+                add_file(&mut payload, name, contents);
+            }
+            let output = self.root.join("forged.wrapped");
+            fs::write(
+                &output,
+                seal(&header, &ephemeral, &recipient, &payload).unwrap(),
+            )
+            .unwrap();
+            output
+        }
+
+        /// The manifest of the key directory, cut down to `validators`.
+        fn manifest_of(&self, validators: &[usize]) -> Vec<u8> {
+            let mut manifest = Manifest::read(&self.dir).unwrap();
+            manifest
+                .validators
+                .retain(|v| validators.contains(&v.index.unwrap()));
+            manifest.num_validators = manifest.validators.len();
+            manifest.to_yaml().into_bytes()
+        }
+
+        fn key_file(&self, name: &str) -> Vec<u8> {
+            fs::read(self.dir.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for Transfer {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let transfer = Transfer::new("round-trip", Duration::from_secs(3600));
+        let wrapped = transfer.wrap(&transfer.pubkeys[..1]).unwrap();
+        let output = transfer.root.join("unwrapped");
+        unwrap(&wrapped, &transfer.secret(), &output, false).unwrap();
+
+        for name in ["validator_0_sk.ssz", "validator_0_pk.ssz"] {
+            assert_eq!(
+                fs::read(output.join(name)).unwrap(),
+                transfer.key_file(name)
+            );
+        }
+        assert!(!output.join("validator_1_sk.ssz").exists());
+        let manifest = Manifest::read(&output).unwrap();
+        assert_eq!(manifest.validators.len(), 1);
+        assert_eq!(manifest.validators[0].pubkey_hex, transfer.pubkeys[0]);
+        assert!(!transfer.secret().exists());
+        assert!(transfer.dir.join(AUDIT_LOG_FILE).is_file());
+        assert!(output.join(AUDIT_LOG_FILE).is_file());
+    }
+
+    #[test]
+    fn rejects_the_wrong_recipient() {
+        let transfer = Transfer::new("recipient", Duration::from_secs(3600));
+        let wrapped = transfer.wrap(&transfer.pubkeys).unwrap();
+        let other = transfer.root.join("other");
+        keygen(&other, Duration::from_secs(3600)).unwrap();
+        let output = transfer.root.join("unwrapped");
+        let err = unwrap(
+            &wrapped,
+            &escrow::with_suffix(&other, ".key"),
+            &output,
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::InvalidArgs);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn rejects_tampered_files() {
+        let transfer = Transfer::new("tampered", Duration::from_secs(3600));
+        let wrapped = transfer.wrap(&transfer.pubkeys).unwrap();
+        let original = fs::read(&wrapped).unwrap();
+
+        let mut bytes = original.clone();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&wrapped, &bytes).unwrap();
+        let err = transfer.unwrap(&wrapped).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::CorruptData);
+
+        // The header is authenticated too
+        let (header, _, _) = split_wrapped(&original, "test").unwrap();
+        let json = serde_json::to_string(&header).unwrap();
+        let tampered = json.replace(&header.pubkeys[1], &header.pubkeys[0]);
+        let mut bytes = original[..PREFIX_LEN].to_vec();
+        bytes.extend_from_slice(tampered.as_bytes());
+        bytes.extend_from_slice(&original[PREFIX_LEN + json.len()..]);
+        fs::write(&wrapped, &bytes).unwrap();
+        let err = transfer.unwrap(&wrapped).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::CorruptData);
+        assert!(!transfer.root.join("unwrapped").exists());
+    }
+
+    #[test]
+    fn rejects_expired_transport_keys() {
+        let transfer = Transfer::new("expired", Duration::ZERO);
+        let err = transfer.wrap(&transfer.pubkeys).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::InvalidArgs);
+
+        let files = [
+            (MANIFEST_FILE, transfer.manifest_of(&[0])),
+            (
+                "validator_0_sk.ssz",
+                transfer.key_file("validator_0_sk.ssz"),
+            ),
+            (
+                "validator_0_pk.ssz",
+                transfer.key_file("validator_0_pk.ssz"),
+            ),
+        ];
+        let forged = transfer.forge(&transfer.pubkeys[..1], &files, unix_now() - 60);
+        let err = transfer.unwrap(&forged).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::InvalidArgs);
+        assert!(!transfer.root.join("unwrapped").exists());
+    }
+
+    #[test]
+    fn rejects_payloads_that_differ_from_their_header() {
+        let transfer = Transfer::new("forged", Duration::from_secs(3600));
+        let expires_at = unix_now() + 3600;
+        let sk = |index: usize| transfer.key_file(&format!("validator_{}_sk.ssz", index));
+        let pk = |index: usize| transfer.key_file(&format!("validator_{}_pk.ssz", index));
+        let check = |header_pubkeys: &[String], files: &[(&str, Vec<u8>)], failure: Failure| {
+            let forged = transfer.forge(header_pubkeys, files, expires_at);
+            let err = transfer.unwrap(&forged).unwrap_err();
+            assert_eq!(Failure::of(&err), failure, "{}", err);
+            assert!(!transfer.root.join("unwrapped").exists());
+        };
+
+        // The header names a validator the payload lacks
+        check(
+            &transfer.pubkeys,
+            &[
+                (MANIFEST_FILE, transfer.manifest_of(&[0])),
+                ("validator_0_sk.ssz", sk(0)),
+                ("validator_0_pk.ssz", pk(0)),
+            ],
+            Failure::CorruptData,
+        );
+        // The payload carries a validator the header does not name
+        check(
+            &transfer.pubkeys[..1],
+            &[
+                (MANIFEST_FILE, transfer.manifest_of(&[0, 1])),
+                ("validator_0_sk.ssz", sk(0)),
+                ("validator_0_pk.ssz", pk(0)),
+                ("validator_1_sk.ssz", sk(1)),
+                ("validator_1_pk.ssz", pk(1)),
+            ],
+            Failure::CorruptData,
+        );
+        // A file that is not a key file of a wrapped validator
+        check(
+            &transfer.pubkeys[..1],
+            &[
+                (MANIFEST_FILE, transfer.manifest_of(&[0])),
+                ("validator_0_sk.ssz", sk(0)),
+                ("validator_0_pk.ssz", pk(0)),
+                ("authorized_keys", b"ssh-ed25519 AAAA".to_vec()),
+            ],
+            Failure::CorruptData,
+        );
+        // A public key that is not the one the manifest lists
+        check(
+            &transfer.pubkeys[..1],
+            &[
+                (MANIFEST_FILE, transfer.manifest_of(&[0])),
+                ("validator_0_sk.ssz", sk(0)),
+                ("validator_0_pk.ssz", pk(1)),
+            ],
+            Failure::CorruptData,
+        );
+        // A secret key that does not belong to its public key
+        check(
+            &transfer.pubkeys[..1],
+            &[
+                (MANIFEST_FILE, transfer.manifest_of(&[0])),
+                ("validator_0_sk.ssz", sk(1)),
+                ("validator_0_pk.ssz", pk(0)),
+            ],
+            Failure::VerificationFailed,
+        );
+
+        // The same payload, honestly labeled, unwraps
+        let files = [
+            (MANIFEST_FILE, transfer.manifest_of(&[0])),
+            ("validator_0_sk.ssz", sk(0)),
+            ("validator_0_pk.ssz", pk(0)),
+        ];
+        let forged = transfer.forge(&transfer.pubkeys[..1], &files, expires_at);
+        transfer.unwrap(&forged).unwrap();
+    }
+}